@@ -0,0 +1,150 @@
+// contracts/rpgf-factory/src/lib.rs
+#![no_std]
+
+// Import necessary Soroban modules
+use soroban_sdk::{
+    contract, contracterror, contractimpl, contracttype, symbol_short, vec, xdr::ToXdr, Address,
+    BytesN, Env, IntoVal, Symbol, Vec,
+};
+
+// Define custom errors for the contract
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum ContractError {
+    ProgramNotFound = 1,
+}
+
+// Define the storage keys for the contract
+#[derive(Clone)]
+#[contracttype]
+pub enum DataKey {
+    ProgramCount, // Programs deployed so far
+    Program(u32), // index -> address of a deployed program
+}
+
+// Define the factory contract structure
+#[contract]
+pub struct RetroPGFFactory;
+
+#[contractimpl]
+impl RetroPGFFactory {
+    // Most programs returned by one page of `list_programs`
+    const MAX_PAGE_SIZE: u32 = 50;
+
+    // Ledgers closed per day, assuming ~5 second ledgers
+    const DAY_IN_LEDGERS: u32 = 17_280;
+
+    // Instance entries (the program count) are kept alive for a week past the last deployment
+    const INSTANCE_BUMP_AMOUNT: u32 = 7 * Self::DAY_IN_LEDGERS;
+    const INSTANCE_LIFETIME_THRESHOLD: u32 = Self::INSTANCE_BUMP_AMOUNT - Self::DAY_IN_LEDGERS;
+
+    // Registry entries are kept alive for a month past their last read or write
+    const PERSISTENT_BUMP_AMOUNT: u32 = 30 * Self::DAY_IN_LEDGERS;
+    const PERSISTENT_LIFETIME_THRESHOLD: u32 = Self::PERSISTENT_BUMP_AMOUNT - Self::DAY_IN_LEDGERS;
+
+    // Function to deploy and initialize an RPGF program administered by `admin`. The salt is
+    // namespaced by the admin, so nobody else can take a program's address, and the program is
+    // initialized in the same call it is deployed in, so it is never left for anyone else to
+    // initialize.
+    pub fn deploy_program(
+        env: Env,
+        admin: Address,
+        wasm_hash: BytesN<32>,
+        salt: BytesN<32>,
+    ) -> Address {
+        // Require authorization from the admin of the new program
+        admin.require_auth();
+
+        let program = env
+            .deployer()
+            .with_current_contract(Self::program_salt(&env, &admin, salt))
+            .deploy(wasm_hash.clone());
+
+        // Initializing fails the whole deployment if the program rejects it
+        env.invoke_contract::<()>(
+            &program,
+            &Symbol::new(&env, "initialize"),
+            vec![&env, admin.into_val(&env)],
+        );
+
+        let index = Self::program_count(env.clone());
+        let key = DataKey::Program(index);
+        env.storage().persistent().set(&key, &program);
+        env.storage().persistent().extend_ttl(
+            &key,
+            Self::PERSISTENT_LIFETIME_THRESHOLD,
+            Self::PERSISTENT_BUMP_AMOUNT,
+        );
+        env.storage()
+            .instance()
+            .set(&DataKey::ProgramCount, &(index + 1));
+        env.storage().instance().extend_ttl(
+            Self::INSTANCE_LIFETIME_THRESHOLD,
+            Self::INSTANCE_BUMP_AMOUNT,
+        );
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("PROG_DEP"), program.clone()),
+            (admin, wasm_hash, index),
+        );
+
+        program
+    }
+
+    // Function to get the address a program deployed by `admin` with `salt` has or would have
+    pub fn program_address(env: Env, admin: Address, salt: BytesN<32>) -> Address {
+        env.deployer()
+            .with_current_contract(Self::program_salt(&env, &admin, salt))
+            .deployed_address()
+    }
+
+    // Function to get the number of programs deployed so far
+    pub fn program_count(env: Env) -> u32 {
+        env.storage()
+            .instance()
+            .get::<DataKey, u32>(&DataKey::ProgramCount)
+            .unwrap_or(0)
+    }
+
+    // Function to get the address of the program deployed at `index`, in order of deployment
+    pub fn get_program(env: Env, index: u32) -> Result<Address, ContractError> {
+        let key = DataKey::Program(index);
+        let program = env
+            .storage()
+            .persistent()
+            .get::<DataKey, Address>(&key)
+            .ok_or(ContractError::ProgramNotFound)?;
+        env.storage().persistent().extend_ttl(
+            &key,
+            Self::PERSISTENT_LIFETIME_THRESHOLD,
+            Self::PERSISTENT_BUMP_AMOUNT,
+        );
+        Ok(program)
+    }
+
+    // Function to list deployed programs in order of deployment, skipping the first `start`
+    // and returning at most `limit`
+    pub fn list_programs(env: Env, start: u32, limit: u32) -> Vec<Address> {
+        let end = Self::program_count(env.clone())
+            .min(start.saturating_add(limit.min(Self::MAX_PAGE_SIZE)));
+
+        let mut programs = Vec::new(&env);
+        for index in start..end {
+            if let Ok(program) = Self::get_program(env.clone(), index) {
+                programs.push_back(program);
+            }
+        }
+        programs
+    }
+
+    // Helper function to derive the deployment salt of a program from its admin and salt
+    fn program_salt(env: &Env, admin: &Address, salt: BytesN<32>) -> BytesN<32> {
+        env.crypto()
+            .sha256(&(admin.clone(), salt).to_xdr(env))
+            .into()
+    }
+}
+
+mod test;
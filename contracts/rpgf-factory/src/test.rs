@@ -0,0 +1,145 @@
+#![cfg(test)]
+extern crate std;
+
+use super::*;
+use soroban_sdk::testutils::{Address as _, Events};
+use soroban_sdk::{Bytes, TryFromVal};
+
+// Assemble a minimal program contract exporting `initialize(admin)` with the given function
+// body. The RPGF contract can't be compiled to Wasm in the test build, so the factory is
+// tested against stand-ins that accept or reject initialization.
+fn program_wasm(env: &Env, body: &[u8]) -> Bytes {
+    let mut wasm = std::vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+    let mut section = |id: u8, contents: &[u8]| {
+        wasm.push(id);
+        wasm.push(contents.len() as u8);
+        wasm.extend_from_slice(contents);
+    };
+
+    // Environment interface version: protocol 21, no pre-release
+    let mut meta = std::vec![17];
+    meta.extend_from_slice(b"contractenvmetav0");
+    meta.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 21, 0, 0, 0, 0]);
+    section(0, &meta);
+
+    section(1, &[0x01, 0x60, 0x01, 0x7e, 0x01, 0x7e]); // (i64) -> i64
+    section(3, &[0x01, 0x00]);
+    let mut export = std::vec![0x01, 10];
+    export.extend_from_slice(b"initialize");
+    export.extend_from_slice(&[0x00, 0x00]);
+    section(7, &export);
+    let mut code = std::vec![0x01, body.len() as u8 + 1, 0x00];
+    code.extend_from_slice(body);
+    section(10, &code);
+
+    Bytes::from_slice(env, &wasm)
+}
+
+// A program that accepts initialization, returning void
+fn accepting_program(env: &Env) -> BytesN<32> {
+    env.deployer()
+        .upload_contract_wasm(program_wasm(env, &[0x42, 0x02, 0x0b]))
+}
+
+// A program that rejects initialization by trapping
+fn rejecting_program(env: &Env) -> BytesN<32> {
+    env.deployer()
+        .upload_contract_wasm(program_wasm(env, &[0x00, 0x0b]))
+}
+
+fn setup() -> (Env, RetroPGFFactoryClient<'static>) {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let contract_id = env.register_contract(None, RetroPGFFactory);
+    let client = RetroPGFFactoryClient::new(&env, &contract_id);
+
+    (env, client)
+}
+
+#[test]
+fn test_deploy_program() {
+    let (env, client) = setup();
+    let wasm_hash = accepting_program(&env);
+    let admin = Address::generate(&env);
+    let salt = BytesN::from_array(&env, &[1; 32]);
+
+    let expected = client.program_address(&admin, &salt);
+    let program = client.deploy_program(&admin, &wasm_hash, &salt);
+    assert_eq!(program, expected);
+    assert_eq!(client.program_count(), 1);
+    assert_eq!(client.get_program(&0), program);
+    assert_eq!(
+        client.try_get_program(&1),
+        Err(Ok(ContractError::ProgramNotFound))
+    );
+
+    let event = env.events().all().last().unwrap();
+    assert_eq!(
+        event.1,
+        (symbol_short!("PROG_DEP"), program.clone()).into_val(&env)
+    );
+    assert_eq!(
+        <(Address, BytesN<32>, u32)>::try_from_val(&env, &event.2).unwrap(),
+        (admin.clone(), wasm_hash.clone(), 0)
+    );
+
+    // A salt can only be used once per admin, but every admin has their own salts
+    assert!(client
+        .try_deploy_program(&admin, &wasm_hash, &salt)
+        .is_err());
+    let other_admin = Address::generate(&env);
+    let other = client.deploy_program(&other_admin, &wasm_hash, &salt);
+    assert_ne!(other, program);
+    assert_eq!(client.program_count(), 2);
+}
+
+#[test]
+fn test_deployment_fails_if_initialization_fails() {
+    let (env, client) = setup();
+    let admin = Address::generate(&env);
+    let salt = BytesN::from_array(&env, &[1; 32]);
+
+    assert!(client
+        .try_deploy_program(&admin, &rejecting_program(&env), &salt)
+        .is_err());
+    assert_eq!(client.program_count(), 0);
+    assert_eq!(client.list_programs(&0, &10).len(), 0);
+
+    // Nothing was left deployed at the program's address
+    let program = client.deploy_program(&admin, &accepting_program(&env), &salt);
+    assert_eq!(program, client.program_address(&admin, &salt));
+}
+
+#[test]
+fn test_list_programs() {
+    let (env, client) = setup();
+    let wasm_hash = accepting_program(&env);
+    let admin = Address::generate(&env);
+
+    let mut programs = Vec::new(&env);
+    for i in 0..5u8 {
+        let salt = BytesN::from_array(&env, &[i; 32]);
+        programs.push_back(client.deploy_program(&admin, &wasm_hash, &salt));
+    }
+
+    assert_eq!(client.list_programs(&0, &10), programs);
+    assert_eq!(client.list_programs(&1, &2), programs.slice(1..3));
+    assert_eq!(client.list_programs(&4, &10), programs.slice(4..5));
+    assert_eq!(client.list_programs(&5, &10).len(), 0);
+    assert_eq!(client.list_programs(&0, &0).len(), 0);
+}
+
+#[test]
+fn test_deploy_program_requires_admin_auth() {
+    let env = Env::default();
+    let client = RetroPGFFactoryClient::new(&env, &env.register_contract(None, RetroPGFFactory));
+    let wasm_hash = accepting_program(&env);
+    let admin = Address::generate(&env);
+    let salt = BytesN::from_array(&env, &[1; 32]);
+
+    assert!(client
+        .try_deploy_program(&admin, &wasm_hash, &salt)
+        .is_err());
+    assert_eq!(client.program_count(), 0);
+}
@@ -0,0 +1,471 @@
+// contracts/rpgf/src/allocation.rs
+//
+// The allocation math of a closing round, free of the Soroban environment so its rounding,
+// cutoff and cap behaviour can be tested directly. `close_voting` and `preview_allocations`
+// both compute a round's totals with `compute`, and every payout path derives a submission's
+// share from them with `Allocation::share`. Funding amounts are non-negative i128s, like the
+// token interface's, and votes are u64s; no product of the two is ever formed in full, so no
+// amount of funding can overflow.
+
+use alloc::vec::Vec;
+
+// Basis points making up the whole distributable funding
+const TOTAL_BPS: u32 = 10_000;
+
+// Define the rules of a round that decide its allocations
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct AllocationConfig {
+    pub min_votes_to_fund: u64, // Submissions with fewer votes are allocated nothing
+    pub top_n: u32,             // Fund only the N most-voted submissions; 0 = fund all
+    pub max_allocation: i128,   // Most funding any one submission receives; 0 = uncapped
+    pub max_match_bps: u32,     // Most of the pool one submission receives; 0 = uncapped
+}
+
+// Define the totals a round's allocations are derived from, recorded on the round at close
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Allocation {
+    pub distributable: i128, // Funding shared by the allocations
+    pub funded_votes: u64,   // Votes of the submissions that made the cutoff
+    pub cutoff_votes: u64,   // Fewest votes a submission within the top-N cutoff has
+    pub cutoff_id: u64,      // Highest id funded among submissions with the cutoff votes
+    pub capped_votes: u64,   // Fewest votes of a submission allocated the cap; u64::MAX if none
+    pub uncapped_pool: i128, // Funding shared by the submissions below the cap
+    pub uncapped_votes: u64, // Votes of the funded submissions below the cap
+}
+
+impl Allocation {
+    // Whether a submission with the given votes reached the vote threshold and made the
+    // funding cutoff. Ties on votes go to the earlier submission.
+    pub fn is_funded(&self, config: &AllocationConfig, submission_id: u64, votes: u64) -> bool {
+        votes >= config.min_votes_to_fund
+            && (votes > self.cutoff_votes
+                || (votes == self.cutoff_votes && submission_id <= self.cutoff_id))
+    }
+
+    // The most funding one submission may receive, if capped: the smaller of the maximum
+    // allocation and the maximum share of the distributable funding
+    pub fn cap(&self, config: &AllocationConfig) -> Option<i128> {
+        let share_cap = (config.max_match_bps > 0).then(|| {
+            mul_div(
+                self.distributable,
+                config.max_match_bps as u64,
+                TOTAL_BPS as u64,
+            )
+        });
+        match (config.max_allocation, share_cap) {
+            (0, share_cap) => share_cap,
+            (max_allocation, Some(share_cap)) => Some(max_allocation.min(share_cap)),
+            (max_allocation, None) => Some(max_allocation),
+        }
+    }
+
+    // The allocation of a submission funded for the given votes: the cap if it was capped,
+    // otherwise its share of the funding below the cap
+    pub fn share(&self, config: &AllocationConfig, votes: u64) -> i128 {
+        if votes == 0 {
+            return 0;
+        }
+        if votes >= self.capped_votes {
+            return self.cap(config).unwrap_or(0);
+        }
+
+        mul_div(self.uncapped_pool, votes, self.uncapped_votes)
+    }
+}
+
+// Function to compute `amount * numerator / denominator`, rounded down, for a non-negative
+// amount and a numerator no larger than the denominator. The amount is split into whole
+// multiples of the denominator and the rest, so the product never overflows; a zero
+// denominator gives zero.
+pub fn mul_div(amount: i128, numerator: u64, denominator: u64) -> i128 {
+    mul_div_rem(amount, numerator, denominator).0
+}
+
+// Helper function to compute `mul_div` and whether the division left a remainder
+fn mul_div_rem(amount: i128, numerator: u64, denominator: u64) -> (i128, bool) {
+    if denominator == 0 {
+        return (0, false);
+    }
+
+    let (amount, numerator, denominator) = (amount as u128, numerator as u128, denominator as u128);
+    let rest = amount % denominator * numerator;
+    let quotient = amount / denominator * numerator + rest / denominator;
+    (quotient as i128, rest % denominator != 0)
+}
+
+// Function to compute the totals a round's allocations are derived from, given the votes of
+// each approved submission as (submission_id, votes) pairs in any order and the funding they
+// share
+pub fn compute(votes: &[(u64, u64)], distributable: i128, config: &AllocationConfig) -> Allocation {
+    let mut allocation = Allocation {
+        distributable,
+        funded_votes: 0,
+        cutoff_votes: 0,
+        cutoff_id: u64::MAX,
+        capped_votes: u64::MAX,
+        uncapped_pool: distributable,
+        uncapped_votes: 0,
+    };
+    apply_funding_cutoff(votes, config, &mut allocation);
+    apply_allocation_cap(votes, config, &mut allocation);
+    allocation
+}
+
+// Function to split the distributable funding between a round's categories in proportion to
+// their budgets, given as (budget, has_submissions) pairs in category order. The share of a
+// category without submissions goes to the category with the largest budget that has some,
+// the earliest on ties; if none has any, every share is 0. Shares are rounded down.
+pub fn split_budgets(distributable: i128, budgets: &[(u64, bool)]) -> Vec<i128> {
+    let total: u64 = budgets.iter().map(|&(budget, _)| budget).sum();
+    let mut shares: Vec<i128> = budgets
+        .iter()
+        .map(|&(budget, _)| mul_div(distributable, budget, total))
+        .collect();
+
+    let largest = budgets
+        .iter()
+        .enumerate()
+        .filter(|(_, &(_, has_submissions))| has_submissions)
+        .max_by(|(a_index, (a_budget, _)), (b_index, (b_budget, _))| {
+            a_budget.cmp(b_budget).then(b_index.cmp(a_index))
+        })
+        .map(|(index, _)| index);
+    for (index, &(_, has_submissions)) in budgets.iter().enumerate() {
+        if !has_submissions {
+            let share = core::mem::take(&mut shares[index]);
+            if let Some(largest) = largest {
+                shares[largest] += share;
+            }
+        }
+    }
+
+    shares
+}
+
+// Function to compute the allocation of every submission, in ascending id order, and the
+// remainder of the distributable funding no allocation takes up
+pub fn allocate(
+    votes: &[(u64, u64)],
+    distributable: i128,
+    config: &AllocationConfig,
+) -> (Vec<(u64, i128)>, i128) {
+    let allocation = compute(votes, distributable, config);
+
+    let mut table: Vec<(u64, i128)> = votes
+        .iter()
+        .map(|&(submission_id, votes)| {
+            let funded_votes = if allocation.is_funded(config, submission_id, votes) {
+                votes
+            } else {
+                0
+            };
+            (submission_id, allocation.share(config, funded_votes))
+        })
+        .collect();
+    table.sort_unstable_by_key(|&(submission_id, _)| submission_id);
+
+    let allocated: i128 = table.iter().map(|&(_, amount)| amount).sum();
+    (table, distributable - allocated)
+}
+
+// Helper function to record which submissions are funded. Submissions below the vote
+// threshold are allocated nothing, and a top-N cutoff funds only the N most-voted qualifying
+// submissions, tie-broken by ascending id. The share the others would have had goes to the
+// funded submissions, as only their votes count towards the allocations.
+fn apply_funding_cutoff(
+    votes: &[(u64, u64)],
+    config: &AllocationConfig,
+    allocation: &mut Allocation,
+) {
+    let mut qualifying: Vec<(u64, u64)> = votes
+        .iter()
+        .copied()
+        .filter(|&(_, votes)| votes >= config.min_votes_to_fund)
+        .collect();
+    allocation.funded_votes = qualifying.iter().map(|&(_, votes)| votes).sum();
+
+    let top_n = config.top_n as usize;
+    if top_n == 0 || qualifying.len() <= top_n {
+        return;
+    }
+
+    qualifying.sort_unstable_by(|(a_id, a_votes), (b_id, b_votes)| {
+        b_votes.cmp(a_votes).then(a_id.cmp(b_id))
+    });
+    let leaders = &qualifying[..top_n];
+    allocation.funded_votes = leaders.iter().map(|&(_, votes)| votes).sum();
+    (allocation.cutoff_id, allocation.cutoff_votes) = leaders[top_n - 1];
+}
+
+// Helper function to cap the allocations at the round's cap. The excess of each capped
+// submission is redistributed among the uncapped ones in proportion to their votes, which can
+// push more of them over the cap, so this repeats until no uncapped share exceeds it. Shares
+// are compared before rounding, so a share just over the cap counts as exceeding it. Whatever
+// the capped submissions can't absorb is left to the remainder.
+fn apply_allocation_cap(
+    votes: &[(u64, u64)],
+    config: &AllocationConfig,
+    allocation: &mut Allocation,
+) {
+    allocation.capped_votes = u64::MAX;
+    allocation.uncapped_pool = allocation.distributable;
+    allocation.uncapped_votes = allocation.funded_votes;
+
+    let Some(cap) = allocation.cap(config) else {
+        return;
+    };
+
+    loop {
+        // The largest shares exceed the cap first, so the capped submissions are always
+        // those with the most votes
+        let mut newly_capped = 0i128;
+        let mut newly_capped_votes = 0u64;
+        let mut capped_votes = allocation.capped_votes;
+        for &(submission_id, votes) in votes {
+            if votes >= allocation.capped_votes
+                || !allocation.is_funded(config, submission_id, votes)
+            {
+                continue;
+            }
+            let (share, rounded) =
+                mul_div_rem(allocation.uncapped_pool, votes, allocation.uncapped_votes);
+            if share > cap || (share == cap && rounded) {
+                newly_capped += 1;
+                newly_capped_votes += votes;
+                capped_votes = capped_votes.min(votes);
+            }
+        }
+
+        if newly_capped == 0 {
+            return;
+        }
+        allocation.capped_votes = capped_votes;
+        allocation.uncapped_pool -= newly_capped * cap;
+        allocation.uncapped_votes -= newly_capped_votes;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec;
+
+    fn config() -> AllocationConfig {
+        AllocationConfig::default()
+    }
+
+    // Small xorshift generator, so the randomized cases are reproducible
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, bound: u64) -> u64 {
+            self.next() % bound.max(1)
+        }
+    }
+
+    // Random tallies with distinct ids whose total fits in a u64, like a round's
+    fn random_votes(rng: &mut Rng, max_votes: u64) -> Vec<(u64, u64)> {
+        let count = rng.below(30) + 1;
+        (1..=count)
+            .map(|submission_id| (submission_id, rng.below(max_votes / count)))
+            .collect()
+    }
+
+    fn random_config(rng: &mut Rng) -> AllocationConfig {
+        AllocationConfig {
+            min_votes_to_fund: if rng.below(2) == 0 { 0 } else { rng.below(50) },
+            top_n: rng.below(10) as u32,
+            max_allocation: if rng.below(2) == 0 {
+                0
+            } else {
+                rng.below(1_000_000) as i128
+            },
+            max_match_bps: if rng.below(2) == 0 {
+                0
+            } else {
+                rng.below(10_000) as u32
+            },
+        }
+    }
+
+    #[test]
+    fn test_proportional_shares_and_remainder() {
+        let (table, remainder) = allocate(&[(1, 1), (2, 2)], 1000, &config());
+        assert_eq!(table, vec![(1, 333), (2, 666)]);
+        assert_eq!(remainder, 1);
+    }
+
+    #[test]
+    fn test_no_votes_leave_everything_to_the_remainder() {
+        let (table, remainder) = allocate(&[(1, 0), (2, 0)], 1000, &config());
+        assert_eq!(table, vec![(1, 0), (2, 0)]);
+        assert_eq!(remainder, 1000);
+
+        let (table, remainder) = allocate(&[], 1000, &config());
+        assert!(table.is_empty());
+        assert_eq!(remainder, 1000);
+    }
+
+    #[test]
+    fn test_min_votes_redistributes_to_funded() {
+        let config = AllocationConfig {
+            min_votes_to_fund: 5,
+            ..config()
+        };
+        let (table, remainder) = allocate(&[(1, 4), (2, 5), (3, 15)], 1000, &config);
+        assert_eq!(table, vec![(1, 0), (2, 250), (3, 750)]);
+        assert_eq!(remainder, 0);
+    }
+
+    #[test]
+    fn test_top_n_ties_go_to_the_lower_id() {
+        let config = AllocationConfig {
+            top_n: 2,
+            ..config()
+        };
+        let (table, remainder) = allocate(&[(3, 5), (1, 10), (2, 5)], 900, &config);
+        assert_eq!(table, vec![(1, 600), (2, 300), (3, 0)]);
+        assert_eq!(remainder, 0);
+
+        let allocation = compute(&[(3, 5), (1, 10), (2, 5)], 900, &config);
+        assert_eq!(allocation.cutoff_votes, 5);
+        assert_eq!(allocation.cutoff_id, 2);
+        assert_eq!(allocation.funded_votes, 15);
+    }
+
+    #[test]
+    fn test_cap_redistributes_the_excess() {
+        let config = AllocationConfig {
+            max_allocation: 400,
+            ..config()
+        };
+        // 700 is capped at 400, which pushes the second share over the cap as well
+        let (table, remainder) = allocate(&[(1, 70), (2, 20), (3, 10)], 1000, &config);
+        assert_eq!(table, vec![(1, 400), (2, 400), (3, 200)]);
+        assert_eq!(remainder, 0);
+    }
+
+    #[test]
+    fn test_cap_leaves_what_nobody_can_absorb() {
+        let config = AllocationConfig {
+            max_match_bps: 2500,
+            ..config()
+        };
+        let (table, remainder) = allocate(&[(1, 3), (2, 1)], 1000, &config);
+        assert_eq!(table, vec![(1, 250), (2, 250)]);
+        assert_eq!(remainder, 500);
+    }
+
+    #[test]
+    fn test_split_budgets_rolls_empty_categories_into_the_largest() {
+        let budgets = [(600, true), (300, true), (100, false)];
+        assert_eq!(split_budgets(1000, &budgets), vec![700, 300, 0]);
+
+        // The largest category without submissions passes its share on too
+        let budgets = [(600, false), (300, true), (100, true)];
+        assert_eq!(split_budgets(1000, &budgets), vec![0, 900, 100]);
+
+        // Ties go to the earlier category, and shares are rounded down
+        let budgets = [(1, true), (1, true), (1, false)];
+        assert_eq!(split_budgets(1000, &budgets), vec![666, 333, 0]);
+
+        let budgets = [(600, false), (400, false)];
+        assert_eq!(split_budgets(1000, &budgets), vec![0, 0]);
+    }
+
+    #[test]
+    fn test_mul_div_rounds_down_without_overflowing() {
+        assert_eq!(mul_div(1000, 1, 3), 333);
+        assert_eq!(mul_div(1000, 3, 3), 1000);
+        assert_eq!(mul_div(1000, 0, 3), 0);
+        assert_eq!(mul_div(1000, 1, 0), 0);
+        assert_eq!(mul_div(i128::MAX, u64::MAX, u64::MAX), i128::MAX);
+        assert_eq!(mul_div(i128::MAX, 1, 2), i128::MAX / 2);
+        assert_eq!(mul_div_rem(900, 1, 3), (300, false));
+        assert_eq!(mul_div_rem(1000, 1, 3), (333, true));
+    }
+
+    #[test]
+    fn test_allocations_conserve_the_funding() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..500 {
+            let votes = random_votes(&mut rng, 1_000);
+            let config = random_config(&mut rng);
+            let distributable = rng.below(u64::MAX) as i128;
+
+            let (table, remainder) = allocate(&votes, distributable, &config);
+            let allocated: i128 = table.iter().map(|&(_, amount)| amount).sum();
+            assert!(remainder >= 0);
+            assert_eq!(allocated + remainder, distributable);
+
+            let allocation = compute(&votes, distributable, &config);
+            for &(_, amount) in &table {
+                assert!(amount <= distributable);
+                if let Some(cap) = allocation.cap(&config) {
+                    assert!(amount <= cap);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_allocations_do_not_overflow() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..200 {
+            let votes = random_votes(&mut rng, u64::MAX);
+            let config = random_config(&mut rng);
+            let (table, remainder) = allocate(&votes, i128::MAX, &config);
+            let allocated: i128 = table.iter().map(|&(_, amount)| amount).sum();
+            assert_eq!(allocated + remainder, i128::MAX);
+        }
+    }
+
+    #[test]
+    fn test_allocations_do_not_depend_on_input_order() {
+        let mut rng = Rng(0xdead_beef_cafe_f00d);
+        for _ in 0..200 {
+            let votes = random_votes(&mut rng, 100);
+            let config = random_config(&mut rng);
+            let distributable = rng.below(1_000_000) as i128;
+
+            let mut shuffled = votes.clone();
+            for index in (1..shuffled.len()).rev() {
+                shuffled.swap(index, rng.below(index as u64 + 1) as usize);
+            }
+            assert_eq!(
+                allocate(&votes, distributable, &config),
+                allocate(&shuffled, distributable, &config)
+            );
+            assert_eq!(
+                compute(&votes, distributable, &config),
+                compute(&shuffled, distributable, &config)
+            );
+        }
+    }
+
+    #[test]
+    fn test_more_votes_never_mean_less_funding() {
+        let mut rng = Rng(0x0123_4567_89ab_cdef);
+        for _ in 0..200 {
+            let votes = random_votes(&mut rng, 1_000);
+            let config = random_config(&mut rng);
+            let (table, _) = allocate(&votes, 1_000_000, &config);
+            for &(a_id, a_votes) in &votes {
+                for &(b_id, b_votes) in &votes {
+                    let a_amount = table[a_id as usize - 1].1;
+                    let b_amount = table[b_id as usize - 1].1;
+                    if a_votes > b_votes {
+                        assert!(a_amount >= b_amount);
+                    }
+                }
+            }
+        }
+    }
+}
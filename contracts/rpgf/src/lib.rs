@@ -1,11 +1,17 @@
 // contracts/rpgf/src/lib.rs
 #![no_std]
+#![allow(clippy::too_many_arguments)]
 
-use core::u64;
+extern crate alloc;
+
+mod allocation;
+pub mod testutils;
 
 // Import necessary Soroban modules
+use allocation::{Allocation, AllocationConfig};
 use soroban_sdk::{
-    contract, contracterror, contractimpl, contracttype, log, symbol_short, token::TokenClient, Address, Bytes, Env, Map, Symbol, Vec
+    contract, contracterror, contractimpl, contracttype, symbol_short, token::TokenClient,
+    xdr::ToXdr, Address, Bytes, BytesN, Env, IntoVal, Map, Symbol, TryFromVal, Val, Vec,
 };
 
 // Define custom errors for the contract
@@ -26,353 +32,8008 @@ pub enum ContractError {
     TransferFailed = 11,
     InsufficientFunds = 12,
     AdminNotSet = 13,
+    NotAVoter = 14,
+    SubmissionNotInRound = 15,
+    InvalidAmount = 16,
+    MathOverflow = 17,
+    FundsNotDisbursed = 18,
+    AlreadyInitialized = 19,
+    InvalidDeadline = 20,
+    VotingStillOpen = 21,
+    InvalidPhase = 22,
+    RoundLocked = 23,
+    PerProjectCapExceeded = 24,
+    SelfVoteNotAllowed = 25,
+    QuorumNotReached = 26,
+    InvalidMetadata = 27,
+    AlreadySubmitted = 28,
+    SubmissionLimitReached = 29,
+    SubmissionNotApproved = 30,
+    AlreadyClaimed = 31,
+    ClaimExpired = 32,
+    ClaimWindowOpen = 33,
+    AllocationsNotComputed = 34,
+    InvalidFundingAmount = 35,
+    ContractPaused = 36,
+    UnsupportedVersion = 37,
+    NotEligible = 38,
+    AlreadyRevealed = 39,
+    CommitmentMismatch = 40,
+    InvalidDelegation = 41,
+    DisbursementHalted = 42,
+    ChallengePeriodActive = 43,
+    AddressBlocked = 44,
+    InvalidLineage = 45,
+    ProjectNotFound = 46,
+    InvalidCategory = 47,
+    AlreadyFlagged = 48,
+    InvalidAppeal = 49,
+    InvalidProposal = 50,
+}
+
+// Define the lifecycle phases of a round
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+#[repr(u32)]
+pub enum RoundPhase {
+    Submission = 0, // Accepting submissions
+    Voting = 1,     // Accepting votes
+    Closed = 2,     // Allocations computed, awaiting disbursement
+    Disbursed = 3,  // Funds paid out
+    Cancelled = 4,
+}
+
+// Define how ballots are charged against a voter's credits
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+#[repr(u32)]
+pub enum VotingMechanism {
+    Linear = 0,    // n votes on a submission cost n credits
+    Quadratic = 1, // n votes on a submission cost n² credits
+    Ranked = 2,    // Voters rank submissions with submit_ranking instead of spending credits
+    QuadraticFunding = 3, // Donors contribute to submissions and the funding matches them
+    Pairwise = 4,  // Voters compare submissions head to head with submit_comparison
+}
+
+// Define who may vote in a round besides the registry
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum TokenGate {
+    None,                   // Voters come from the registry
+    Holding(Address, i128), // Holders of at least the balance of the token may vote
+}
+
+// Define the deposit a round takes from each submission, returned unless it is spam
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum SubmissionDeposit {
+    None,                 // Submissions are free
+    Token(Address, i128), // Submitters lock the amount of the token with their submission
+}
+
+// Define the voting rules of a round
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct RoundConfig {
+    pub vote_credits: u64, // Credits each voter may spend; 0 at creation = the config's default
+    pub mechanism: VotingMechanism,
+    pub max_votes_per_submission: u64, // 0 = unlimited
+    pub allow_self_votes: bool,        // Whether submitters may vote for their own projects
+    pub min_voters: u32,               // Distinct voters needed before the round can close
+    pub max_submissions: u32,          // 0 = unlimited
+    pub inherit_global_voters: bool,   // Whether voters in the global registry may vote too
+    pub token_gate: TokenGate,         // Token holding that replaces the registry, if any
+    pub reveal_period: u64,            // Seconds to reveal committed ballots after voting; 0 = open
+    pub grace_seconds: u64,            // Late ballots count at a discount this long after voting
+    pub top_n: u32,                    // Fund only the N most-voted submissions; 0 = fund all
+    pub min_votes_to_fund: u64,        // Submissions with fewer votes are allocated nothing
+    pub max_allocation: i128,          // Most funding any one submission receives; 0 = uncapped
+    pub max_match_bps: u32,            // Most of the pool one submission receives; 0 = uncapped
+    pub cliff_seconds: u64,            // Seconds after disbursement before anything vests
+    pub duration_seconds: u64,         // Seconds payouts vest over linearly; 0 = paid at once
+    pub tranches: Vec<u32>,            // Basis points released per milestone; empty = all at once
+    pub challenge_period_seconds: u64, // Seconds after close before any payout can go out
+    pub time_weighted: bool,           // Whether later ballots in the voting window count for less
+    pub carryover_bps: u32,            // Share of a voter's banked credits added to their budget
+    pub stake_unit: i128,              // Tokens locked per credit by staking; 0 = no staking
+    pub max_stake_credits: u64,        // Most credits a voter may stake for; 0 = uncapped
+    pub attestation_budget: u64,       // Vote bonus each reviewer may attest; 0 = no attestations
+    pub submission_deposit: SubmissionDeposit, // Deposit locked by each submission, if any
+    pub categories: Vec<CategoryBudget>, // Budgets splitting the funding; empty = one pool
+    pub flag_threshold: u32, // Voter flags that suspend a submission; 0 = never suspended
+    pub appeal_window_seconds: u64, // Seconds to appeal a rejection in; 0 = no appeals
+}
+
+// Define a category of a round and the part of the funding it shares out
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct CategoryBudget {
+    pub name: Symbol,
+    pub budget: i128, // Part of the funding amount allocated within the category
+}
+
+// Define the totals a category's allocations are derived from, recorded on the round at close
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct CategoryAllocation {
+    pub distributable: i128, // Funding shared in the category, after any budget rolled into it
+    pub funded_votes: u64,   // Votes of the category's submissions that made the cutoff
+    pub cutoff_votes: u64,   // Fewest votes a submission within the top-N cutoff has
+    pub cutoff_id: u64,      // Highest id funded among submissions with the cutoff votes
+    pub capped_votes: u64,   // Fewest votes of a submission allocated the cap; u64::MAX if none
+    pub uncapped_pool: i128, // Funding shared by the submissions below the cap
+    pub uncapped_votes: u64, // Votes of the funded submissions below the cap
+}
+
+impl From<Allocation> for CategoryAllocation {
+    fn from(allocation: Allocation) -> Self {
+        CategoryAllocation {
+            distributable: allocation.distributable,
+            funded_votes: allocation.funded_votes,
+            cutoff_votes: allocation.cutoff_votes,
+            cutoff_id: allocation.cutoff_id,
+            capped_votes: allocation.capped_votes,
+            uncapped_pool: allocation.uncapped_pool,
+            uncapped_votes: allocation.uncapped_votes,
+        }
+    }
+}
+
+impl From<CategoryAllocation> for Allocation {
+    fn from(allocation: CategoryAllocation) -> Self {
+        Allocation {
+            distributable: allocation.distributable,
+            funded_votes: allocation.funded_votes,
+            cutoff_votes: allocation.cutoff_votes,
+            cutoff_id: allocation.cutoff_id,
+            capped_votes: allocation.capped_votes,
+            uncapped_pool: allocation.uncapped_pool,
+            uncapped_votes: allocation.uncapped_votes,
+        }
+    }
+}
+
+// Define a category's results in a closed round
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct CategoryResults {
+    pub name: Symbol,
+    pub budget: i128,
+    pub distributable: i128, // Funding shared in the category, after any budget rolled into it
+    pub funded_votes: u64,
+    pub allocations: Map<u64, i128>, // Allocation of each approved submission in the category
 }
 
 // Define the Round struct
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[contracttype]
 pub struct Round {
+    pub id: u64,
+    pub name: Bytes,
+    pub metadata_hash: Bytes, // Content hash of the off-chain round description, e.g. an IPFS CID
+    pub funding_amount: i128,
+    pub submission_deadline: u64, // Unix timestamp
+    pub voting_deadline: u64,     // Unix timestamp
+    pub config: RoundConfig,
+    pub phase: RoundPhase,
+    pub submissions: Vec<u64>,  // List of submission IDs
+    pub voter_count: u32,       // Distinct voters with a ballot in the round
+    pub total_votes: u64,       // Votes on approved submissions, kept up to date as ballots change
+    pub distributable: i128,    // Funding shared by the allocations once the fee is set aside at close
+    pub credits_spent: u64,     // Credits spent by the round's current ballots
+    pub tallies: Map<u64, u64>, // Votes of each approved submission, so close needn't load them
+    pub funded_votes: u64,      // Votes of the submissions that made the cutoff, set at close
+    pub cutoff_votes: u64,      // Fewest votes a submission within the top-N cutoff has
+    pub cutoff_id: u64,         // Highest id funded among submissions with the cutoff votes
+    pub capped_votes: u64,      // Fewest votes of a submission allocated the cap; u64::MAX if none
+    pub uncapped_pool: i128,    // Funding shared by the submissions below the cap
+    pub uncapped_votes: u64,    // Votes of the funded submissions below the cap
+    pub halted: bool,           // Payouts frozen by the admin, e.g. over a compromised address
+    pub overrides: Map<u64, i128>, // Allocations corrected by the admin after close
+    pub closed_at: u64,         // When voting was closed
+    pub challenge_ends: u64,    // When the challenge window after close elapses
+    pub challenge_extended: bool, // Whether the admin has extended the challenge window
+    pub voting_opened_at: u64,  // When voting was opened; 0 until then
+    pub raw_votes: u64,         // Votes of the current ballots as cast, before any weighting
+    pub abstentions: u32,       // Voters who deliberately abstained, counted towards the quorum
+    pub submission_categories: Map<u64, u32>, // Index of each submission's category, if any
+    pub category_totals: Vec<CategoryAllocation>, // Allocation totals of each category, at close
+}
+
+// Define the turnout figures of a round, live while voting and frozen at close
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct RoundStats {
+    pub voter_count: u32,      // Distinct voters with a ballot
+    pub credits_spent: u64,    // Credits spent across all ballots
+    pub votes_cast: u64,       // Votes on approved submissions, as weighted
+    pub raw_votes: u64,        // Votes of all ballots as cast, before any weighting
+    pub abstentions: u32,      // Voters who deliberately abstained instead of voting
+    pub submission_count: u32, // Submissions received, including withdrawn ones
+}
+
+impl Round {
+    // A round is active while it accepts submissions or votes
+    pub fn is_active(&self) -> bool {
+        matches!(self.phase, RoundPhase::Submission | RoundPhase::Voting)
+    }
+
+    // Committed ballots may be revealed until this timestamp; it is the voting deadline for
+    // rounds with open ballots
+    pub fn reveal_deadline(&self) -> u64 {
+        self.voting_deadline
+            .saturating_add(self.config.reveal_period)
+    }
+
+    // Late open ballots, counted at a discount, are accepted until this timestamp
+    pub fn grace_deadline(&self) -> u64 {
+        self.voting_deadline
+            .saturating_add(self.config.grace_seconds)
+    }
+
+    // The rules of the round that decide its allocations
+    pub fn allocation_config(&self) -> AllocationConfig {
+        AllocationConfig {
+            min_votes_to_fund: self.config.min_votes_to_fund,
+            top_n: self.config.top_n,
+            max_allocation: self.config.max_allocation,
+            max_match_bps: self.config.max_match_bps,
+        }
+    }
+
+    // The totals the closed round's allocations are derived from
+    pub fn allocation(&self) -> Allocation {
+        Allocation {
+            distributable: self.distributable,
+            funded_votes: self.funded_votes,
+            cutoff_votes: self.cutoff_votes,
+            cutoff_id: self.cutoff_id,
+            capped_votes: self.capped_votes,
+            uncapped_pool: self.uncapped_pool,
+            uncapped_votes: self.uncapped_votes,
+        }
+    }
+
+    // Record the totals computed for the round at close
+    pub fn set_allocation(&mut self, allocation: Allocation) {
+        self.distributable = allocation.distributable;
+        self.funded_votes = allocation.funded_votes;
+        self.cutoff_votes = allocation.cutoff_votes;
+        self.cutoff_id = allocation.cutoff_id;
+        self.capped_votes = allocation.capped_votes;
+        self.uncapped_pool = allocation.uncapped_pool;
+        self.uncapped_votes = allocation.uncapped_votes;
+    }
+
+    // The most funding one submission of the closed round may receive, if capped: the smaller
+    // of the maximum allocation and the maximum share of the distributable funding
+    pub fn allocation_cap(&self) -> Option<i128> {
+        self.allocation().cap(&self.allocation_config())
+    }
+
+    // The totals a submission's allocation is derived from: its category's in a round with
+    // categories, otherwise the round's
+    pub fn allocation_of(&self, submission_id: u64) -> Allocation {
+        self.submission_categories
+            .get(submission_id)
+            .and_then(|category| self.category_totals.get(category))
+            .map_or_else(|| self.allocation(), Allocation::from)
+    }
+
+    // Whether a submission with the given votes reached the vote threshold and made the
+    // funding cutoff set at close. Ties on votes go to the earlier submission.
+    pub fn is_funded(&self, submission_id: u64, votes: u64) -> bool {
+        self.allocation_of(submission_id)
+            .is_funded(&self.allocation_config(), submission_id, votes)
+    }
+}
+
+// Define the voting rules of a round as release 1 stored them, with a u64 allocation cap
+#[derive(Clone)]
+#[contracttype(export = false)]
+struct RoundConfigV1 {
+    vote_credits: u64,
+    mechanism: VotingMechanism,
+    max_votes_per_submission: u64,
+    allow_self_votes: bool,
+    min_voters: u32,
+    max_submissions: u32,
+    inherit_global_voters: bool,
+    token_gate: TokenGate,
+    reveal_period: u64,
+    grace_seconds: u64,
+    top_n: u32,
+    min_votes_to_fund: u64,
+    max_allocation: u64,
+    max_match_bps: u32,
+    cliff_seconds: u64,
+    duration_seconds: u64,
+    tranches: Vec<u32>,
+    challenge_period_seconds: u64,
+}
+
+// Define a round as release 1 stored it, with u64 funding amounts and allocations
+#[derive(Clone)]
+#[contracttype(export = false)]
+struct RoundV1 {
     id: u64,
+    name: Bytes,
+    metadata_hash: Bytes,
     funding_amount: u64,
-    deadline: u64, // Unix timestamp
-    is_active: bool,
-    submissions: Vec<u64>, // List of submission IDs
-    funds_disbursed: bool,
+    submission_deadline: u64,
+    voting_deadline: u64,
+    config: RoundConfigV1,
+    phase: RoundPhase,
+    submissions: Vec<u64>,
+    voter_count: u32,
+    total_votes: u64,
+    distributable: u64,
+    credits_spent: u64,
+    tallies: Map<u64, u64>,
+    funded_votes: u64,
+    cutoff_votes: u64,
+    cutoff_id: u64,
+    capped_votes: u64,
+    uncapped_pool: u64,
+    uncapped_votes: u64,
+    halted: bool,
+    overrides: Map<u64, u64>,
+    closed_at: u64,
+    challenge_ends: u64,
+    challenge_extended: bool,
+}
+
+impl RoundV1 {
+    // The round in the current layout; every u64 amount fits an i128
+    fn upgrade(self, env: &Env) -> Round {
+        let config = self.config;
+        let mut overrides = Map::new(env);
+        for (submission_id, amount) in self.overrides.iter() {
+            overrides.set(submission_id, amount as i128);
+        }
+
+        Round {
+            id: self.id,
+            name: self.name,
+            metadata_hash: self.metadata_hash,
+            funding_amount: self.funding_amount as i128,
+            submission_deadline: self.submission_deadline,
+            voting_deadline: self.voting_deadline,
+            config: RoundConfig {
+                vote_credits: config.vote_credits,
+                mechanism: config.mechanism,
+                max_votes_per_submission: config.max_votes_per_submission,
+                allow_self_votes: config.allow_self_votes,
+                min_voters: config.min_voters,
+                max_submissions: config.max_submissions,
+                inherit_global_voters: config.inherit_global_voters,
+                token_gate: config.token_gate,
+                reveal_period: config.reveal_period,
+                grace_seconds: config.grace_seconds,
+                top_n: config.top_n,
+                min_votes_to_fund: config.min_votes_to_fund,
+                max_allocation: config.max_allocation as i128,
+                max_match_bps: config.max_match_bps,
+                cliff_seconds: config.cliff_seconds,
+                duration_seconds: config.duration_seconds,
+                tranches: config.tranches,
+                challenge_period_seconds: config.challenge_period_seconds,
+                time_weighted: false,
+                carryover_bps: 0,
+                stake_unit: 0,
+                max_stake_credits: 0,
+                attestation_budget: 0,
+                submission_deposit: SubmissionDeposit::None,
+                categories: Vec::new(env),
+                flag_threshold: 0,
+                appeal_window_seconds: 0,
+            },
+            phase: self.phase,
+            submissions: self.submissions,
+            voter_count: self.voter_count,
+            total_votes: self.total_votes,
+            distributable: self.distributable as i128,
+            credits_spent: self.credits_spent,
+            tallies: self.tallies,
+            funded_votes: self.funded_votes,
+            cutoff_votes: self.cutoff_votes,
+            cutoff_id: self.cutoff_id,
+            capped_votes: self.capped_votes,
+            uncapped_pool: self.uncapped_pool as i128,
+            uncapped_votes: self.uncapped_votes,
+            halted: self.halted,
+            overrides,
+            closed_at: self.closed_at,
+            challenge_ends: self.challenge_ends,
+            challenge_extended: self.challenge_extended,
+            voting_opened_at: 0,
+            raw_votes: self.total_votes,
+            abstentions: 0,
+            submission_categories: Map::new(env),
+            category_totals: Vec::new(env),
+        }
+    }
+}
+
+// Define a submission as releases 1 and 2 stored it, before continuation links
+#[derive(Clone)]
+#[contracttype(export = false)]
+struct SubmissionV1 {
+    id: u64,
+    round_id: u64,
+    submitter: Address,
+    payout_address: Address,
+    name: Bytes,
+    metadata_hash: Bytes,
+    total_votes: u64,
+    status: SubmissionStatus,
+    withdrawn: bool,
+}
+
+impl SubmissionV1 {
+    // The submission in the current layout, continuing from no earlier submission
+    fn upgrade(self) -> Submission {
+        Submission {
+            id: self.id,
+            round_id: self.round_id,
+            submitter: self.submitter,
+            payout_address: self.payout_address,
+            name: self.name,
+            metadata_hash: self.metadata_hash,
+            total_votes: self.total_votes,
+            status: self.status,
+            withdrawn: self.withdrawn,
+            previous_submission_id: 0,
+            project_id: 0,
+        }
+    }
+}
+
+// Define the roles the admin can delegate; the admin itself holds every role
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+#[repr(u32)]
+pub enum Role {
+    RoundManager = 0, // Creates and closes rounds
+    Treasurer = 1,    // Disburses funds
+    Pauser = 2,       // Pauses and unpauses the contract
+}
+
+// Define the review states of a submission
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+#[repr(u32)]
+pub enum SubmissionStatus {
+    Pending = 0,  // Awaiting admin review
+    Approved = 1, // Eligible for votes and funding
+    Rejected = 2,
+    Suspended = 3, // Flagged by enough voters; takes no votes until the admin reviews it
+    UnderAppeal = 4, // Rejection appealed by the submitter, awaiting the admin's ruling
+}
+
+// Define the Submission struct
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Submission {
+    pub id: u64,
+    pub round_id: u64,
+    pub submitter: Address,
+    pub payout_address: Address, // Receives the submission's funding; defaults to the submitter
+    pub name: Bytes,
+    pub metadata_hash: Bytes, // Content hash of the off-chain project description, e.g. an IPFS CID
+    pub total_votes: u64,
+    pub status: SubmissionStatus,
+    pub withdrawn: bool, // Withdrawn by the submitter; no longer part of the round
+    pub previous_submission_id: u64, // Continues an earlier round's submission; 0 = none
+    pub project_id: u64, // Catalog project the submission was entered from; 0 = none
+}
+
+// Define a project in the catalog, entered into rounds as submissions with its details
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Project {
+    pub id: u64,
+    pub owner: Address,
+    pub name: Bytes,
+    pub metadata_hash: Bytes, // Content hash of the off-chain project description, e.g. an IPFS CID
+    pub payout: Address,      // Payout address copied into the project's submissions
+}
+
+// Define a submitter's appeal against the rejection of their submission
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Appeal {
+    pub evidence_hash: Bytes, // Content hash of the off-chain evidence, e.g. an IPFS CID
+    pub filed_at: u64,        // Unix timestamp
+    pub resolved_at: u64,     // When the admin ruled on the appeal; 0 until then
+    pub upheld: bool,         // Whether the rejection was upheld, making it final
+    pub compensation: i128,   // Funding paid from the remainder for an overturn after payouts
+}
+
+// Define the council whose members approve treasury actions together
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Council {
+    pub members: Vec<Address>,
+    pub threshold: u32,    // Approvals that execute a proposal
+    pub proposal_ttl: u64, // Seconds a proposal can be approved for
+    pub term: u32,         // Councils set so far; proposals lapse when the council is replaced
+}
+
+// Define a treasury action the council can take, with its parameters
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum ProposalAction {
+    Disburse(u64, Address, u32, u32),   // (round_id, token, start, count) of disburse_funds
+    Sweep(u64, Address, Address),       // (round_id, token, to) of sweep_unclaimed
+    Override(u64, u64, i128, Symbol),   // (round_id, submission_id, new_amount, reason)
+    SetCouncil(Vec<Address>, u32, u64), // (members, threshold, proposal_ttl) of a new council
+}
+
+// Define a proposal to the council and the approvals it has gathered
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Proposal {
+    pub id: u64,
+    pub proposer: Address,
+    pub action: ProposalAction,
+    pub approvals: Vec<Address>, // Members who approved, the proposer first
+    pub term: u32,               // Term of the council the proposal was made to
+    pub expires_at: u64,         // Unix timestamp after which it can't be approved
+    pub executed: bool,
+}
+
+// Define the protocol fee taken from each round's funding
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct FeeConfig {
+    pub bps: u32, // Share of the funding amount in basis points
+    pub recipient: Address,
+}
+
+// Define where the protocol fee is paid
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum FeeRecipient {
+    None,             // No fee is taken
+    Account(Address), // The fee is transferred to the address
+}
+
+// Define the contract-wide settings, set at initialization and amended with update_config
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Config {
+    pub admin: Address,              // Address administering the contract
+    pub default_vote_credits: u64,   // Credits of rounds created without their own
+    pub fee_bps: u32,                // Protocol fee in basis points of a round's funding
+    pub fee_recipient: FeeRecipient, // Recipient of the protocol fee
+    pub paused: bool, // Whether submissions, voting, closing and disbursement are paused
+    pub timelock_delay: u64, // Seconds a scheduled operation waits before it can be executed
+    pub timelocked: Vec<TimelockedOp>, // Operations that must be scheduled rather than called
+}
+
+// Define the sensitive admin operations that can be put behind the timelock
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+#[repr(u32)]
+pub enum TimelockedOp {
+    SetFee = 0,       // set_fee
+    UpdateConfig = 1, // update_config, including the timelock settings themselves
+    Override = 2,     // override_allocation
+    Upgrade = 3,      // upgrade
+}
+
+// Define a timelocked operation with its parameters
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum TimelockAction {
+    SetFee(u32, Address),             // (bps, recipient) of set_fee
+    UpdateConfig(Config),             // Config of update_config
+    Override(u64, u64, i128, Symbol), // (round_id, submission_id, new_amount, reason)
+    Upgrade(BytesN<32>),              // New code hash of upgrade
+}
+
+impl TimelockAction {
+    // The operation the action performs
+    pub fn op(&self) -> TimelockedOp {
+        match self {
+            TimelockAction::SetFee(..) => TimelockedOp::SetFee,
+            TimelockAction::UpdateConfig(..) => TimelockedOp::UpdateConfig,
+            TimelockAction::Override(..) => TimelockedOp::Override,
+            TimelockAction::Upgrade(..) => TimelockedOp::Upgrade,
+        }
+    }
+}
+
+// Define an operation waiting in the timelock queue
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct ScheduledAction {
+    pub id: u64,
+    pub action: TimelockAction,
+    pub ready_at: u64, // Unix timestamp from which it can be executed
+}
+
+// Define an administrative action recorded in the audit log
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct AuditEntry {
+    pub action: Symbol,     // What was done, such as create, close or disburse
+    pub actor: Address,     // Address that took the action
+    pub round_id: u64,      // Round acted on, or 0 for contract-wide settings
+    pub timestamp: u64,     // Ledger timestamp of the action
+    pub payload: Vec<i128>, // Figures describing the action, laid out per action
+}
+
+// Define a withdrawal of stuck tokens waiting out its delay
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct EmergencyWithdrawal {
+    pub id: u64,
+    pub token: Address,
+    pub amount: i128,
+    pub to: Address,
+    pub ready_at: u64, // Unix timestamp from which it can be executed
+}
+
+// Define a page of the audit log
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct AuditLogPage {
+    pub total: u64, // Entries recorded so far
+    pub entries: Vec<AuditEntry>,
+}
+
+// Define a submission's payout in a token that vests over its round's vesting schedule
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct VestingPosition {
+    pub total: i128,   // Amount vesting
+    pub claimed: i128, // Amount transferred so far
+    pub start: u64,    // When the payout was disbursed and the schedule began
+}
+
+// Define a voter's credit budget in a round
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct CreditBalance {
+    pub total: u64,     // Credits the voter may spend in the round
+    pub remaining: u64, // Credits not yet spent on the voter's allocation
+}
+
+// Define the data of the `RND_CREAT` event, published when a round is created
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct RoundCreatedEvent {
+    pub round_id: u64,
+    pub creator: Address, // Admin or round manager who created the round
+    pub funding_amount: i128,
+    pub submission_deadline: u64,
+    pub voting_deadline: u64,
+    pub mechanism: VotingMechanism,
+}
+
+// Define the data of the `PROJ_SUB` event, published when a project is submitted
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct ProjectSubmittedEvent {
+    pub submission_id: u64,
+    pub round_id: u64,
+    pub submitter: Address,
+    pub previous_submission_id: u64, // Submission continued from an earlier round; 0 = none
+}
+
+// Define the data of the `VOTE_ALC` event, published when a voter casts a ballot
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct VotesAllocatedEvent {
+    pub round_id: u64,
+    pub voter: Address,
+    pub allocations: Map<u64, u64>, // Voter's whole allocation in the round after the ballot
+    pub credits_spent: u64,         // Credits the voter has spent in the round
+}
+
+// Define the data of the `VOTE_CLSD` event, published when voting closes
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct VotingClosedEvent {
+    pub round_id: u64,
+    pub total_votes: u64,
+    pub funded_votes: u64,   // Votes of the submissions funded
+    pub distributable: i128, // Funding shared by the allocations once the fee is set aside
+    pub fee: i128,           // Protocol fee set aside
+    pub allocations_hash: BytesN<32>, // SHA-256 of the XDR of the allocations as closed
+}
+
+// Define the data of the `FUND_DISB` event, published once a token is fully paid out
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct FundsDisbursedEvent {
+    pub round_id: u64,
+    pub token: Address,
+    pub pool: i128,      // Amount of the token shared out by the allocations
+    pub disbursed: i128, // Amount paid to the submissions and as the fee
+    pub remainder: i128, // Rounding remainder left in escrow
+}
+
+// Define the storage keys of the contract
+#[derive(Clone)]
+#[contracttype]
+pub enum DataKey {
+    // Instance storage
+    Config,
+    PendingAdmin,
+    NextRoundId,
+    NextSubmissionId,
+    WasmHash,       // Hash of the code installed by the last upgrade
+    StorageVersion, // Release whose storage layout the instance holds
+    // Persistent storage
+    Role(Address, Role),
+    Voter(Address),
+    VoterWeight(Address), // Credits granted to a voter in every round, overriding the default
+    Blacklisted(Address), // Addresses barred from submitting, voting and receiving payouts
+    Blacklist,            // Blacklisted addresses in the order they were blacklisted
+    RoundVoter(u64, Address),
+    RoundVoters(u64),     // Round-scoped voters in registration order
+    EligibilityRoot(u64), // Merkle root of the voters admitted to a round by proof
+    Round(u64),
+    Submission(u64),
+    Submitter(u64, Address),       // (round_id, submitter) -> submission_id
+    VoterAllocation(u64, Address), // (round_id, voter) -> votes per submission
+    VoterCredits(u64, Address),    // (round_id, voter) -> credits spent
+    RawVotes(u64, Address),        // (round_id, voter) -> votes of the ballot as cast
+    Commitment(u64, Address),      // (round_id, voter) -> hash of the ballot to reveal
+    Delegate(u64, Address),        // (round_id, delegator) -> (delegate, credits handed over)
+    DelegatedCredits(u64, Address), // (round_id, delegate) -> credits received
+    Contribution(u64, Address),    // (round_id, donor) -> amount contributed per submission
+    ContributionToken(u64),        // Token every contribution to a round is made in
+    ContributionRoots(u64),        // submission_id -> sum of the square roots of contributions
+    Remainder(u64, Address), // (round_id, token) -> rounding remainder once all is paid
+    Escrow(u64, Address),    // (round_id, token) -> escrowed amount
+    EscrowTokens(u64),       // Tokens escrowed for a round; the first is its funding token
+    TokenPool(u64, Address), // (round_id, token) -> pool of another token, fixed at close
+    Donation(u64, Address), // (round_id, donor) -> amount donated per token
+    Donors(u64),            // Donors to a round in order of their first donation
+    Deposit(u64, Address),  // (round_id, depositor) -> amount deposited per token
+    Depositors(u64),        // Depositors to a round in order of their first deposit
+    RefundTo(u64),          // Recipient of a cancelled round's escrow no funder is owed
+    RefundCursor(u64),      // Funders of a cancelled round refunded so far
+    PayoutSplits(u64),
+    ClaimDeadline(u64, Address), // (round_id, token) -> deadline of claims in a finalized token
+    Claimed(u64, Address),       // (submission_id, token) -> whether the allocation was paid
+    FeeDue(u64),
+    Disbursed(u64, Address), // Pool transferred out so far in the token, including the fee
+    VotesPaid(u64, Address), // Votes whose allocations have been paid out in the token
+    FinalStats(u64), // Round statistics frozen when voting closed
+    Vesting(u64, Address), // (submission_id, token) -> payout vesting instead of paid at once
+    TranchesReleased(u64), // Milestone tranches of a round released so far; the first up front
+    Paid(u64, Address),    // (submission_id, token) -> amount paid of a partly released allocation
+    Index(IndexKey),       // Indexes kept for queries
+    Credit(CreditKey),     // Banked, carried over and staked credits
+    Ballot(BallotKey),     // Voters' choices in a round other than their allocations
+    Project(ProjectKey),   // Standing of submissions beyond their votes
 }
 
-// Define the Submission struct
-#[derive(Clone, Debug, Eq, PartialEq)]
-#[contracttype]
-pub struct Submission {
-    id: u64,
-    round_id: u64,
-    submitter: Address,
-    total_votes: u64,
-}
+// Define the storage keys of submissions' standing beyond their votes, nested under
+// `DataKey::Project`
+#[derive(Clone)]
+#[contracttype]
+pub enum ProjectKey {
+    Reviewer(Address),          // Registration number of a trusted reviewer
+    ReviewerGrants,             // Reviewer registrations made so far
+    Attesters(u64),             // Reviewers with attestations in a round
+    Attestations(u64, Address), // (round_id, reviewer) -> attestations awaiting close
+    Catalog(u64),               // Project registered in the catalog
+    NextProjectId,              // Projects registered so far
+    Deposits(u64),              // Submission deposits a round still holds, by submission
+    Flag(u64, Address),         // (submission_id, reporter) -> reason the reporter gave
+    FlagCount(u64),             // Flags raised against a submission since its last review
+    RejectedAt(u64),            // When a submission was last rejected
+    Appeal(u64),                // Appeal against a submission's rejection, once filed
+}
+
+// Protocol fee set aside when a round closes: its recipient and amount
+type FeeDue = (Address, i128);
+
+// Define a reviewer's attestations in a round, applied when the round closes if the
+// reviewer still holds the registration they were made under
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype(export = false)]
+struct Attestations {
+    grant: u32,             // Registration of the reviewer the attestations were made under
+    weights: Map<u64, u64>, // Vote bonus attested to each submission
+}
+
+// Define the storage keys of voters' choices other than allocations, nested under
+// `DataKey::Ballot`
+#[derive(Clone)]
+#[contracttype]
+pub enum BallotKey {
+    Abstained(u64, Address),            // (round_id, voter) -> whether the voter abstained
+    Comparison(u64, Address, u64, u64), // (round_id, voter, lower id, higher id) -> winner
+    PairMargin(u64, u64, u64),          // (round_id, lower id, higher id) -> lower id's net wins
+}
+
+// Define the storage keys of banked and staked credits, nested under `DataKey::Credit`
+#[derive(Clone)]
+#[contracttype]
+pub enum CreditKey {
+    Banked(Address),             // Credits a voter has banked and not yet carried over
+    BankedFrom(u64, Address),    // (round_id, voter) -> credits banked from the round
+    Carryover(u64, Address),     // (round_id, voter) -> banked credits added to the budget
+    StakeToken(u64),             // Token a round's stakes are locked in, set by the first one
+    Staked(u64, Address),        // (round_id, voter) -> tokens locked until the round closes
+    StakedCredits(u64, Address), // (round_id, voter) -> credits earned by staking
+    CastVotes(u64, Address),     // (round_id, voter) -> votes as cast and credits charged
+}
+
+// Define the storage keys of the indexes kept for queries, nested under `DataKey::Index`
+#[derive(Clone)]
+#[contracttype]
+pub enum IndexKey {
+    SubmitterSubmissions(Address),     // Latest submissions of a submitter across rounds
+    VoterRounds(Address),              // Rounds a voter has cast a ballot in, in order of the first
+    SubmissionVoters(u64, u32),        // (submission_id, chunk) -> voters and their votes
+    SubmissionVoterCount(u64),         // Voters with votes on a submission
+    SubmissionVoterSlot(u64, Address), // (submission_id, voter) -> position in the voters
+}
+
+// Define the storage keys of the council and its proposals, the timelock queue, round
+// operators, the audit log and emergency withdrawals. `DataKey` has no room for another
+// family, so they are persistent keys of their own, named apart from `DataKey`'s variants.
+#[derive(Clone)]
+#[contracttype]
+pub enum GovernanceKey {
+    Council,              // Members approving treasury actions, once set
+    Proposal(u64),        // Treasury action proposed to the council
+    NextProposalId,       // Proposals made so far
+    Scheduled(u64),       // Operation waiting in the timelock queue
+    NextScheduleId,       // Operations scheduled so far
+    Operator(u64),        // Program manager running the round alongside the admin
+    AuditChunk(u64),      // Chunk of `AUDIT_LOG_CHUNK` audit log entries
+    AuditCount,           // Audit log entries recorded so far
+    Withdrawal(u64),      // Emergency withdrawal waiting out its delay
+    NextWithdrawalId,     // Emergency withdrawals scheduled so far
+    Obligations(Address), // Total the contract owes in a token
+}
+
+// Define the instance keys release 2 stored the admin, fee and pause flag under, before they
+// were folded into `Config`
+#[derive(Clone)]
+#[contracttype(export = false)]
+enum DataKeyV2 {
+    Admin,
+    FeeConfig,
+    Paused,
+}
+
+// Define the main contract structure
+#[contract]
+pub struct RetroPGFContract;
+
+#[contractimpl]
+impl RetroPGFContract {
+    pub fn initialize(env: Env, admin: Address) -> Result<(), ContractError> {
+        // The admin can only be set once, including by a deployment awaiting a migration
+        if env.storage().instance().has(&DataKey::Config)
+            || env.storage().instance().has(&DataKeyV2::Admin)
+            || env.storage().instance().has(&symbol_short!("ADMIN"))
+        {
+            return Err(ContractError::AlreadyInitialized);
+        }
+
+        let config = Config {
+            admin,
+            default_vote_credits: Self::DEFAULT_VOTE_CREDITS,
+            fee_bps: 0,
+            fee_recipient: FeeRecipient::None,
+            paused: false,
+            timelock_delay: 0,
+            timelocked: Vec::new(&env),
+        };
+        Self::write_config(&env, &config);
+        env.storage()
+            .instance()
+            .set(&DataKey::StorageVersion, &Self::VERSION);
+        Self::extend_instance(&env);
+        Ok(())
+    }
+
+    // Function for the current admin to nominate a new admin
+    pub fn transfer_admin(env: Env, new_admin: Address) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        let admin = Self::require_admin(&env)?;
+
+        env.storage()
+            .instance()
+            .set(&DataKey::PendingAdmin, &new_admin);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("ADM_XFER"), symbol_short!("pending")),
+            (admin, new_admin),
+        );
+
+        Ok(())
+    }
+
+    // Function for the nominated admin to accept the role
+    pub fn accept_admin(env: Env) -> Result<(), ContractError> {
+        let pending_key = DataKey::PendingAdmin;
+        let new_admin = env
+            .storage()
+            .instance()
+            .get::<DataKey, Address>(&pending_key)
+            .ok_or(ContractError::Unauthorized)?;
+
+        // Require authorization from the pending admin
+        new_admin.require_auth();
+
+        let mut config = Self::get_config(env.clone())?;
+        let old_admin = config.admin;
+        config.admin = new_admin.clone();
+        Self::write_config(&env, &config);
+        env.storage().instance().remove(&pending_key);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("ADM_XFER"), symbol_short!("accepted")),
+            (old_admin, new_admin),
+        );
+
+        Ok(())
+    }
+
+    // Function to get the current admin
+    pub fn get_admin(env: Env) -> Result<Address, ContractError> {
+        Ok(Self::get_config(env)?.admin)
+    }
+
+    // Function to get the contract-wide settings
+    pub fn get_config(env: Env) -> Result<Config, ContractError> {
+        env.storage()
+            .instance()
+            .get::<DataKey, Config>(&DataKey::Config)
+            .ok_or(ContractError::AdminNotSet)
+    }
+
+    // Helper function to store the contract-wide settings
+    fn write_config(env: &Env, config: &Config) {
+        env.storage().instance().set(&DataKey::Config, config);
+    }
+
+    // Credits of rounds created without their own until the admin configures a default
+    const DEFAULT_VOTE_CREDITS: u64 = 100;
+
+    // Function for the admin to amend the contract-wide settings. The admin itself changes
+    // hands through transfer_admin, so the nominee has to accept.
+    pub fn update_config(env: Env, config: Config) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        let admin = Self::require_admin(&env)?;
+        Self::require_not_timelocked(&env, TimelockedOp::UpdateConfig)?;
+
+        Self::apply_config(&env, &admin, config)
+    }
+
+    // Helper function to validate and store new contract-wide settings, for update_config or
+    // a scheduled operation
+    fn apply_config(env: &Env, actor: &Address, config: Config) -> Result<(), ContractError> {
+        if config.admin != Self::get_admin(env.clone())? {
+            return Err(ContractError::Unauthorized);
+        }
+
+        // Rounds created without their own credits still need a budget to vote with
+        if config.default_vote_credits == 0 {
+            return Err(ContractError::InvalidAmount);
+        }
+
+        // The fee is capped, and a fee needs somewhere to go
+        if config.fee_bps > Self::MAX_FEE_BPS
+            || (config.fee_bps > 0 && config.fee_recipient == FeeRecipient::None)
+        {
+            return Err(ContractError::InvalidAmount);
+        }
+
+        Self::write_config(env, &config);
+        let payload = Vec::from_array(
+            env,
+            [config.fee_bps.into(), config.default_vote_credits.into()],
+        );
+        Self::record_audit(env, symbol_short!("config"), actor, 0, payload);
+
+        // Emit event
+        env.events().publish((symbol_short!("CFG_UPD"),), config);
+
+        Ok(())
+    }
+
+    // Helper function to load the admin and require its authorization
+    fn require_admin(env: &Env) -> Result<Address, ContractError> {
+        let admin = Self::get_admin(env.clone()).map_err(|_| ContractError::Unauthorized)?;
+        admin.require_auth();
+        Self::extend_instance(env);
+        Ok(admin)
+    }
+
+    // Function to move entries written under the legacy `(Symbol, ...)` tuple keys to their
+    // `DataKey` equivalents. Voter-scoped entries can't be enumerated on-chain, so the admin
+    // passes the addresses that were registered or cast ballots.
+    pub fn migrate_storage(env: Env, voters: Vec<Address>) -> Result<(), ContractError> {
+        // The admin itself still lives under the legacy key until it has been migrated
+        let admin = env
+            .storage()
+            .instance()
+            .get::<Symbol, Address>(&symbol_short!("ADMIN"))
+            .ok_or(ContractError::AlreadyInitialized)?;
+        admin.require_auth();
+
+        let instance_entries = [
+            (symbol_short!("PEND_ADM"), DataKey::PendingAdmin),
+            (symbol_short!("NEXT_RND"), DataKey::NextRoundId),
+            (symbol_short!("NEXT_SUB"), DataKey::NextSubmissionId),
+        ];
+        for (old, new) in instance_entries {
+            if let Some(value) = env.storage().instance().get::<Symbol, Val>(&old) {
+                env.storage().instance().set(&new, &value);
+                env.storage().instance().remove(&old);
+            }
+        }
+
+        // The admin and fee move to release 2's keys, which the config is built from below
+        let settings_entries = [
+            (symbol_short!("ADMIN"), DataKeyV2::Admin),
+            (symbol_short!("FEE_CFG"), DataKeyV2::FeeConfig),
+        ];
+        for (old, new) in settings_entries {
+            if let Some(value) = env.storage().instance().get::<Symbol, Val>(&old) {
+                env.storage().instance().set(&new, &value);
+                env.storage().instance().remove(&old);
+            }
+        }
+
+        let next_round_id = env
+            .storage()
+            .instance()
+            .get::<DataKey, u64>(&DataKey::NextRoundId)
+            .unwrap_or(0);
+        for round_id in 1..=next_round_id {
+            let round_entries = [
+                (symbol_short!("ROUND"), DataKey::Round(round_id)),
+                (symbol_short!("ESC_TKNS"), DataKey::EscrowTokens(round_id)),
+                (symbol_short!("FEE_DUE"), DataKey::FeeDue(round_id)),
+            ];
+            for (prefix, new) in round_entries {
+                Self::migrate_entry(&env, (prefix, round_id), new);
+            }
+
+            let tokens =
+                Self::read_persistent::<Vec<Address>>(&env, &DataKey::EscrowTokens(round_id))
+                    .unwrap_or(Vec::new(&env));
+            for token in tokens.iter() {
+                Self::migrate_entry(
+                    &env,
+                    (symbol_short!("ESCROW"), round_id, token.clone()),
+                    DataKey::Escrow(round_id, token),
+                );
+            }
+
+            // Legacy rounds were paid out in a single token, which becomes their funding token
+            if let Some(token) = tokens.get(0) {
+                let token_entries = [
+                    (
+                        symbol_short!("REMAINDR"),
+                        DataKey::Remainder(round_id, token.clone()),
+                    ),
+                    (
+                        symbol_short!("CLM_DDL"),
+                        DataKey::ClaimDeadline(round_id, token.clone()),
+                    ),
+                    (
+                        symbol_short!("DISBURSD"),
+                        DataKey::Disbursed(round_id, token),
+                    ),
+                ];
+                for (prefix, new) in token_entries {
+                    Self::migrate_entry(&env, (prefix, round_id), new);
+                }
+            }
+            env.storage()
+                .persistent()
+                .remove(&(symbol_short!("CLM_TKN"), round_id));
+
+            for voter in voters.iter() {
+                Self::migrate_entry(
+                    &env,
+                    (symbol_short!("VOTR_ALC"), round_id, voter.clone()),
+                    DataKey::VoterAllocation(round_id, voter.clone()),
+                );
+                Self::migrate_entry(
+                    &env,
+                    (symbol_short!("VOTR_CRD"), round_id, voter.clone()),
+                    DataKey::VoterCredits(round_id, voter),
+                );
+            }
+        }
+
+        let next_submission_id = env
+            .storage()
+            .instance()
+            .get::<DataKey, u64>(&DataKey::NextSubmissionId)
+            .unwrap_or(0);
+        for submission_id in 1..=next_submission_id {
+            let submission_entries = [
+                (
+                    symbol_short!("SUBMISSN"),
+                    DataKey::Submission(submission_id),
+                ),
+                (
+                    symbol_short!("PAY_SPLT"),
+                    DataKey::PayoutSplits(submission_id),
+                ),
+            ];
+            for (prefix, new) in submission_entries {
+                Self::migrate_entry(&env, (prefix, submission_id), new);
+            }
+
+            // Legacy submissions hold the first release's layout until `migrate_layout` runs
+            let legacy = env
+                .storage()
+                .persistent()
+                .get::<DataKey, SubmissionV1>(&DataKey::Submission(submission_id));
+            if let Some(submission) = legacy {
+                if let Some(token) = Self::funding_token(&env, submission.round_id) {
+                    Self::migrate_entry(
+                        &env,
+                        (symbol_short!("CLAIMED"), submission_id),
+                        DataKey::Claimed(submission_id, token),
+                    );
+                }
+
+                Self::migrate_entry(
+                    &env,
+                    (
+                        symbol_short!("SUBMITTR"),
+                        submission.round_id,
+                        submission.submitter.clone(),
+                    ),
+                    DataKey::Submitter(submission.round_id, submission.submitter),
+                );
+            }
+        }
+
+        for voter in voters.iter() {
+            Self::migrate_entry(
+                &env,
+                (symbol_short!("VOTER"), voter.clone()),
+                DataKey::Voter(voter),
+            );
+        }
+
+        // Legacy entries hold the first release's layout; bring them up to the current one
+        for version in 1..Self::VERSION {
+            Self::migrate_layout(&env, version);
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::StorageVersion, &Self::VERSION);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("MIGRATED"),),
+            (next_round_id, next_submission_id),
+        );
+
+        Ok(())
+    }
+
+    // Helper function to move a persistent entry from a legacy key to its `DataKey`
+    fn migrate_entry<K: IntoVal<Env, Val>>(env: &Env, old: K, new: DataKey) {
+        let storage = env.storage().persistent();
+        if let Some(value) = storage.get::<K, Val>(&old) {
+            storage.set(&new, &value);
+            storage.remove(&old);
+            Self::extend_persistent(env, &new);
+        }
+    }
+
+    // Contract release, bumped whenever a new version of the code is shipped
+    const VERSION: u32 = 4;
+
+    // Function to get the release of the running code
+    pub fn version(_env: Env) -> u32 {
+        Self::VERSION
+    }
+
+    // Function for the admin to replace the contract's code. Call `migrate` afterwards so
+    // the new code can convert storage written by the old one.
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+        Self::require_not_timelocked(&env, TimelockedOp::Upgrade)?;
+
+        Self::apply_upgrade(&env, new_wasm_hash);
+
+        Ok(())
+    }
+
+    // Helper function to replace the contract's code, for upgrade or a scheduled operation
+    fn apply_upgrade(env: &Env, new_wasm_hash: BytesN<32>) {
+        let old_wasm_hash = env
+            .storage()
+            .instance()
+            .get::<DataKey, BytesN<32>>(&DataKey::WasmHash);
+        env.deployer()
+            .update_current_contract_wasm(new_wasm_hash.clone());
+        env.storage()
+            .instance()
+            .set(&DataKey::WasmHash, &new_wasm_hash);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("UPGRADED"),), (old_wasm_hash, new_wasm_hash));
+    }
+
+    // Function to get the release whose storage layout the instance holds. Instances set up
+    // before the layout was versioned hold the first release's layout.
+    pub fn storage_version(env: Env) -> u32 {
+        env.storage()
+            .instance()
+            .get::<DataKey, u32>(&DataKey::StorageVersion)
+            .unwrap_or(1)
+    }
+
+    // Function for the admin to bring storage written by an older release up to the running
+    // release's layout after an upgrade
+    pub fn migrate(env: Env) -> Result<(), ContractError> {
+        // Require authorization from the admin, which releases before the config stored on
+        // its own key
+        let admin = match Self::get_admin(env.clone()) {
+            Ok(admin) => admin,
+            Err(_) => env
+                .storage()
+                .instance()
+                .get::<DataKeyV2, Address>(&DataKeyV2::Admin)
+                .ok_or(ContractError::Unauthorized)?,
+        };
+        admin.require_auth();
+        Self::extend_instance(&env);
+
+        // Storage written by a newer release can't be converted back
+        let from = Self::storage_version(env.clone());
+        if from > Self::VERSION {
+            return Err(ContractError::UnsupportedVersion);
+        }
+
+        for version in from..Self::VERSION {
+            Self::migrate_layout(&env, version);
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::StorageVersion, &Self::VERSION);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("STORE_MIG"),), (from, Self::VERSION));
+
+        Ok(())
+    }
+
+    // Helper function to convert storage written by the given release to the next release's
+    // layout. Each release that changes the layout adds its conversion here.
+    fn migrate_layout(env: &Env, from: u32) {
+        match from {
+            1 => Self::migrate_amounts_to_i128(env),
+            2 => {
+                Self::migrate_settings_to_config(env);
+                Self::migrate_submissions(env);
+            }
+            3 => Self::seed_obligations(env),
+            _ => {}
+        }
+    }
+
+    // Helper function to total what the contract owes in each token, which releases before 4
+    // did not keep: every round's escrow, the submission deposits it holds and the vesting
+    // payouts not yet claimed. Stakes are stored per voter and can't be enumerated, so every
+    // token a round takes stakes in counts as owed in full until it is paid out.
+    fn seed_obligations(env: &Env) {
+        let next_round_id = env
+            .storage()
+            .instance()
+            .get::<DataKey, u64>(&DataKey::NextRoundId)
+            .unwrap_or(0);
+        let mut obligations: Map<Address, i128> = Map::new(env);
+        let mut stake_tokens: Vec<Address> = Vec::new(env);
+        for round_id in 1..=next_round_id {
+            let Some(round) = Self::read_persistent::<Round>(env, &DataKey::Round(round_id)) else {
+                continue;
+            };
+
+            let tokens_key = DataKey::EscrowTokens(round_id);
+            let tokens =
+                Self::read_persistent::<Vec<Address>>(env, &tokens_key).unwrap_or(Vec::new(env));
+            for token in tokens.iter() {
+                let mut owed =
+                    Self::read_persistent::<i128>(env, &DataKey::Escrow(round_id, token.clone()))
+                        .unwrap_or(0);
+                for submission_id in round.submissions.iter() {
+                    let vesting_key = DataKey::Vesting(submission_id, token.clone());
+                    if let Some(position) =
+                        Self::read_persistent::<VestingPosition>(env, &vesting_key)
+                    {
+                        owed += position.total - position.claimed;
+                    }
+                }
+                obligations.set(token.clone(), obligations.get(token).unwrap_or(0) + owed);
+            }
+
+            if let SubmissionDeposit::Token(token, _) = &round.config.submission_deposit {
+                let deposits_key = DataKey::Project(ProjectKey::Deposits(round_id));
+                let held: i128 = Self::read_persistent::<Map<u64, i128>>(env, &deposits_key)
+                    .map_or(0, |deposits| deposits.values().iter().sum());
+                let owed = obligations.get(token.clone()).unwrap_or(0) + held;
+                obligations.set(token.clone(), owed);
+            }
+
+            let stake_token_key = DataKey::Credit(CreditKey::StakeToken(round_id));
+            if let Some(token) = Self::read_persistent::<Address>(env, &stake_token_key) {
+                if !stake_tokens.contains(&token) {
+                    stake_tokens.push_back(token);
+                }
+            }
+        }
+
+        for token in stake_tokens.iter() {
+            let balance = TokenClient::new(env, &token).balance(&env.current_contract_address());
+            obligations.set(token, balance);
+        }
+        for (token, owed) in obligations.iter() {
+            Self::write_persistent(env, &GovernanceKey::Obligations(token), &owed);
+        }
+    }
+
+    // Helper function to give the submissions releases 1 and 2 stored a continuation link.
+    // Submissions already in the current layout are left alone.
+    fn migrate_submissions(env: &Env) {
+        let next_submission_id = env
+            .storage()
+            .instance()
+            .get::<DataKey, u64>(&DataKey::NextSubmissionId)
+            .unwrap_or(0);
+        for submission_id in 1..=next_submission_id {
+            let key = DataKey::Submission(submission_id);
+            let Some(value) = env.storage().persistent().get::<DataKey, Val>(&key) else {
+                continue;
+            };
+            if let Ok(submission) = SubmissionV1::try_from_val(env, &value) {
+                Self::write_persistent(env, &key, &submission.upgrade());
+            }
+        }
+    }
+
+    // Helper function to fold the admin, protocol fee and pause flag release 2 stored on
+    // their own keys into the config
+    fn migrate_settings_to_config(env: &Env) {
+        let instance = env.storage().instance();
+        let Some(admin) = instance.get::<DataKeyV2, Address>(&DataKeyV2::Admin) else {
+            return;
+        };
+        let fee = instance.get::<DataKeyV2, FeeConfig>(&DataKeyV2::FeeConfig);
+
+        let config = Config {
+            admin,
+            default_vote_credits: Self::DEFAULT_VOTE_CREDITS,
+            fee_bps: fee.as_ref().map_or(0, |fee| fee.bps),
+            fee_recipient: fee.map_or(FeeRecipient::None, |fee| {
+                FeeRecipient::Account(fee.recipient)
+            }),
+            paused: instance
+                .get::<DataKeyV2, bool>(&DataKeyV2::Paused)
+                .unwrap_or(false),
+            timelock_delay: 0,
+            timelocked: Vec::new(env),
+        };
+        for key in [DataKeyV2::Admin, DataKeyV2::FeeConfig, DataKeyV2::Paused] {
+            instance.remove(&key);
+        }
+        Self::write_config(env, &config);
+    }
+
+    // Helper function to convert the u64 funding amounts release 1 stored to i128: each
+    // round, with its allocation cap and overrides, its fee due, and in each of its tokens the
+    // pool fixed at close and the rounding remainder
+    fn migrate_amounts_to_i128(env: &Env) {
+        let next_round_id = env
+            .storage()
+            .instance()
+            .get::<DataKey, u64>(&DataKey::NextRoundId)
+            .unwrap_or(0);
+        for round_id in 1..=next_round_id {
+            let round_key = DataKey::Round(round_id);
+            if let Some(round) = env
+                .storage()
+                .persistent()
+                .get::<DataKey, RoundV1>(&round_key)
+            {
+                Self::write_persistent(env, &round_key, &round.upgrade(env));
+            }
+
+            let fee_key = DataKey::FeeDue(round_id);
+            if let Some((recipient, amount)) = env
+                .storage()
+                .persistent()
+                .get::<DataKey, (Address, u64)>(&fee_key)
+            {
+                Self::write_persistent(env, &fee_key, &(recipient, amount as i128));
+            }
+
+            let tokens =
+                Self::read_persistent::<Vec<Address>>(env, &DataKey::EscrowTokens(round_id))
+                    .unwrap_or(Vec::new(env));
+            for token in tokens.iter() {
+                let token_keys = [
+                    DataKey::TokenPool(round_id, token.clone()),
+                    DataKey::Remainder(round_id, token),
+                ];
+                for key in token_keys {
+                    if let Some(amount) = env.storage().persistent().get::<DataKey, u64>(&key) {
+                        Self::write_persistent(env, &key, &(amount as i128));
+                    }
+                }
+            }
+        }
+    }
+
+    // Ledgers closed per day, assuming ~5 second ledgers
+    const DAY_IN_LEDGERS: u32 = 17_280;
+
+    // Instance entries (admin, counters) are kept alive for a week past the last hot-path call
+    const INSTANCE_BUMP_AMOUNT: u32 = 7 * Self::DAY_IN_LEDGERS;
+    const INSTANCE_LIFETIME_THRESHOLD: u32 = Self::INSTANCE_BUMP_AMOUNT - Self::DAY_IN_LEDGERS;
+
+    // Persistent entries are kept alive for a month past their last read or write, long
+    // enough to outlast a claim window between touches
+    const PERSISTENT_BUMP_AMOUNT: u32 = 30 * Self::DAY_IN_LEDGERS;
+    const PERSISTENT_LIFETIME_THRESHOLD: u32 = Self::PERSISTENT_BUMP_AMOUNT - Self::DAY_IN_LEDGERS;
+
+    // Helper function to extend the TTL of the contract instance and its storage
+    fn extend_instance(env: &Env) {
+        env.storage().instance().extend_ttl(
+            Self::INSTANCE_LIFETIME_THRESHOLD,
+            Self::INSTANCE_BUMP_AMOUNT,
+        );
+    }
+
+    // Helper function to extend the TTL of an existing persistent entry
+    fn extend_persistent(env: &Env, key: &impl IntoVal<Env, Val>) {
+        env.storage().persistent().extend_ttl(
+            key,
+            Self::PERSISTENT_LIFETIME_THRESHOLD,
+            Self::PERSISTENT_BUMP_AMOUNT,
+        );
+    }
+
+    // Helper function to read a persistent entry, extending its TTL if it exists
+    fn read_persistent<V: TryFromVal<Env, Val>>(
+        env: &Env,
+        key: &impl IntoVal<Env, Val>,
+    ) -> Option<V> {
+        let value = env.storage().persistent().get(key);
+        if value.is_some() {
+            Self::extend_persistent(env, key);
+        }
+        value
+    }
+
+    // Helper function to write a persistent entry and extend its TTL
+    fn write_persistent<V: IntoVal<Env, Val>>(env: &Env, key: &impl IntoVal<Env, Val>, value: &V) {
+        env.storage().persistent().set(key, value);
+        Self::extend_persistent(env, key);
+    }
+
+    // Helper function to check whether a persistent entry exists, extending its TTL if so
+    fn has_persistent(env: &Env, key: &impl IntoVal<Env, Val>) -> bool {
+        let exists = env.storage().persistent().has(key);
+        if exists {
+            Self::extend_persistent(env, key);
+        }
+        exists
+    }
+
+    // Maximum protocol fee in basis points
+    const MAX_FEE_BPS: u32 = 1000;
+
+    // Function to set the protocol fee applied to rounds closed from now on
+    pub fn set_fee(env: Env, bps: u32, recipient: Address) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        let admin = Self::require_admin(&env)?;
+        Self::require_not_timelocked(&env, TimelockedOp::SetFee)?;
+
+        Self::apply_fee(&env, &admin, bps, recipient)
+    }
+
+    // Helper function to set the protocol fee, for set_fee or a scheduled operation
+    fn apply_fee(
+        env: &Env,
+        actor: &Address,
+        bps: u32,
+        recipient: Address,
+    ) -> Result<(), ContractError> {
+        if bps > Self::MAX_FEE_BPS {
+            return Err(ContractError::InvalidAmount);
+        }
+
+        let mut config = Self::get_config(env.clone())?;
+        config.fee_bps = bps;
+        config.fee_recipient = FeeRecipient::Account(recipient.clone());
+        Self::write_config(env, &config);
+        let payload = Vec::from_array(env, [bps.into()]);
+        Self::record_audit(env, symbol_short!("fee"), actor, 0, payload);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("FEE_SET"),), (bps, recipient));
+
+        Ok(())
+    }
+
+    // Function to get the protocol fee, if one is configured
+    pub fn get_fee(env: Env) -> Option<FeeConfig> {
+        let config = Self::get_config(env).ok()?;
+        match config.fee_recipient {
+            FeeRecipient::None => None,
+            FeeRecipient::Account(recipient) => Some(FeeConfig {
+                bps: config.fee_bps,
+                recipient,
+            }),
+        }
+    }
+
+    // Function for the admin to grant a role to an address
+    pub fn grant_role(env: Env, addr: Address, role: Role) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        Self::write_persistent(&env, &DataKey::Role(addr.clone(), role), &true);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("ROLE_GRNT"), addr), role);
+
+        Ok(())
+    }
+
+    // Function for the admin to revoke a role from an address
+    pub fn revoke_role(env: Env, addr: Address, role: Role) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Role(addr.clone(), role));
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("ROLE_RVK"), addr), role);
+
+        Ok(())
+    }
+
+    // Function to check whether an address has been granted a role
+    pub fn has_role(env: Env, addr: Address, role: Role) -> bool {
+        Self::has_persistent(&env, &DataKey::Role(addr, role))
+    }
+
+    // Helper function to require authorization from a caller that is the admin or has
+    // been granted the given role
+    fn require_role(env: &Env, caller: &Address, role: Role) -> Result<(), ContractError> {
+        caller.require_auth();
+        Self::extend_instance(env);
+
+        let is_admin = Self::get_admin(env.clone()).is_ok_and(|admin| admin == *caller);
+        if !is_admin && !Self::has_role(env.clone(), caller.clone(), role) {
+            return Err(ContractError::Unauthorized);
+        }
+        Ok(())
+    }
+
+    // Function for the admin to delegate the day-to-day running of a round to an operator, who
+    // may then close its voting, review its submissions and disburse its funds. The admin keeps
+    // every right; setting another operator replaces the previous one.
+    pub fn set_round_operator(
+        env: Env,
+        round_id: u64,
+        operator: Address,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        Self::get_round(env.clone(), round_id)?;
+        Self::write_persistent(&env, &GovernanceKey::Operator(round_id), &operator);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("OP_SET"), round_id), operator);
+
+        Ok(())
+    }
+
+    // Function for the admin to take a round's operator off it
+    pub fn remove_round_operator(env: Env, round_id: u64) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        let operator =
+            Self::get_round_operator(env.clone(), round_id).ok_or(ContractError::Unauthorized)?;
+        env.storage()
+            .persistent()
+            .remove(&GovernanceKey::Operator(round_id));
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("OP_RMV"), round_id), operator);
+
+        Ok(())
+    }
+
+    // Function to get a round's operator, if any
+    pub fn get_round_operator(env: Env, round_id: u64) -> Option<Address> {
+        Self::read_persistent(&env, &GovernanceKey::Operator(round_id))
+    }
+
+    // Helper function to require authorization from a caller that is the admin, has been
+    // granted the given role, or operates the round. A caller authorized only as the round's
+    // operator has the action recorded in an OPERATOR event tagged with their address.
+    fn require_round_role(
+        env: &Env,
+        caller: &Address,
+        round_id: u64,
+        role: Option<Role>,
+        action: Symbol,
+    ) -> Result<(), ContractError> {
+        caller.require_auth();
+        Self::extend_instance(env);
+
+        let is_admin = Self::get_admin(env.clone()).is_ok_and(|admin| admin == *caller);
+        if is_admin || role.is_some_and(|role| Self::has_role(env.clone(), caller.clone(), role)) {
+            return Ok(());
+        }
+        if Self::get_round_operator(env.clone(), round_id).as_ref() != Some(caller) {
+            return Err(ContractError::Unauthorized);
+        }
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("OPERATOR"), round_id, caller.clone()),
+            action,
+        );
+
+        Ok(())
+    }
+
+    // Function for the admin or a pauser to pause submissions, voting, closing and
+    // disbursement
+    pub fn pause(env: Env, caller: Address) -> Result<(), ContractError> {
+        // Require authorization from the admin or a pauser
+        Self::require_role(&env, &caller, Role::Pauser)?;
+
+        Self::set_paused(&env, true)?;
+        Self::record_audit(&env, symbol_short!("pause"), &caller, 0, Vec::new(&env));
+
+        // Emit event
+        env.events().publish((symbol_short!("PAUSED"),), caller);
+
+        Ok(())
+    }
+
+    // Function for the admin or a pauser to lift a pause
+    pub fn unpause(env: Env, caller: Address) -> Result<(), ContractError> {
+        // Require authorization from the admin or a pauser
+        Self::require_role(&env, &caller, Role::Pauser)?;
+
+        Self::set_paused(&env, false)?;
+        Self::record_audit(&env, symbol_short!("unpause"), &caller, 0, Vec::new(&env));
+
+        // Emit event
+        env.events().publish((symbol_short!("UNPAUSED"),), caller);
+
+        Ok(())
+    }
+
+    // Function to check whether the contract is paused
+    pub fn is_paused(env: Env) -> bool {
+        Self::get_config(env).is_ok_and(|config| config.paused)
+    }
+
+    // Helper function to set or lift the pause in the config
+    fn set_paused(env: &Env, paused: bool) -> Result<(), ContractError> {
+        let mut config = Self::get_config(env.clone())?;
+        config.paused = paused;
+        Self::write_config(env, &config);
+        Ok(())
+    }
+
+    // Helper function to reject state-changing calls while the contract is paused
+    fn require_not_paused(env: &Env) -> Result<(), ContractError> {
+        if Self::is_paused(env.clone()) {
+            return Err(ContractError::ContractPaused);
+        }
+        Ok(())
+    }
+
+    // Maximum number of council members
+    const MAX_COUNCIL_MEMBERS: u32 = 20;
+
+    // Function for the admin to set up the council whose members approve disbursements, sweeps
+    // and allocation overrides together. Once a council is set those actions, and replacing
+    // the council, only go through its proposals.
+    pub fn set_council(
+        env: Env,
+        members: Vec<Address>,
+        threshold: u32,
+        proposal_ttl: u64,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+        Self::require_no_council(&env)?;
+
+        Self::write_council(&env, members, threshold, proposal_ttl)
+    }
+
+    // Helper function to validate and store a new council, starting its term
+    fn write_council(
+        env: &Env,
+        members: Vec<Address>,
+        threshold: u32,
+        proposal_ttl: u64,
+    ) -> Result<(), ContractError> {
+        if members.is_empty() || members.len() > Self::MAX_COUNCIL_MEMBERS {
+            return Err(ContractError::InvalidAmount);
+        }
+        for (index, member) in members.iter().enumerate() {
+            if members.first_index_of(&member) != Some(index as u32) {
+                return Err(ContractError::InvalidAmount);
+            }
+        }
+        if threshold == 0 || threshold > members.len() || proposal_ttl == 0 {
+            return Err(ContractError::InvalidAmount);
+        }
+
+        let term = Self::get_council(env.clone()).map_or(0, |council| council.term) + 1;
+        Self::write_persistent(
+            env,
+            &GovernanceKey::Council,
+            &Council {
+                members: members.clone(),
+                threshold,
+                proposal_ttl,
+                term,
+            },
+        );
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("COUNCIL"), term), (members, threshold));
+
+        Ok(())
+    }
+
+    // Function to get the council, if one is set
+    pub fn get_council(env: Env) -> Option<Council> {
+        Self::read_persistent(&env, &GovernanceKey::Council)
+    }
+
+    // Helper function to refuse treasury actions taken outside the council once it is set
+    fn require_no_council(env: &Env) -> Result<(), ContractError> {
+        if Self::get_council(env.clone()).is_some() {
+            return Err(ContractError::Unauthorized);
+        }
+        Ok(())
+    }
+
+    // Helper function to get the council, checking that an address sits on it
+    fn require_member(env: &Env, member: &Address) -> Result<Council, ContractError> {
+        match Self::get_council(env.clone()) {
+            Some(council) if council.members.contains(member) => Ok(council),
+            _ => Err(ContractError::Unauthorized),
+        }
+    }
+
+    // Function for a council member to propose a treasury action, counting as its first
+    // approval. Returns the proposal's id.
+    pub fn propose(
+        env: Env,
+        proposer: Address,
+        action: ProposalAction,
+    ) -> Result<u64, ContractError> {
+        // Require authorization from the proposer
+        proposer.require_auth();
+
+        let council = Self::require_member(&env, &proposer)?;
+
+        let next_key = GovernanceKey::NextProposalId;
+        let proposal_id = Self::read_persistent::<u64>(&env, &next_key).unwrap_or(0) + 1;
+        Self::write_persistent(&env, &next_key, &proposal_id);
+
+        let proposal = Proposal {
+            id: proposal_id,
+            proposer: proposer.clone(),
+            action: action.clone(),
+            approvals: Vec::from_array(&env, [proposer.clone()]),
+            term: council.term,
+            expires_at: env
+                .ledger()
+                .timestamp()
+                .saturating_add(council.proposal_ttl),
+            executed: false,
+        };
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("PROPOSED"), proposal_id, proposer), action);
+
+        Self::settle_proposal(env, &council, proposal)?;
+
+        Ok(proposal_id)
+    }
+
+    // Function for a council member to approve a proposal of the current council before it
+    // expires. The approval that meets the threshold executes it.
+    pub fn approve_proposal(
+        env: Env,
+        member: Address,
+        proposal_id: u64,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the member
+        member.require_auth();
+
+        let council = Self::require_member(&env, &member)?;
+
+        let mut proposal = Self::get_proposal(env.clone(), proposal_id)?;
+        if proposal.executed
+            || proposal.term != council.term
+            || env.ledger().timestamp() > proposal.expires_at
+        {
+            return Err(ContractError::InvalidProposal);
+        }
+
+        if proposal.approvals.contains(&member) {
+            return Err(ContractError::AlreadyVoted);
+        }
+        proposal.approvals.push_back(member.clone());
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("APPROVED"), proposal_id, member),
+            proposal.approvals.len(),
+        );
+
+        Self::settle_proposal(env, &council, proposal)
+    }
+
+    // Helper function to store a proposal, executing it once it has the council's threshold
+    // of approvals. It is marked executed before its action runs.
+    fn settle_proposal(
+        env: Env,
+        council: &Council,
+        mut proposal: Proposal,
+    ) -> Result<(), ContractError> {
+        let proposal_key = GovernanceKey::Proposal(proposal.id);
+        proposal.executed = proposal.approvals.len() >= council.threshold;
+        Self::write_persistent(&env, &proposal_key, &proposal);
+        if !proposal.executed {
+            return Ok(());
+        }
+
+        // The approval that met the threshold takes the action
+        let actor = proposal.approvals.last_unchecked();
+        match proposal.action {
+            ProposalAction::Disburse(round_id, token, start, count) => {
+                Self::disburse(env.clone(), &actor, round_id, token, start, count)?;
+            }
+            ProposalAction::Sweep(round_id, token, to) => {
+                Self::sweep(env.clone(), round_id, token, to)?;
+            }
+            ProposalAction::Override(round_id, submission_id, new_amount, reason) => {
+                Self::apply_override(
+                    env.clone(),
+                    &actor,
+                    round_id,
+                    submission_id,
+                    new_amount,
+                    reason,
+                )?;
+            }
+            ProposalAction::SetCouncil(members, threshold, proposal_ttl) => {
+                Self::write_council(&env, members, threshold, proposal_ttl)?;
+            }
+        }
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("EXECUTED"), proposal.id), proposal.approvals);
+
+        Ok(())
+    }
+
+    // Function to get a proposal to the council
+    pub fn get_proposal(env: Env, proposal_id: u64) -> Result<Proposal, ContractError> {
+        Self::read_persistent(&env, &GovernanceKey::Proposal(proposal_id))
+            .ok_or(ContractError::InvalidProposal)
+    }
+
+    // Function to list proposals to the council in the order they were made, skipping the
+    // first `start` and returning at most `limit`
+    pub fn get_proposals(env: Env, start: u64, limit: u32) -> Vec<Proposal> {
+        let proposal_count =
+            Self::read_persistent::<u64>(&env, &GovernanceKey::NextProposalId).unwrap_or(0);
+
+        let mut proposals = Vec::new(&env);
+        let limit = limit.min(Self::MAX_PAGE_SIZE) as u64;
+        let mut proposal_id = start.saturating_add(1);
+        while proposal_id <= proposal_count && (proposals.len() as u64) < limit {
+            if let Ok(proposal) = Self::get_proposal(env.clone(), proposal_id) {
+                proposals.push_back(proposal);
+            }
+            proposal_id += 1;
+        }
+
+        proposals
+    }
+
+    // Helper function to refuse a timelocked operation called directly
+    fn require_not_timelocked(env: &Env, op: TimelockedOp) -> Result<(), ContractError> {
+        if Self::get_config(env.clone())?.timelocked.contains(op) {
+            return Err(ContractError::Unauthorized);
+        }
+        Ok(())
+    }
+
+    // Function for the admin to queue a sensitive operation, which can be executed once the
+    // config's timelock delay has elapsed. The delay in force when it is scheduled applies.
+    // Returns the id of the scheduled operation.
+    pub fn schedule(env: Env, action: TimelockAction) -> Result<u64, ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        let next_key = GovernanceKey::NextScheduleId;
+        let action_id = Self::read_persistent::<u64>(&env, &next_key).unwrap_or(0) + 1;
+        Self::write_persistent(&env, &next_key, &action_id);
+
+        let ready_at = env
+            .ledger()
+            .timestamp()
+            .saturating_add(Self::get_config(env.clone())?.timelock_delay);
+        Self::write_persistent(
+            &env,
+            &GovernanceKey::Scheduled(action_id),
+            &ScheduledAction {
+                id: action_id,
+                action: action.clone(),
+                ready_at,
+            },
+        );
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("SCHEDULED"), action_id), (action, ready_at));
+
+        Ok(action_id)
+    }
+
+    // Function for the admin to execute a scheduled operation once its delay has elapsed,
+    // taking it off the queue
+    pub fn execute(env: Env, action_id: u64) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        let admin = Self::require_admin(&env)?;
+
+        let scheduled = Self::get_scheduled(env.clone(), action_id)?;
+        if env.ledger().timestamp() < scheduled.ready_at {
+            return Err(ContractError::InvalidProposal);
+        }
+        env.storage()
+            .persistent()
+            .remove(&GovernanceKey::Scheduled(action_id));
+
+        match scheduled.action {
+            TimelockAction::SetFee(bps, recipient) => {
+                Self::apply_fee(&env, &admin, bps, recipient)?;
+            }
+            TimelockAction::UpdateConfig(config) => Self::apply_config(&env, &admin, config)?,
+            TimelockAction::Override(round_id, submission_id, new_amount, reason) => {
+                // Once a council is set, allocations are only overridden by its proposals
+                Self::require_no_council(&env)?;
+                Self::apply_override(
+                    env.clone(),
+                    &admin,
+                    round_id,
+                    submission_id,
+                    new_amount,
+                    reason,
+                )?;
+            }
+            TimelockAction::Upgrade(new_wasm_hash) => Self::apply_upgrade(&env, new_wasm_hash),
+        }
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("TL_EXEC"), action_id), ());
+
+        Ok(())
+    }
+
+    // Function for the admin to take a scheduled operation off the queue before it is executed
+    pub fn cancel_scheduled(env: Env, action_id: u64) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        Self::get_scheduled(env.clone(), action_id)?;
+        env.storage()
+            .persistent()
+            .remove(&GovernanceKey::Scheduled(action_id));
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("TL_CANCEL"), action_id), ());
+
+        Ok(())
+    }
+
+    // Function to get an operation waiting in the timelock queue
+    pub fn get_scheduled(env: Env, action_id: u64) -> Result<ScheduledAction, ContractError> {
+        Self::read_persistent(&env, &GovernanceKey::Scheduled(action_id))
+            .ok_or(ContractError::InvalidProposal)
+    }
+
+    // Number of audit log entries stored under a single key
+    const AUDIT_LOG_CHUNK: u64 = 50;
+
+    // Helper function to append an administrative action to the audit log. The log is split
+    // into chunks of `AUDIT_LOG_CHUNK` entries, so appending rewrites a single small entry.
+    fn record_audit(env: &Env, action: Symbol, actor: &Address, round_id: u64, payload: Vec<i128>) {
+        let count = Self::read_persistent::<u64>(env, &GovernanceKey::AuditCount).unwrap_or(0);
+        let chunk_key = GovernanceKey::AuditChunk(count / Self::AUDIT_LOG_CHUNK);
+        let mut chunk =
+            Self::read_persistent::<Vec<AuditEntry>>(env, &chunk_key).unwrap_or(Vec::new(env));
+        chunk.push_back(AuditEntry {
+            action,
+            actor: actor.clone(),
+            round_id,
+            timestamp: env.ledger().timestamp(),
+            payload,
+        });
+        Self::write_persistent(env, &chunk_key, &chunk);
+        Self::write_persistent(env, &GovernanceKey::AuditCount, &(count + 1));
+    }
+
+    // Function to page through the audit log, oldest first, skipping the first `start` entries
+    // and returning at most `limit` along with the number of entries recorded so far
+    pub fn get_audit_log(env: Env, start: u64, limit: u32) -> AuditLogPage {
+        let total = Self::read_persistent::<u64>(&env, &GovernanceKey::AuditCount).unwrap_or(0);
+
+        let mut entries = Vec::new(&env);
+        let end = start
+            .saturating_add(limit.min(Self::MAX_PAGE_SIZE) as u64)
+            .min(total);
+        let mut position = start;
+        while position < end {
+            let chunk_key = GovernanceKey::AuditChunk(position / Self::AUDIT_LOG_CHUNK);
+            let chunk = Self::read_persistent::<Vec<AuditEntry>>(&env, &chunk_key)
+                .unwrap_or(Vec::new(&env));
+            let offset = position % Self::AUDIT_LOG_CHUNK;
+            let taken = (end - position).min(Self::AUDIT_LOG_CHUNK - offset);
+            for entry in chunk.iter().skip(offset as usize).take(taken as usize) {
+                entries.push_back(entry);
+            }
+            position += taken;
+        }
+
+        AuditLogPage { total, entries }
+    }
+
+    // Minimum number of seconds between scheduling an emergency withdrawal and executing it
+    const EMERGENCY_WITHDRAW_DELAY: u64 = 7 * 24 * 60 * 60;
+
+    // Function for the admin to schedule the recovery of tokens stuck in the contract, such as
+    // mis-sent assets or dust left by cancelled rounds. The withdrawal can be executed once
+    // `EMERGENCY_WITHDRAW_DELAY` has elapsed. Returns its id.
+    pub fn schedule_emergency_withdraw(
+        env: Env,
+        token: Address,
+        amount: i128,
+        to: Address,
+    ) -> Result<u64, ContractError> {
+        // Require authorization from the admin
+        let admin = Self::require_admin(&env)?;
+
+        if amount <= 0 {
+            return Err(ContractError::InvalidAmount);
+        }
+
+        let next_key = GovernanceKey::NextWithdrawalId;
+        let withdrawal_id = Self::read_persistent::<u64>(&env, &next_key).unwrap_or(0) + 1;
+        Self::write_persistent(&env, &next_key, &withdrawal_id);
+
+        let ready_at = env.ledger().timestamp() + Self::EMERGENCY_WITHDRAW_DELAY;
+        Self::write_persistent(
+            &env,
+            &GovernanceKey::Withdrawal(withdrawal_id),
+            &EmergencyWithdrawal {
+                id: withdrawal_id,
+                token: token.clone(),
+                amount,
+                to: to.clone(),
+                ready_at,
+            },
+        );
+        let payload = Vec::from_array(&env, [withdrawal_id.into(), amount]);
+        Self::record_audit(&env, symbol_short!("emrg_schd"), &admin, 0, payload);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("EMRG_SCHD"), withdrawal_id, token),
+            (amount, to, ready_at),
+        );
+
+        Ok(withdrawal_id)
+    }
+
+    // Function for the admin to execute a scheduled emergency withdrawal once its delay has
+    // elapsed. It may not touch the tokens the contract owes: the contract's balance has to
+    // stay at or above the obligations in the token of every round, escrow, deposit, stake
+    // and vesting payout combined.
+    pub fn execute_emergency_withdraw(env: Env, withdrawal_id: u64) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        let admin = Self::require_admin(&env)?;
+
+        let withdrawal = Self::get_emergency_withdrawal(env.clone(), withdrawal_id)?;
+        if env.ledger().timestamp() < withdrawal.ready_at {
+            return Err(ContractError::InvalidProposal);
+        }
+
+        let token_client = TokenClient::new(&env, &withdrawal.token);
+        let balance = token_client.balance(&env.current_contract_address());
+        let obligations = Self::get_obligations(env.clone(), withdrawal.token.clone());
+        let left = balance
+            .checked_sub(withdrawal.amount)
+            .ok_or(ContractError::InsufficientFunds)?;
+        if left < obligations {
+            return Err(ContractError::InsufficientFunds);
+        }
+
+        env.storage()
+            .persistent()
+            .remove(&GovernanceKey::Withdrawal(withdrawal_id));
+        token_client.transfer(
+            &env.current_contract_address(),
+            &withdrawal.to,
+            &withdrawal.amount,
+        );
+        let payload = Vec::from_array(&env, [withdrawal_id.into(), withdrawal.amount]);
+        Self::record_audit(&env, symbol_short!("emrg_exec"), &admin, 0, payload);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("EMRG_EXEC"), withdrawal_id, withdrawal.token),
+            (withdrawal.amount, withdrawal.to, obligations),
+        );
+
+        Ok(())
+    }
+
+    // Function to get an emergency withdrawal waiting out its delay
+    pub fn get_emergency_withdrawal(
+        env: Env,
+        withdrawal_id: u64,
+    ) -> Result<EmergencyWithdrawal, ContractError> {
+        Self::read_persistent(&env, &GovernanceKey::Withdrawal(withdrawal_id))
+            .ok_or(ContractError::InvalidProposal)
+    }
+
+    // Function to get the total the contract owes in a token: its rounds' escrow, the
+    // submission deposits and stakes it holds and the vesting payouts not yet claimed
+    pub fn get_obligations(env: Env, token: Address) -> i128 {
+        Self::read_persistent(&env, &GovernanceKey::Obligations(token)).unwrap_or(0)
+    }
+
+    // Helper function to add to, or take from, the total the contract owes in a token, never
+    // letting it drop below 0
+    fn adjust_obligations(env: &Env, token: &Address, delta: i128) {
+        if delta == 0 {
+            return;
+        }
+        let key = GovernanceKey::Obligations(token.clone());
+        let total = Self::read_persistent::<i128>(env, &key).unwrap_or(0);
+        Self::write_persistent(env, &key, &total.saturating_add(delta).max(0));
+    }
+
+    // Helper function to store a round's escrowed balance of a token, keeping the total the
+    // contract owes in the token in step
+    fn write_escrow(env: &Env, round_id: u64, token: &Address, escrow: i128) {
+        let escrow_key = DataKey::Escrow(round_id, token.clone());
+        let previous = Self::read_persistent::<i128>(env, &escrow_key).unwrap_or(0);
+        Self::write_persistent(env, &escrow_key, &escrow);
+        Self::adjust_obligations(env, token, escrow - previous);
+    }
+
+    // Maximum number of entries returned by a single paginated query
+    const MAX_PAGE_SIZE: u32 = 50;
+
+    // Maximum length in bytes of a round or submission name or metadata hash
+    const MAX_METADATA_LEN: u32 = 64;
+
+    // Maximum number of recipients a submission's payout can be split between
+    const MAX_PAYOUT_SPLITS: u32 = 10;
+
+    // Maximum number of submissions indexed per submitter; the oldest drop out first
+    const MAX_SUBMITTER_SUBMISSIONS: u32 = 20;
+
+    // Number of voters stored under each key of a submission's voter breakdown
+    const SUBMISSION_VOTER_CHUNK: u32 = 50;
+
+    // Basis points making up a whole payout
+    const TOTAL_BPS: u32 = 10_000;
+
+    // Function to register a voter
+    pub fn add_voter(env: Env, voter: Address) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        Self::write_persistent(&env, &DataKey::Voter(voter.clone()), &true);
+
+        // Registering without a weight restores the round's default credits
+        env.storage()
+            .persistent()
+            .remove(&DataKey::VoterWeight(voter.clone()));
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("VTR_ADD"), voter.clone()), voter);
+
+        Ok(())
+    }
+
+    // Function to register a voter with a personal credit budget that replaces the
+    // round's base credits in every round the voter takes part in
+    pub fn add_voter_with_credits(
+        env: Env,
+        voter: Address,
+        credits: u64,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        if credits == 0 {
+            return Err(ContractError::InvalidAmount);
+        }
+
+        Self::write_persistent(&env, &DataKey::Voter(voter.clone()), &true);
+        Self::write_persistent(&env, &DataKey::VoterWeight(voter.clone()), &credits);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("VTR_ADD"), voter.clone()), voter.clone());
+        env.events()
+            .publish((symbol_short!("VTR_CRED"), voter), credits);
+
+        Ok(())
+    }
+
+    // Function to remove a voter from the registry. Allocations already cast
+    // by the voter are kept, but no new ones will be accepted. Credits the voter
+    // has banked are kept too, and carried over if they're registered again.
+    pub fn remove_voter(env: Env, voter: Address) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        if !Self::is_voter(env.clone(), voter.clone()) {
+            return Err(ContractError::NotAVoter);
+        }
+
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Voter(voter.clone()));
+        env.storage()
+            .persistent()
+            .remove(&DataKey::VoterWeight(voter.clone()));
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("VTR_RMV"), voter.clone()), voter);
+
+        Ok(())
+    }
+
+    // Maximum number of voters registered or removed by a single batch call
+    const MAX_VOTER_BATCH: u32 = 100;
+
+    // Function to register a batch of voters under a single admin authorization. Processes
+    // at most `MAX_VOTER_BATCH` entries, skipping voters that are already registered, and
+    // returns how many entries were processed so the caller can resume with the rest.
+    pub fn add_voters(env: Env, voters: Vec<Address>) -> Result<u32, ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        let processed = voters.len().min(Self::MAX_VOTER_BATCH);
+        let mut added: u32 = 0;
+        for voter in voters.iter().take(processed as usize) {
+            let voter_key = DataKey::Voter(voter);
+            if Self::has_persistent(&env, &voter_key) {
+                continue;
+            }
+            Self::write_persistent(&env, &voter_key, &true);
+            added += 1;
+        }
+
+        // Emit event
+        env.events().publish((symbol_short!("VTRS_ADD"),), added);
+
+        Ok(processed)
+    }
+
+    // Function to remove a batch of voters under a single admin authorization. Processes
+    // at most `MAX_VOTER_BATCH` entries, skipping addresses that aren't registered, and
+    // returns how many entries were processed so the caller can resume with the rest.
+    pub fn remove_voters(env: Env, voters: Vec<Address>) -> Result<u32, ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        let processed = voters.len().min(Self::MAX_VOTER_BATCH);
+        let mut removed: u32 = 0;
+        for voter in voters.iter().take(processed as usize) {
+            let voter_key = DataKey::Voter(voter.clone());
+            if !env.storage().persistent().has(&voter_key) {
+                continue;
+            }
+            env.storage().persistent().remove(&voter_key);
+            env.storage()
+                .persistent()
+                .remove(&DataKey::VoterWeight(voter));
+            removed += 1;
+        }
+
+        // Emit event
+        env.events().publish((symbol_short!("VTRS_RMV"),), removed);
+
+        Ok(processed)
+    }
+
+    // Function to bar an address from submitting projects, voting and receiving payouts. A
+    // blacklisted submitter's votes are discarded when their round closes.
+    pub fn blacklist(env: Env, addr: Address) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        let flag_key = DataKey::Blacklisted(addr.clone());
+        if !Self::has_persistent(&env, &flag_key) {
+            Self::write_persistent(&env, &flag_key, &true);
+            let mut blacklist = Self::read_persistent::<Vec<Address>>(&env, &DataKey::Blacklist)
+                .unwrap_or(Vec::new(&env));
+            blacklist.push_back(addr.clone());
+            Self::write_persistent(&env, &DataKey::Blacklist, &blacklist);
+        }
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("BLOCKED"), addr.clone()), addr);
+
+        Ok(())
+    }
+
+    // Function to lift a blacklisting. Payouts it held back can be made again, but votes
+    // already discarded at close are not restored.
+    pub fn unblacklist(env: Env, addr: Address) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        let flag_key = DataKey::Blacklisted(addr.clone());
+        if Self::has_persistent(&env, &flag_key) {
+            env.storage().persistent().remove(&flag_key);
+            let mut blacklist = Self::read_persistent::<Vec<Address>>(&env, &DataKey::Blacklist)
+                .unwrap_or(Vec::new(&env));
+            if let Some(index) = blacklist.first_index_of(&addr) {
+                blacklist.remove(index);
+            }
+            Self::write_persistent(&env, &DataKey::Blacklist, &blacklist);
+        }
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("UNBLOCKED"), addr.clone()), addr);
+
+        Ok(())
+    }
+
+    // Function to check whether an address is blacklisted
+    pub fn is_blacklisted(env: Env, addr: Address) -> bool {
+        Self::has_persistent(&env, &DataKey::Blacklisted(addr))
+    }
+
+    // Helper function to reject calls on behalf of a blacklisted address
+    fn require_not_blocked(env: &Env, addr: &Address) -> Result<(), ContractError> {
+        if Self::is_blacklisted(env.clone(), addr.clone()) {
+            return Err(ContractError::AddressBlocked);
+        }
+        Ok(())
+    }
+
+    // Helper function to check whether a submission's submitter, payout address or any
+    // recipient of its payout split is blacklisted
+    fn payout_blocked(env: &Env, submission: &Submission) -> bool {
+        if Self::is_blacklisted(env.clone(), submission.submitter.clone())
+            || Self::is_blacklisted(env.clone(), submission.payout_address.clone())
+        {
+            return true;
+        }
+
+        Self::get_payout_splits(env.clone(), submission.id).is_some_and(|splits| {
+            splits
+                .keys()
+                .iter()
+                .any(|recipient| Self::is_blacklisted(env.clone(), recipient))
+        })
+    }
+
+    // Function to check whether an address is a registered voter
+    pub fn is_voter(env: Env, addr: Address) -> bool {
+        Self::has_persistent(&env, &DataKey::Voter(addr.clone()))
+    }
+
+    // Helper function to get the credits a voter may spend in a round, including credits
+    // delegated to them. Delegators have handed their whole budget over.
+    fn voter_budget(env: &Env, round: &Round, voter: &Address) -> u64 {
+        if Self::has_persistent(env, &DataKey::Delegate(round.id, voter.clone())) {
+            return 0;
+        }
+        let received: u64 =
+            Self::read_persistent(env, &DataKey::DelegatedCredits(round.id, voter.clone()))
+                .unwrap_or(0);
+        Self::own_budget(env, round, voter).saturating_add(received)
+    }
+
+    // Helper function to get the credits a voter is granted in a round, by weight or default,
+    // plus any banked credits carried over into it and credits earned by staking
+    fn own_budget(env: &Env, round: &Round, voter: &Address) -> u64 {
+        let granted = Self::read_persistent(env, &DataKey::VoterWeight(voter.clone()))
+            .unwrap_or(round.config.vote_credits);
+        let carried: u64 = Self::read_persistent(
+            env,
+            &DataKey::Credit(CreditKey::Carryover(round.id, voter.clone())),
+        )
+        .unwrap_or(0);
+        let staked: u64 = Self::read_persistent(
+            env,
+            &DataKey::Credit(CreditKey::StakedCredits(round.id, voter.clone())),
+        )
+        .unwrap_or(0);
+        granted.saturating_add(carried).saturating_add(staked)
+    }
+
+    // Function to bank the credits a voter left unspent in a closed round, to be carried over
+    // into the next round they vote in that allows it. Anyone may bank a voter's credits, once
+    // per round. Voters with a ballot in the round may bank even after being removed from the
+    // registry; the others must still be eligible. Returns the credits banked.
+    pub fn bank_credits(env: Env, round_id: u64, voter: Address) -> Result<u64, ContractError> {
+        let round = Self::get_round(env.clone(), round_id)?;
+        if !matches!(round.phase, RoundPhase::Closed | RoundPhase::Disbursed) {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        let banked_from_key = DataKey::Credit(CreditKey::BankedFrom(round_id, voter.clone()));
+        if Self::has_persistent(&env, &banked_from_key) {
+            return Err(ContractError::AlreadyClaimed);
+        }
+
+        let has_ballot =
+            Self::has_persistent(&env, &DataKey::VoterAllocation(round_id, voter.clone()));
+        if !has_ballot {
+            Self::require_eligible(&env, &round, &voter)?;
+        }
+
+        let spent: u64 =
+            Self::read_persistent(&env, &DataKey::VoterCredits(round_id, voter.clone()))
+                .unwrap_or(0);
+        // Only the voter's own credits are banked: delegated credits belong to their delegators,
+        // and credits earned by staking are backed by tokens the voter takes back
+        let own = if Self::has_persistent(&env, &DataKey::Delegate(round_id, voter.clone())) {
+            0
+        } else {
+            let staked: u64 = Self::read_persistent(
+                &env,
+                &DataKey::Credit(CreditKey::StakedCredits(round_id, voter.clone())),
+            )
+            .unwrap_or(0);
+            Self::own_budget(&env, &round, &voter).saturating_sub(staked)
+        };
+        let unused = own.saturating_sub(spent);
+        if unused == 0 {
+            return Err(ContractError::InvalidAmount);
+        }
+
+        let banked_key = DataKey::Credit(CreditKey::Banked(voter.clone()));
+        let banked = Self::read_persistent::<u64>(&env, &banked_key)
+            .unwrap_or(0)
+            .checked_add(unused)
+            .ok_or(ContractError::MathOverflow)?;
+        Self::write_persistent(&env, &banked_key, &banked);
+        Self::write_persistent(&env, &banked_from_key, &unused);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("CRED_BANK"), round_id, voter), unused);
+
+        Ok(unused)
+    }
+
+    // Helper function to carry a voter's banked credits over into a round the first time they
+    // vote in it. The whole bank is consumed, and the round's share of it, capped at the
+    // round's default credits, is added to the voter's budget.
+    fn apply_carryover(env: &Env, round: &Round, voter: &Address) {
+        if round.config.carryover_bps == 0 {
+            return;
+        }
+        let carryover_key = DataKey::Credit(CreditKey::Carryover(round.id, voter.clone()));
+        if Self::has_persistent(env, &carryover_key) {
+            return;
+        }
+        let banked_key = DataKey::Credit(CreditKey::Banked(voter.clone()));
+        let banked: u64 = Self::read_persistent(env, &banked_key).unwrap_or(0);
+        if banked == 0 {
+            return;
+        }
+
+        let share =
+            (banked as u128 * round.config.carryover_bps as u128 / Self::TOTAL_BPS as u128) as u64;
+        let added = share.min(round.config.vote_credits);
+        Self::write_persistent(env, &carryover_key, &added);
+        env.storage().persistent().remove(&banked_key);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("CRED_CARY"), round.id, voter.clone()),
+            (banked, added),
+        );
+    }
+
+    // Function to get the credits a voter has banked and not yet carried over
+    pub fn get_banked_credits(env: Env, voter: Address) -> u64 {
+        Self::read_persistent(&env, &DataKey::Credit(CreditKey::Banked(voter))).unwrap_or(0)
+    }
+
+    // Function for a voter to lock tokens in the contract until a round closes, earning one
+    // credit in the round for every `stake_unit` tokens locked. The round's stakes are all in
+    // the token of the first one, and are kept apart from its funding pool. Returns the
+    // credits the voter has earned by staking in the round.
+    pub fn stake_for_credits(
+        env: Env,
+        voter: Address,
+        round_id: u64,
+        token: Address,
+        amount: i128,
+    ) -> Result<u64, ContractError> {
+        // Require authorization from the voter
+        voter.require_auth();
+        Self::extend_instance(&env);
+
+        Self::require_not_paused(&env)?;
+        Self::require_not_blocked(&env, &voter)?;
+
+        if amount <= 0 {
+            return Err(ContractError::InvalidAmount);
+        }
+
+        let round = Self::get_round(env.clone(), round_id)?;
+
+        // Only the round's voters may stake for its credits
+        Self::require_eligible(&env, &round, &voter)?;
+
+        if !round.is_active() {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        if env.ledger().timestamp() > round.voting_deadline {
+            return Err(ContractError::VotingClosed);
+        }
+
+        if round.config.stake_unit == 0 {
+            return Err(ContractError::InvalidAllocations);
+        }
+
+        // Credits handed to a delegate were counted when they were delegated
+        if Self::has_persistent(&env, &DataKey::Delegate(round_id, voter.clone())) {
+            return Err(ContractError::InvalidDelegation);
+        }
+
+        let token_key = DataKey::Credit(CreditKey::StakeToken(round_id));
+        match Self::read_persistent::<Address>(&env, &token_key) {
+            Some(round_token) if round_token != token => {
+                return Err(ContractError::InvalidAmount);
+            }
+            Some(_) => {}
+            None => Self::write_persistent(&env, &token_key, &token),
+        }
+
+        // Credits are earned on the running stake, so no tokens are lost to rounding between
+        // stakes
+        let staked_key = DataKey::Credit(CreditKey::Staked(round_id, voter.clone()));
+        let staked = Self::read_persistent::<i128>(&env, &staked_key)
+            .unwrap_or(0)
+            .checked_add(amount)
+            .ok_or(ContractError::MathOverflow)?;
+        let credits = u64::try_from(staked / round.config.stake_unit)
+            .map_err(|_| ContractError::MathOverflow)?;
+        if round.config.max_stake_credits > 0 && credits > round.config.max_stake_credits {
+            return Err(ContractError::ExceededVoteLimit);
+        }
+
+        TokenClient::new(&env, &token).transfer(&voter, &env.current_contract_address(), &amount);
+        Self::adjust_obligations(&env, &token, amount);
+        Self::write_persistent(&env, &staked_key, &staked);
+        Self::write_persistent(
+            &env,
+            &DataKey::Credit(CreditKey::StakedCredits(round_id, voter.clone())),
+            &credits,
+        );
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("STAKE"), round_id, voter),
+            (token, amount, credits),
+        );
+
+        Ok(credits)
+    }
+
+    // Function for a voter to take back the tokens they staked in a round once it has been
+    // closed or cancelled. The credits they earned stay counted in the round. Returns the
+    // amount returned.
+    pub fn unstake(env: Env, voter: Address, round_id: u64) -> Result<i128, ContractError> {
+        // Require authorization from the voter
+        voter.require_auth();
+        Self::extend_instance(&env);
+
+        let round = Self::get_round(env.clone(), round_id)?;
+
+        // Ballots stay live until the round is closed
+        if !matches!(
+            round.phase,
+            RoundPhase::Closed | RoundPhase::Disbursed | RoundPhase::Cancelled
+        ) {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        let staked_key = DataKey::Credit(CreditKey::Staked(round_id, voter.clone()));
+        let staked = Self::read_persistent::<i128>(&env, &staked_key).unwrap_or(0);
+        if staked == 0 {
+            return Err(ContractError::InvalidAmount);
+        }
+        let token: Address =
+            Self::read_persistent(&env, &DataKey::Credit(CreditKey::StakeToken(round_id)))
+                .ok_or(ContractError::InvalidAmount)?;
+
+        env.storage().persistent().remove(&staked_key);
+        TokenClient::new(&env, &token).transfer(&env.current_contract_address(), &voter, &staked);
+        Self::adjust_obligations(&env, &token, -staked);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("UNSTAKE"), round_id, voter), (token, staked));
+
+        Ok(staked)
+    }
+
+    // Function to get the tokens a voter has staked in a round and not yet taken back
+    pub fn get_stake(env: Env, round_id: u64, voter: Address) -> i128 {
+        Self::read_persistent(&env, &DataKey::Credit(CreditKey::Staked(round_id, voter)))
+            .unwrap_or(0)
+    }
+
+    // Function to get a voter's total and unspent credits in a round
+    pub fn get_voter_credits(
+        env: Env,
+        round_id: u64,
+        voter: Address,
+    ) -> Result<CreditBalance, ContractError> {
+        let round = Self::get_round(env.clone(), round_id)?;
+        let total = Self::voter_budget(&env, &round, &voter);
+        let spent: u64 =
+            Self::read_persistent(&env, &DataKey::VoterCredits(round_id, voter)).unwrap_or(0);
+
+        Ok(CreditBalance {
+            total,
+            remaining: total.saturating_sub(spent),
+        })
+    }
+
+    // Function to check whether a voter has a ballot in a round
+    pub fn has_voted(env: Env, round_id: u64, voter: Address) -> Result<bool, ContractError> {
+        Self::get_round(env.clone(), round_id)?;
+        Ok(Self::has_persistent(
+            &env,
+            &DataKey::VoterAllocation(round_id, voter),
+        ))
+    }
+
+    // Function to get the credits a voter has left to spend in a round; 0 for addresses
+    // that may not vote in it
+    pub fn remaining_credits(
+        env: Env,
+        round_id: u64,
+        voter: Address,
+    ) -> Result<u64, ContractError> {
+        let round = Self::get_round(env.clone(), round_id)?;
+        if Self::require_eligible(&env, &round, &voter).is_err() {
+            return Ok(0);
+        }
+        Ok(Self::get_voter_credits(env, round_id, voter)?.remaining)
+    }
+
+    // Function to register a voter for a single round while the round is active
+    pub fn add_round_voter(env: Env, round_id: u64, voter: Address) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        let round = Self::get_round(env.clone(), round_id)?;
+        if !round.is_active() {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        Self::admit_round_voter(&env, round_id, &voter);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("RVTR_ADD"), round_id), voter);
+
+        Ok(())
+    }
+
+    // Helper function to add a voter to a round's voters, returning false if already listed
+    fn admit_round_voter(env: &Env, round_id: u64, voter: &Address) -> bool {
+        let voter_key = DataKey::RoundVoter(round_id, voter.clone());
+        if Self::has_persistent(env, &voter_key) {
+            return false;
+        }
+        Self::write_persistent(env, &voter_key, &true);
+
+        let voters_key = DataKey::RoundVoters(round_id);
+        let mut voters =
+            Self::read_persistent::<Vec<Address>>(env, &voters_key).unwrap_or(Vec::new(env));
+        voters.push_back(voter.clone());
+        Self::write_persistent(env, &voters_key, &voters);
+        true
+    }
+
+    // Function to remove a round-scoped voter while the round is active. Allocations
+    // already cast by the voter are kept, but no new ones will be accepted.
+    pub fn remove_round_voter(
+        env: Env,
+        round_id: u64,
+        voter: Address,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        let round = Self::get_round(env.clone(), round_id)?;
+        if !round.is_active() {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        let voter_key = DataKey::RoundVoter(round_id, voter.clone());
+        if !Self::has_persistent(&env, &voter_key) {
+            return Err(ContractError::NotAVoter);
+        }
+        env.storage().persistent().remove(&voter_key);
+
+        let voters_key = DataKey::RoundVoters(round_id);
+        let mut voters =
+            Self::read_persistent::<Vec<Address>>(&env, &voters_key).unwrap_or(Vec::new(&env));
+        if let Some(index) = voters.first_index_of(&voter) {
+            voters.remove(index);
+        }
+        Self::write_persistent(&env, &voters_key, &voters);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("RVTR_RMV"), round_id), voter);
+
+        Ok(())
+    }
+
+    // Function to check whether an address may vote in a round, either as a round-scoped
+    // voter or through the global registry if the round inherits it
+    pub fn is_round_voter(env: Env, round_id: u64, addr: Address) -> bool {
+        Self::get_round(env.clone(), round_id)
+            .is_ok_and(|round| Self::is_eligible(&env, &round, &addr))
+    }
+
+    // Helper function to check a voter's eligibility for a round
+    fn is_eligible(env: &Env, round: &Round, voter: &Address) -> bool {
+        Self::has_persistent(env, &DataKey::RoundVoter(round.id, voter.clone()))
+            || (round.config.inherit_global_voters && Self::is_voter(env.clone(), voter.clone()))
+    }
+
+    // Helper function to check that a voter may cast a ballot in a round. Token-gated rounds
+    // admit holders of the round's token in place of the registry.
+    fn require_eligible(env: &Env, round: &Round, voter: &Address) -> Result<(), ContractError> {
+        let TokenGate::Holding(token, min_balance) = &round.config.token_gate else {
+            if !Self::is_eligible(env, round, voter) {
+                return Err(ContractError::NotAVoter);
+            }
+            return Ok(());
+        };
+
+        // A missing or failing token contract can't vouch for the voter
+        match TokenClient::new(env, token).try_balance(voter) {
+            Ok(Ok(balance)) if balance >= *min_balance => Ok(()),
+            _ => Err(ContractError::NotEligible),
+        }
+    }
+
+    // Function to get the Merkle root of the voters admitted to a round by proof, if any
+    pub fn get_eligibility_root(env: Env, round_id: u64) -> Option<BytesN<32>> {
+        Self::read_persistent(&env, &DataKey::EligibilityRoot(round_id))
+    }
+
+    // Function to list a round's round-scoped voters, skipping the first `start` and
+    // returning at most `limit`
+    pub fn get_round_voters(env: Env, round_id: u64, start: u32, limit: u32) -> Vec<Address> {
+        let voters = Self::read_persistent::<Vec<Address>>(&env, &DataKey::RoundVoters(round_id))
+            .unwrap_or(Vec::new(&env));
+
+        let end = start
+            .saturating_add(limit.min(Self::MAX_PAGE_SIZE))
+            .min(voters.len());
+        if start >= end {
+            return Vec::new(&env);
+        }
+        voters.slice(start..end)
+    }
+
+    // Function to create a new round
+    pub fn create_round(
+        env: Env,
+        caller: Address,
+        funding_amount: i128,
+        submission_deadline: u64,
+        voting_deadline: u64,
+        config: RoundConfig,
+        name: Bytes,
+        metadata_hash: Bytes,
+        eligibility_root: Option<BytesN<32>>,
+    ) -> Result<u64, ContractError> {
+        // Require authorization from the admin or a round manager
+        Self::require_role(&env, &caller, Role::RoundManager)?;
+
+        Self::validate_metadata(&name, &metadata_hash)?;
+
+        // A round needs something to distribute. Amounts are i128, like the token
+        // interface's, so a negative one must be rejected.
+        if funding_amount <= 0 {
+            return Err(ContractError::InvalidFundingAmount);
+        }
+
+        // Submissions must still be possible, and voting can't end before submissions do
+        if submission_deadline <= env.ledger().timestamp() || voting_deadline < submission_deadline
+        {
+            return Err(ContractError::InvalidDeadline);
+        }
+
+        // Rounds created without their own credits give voters the configured default
+        let mut config = config;
+        if config.vote_credits == 0 {
+            config.vote_credits = Self::get_config(env.clone())?.default_vote_credits;
+        }
+
+        // A token gate needs a positive threshold to exclude anyone
+        if let TokenGate::Holding(_, min_balance) = config.token_gate {
+            if min_balance <= 0 {
+                return Err(ContractError::InvalidAmount);
+            }
+        }
+
+        // A submission can't be capped above the whole pool, or below nothing
+        if config.max_match_bps > Self::TOTAL_BPS || config.max_allocation < 0 {
+            return Err(ContractError::InvalidAmount);
+        }
+
+        // Payouts must have finished vesting once the schedule ends
+        if config.cliff_seconds > config.duration_seconds {
+            return Err(ContractError::InvalidDeadline);
+        }
+
+        // Milestone tranches must each release something and together release everything
+        if !config.tranches.is_empty() {
+            let total: u64 = config.tranches.iter().map(u64::from).sum();
+            if config.tranches.contains(0) || total != Self::TOTAL_BPS as u64 {
+                return Err(ContractError::InvalidAmount);
+            }
+        }
+
+        // Committed ballots are only seen once voting is over, too late to tell when they
+        // were cast
+        if config.time_weighted && config.reveal_period > 0 {
+            return Err(ContractError::InvalidDeadline);
+        }
+
+        // Rounds can't add more than a voter's whole bank
+        if config.carryover_bps > Self::TOTAL_BPS {
+            return Err(ContractError::InvalidAmount);
+        }
+
+        if config.stake_unit < 0 {
+            return Err(ContractError::InvalidAmount);
+        }
+
+        if let SubmissionDeposit::Token(_, amount) = config.submission_deposit {
+            if amount <= 0 {
+                return Err(ContractError::InvalidAmount);
+            }
+        }
+
+        // Category budgets split the whole funding amount between distinct categories
+        if !config.categories.is_empty() {
+            if config.categories.len() > Self::MAX_ROUND_CATEGORIES {
+                return Err(ContractError::InvalidCategory);
+            }
+            let mut total: i128 = 0;
+            for (position, category) in config.categories.iter().enumerate() {
+                let duplicate = config
+                    .categories
+                    .iter()
+                    .take(position)
+                    .any(|other| other.name == category.name);
+                if duplicate || category.budget < 0 {
+                    return Err(ContractError::InvalidCategory);
+                }
+                total = total
+                    .checked_add(category.budget)
+                    .ok_or(ContractError::InvalidAmount)?;
+            }
+            if total != funding_amount || u64::try_from(total).is_err() {
+                return Err(ContractError::InvalidAmount);
+            }
+        }
+
+        // Generate a new round ID
+        let next_round_id_key = DataKey::NextRoundId;
+        let mut round_id = env
+            .storage()
+            .instance()
+            .get::<DataKey, u64>(&next_round_id_key)
+            .unwrap_or(0);
+
+        round_id += 1;
+        env.storage().instance().set(&next_round_id_key, &round_id);
+
+        // Create a new round
+        let round = Round {
+            id: round_id,
+            name,
+            metadata_hash,
+            funding_amount,
+            submission_deadline,
+            voting_deadline,
+            config,
+            phase: RoundPhase::Submission,
+            submissions: Vec::new(&env),
+            voter_count: 0,
+            total_votes: 0,
+            distributable: 0,
+            credits_spent: 0,
+            tallies: Map::new(&env),
+            funded_votes: 0,
+            cutoff_votes: 0,
+            cutoff_id: 0,
+            capped_votes: 0,
+            uncapped_pool: 0,
+            uncapped_votes: 0,
+            halted: false,
+            overrides: Map::new(&env),
+            closed_at: 0,
+            challenge_ends: 0,
+            challenge_extended: false,
+            voting_opened_at: 0,
+            raw_votes: 0,
+            abstentions: 0,
+            submission_categories: Map::new(&env),
+            category_totals: Vec::new(&env),
+        };
+
+        // Store the round
+        Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+        if let Some(root) = eligibility_root {
+            Self::write_persistent(&env, &DataKey::EligibilityRoot(round_id), &root);
+        }
+        let payload = Vec::from_array(&env, [funding_amount]);
+        Self::record_audit(&env, symbol_short!("create"), &caller, round_id, payload);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("RND_CREAT"), round_id),
+            RoundCreatedEvent {
+                round_id,
+                creator: caller,
+                funding_amount: round.funding_amount,
+                submission_deadline: round.submission_deadline,
+                voting_deadline: round.voting_deadline,
+                mechanism: round.config.mechanism,
+            },
+        );
+
+        Ok(round_id)
+    }
+
+    // Function to get a round
+    pub fn get_round(env: Env, round_id: u64) -> Result<Round, ContractError> {
+        Self::read_persistent::<Round>(&env, &DataKey::Round(round_id))
+            .ok_or(ContractError::RoundNotFound)
+    }
+
+    // Function for anyone to extend the TTL of a round, its submissions and its payout state
+    // in one pass. Voter-scoped entries are extended whenever their voter touches them.
+    pub fn bump_round(env: Env, round_id: u64) -> Result<(), ContractError> {
+        Self::extend_instance(&env);
+
+        // Reading the round and its submissions extends their TTLs
+        let round = Self::get_round(env.clone(), round_id)?;
+        let tokens = Self::read_persistent::<Vec<Address>>(&env, &DataKey::EscrowTokens(round_id))
+            .unwrap_or(Vec::new(&env));
+        for submission_id in round.submissions.iter() {
+            let submission = Self::get_submission(env.clone(), submission_id)?;
+            let submission_keys = [
+                DataKey::Submitter(round_id, submission.submitter),
+                DataKey::PayoutSplits(submission_id),
+            ];
+            for key in submission_keys {
+                Self::has_persistent(&env, &key);
+            }
+            for token in tokens.iter() {
+                Self::has_persistent(&env, &DataKey::Claimed(submission_id, token.clone()));
+                Self::has_persistent(&env, &DataKey::Vesting(submission_id, token.clone()));
+                Self::has_persistent(&env, &DataKey::Paid(submission_id, token));
+            }
+        }
+
+        let round_keys = [
+            DataKey::RoundVoters(round_id),
+            DataKey::EligibilityRoot(round_id),
+            DataKey::FeeDue(round_id),
+            DataKey::FinalStats(round_id),
+            DataKey::Donors(round_id),
+            DataKey::Depositors(round_id),
+            DataKey::RefundTo(round_id),
+            DataKey::RefundCursor(round_id),
+            DataKey::TranchesReleased(round_id),
+        ];
+        for key in round_keys {
+            Self::has_persistent(&env, &key);
+        }
+
+        for token in tokens.iter() {
+            let token_keys = [
+                DataKey::Escrow(round_id, token.clone()),
+                DataKey::TokenPool(round_id, token.clone()),
+                DataKey::Remainder(round_id, token.clone()),
+                DataKey::ClaimDeadline(round_id, token.clone()),
+                DataKey::Disbursed(round_id, token.clone()),
+                DataKey::VotesPaid(round_id, token),
+            ];
+            for key in token_keys {
+                Self::has_persistent(&env, &key);
+            }
+        }
+
+        let voters = Self::read_persistent::<Vec<Address>>(&env, &DataKey::RoundVoters(round_id))
+            .unwrap_or(Vec::new(&env));
+        for voter in voters.iter() {
+            Self::has_persistent(&env, &DataKey::RoundVoter(round_id, voter));
+        }
+
+        let donors = Self::read_persistent::<Vec<Address>>(&env, &DataKey::Donors(round_id))
+            .unwrap_or(Vec::new(&env));
+        for donor in donors.iter() {
+            Self::has_persistent(&env, &DataKey::Donation(round_id, donor));
+        }
+
+        let depositors =
+            Self::read_persistent::<Vec<Address>>(&env, &DataKey::Depositors(round_id))
+                .unwrap_or(Vec::new(&env));
+        for depositor in depositors.iter() {
+            Self::has_persistent(&env, &DataKey::Deposit(round_id, depositor));
+        }
+
+        Ok(())
+    }
+
+    // Function to list rounds, skipping the first `start` rounds and returning at most `limit`
+    pub fn list_rounds(env: Env, start: u64, limit: u32) -> Vec<Round> {
+        let round_count = env
+            .storage()
+            .instance()
+            .get::<DataKey, u64>(&DataKey::NextRoundId)
+            .unwrap_or(0);
+
+        let mut rounds = Vec::new(&env);
+        let limit = limit.min(Self::MAX_PAGE_SIZE) as u64;
+        let mut round_id = start.saturating_add(1);
+        while round_id <= round_count && (rounds.len() as u64) < limit {
+            if let Ok(round) = Self::get_round(env.clone(), round_id) {
+                rounds.push_back(round);
+            }
+            round_id += 1;
+        }
+
+        rounds
+    }
+
+    // Function to amend the deadlines or funding of an active round
+    pub fn update_round(
+        env: Env,
+        round_id: u64,
+        new_submission_deadline: Option<u64>,
+        new_voting_deadline: Option<u64>,
+        new_funding: Option<i128>,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        let mut round = Self::get_round(env.clone(), round_id)?;
+
+        if !round.is_active() {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        // New deadlines must lie in the future
+        let current_timestamp = env.ledger().timestamp();
+        if let Some(deadline) = new_submission_deadline {
+            if deadline <= current_timestamp {
+                return Err(ContractError::InvalidDeadline);
+            }
+            round.submission_deadline = deadline;
+        }
+        if let Some(deadline) = new_voting_deadline {
+            if deadline <= current_timestamp {
+                return Err(ContractError::InvalidDeadline);
+            }
+            round.voting_deadline = deadline;
+        }
+        if round.voting_deadline < round.submission_deadline {
+            return Err(ContractError::InvalidDeadline);
+        }
+
+        // The funding can't shrink once votes have been cast against it
+        if let Some(funding) = new_funding {
+            if funding <= 0 {
+                return Err(ContractError::InvalidFundingAmount);
+            }
+            if funding < round.funding_amount {
+                for submission_id in round.submissions.iter() {
+                    if Self::get_submission(env.clone(), submission_id)?.total_votes > 0 {
+                        return Err(ContractError::RoundLocked);
+                    }
+                }
+            }
+            Self::rescale_categories(&env, &mut round, funding)?;
+            round.funding_amount = funding;
+        }
+
+        Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("RND_UPD"), round_id),
+            (
+                round.submission_deadline,
+                round.voting_deadline,
+                round.funding_amount,
+            ),
+        );
+
+        Ok(())
+    }
+
+    // Function to correct the name or metadata hash of an active round
+    pub fn set_round_metadata(
+        env: Env,
+        round_id: u64,
+        name: Bytes,
+        metadata_hash: Bytes,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        let mut round = Self::get_round(env.clone(), round_id)?;
+        if !round.is_active() {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        Self::validate_metadata(&name, &metadata_hash)?;
+
+        round.name = name;
+        round.metadata_hash = metadata_hash.clone();
+        Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("RND_META"), round_id), metadata_hash);
+
+        Ok(())
+    }
+
+    // Function to get the number of votes each voter may allocate in a round
+    pub fn get_vote_credits(env: Env, round_id: u64) -> Result<u64, ContractError> {
+        Ok(Self::get_round(env, round_id)?.config.vote_credits)
+    }
+
+    // Function to get a round's turnout figures, live while voting is open and as frozen
+    // when voting closed afterwards
+    pub fn get_round_stats(env: Env, round_id: u64) -> Result<RoundStats, ContractError> {
+        if let Some(stats) = Self::read_persistent(&env, &DataKey::FinalStats(round_id)) {
+            return Ok(stats);
+        }
+        Ok(Self::live_stats(&Self::get_round(env, round_id)?))
+    }
+
+    // Helper function to derive a round's statistics from its running totals
+    fn live_stats(round: &Round) -> RoundStats {
+        RoundStats {
+            voter_count: round.voter_count,
+            credits_spent: round.credits_spent,
+            votes_cast: round.total_votes,
+            raw_votes: round.raw_votes,
+            abstentions: round.abstentions,
+            submission_count: round.submissions.len(),
+        }
+    }
+
+    // Function to get the number of distinct voters with a ballot in a round
+    pub fn get_voter_count(env: Env, round_id: u64) -> Result<u32, ContractError> {
+        Ok(Self::get_round(env, round_id)?.voter_count)
+    }
+
+    // Function to get the voting rules of a round
+    pub fn get_round_config(env: Env, round_id: u64) -> Result<RoundConfig, ContractError> {
+        Ok(Self::get_round(env, round_id)?.config)
+    }
+
+    // Function to get the current phase of a round
+    pub fn get_round_phase(env: Env, round_id: u64) -> Result<RoundPhase, ContractError> {
+        Ok(Self::get_round(env, round_id)?.phase)
+    }
+
+    // Function to close submissions and open voting for a round
+    pub fn start_voting(env: Env, round_id: u64) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        let mut round = Self::get_round(env.clone(), round_id)?;
+
+        if round.phase != RoundPhase::Submission {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        round.voting_opened_at = env.ledger().timestamp();
+        Self::set_phase(&env, &mut round, RoundPhase::Voting);
+        Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+
+        Ok(())
+    }
+
+    // Helper function to move a round to a new phase and emit the transition.
+    // The caller is responsible for storing the round.
+    fn set_phase(env: &Env, round: &mut Round, phase: RoundPhase) {
+        let previous = round.phase;
+        round.phase = phase;
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("PHASE"), round.id), (previous, phase));
+    }
+
+    // Function to submit a project to a round
+    pub fn submit_project(
+        env: Env,
+        submitter: Address,
+        round_id: u64,
+        name: Bytes,
+        metadata_hash: Bytes,
+        previous_submission_id: Option<u64>,
+        category: Option<Symbol>,
+    ) -> Result<u64, ContractError> {
+        // Require authorization from the submitter
+        submitter.require_auth();
+
+        Self::record_submission(
+            &env,
+            Submission {
+                id: 0,
+                round_id,
+                submitter: submitter.clone(),
+                payout_address: submitter,
+                name,
+                metadata_hash,
+                total_votes: 0,
+                status: SubmissionStatus::Pending,
+                withdrawn: false,
+                previous_submission_id: previous_submission_id.unwrap_or(0),
+                project_id: 0,
+            },
+            category,
+        )
+    }
+
+    // Helper function to check a new submission against its round, assign it an id and store it
+    // under its category
+    fn record_submission(
+        env: &Env,
+        mut submission: Submission,
+        category: Option<Symbol>,
+    ) -> Result<u64, ContractError> {
+        Self::extend_instance(env);
+        let submitter = submission.submitter.clone();
+        let round_id = submission.round_id;
+
+        Self::require_not_paused(env)?;
+        Self::require_not_blocked(env, &submitter)?;
+
+        Self::validate_metadata(&submission.name, &submission.metadata_hash)?;
+
+        // Check if the round exists and is accepting submissions
+        let mut round = Self::get_round(env.clone(), round_id)?;
+
+        if !round.is_active() {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        if round.phase != RoundPhase::Submission {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        // Check if the submission deadline has not passed
+        let current_timestamp = env.ledger().timestamp();
+        if current_timestamp > round.submission_deadline {
+            return Err(ContractError::SubmissionDeadlinePassed);
+        }
+
+        // Each address may submit a single project per round
+        let submitter_key = DataKey::Submitter(round_id, submitter.clone());
+        if Self::has_persistent(env, &submitter_key) {
+            return Err(ContractError::AlreadySubmitted);
+        }
+
+        let max_submissions = round.config.max_submissions;
+        if max_submissions > 0 && round.submissions.len() >= max_submissions {
+            return Err(ContractError::SubmissionLimitReached);
+        }
+
+        // Rounds with categories file every submission under one of theirs
+        let category_index = Self::category_index(&round, category)?;
+
+        // A continuation links to the same project's submission to an earlier round
+        let previous_id = submission.previous_submission_id;
+        if previous_id != 0 {
+            let previous = Self::get_submission(env.clone(), previous_id)?;
+            if previous.round_id >= round_id
+                || (previous.submitter != submitter && previous.payout_address != submitter)
+            {
+                return Err(ContractError::InvalidLineage);
+            }
+        }
+
+        // Lock the round's submission deposit until the submission is reviewed
+        let deposit = Self::take_deposit(env, &round, &submitter)?;
+
+        // Generate a new submission ID
+        let next_submission_id_key = DataKey::NextSubmissionId;
+        let mut submission_id = env
+            .storage()
+            .instance()
+            .get::<DataKey, u64>(&next_submission_id_key)
+            .unwrap_or(0);
+
+        submission_id += 1;
+        env.storage()
+            .instance()
+            .set(&next_submission_id_key, &submission_id);
+        submission.id = submission_id;
+
+        // Store the submission
+        Self::write_persistent(env, &DataKey::Submission(submission_id), &submission);
+
+        Self::write_persistent(env, &submitter_key, &submission_id);
+
+        // Index the submission under its submitter, keeping only the latest ones
+        let index_key = DataKey::Index(IndexKey::SubmitterSubmissions(submitter));
+        let mut index = Self::read_persistent::<Vec<u64>>(env, &index_key).unwrap_or(Vec::new(env));
+        if index.len() >= Self::MAX_SUBMITTER_SUBMISSIONS {
+            index.pop_front();
+        }
+        index.push_back(submission_id);
+        Self::write_persistent(env, &index_key, &index);
+
+        // Add submission ID to the round
+        round.submissions.push_back(submission_id);
+        if let Some(index) = category_index {
+            round.submission_categories.set(submission_id, index);
+        }
+        Self::write_persistent(env, &DataKey::Round(round_id), &round);
+
+        if deposit > 0 {
+            let deposits_key = DataKey::Project(ProjectKey::Deposits(round_id));
+            let mut deposits = Self::read_persistent::<Map<u64, i128>>(env, &deposits_key)
+                .unwrap_or(Map::new(env));
+            deposits.set(submission_id, deposit);
+            Self::write_persistent(env, &deposits_key, &deposits);
+        }
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("PROJ_SUB"), submission_id),
+            ProjectSubmittedEvent {
+                submission_id,
+                round_id,
+                submitter: submission.submitter,
+                previous_submission_id: submission.previous_submission_id,
+            },
+        );
+
+        Ok(submission_id)
+    }
+
+    // Helper function to scale a round's category budgets to a new funding amount, keeping
+    // them in proportion and adding up to it. The rounding dust goes to the largest budget.
+    fn rescale_categories(
+        env: &Env,
+        round: &mut Round,
+        funding: i128,
+    ) -> Result<(), ContractError> {
+        let categories = &round.config.categories;
+        if categories.is_empty() {
+            return Ok(());
+        }
+
+        // Budgets must keep fitting a u64, as at creation
+        if u64::try_from(funding).is_err() {
+            return Err(ContractError::InvalidAmount);
+        }
+
+        let mut scaled = Vec::new(env);
+        let mut total: i128 = 0;
+        let mut largest = 0;
+        for (index, mut category) in categories.iter().enumerate() {
+            if category.budget > categories.get_unchecked(largest).budget {
+                largest = index as u32;
+            }
+            category.budget = category
+                .budget
+                .checked_mul(funding)
+                .ok_or(ContractError::MathOverflow)?
+                / round.funding_amount;
+            total += category.budget;
+            scaled.push_back(category);
+        }
+        let mut category = scaled.get_unchecked(largest);
+        category.budget += funding - total;
+        scaled.set(largest, category);
+        round.config.categories = scaled;
+
+        Ok(())
+    }
+
+    // Maximum number of categories a round's funding can be split between
+    const MAX_ROUND_CATEGORIES: u32 = 10;
+
+    // Helper function to find the index of a category among the round's. A round without
+    // categories takes no category, and a round with some requires one of them.
+    fn category_index(
+        round: &Round,
+        category: Option<Symbol>,
+    ) -> Result<Option<u32>, ContractError> {
+        let categories = &round.config.categories;
+        match category {
+            None if categories.is_empty() => Ok(None),
+            None => Err(ContractError::InvalidCategory),
+            Some(name) => categories
+                .iter()
+                .position(|category| category.name == name)
+                .map(|index| Some(index as u32))
+                .ok_or(ContractError::InvalidCategory),
+        }
+    }
+
+    // Helper function to pull a round's submission deposit from a submitter into the contract.
+    // Returns the amount deposited, 0 if the round takes no deposit.
+    fn take_deposit(env: &Env, round: &Round, submitter: &Address) -> Result<i128, ContractError> {
+        let SubmissionDeposit::Token(token, amount) = &round.config.submission_deposit else {
+            return Ok(0);
+        };
+
+        let token_client = TokenClient::new(env, token);
+        if token_client.balance(submitter) < *amount {
+            return Err(ContractError::InsufficientFunds);
+        }
+        token_client.transfer(submitter, &env.current_contract_address(), amount);
+        Self::adjust_obligations(env, token, *amount);
+
+        Ok(*amount)
+    }
+
+    // Helper function to pay out the deposit a round holds for a submission, to its submitter
+    // or, when slashed, to `to`. Does nothing if the deposit has already been paid out.
+    fn release_deposit(
+        env: &Env,
+        round: &Round,
+        submission: &Submission,
+        slashed_to: Option<Address>,
+    ) {
+        let SubmissionDeposit::Token(token, _) = &round.config.submission_deposit else {
+            return;
+        };
+        let deposits_key = DataKey::Project(ProjectKey::Deposits(round.id));
+        let Some(mut deposits) = Self::read_persistent::<Map<u64, i128>>(env, &deposits_key) else {
+            return;
+        };
+        let Some(amount) = deposits.get(submission.id) else {
+            return;
+        };
+
+        // Remove the deposit before paying it out so it can't be paid twice
+        deposits.remove(submission.id);
+        Self::write_persistent(env, &deposits_key, &deposits);
+
+        let topic = if slashed_to.is_some() {
+            symbol_short!("DEP_SLSH")
+        } else {
+            symbol_short!("DEP_RFND")
+        };
+        let to = slashed_to.unwrap_or(submission.submitter.clone());
+        TokenClient::new(env, token).transfer(&env.current_contract_address(), &to, &amount);
+        Self::adjust_obligations(env, token, -amount);
+
+        // Emit event
+        env.events().publish((topic, submission.id), (to, amount));
+    }
+
+    // Helper function to refund every deposit a round still holds once it closes or is
+    // cancelled. Blacklisted submitters are not in good standing, so their deposits are
+    // slashed as for spam.
+    fn refund_deposits(env: &Env, round: &Round) -> Result<(), ContractError> {
+        let deposits_key = DataKey::Project(ProjectKey::Deposits(round.id));
+        let Some(deposits) = Self::read_persistent::<Map<u64, i128>>(env, &deposits_key) else {
+            return Ok(());
+        };
+        for submission_id in deposits.keys().iter() {
+            if let Some(submission) =
+                Self::read_persistent::<Submission>(env, &DataKey::Submission(submission_id))
+            {
+                let slashed_to = if Self::is_blacklisted(env.clone(), submission.submitter.clone())
+                {
+                    Some(Self::slash_recipient(env)?)
+                } else {
+                    None
+                };
+                Self::release_deposit(env, round, &submission, slashed_to);
+            }
+        }
+        Ok(())
+    }
+
+    // Function to get the deposit a round still holds for a submission
+    pub fn get_submission_deposit(env: Env, submission_id: u64) -> i128 {
+        let Ok(submission) = Self::get_submission(env.clone(), submission_id) else {
+            return 0;
+        };
+        Self::read_persistent::<Map<u64, i128>>(
+            &env,
+            &DataKey::Project(ProjectKey::Deposits(submission.round_id)),
+        )
+        .and_then(|deposits| deposits.get(submission_id))
+        .unwrap_or(0)
+    }
+
+    // Maximum number of earlier submissions `get_submission_lineage` follows
+    const MAX_LINEAGE_HOPS: u32 = 20;
+
+    // Function to get a submission's lineage: the submission followed by the earlier ones it
+    // continues, newest first, for at most `MAX_LINEAGE_HOPS` hops back
+    pub fn get_submission_lineage(env: Env, submission_id: u64) -> Result<Vec<u64>, ContractError> {
+        let mut submission = Self::get_submission(env.clone(), submission_id)?;
+        let mut lineage = Vec::from_array(&env, [submission_id]);
+        while submission.previous_submission_id != 0 && lineage.len() <= Self::MAX_LINEAGE_HOPS {
+            let previous_id = submission.previous_submission_id;
+            lineage.push_back(previous_id);
+            submission = Self::get_submission(env.clone(), previous_id)?;
+        }
+        Ok(lineage)
+    }
+
+    // Function to register a project in the catalog, so that its owner can enter it into rounds
+    // without restating its details
+    pub fn register_project(
+        env: Env,
+        owner: Address,
+        name: Bytes,
+        metadata_hash: Bytes,
+        payout: Address,
+    ) -> Result<u64, ContractError> {
+        // Require authorization from the owner
+        owner.require_auth();
+        Self::extend_instance(&env);
+
+        Self::require_not_paused(&env)?;
+        Self::require_not_blocked(&env, &owner)?;
+        Self::validate_metadata(&name, &metadata_hash)?;
+
+        let next_key = DataKey::Project(ProjectKey::NextProjectId);
+        let project_id = Self::read_persistent::<u64>(&env, &next_key).unwrap_or(0) + 1;
+        Self::write_persistent(&env, &next_key, &project_id);
+
+        let project = Project {
+            id: project_id,
+            owner: owner.clone(),
+            name,
+            metadata_hash,
+            payout,
+        };
+        Self::write_persistent(
+            &env,
+            &DataKey::Project(ProjectKey::Catalog(project_id)),
+            &project,
+        );
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("PROJ_REG"), project_id), owner);
+
+        Ok(project_id)
+    }
+
+    // Function for a project's owner to update its catalog entry. Submissions already entered
+    // keep the details they were entered with.
+    pub fn update_project(
+        env: Env,
+        owner: Address,
+        project_id: u64,
+        name: Bytes,
+        metadata_hash: Bytes,
+        payout: Address,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the owner
+        owner.require_auth();
+
+        let mut project = Self::get_project(env.clone(), project_id)?;
+        if project.owner != owner {
+            return Err(ContractError::Unauthorized);
+        }
+        Self::validate_metadata(&name, &metadata_hash)?;
+
+        project.name = name;
+        project.metadata_hash = metadata_hash;
+        project.payout = payout;
+        Self::write_persistent(
+            &env,
+            &DataKey::Project(ProjectKey::Catalog(project_id)),
+            &project,
+        );
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("PROJ_UPD"), project_id), owner);
+
+        Ok(())
+    }
+
+    // Function to get a project from the catalog
+    pub fn get_project(env: Env, project_id: u64) -> Result<Project, ContractError> {
+        Self::read_persistent::<Project>(&env, &DataKey::Project(ProjectKey::Catalog(project_id)))
+            .ok_or(ContractError::ProjectNotFound)
+    }
+
+    // Function for a project's owner to enter it into a round, creating a submission with the
+    // project's current name, metadata and payout address
+    pub fn enter_round(
+        env: Env,
+        owner: Address,
+        project_id: u64,
+        round_id: u64,
+        category: Option<Symbol>,
+    ) -> Result<u64, ContractError> {
+        // Require authorization from the owner
+        owner.require_auth();
+
+        let project = Self::get_project(env.clone(), project_id)?;
+        if project.owner != owner {
+            return Err(ContractError::Unauthorized);
+        }
+
+        Self::record_submission(
+            &env,
+            Submission {
+                id: 0,
+                round_id,
+                submitter: owner,
+                payout_address: project.payout,
+                name: project.name,
+                metadata_hash: project.metadata_hash,
+                total_votes: 0,
+                status: SubmissionStatus::Pending,
+                withdrawn: false,
+                previous_submission_id: 0,
+                project_id,
+            },
+            category,
+        )
+    }
+
+    // Function for a submitter to update the metadata of their submission while the round is active
+    pub fn update_submission_metadata(
+        env: Env,
+        submitter: Address,
+        submission_id: u64,
+        name: Bytes,
+        metadata_hash: Bytes,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the submitter
+        submitter.require_auth();
+
+        let mut submission = Self::get_submission(env.clone(), submission_id)?;
+        if submission.submitter != submitter {
+            return Err(ContractError::Unauthorized);
+        }
+
+        let round = Self::get_round(env.clone(), submission.round_id)?;
+        if !round.is_active() {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        Self::validate_metadata(&name, &metadata_hash)?;
+
+        submission.name = name;
+        submission.metadata_hash = metadata_hash.clone();
+        Self::write_persistent(&env, &DataKey::Submission(submission_id), &submission);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("SUB_META"), submission_id), metadata_hash);
+
+        Ok(())
+    }
+
+    // Function for a submitter to redirect their submission's payout before voting closes, or
+    // while the round's disbursement is halted
+    pub fn set_payout_address(
+        env: Env,
+        submitter: Address,
+        submission_id: u64,
+        payout: Address,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the submitter
+        submitter.require_auth();
+
+        let mut submission = Self::get_submission(env.clone(), submission_id)?;
+        if submission.submitter != submitter {
+            return Err(ContractError::Unauthorized);
+        }
+
+        // Payouts can also be corrected while a round's disbursement is halted
+        let round = Self::get_round(env.clone(), submission.round_id)?;
+        if !round.is_active() && !round.halted {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        submission.payout_address = payout.clone();
+        Self::write_persistent(&env, &DataKey::Submission(submission_id), &submission);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("PAY_ADDR"), submission_id),
+            (submitter, payout),
+        );
+
+        Ok(())
+    }
+
+    // Function for a submitter to hand their submission over to a new submitter while the
+    // round is active. A payout address left at the old submitter follows the submission.
+    pub fn transfer_submission(
+        env: Env,
+        current_submitter: Address,
+        submission_id: u64,
+        new_submitter: Address,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the current submitter
+        current_submitter.require_auth();
+
+        let mut submission = Self::get_submission(env.clone(), submission_id)?;
+        if submission.submitter != current_submitter {
+            return Err(ContractError::Unauthorized);
+        }
+
+        // Ownership is fixed once voting closes, so results can't be redirected
+        let round = Self::get_round(env.clone(), submission.round_id)?;
+        if !round.is_active() {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        if submission.withdrawn {
+            return Err(ContractError::SubmissionNotInRound);
+        }
+
+        Self::require_not_blocked(&env, &new_submitter)?;
+
+        // The new submitter may not already have a submission in the round
+        let new_key = DataKey::Submitter(round.id, new_submitter.clone());
+        if Self::has_persistent(&env, &new_key) {
+            return Err(ContractError::AlreadySubmitted);
+        }
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Submitter(round.id, current_submitter.clone()));
+        Self::write_persistent(&env, &new_key, &submission_id);
+
+        submission.submitter = new_submitter.clone();
+        if submission.payout_address == current_submitter {
+            submission.payout_address = new_submitter.clone();
+        }
+        Self::write_persistent(&env, &DataKey::Submission(submission_id), &submission);
+
+        // Move the submission between the submitters' indexes
+        let old_index_key =
+            DataKey::Index(IndexKey::SubmitterSubmissions(current_submitter.clone()));
+        if let Some(mut index) = Self::read_persistent::<Vec<u64>>(&env, &old_index_key) {
+            if let Some(position) = index.first_index_of(submission_id) {
+                index.remove(position);
+                Self::write_persistent(&env, &old_index_key, &index);
+            }
+        }
+        let new_index_key = DataKey::Index(IndexKey::SubmitterSubmissions(new_submitter.clone()));
+        let mut index =
+            Self::read_persistent::<Vec<u64>>(&env, &new_index_key).unwrap_or(Vec::new(&env));
+        if index.len() >= Self::MAX_SUBMITTER_SUBMISSIONS {
+            index.pop_front();
+        }
+        index.push_back(submission_id);
+        Self::write_persistent(&env, &new_index_key, &index);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("SUB_XFER"), submission_id),
+            (current_submitter, new_submitter),
+        );
+
+        Ok(())
+    }
+
+    // Function for a submitter to split their submission's payout between several recipients,
+    // in basis points summing to 10000. An empty map removes the split.
+    pub fn set_payout_splits(
+        env: Env,
+        submission_id: u64,
+        splits: Map<Address, u32>,
+    ) -> Result<(), ContractError> {
+        let submission = Self::get_submission(env.clone(), submission_id)?;
+
+        // Require authorization from the submitter
+        submission.submitter.require_auth();
+
+        // Payouts can also be corrected while a round's disbursement is halted
+        let round = Self::get_round(env.clone(), submission.round_id)?;
+        if !round.is_active() && !round.halted {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        let splits_key = DataKey::PayoutSplits(submission_id);
+        if splits.is_empty() {
+            env.storage().persistent().remove(&splits_key);
+        } else {
+            if splits.len() > Self::MAX_PAYOUT_SPLITS {
+                return Err(ContractError::InvalidAllocations);
+            }
+            let mut total_bps: u64 = 0;
+            for bps in splits.values().iter() {
+                total_bps += bps as u64;
+            }
+            if total_bps != Self::TOTAL_BPS as u64 {
+                return Err(ContractError::InvalidAllocations);
+            }
+            Self::write_persistent(&env, &splits_key, &splits);
+        }
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("PAY_SPLT"), submission_id), splits);
+
+        Ok(())
+    }
+
+    // Function to get the payout split of a submission, if one is set
+    pub fn get_payout_splits(env: Env, submission_id: u64) -> Option<Map<Address, u32>> {
+        Self::read_persistent(&env, &DataKey::PayoutSplits(submission_id))
+    }
+
+    // Function for a submitter to withdraw their submission from an active round
+    pub fn withdraw_submission(
+        env: Env,
+        submitter: Address,
+        submission_id: u64,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the submitter
+        submitter.require_auth();
+
+        let mut submission = Self::get_submission(env.clone(), submission_id)?;
+        if submission.submitter != submitter {
+            return Err(ContractError::Unauthorized);
+        }
+
+        let mut round = Self::get_round(env.clone(), submission.round_id)?;
+        if !round.is_active() {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        if submission.withdrawn {
+            return Err(ContractError::SubmissionNotInRound);
+        }
+
+        // Votes already cast on the submission stop counting, and their credits are refunded
+        // below
+        if submission.status == SubmissionStatus::Approved {
+            round.total_votes -= submission.total_votes;
+            round.tallies.remove(submission_id);
+        }
+        submission.withdrawn = true;
+        submission.total_votes = 0;
+        Self::write_persistent(&env, &DataKey::Submission(submission_id), &submission);
+
+        // Remove the submission from the round so it is never allocated or paid
+        if let Some(index) = round.submissions.first_index_of(submission_id) {
+            round.submissions.remove(index);
+        }
+        round.submission_categories.remove(submission_id);
+        Self::write_persistent(&env, &DataKey::Round(round.id), &round);
+
+        // And from the submitter's index
+        let index_key = DataKey::Index(IndexKey::SubmitterSubmissions(submitter.clone()));
+        if let Some(mut index) = Self::read_persistent::<Vec<u64>>(&env, &index_key) {
+            if let Some(position) = index.first_index_of(submission_id) {
+                index.remove(position);
+                Self::write_persistent(&env, &index_key, &index);
+            }
+        }
+
+        // Return the deposit, if the round still holds it
+        Self::release_deposit(&env, &round, &submission, None);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("SUB_WDRW"), submission_id), submitter);
+
+        // Refund the first voters' credits now; any others are refunded with
+        // refund_suspended_votes, or when they next cast a ballot
+        Self::refund_suspended_votes(env, submission_id, Self::MAX_VOTE_REFUNDS)?;
+
+        Ok(())
+    }
+
+    // Function for the admin or the round's operator to approve or reject a submission while
+    // its round is active
+    pub fn review_submission(
+        env: Env,
+        caller: Address,
+        submission_id: u64,
+        approve: bool,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the admin or the round's operator
+        let round_id = Self::get_submission(env.clone(), submission_id)?.round_id;
+        Self::require_round_role(&env, &caller, round_id, None, symbol_short!("review"))?;
+
+        let (round, submission) = Self::apply_review(&env, submission_id, approve)?;
+
+        // Approved submissions get their deposit back; rejected ones only when the round
+        // closes, so they can still be found to be spam
+        if approve {
+            Self::release_deposit(&env, &round, &submission, None);
+        }
+
+        Ok(())
+    }
+
+    // Function for the admin to reject a submission as spam, slashing its deposit to the fee
+    // recipient, or to the admin if no fee is configured
+    pub fn reject_as_spam(env: Env, submission_id: u64) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        let (round, submission) = Self::apply_review(&env, submission_id, false)?;
+        let slashed_to = Self::slash_recipient(&env)?;
+        Self::release_deposit(&env, &round, &submission, Some(slashed_to));
+
+        Ok(())
+    }
+
+    // Helper function to get the address slashed deposits go to: the fee recipient, or the
+    // admin if no fee is configured
+    fn slash_recipient(env: &Env) -> Result<Address, ContractError> {
+        let config = Self::get_config(env.clone())?;
+        Ok(match config.fee_recipient {
+            FeeRecipient::Account(recipient) => recipient,
+            FeeRecipient::None => config.admin,
+        })
+    }
+
+    // Helper function to approve or reject a submission of an active round
+    fn apply_review(
+        env: &Env,
+        submission_id: u64,
+        approve: bool,
+    ) -> Result<(Round, Submission), ContractError> {
+        let mut submission = Self::get_submission(env.clone(), submission_id)?;
+        let mut round = Self::get_round(env.clone(), submission.round_id)?;
+        if !round.is_active() {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        if submission.withdrawn {
+            return Err(ContractError::SubmissionNotInRound);
+        }
+
+        // Appeals are ruled on with resolve_appeal, and an upheld rejection is final
+        if submission.status == SubmissionStatus::UnderAppeal
+            || Self::get_appeal(env.clone(), submission_id).is_some_and(|appeal| appeal.upheld)
+        {
+            return Err(ContractError::InvalidAppeal);
+        }
+
+        // A suspended submission the admin approves takes a fresh set of flags to suspend
+        // again
+        if submission.status == SubmissionStatus::Suspended && approve {
+            let count_key = DataKey::Project(ProjectKey::FlagCount(submission_id));
+            env.storage().persistent().remove(&count_key);
+        }
+
+        // Only approved submissions' votes count towards the round's running total
+        let was_approved = submission.status == SubmissionStatus::Approved;
+        if was_approved && !approve {
+            round.total_votes -= submission.total_votes;
+            round.tallies.remove(submission_id);
+        } else if !was_approved && approve {
+            round.total_votes = round
+                .total_votes
+                .checked_add(submission.total_votes)
+                .ok_or(ContractError::MathOverflow)?;
+            round.tallies.set(submission_id, submission.total_votes);
+        }
+        Self::write_persistent(env, &DataKey::Round(round.id), &round);
+
+        submission.status = if approve {
+            SubmissionStatus::Approved
+        } else {
+            Self::record_rejection(env, submission_id);
+            SubmissionStatus::Rejected
+        };
+        Self::write_persistent(env, &DataKey::Submission(submission_id), &submission);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("SUB_RVW"), submission_id), submission.status);
+
+        Ok((round, submission))
+    }
+
+    // Function for a registered voter to flag an approved submission of an active round,
+    // e.g. as plagiarized or malicious. Each voter flags a submission once. Once the flags
+    // reach the round's threshold the submission is suspended: it takes no new votes and
+    // its votes stop counting until the admin approves or rejects it with review_submission.
+    pub fn flag_submission(
+        env: Env,
+        reporter: Address,
+        submission_id: u64,
+        reason: Symbol,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the reporter
+        reporter.require_auth();
+        Self::extend_instance(&env);
+
+        Self::require_not_paused(&env)?;
+        Self::require_not_blocked(&env, &reporter)?;
+
+        let mut submission = Self::get_submission(env.clone(), submission_id)?;
+        let mut round = Self::get_round(env.clone(), submission.round_id)?;
+        if !round.is_active() {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        if submission.withdrawn {
+            return Err(ContractError::SubmissionNotInRound);
+        }
+
+        if submission.status != SubmissionStatus::Approved {
+            return Err(ContractError::SubmissionNotApproved);
+        }
+
+        Self::require_eligible(&env, &round, &reporter)?;
+
+        let flag_key = DataKey::Project(ProjectKey::Flag(submission_id, reporter.clone()));
+        if Self::has_persistent(&env, &flag_key) {
+            return Err(ContractError::AlreadyFlagged);
+        }
+        Self::write_persistent(&env, &flag_key, &reason);
+
+        let count_key = DataKey::Project(ProjectKey::FlagCount(submission_id));
+        let flags = Self::read_persistent::<u32>(&env, &count_key).unwrap_or(0) + 1;
+        Self::write_persistent(&env, &count_key, &flags);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("SUB_FLAG"), submission_id, reporter), reason);
+
+        let threshold = round.config.flag_threshold;
+        if threshold == 0 || flags < threshold {
+            return Ok(());
+        }
+
+        // Suspend the submission, taking its votes out of the running total. They stay on
+        // the submission, so approving it again restores those not refunded by then.
+        round.total_votes -= submission.total_votes;
+        round.tallies.remove(submission_id);
+        Self::write_persistent(&env, &DataKey::Round(round.id), &round);
+
+        submission.status = SubmissionStatus::Suspended;
+        Self::write_persistent(&env, &DataKey::Submission(submission_id), &submission);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("SUB_SUSP"), submission_id), flags);
+
+        Ok(())
+    }
+
+    // Function to get the number of flags raised against a submission since its last review
+    pub fn get_flag_count(env: Env, submission_id: u64) -> u32 {
+        Self::read_persistent(
+            &env,
+            &DataKey::Project(ProjectKey::FlagCount(submission_id)),
+        )
+        .unwrap_or(0)
+    }
+
+    // Function to get the reason a voter gave for flagging a submission, if they flagged it
+    pub fn get_flag(env: Env, submission_id: u64, reporter: Address) -> Option<Symbol> {
+        Self::read_persistent(
+            &env,
+            &DataKey::Project(ProjectKey::Flag(submission_id, reporter)),
+        )
+    }
+
+    // Maximum number of voters refunded by a single call to refund_suspended_votes
+    const MAX_VOTE_REFUNDS: u32 = 10;
+
+    // Function to refund the votes cast on a suspended, rejected or withdrawn submission of an
+    // active round, taking them off each voter's ballot and giving back the credits they cost.
+    // Anyone may call it; up to `limit` voters are refunded per call, most recent first, and
+    // the number refunded is returned.
+    pub fn refund_suspended_votes(
+        env: Env,
+        submission_id: u64,
+        limit: u32,
+    ) -> Result<u32, ContractError> {
+        Self::extend_instance(&env);
+
+        let mut submission = Self::get_submission(env.clone(), submission_id)?;
+        let mut round = Self::get_round(env.clone(), submission.round_id)?;
+        if !round.is_active() {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        // Votes on approved submissions still count, and pending ones have none
+        if !submission.withdrawn
+            && !matches!(
+                submission.status,
+                SubmissionStatus::Suspended | SubmissionStatus::Rejected
+            )
+        {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        let count_key = DataKey::Index(IndexKey::SubmissionVoterCount(submission_id));
+        let mut refunded = 0;
+        while refunded < limit.min(Self::MAX_VOTE_REFUNDS) {
+            let count = Self::read_persistent::<u32>(&env, &count_key).unwrap_or(0);
+            if count == 0 {
+                break;
+            }
+
+            // Take the last voter's votes off their ballot, which drops them from the
+            // submission's voter breakdown
+            let last = count - 1;
+            let chunk = Self::voter_chunk(&env, submission_id, last);
+            let (voter, votes) = chunk.get_unchecked(last % Self::SUBMISSION_VOTER_CHUNK);
+            Self::refund_voter_votes(&env, &mut round, &voter, submission_id, votes)?;
+            submission.total_votes = submission.total_votes.saturating_sub(votes);
+
+            // Emit event
+            env.events()
+                .publish((symbol_short!("VOTE_RFND"), submission_id, voter), votes);
+
+            refunded += 1;
+        }
+
+        Self::write_persistent(&env, &DataKey::Submission(submission_id), &submission);
+        Self::write_persistent(&env, &DataKey::Round(round.id), &round);
+
+        Ok(refunded)
+    }
+
+    // Helper function to take a voter's votes on a submission off their ballot in a round,
+    // which drops them from the submission's voter breakdown, and give back the credits
+    // charged for the votes as cast. These may differ from the votes counted when the ballot
+    // was weighted or late. The caller is responsible for storing the round.
+    fn refund_voter_votes(
+        env: &Env,
+        round: &mut Round,
+        voter: &Address,
+        submission_id: u64,
+        votes: u64,
+    ) -> Result<(), ContractError> {
+        let allocation_key = DataKey::VoterAllocation(round.id, voter.clone());
+        let mut allocations = Self::read_persistent::<Map<u64, u64>>(env, &allocation_key)
+            .unwrap_or(Map::new(env));
+        allocations.remove(submission_id);
+        Self::store_voter_allocation(env, round.id, voter, &allocations);
+
+        let cast_key = DataKey::Credit(CreditKey::CastVotes(round.id, voter.clone()));
+        let mut cast = Self::read_persistent::<Map<u64, (u64, u64)>>(env, &cast_key)
+            .unwrap_or(Map::new(env));
+        let (raw, charged) = match cast.get(submission_id) {
+            Some(entry) => entry,
+            None => (votes, Self::entry_cost(&round.config, votes)?),
+        };
+        cast.remove(submission_id);
+        Self::store_cast_votes(env, round.id, voter, &cast);
+
+        let spent: u64 =
+            Self::read_persistent(env, &DataKey::VoterCredits(round.id, voter.clone()))
+                .unwrap_or(0);
+        Self::set_credits_spent(env, round, voter, spent.saturating_sub(charged))?;
+        let raw_votes: u64 =
+            Self::read_persistent(env, &DataKey::RawVotes(round.id, voter.clone())).unwrap_or(0);
+        Self::set_raw_votes(env, round, voter, raw_votes.saturating_sub(raw))
+    }
+
+    // Helper function to record when a submission was rejected, which opens its appeal window
+    fn record_rejection(env: &Env, submission_id: u64) {
+        let rejected_key = DataKey::Project(ProjectKey::RejectedAt(submission_id));
+        Self::write_persistent(env, &rejected_key, &env.ledger().timestamp());
+    }
+
+    // Function for a submitter to appeal the rejection of their submission, once, within the
+    // round's appeal window after the rejection. The submission is under appeal until the
+    // admin rules on it with resolve_appeal.
+    pub fn appeal(
+        env: Env,
+        submitter: Address,
+        submission_id: u64,
+        evidence_hash: Bytes,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the submitter
+        submitter.require_auth();
+
+        Self::require_not_blocked(&env, &submitter)?;
+
+        let mut submission = Self::get_submission(env.clone(), submission_id)?;
+        if submission.submitter != submitter {
+            return Err(ContractError::Unauthorized);
+        }
+
+        let round = Self::get_round(env.clone(), submission.round_id)?;
+        if round.phase == RoundPhase::Cancelled {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        if submission.withdrawn {
+            return Err(ContractError::SubmissionNotInRound);
+        }
+
+        if evidence_hash.is_empty() || evidence_hash.len() > Self::MAX_METADATA_LEN {
+            return Err(ContractError::InvalidMetadata);
+        }
+
+        // Only a rejection can be appealed, once, until the window after it elapses
+        let appeal_key = DataKey::Project(ProjectKey::Appeal(submission_id));
+        let rejected_at: u64 = Self::read_persistent(
+            &env,
+            &DataKey::Project(ProjectKey::RejectedAt(submission_id)),
+        )
+        .unwrap_or(0);
+        let window = round.config.appeal_window_seconds;
+        let now = env.ledger().timestamp();
+        if submission.status != SubmissionStatus::Rejected
+            || window == 0
+            || now > rejected_at.saturating_add(window)
+            || Self::has_persistent(&env, &appeal_key)
+        {
+            return Err(ContractError::InvalidAppeal);
+        }
+
+        Self::write_persistent(
+            &env,
+            &appeal_key,
+            &Appeal {
+                evidence_hash: evidence_hash.clone(),
+                filed_at: now,
+                resolved_at: 0,
+                upheld: false,
+                compensation: 0,
+            },
+        );
+        submission.status = SubmissionStatus::UnderAppeal;
+        Self::write_persistent(&env, &DataKey::Submission(submission_id), &submission);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("APPEAL"), submission_id), evidence_hash);
+
+        Ok(())
+    }
+
+    // Function for the admin to rule on an appeal. An upheld rejection is final. An overturned
+    // one approves the submission again: while the round is active its votes count again, if
+    // the round has closed with nothing paid out yet its allocations are recomputed with the
+    // submission in them, and otherwise it is compensated from the funding token's remainder.
+    pub fn resolve_appeal(env: Env, submission_id: u64, uphold: bool) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        let mut submission = Self::get_submission(env.clone(), submission_id)?;
+        let appeal_key = DataKey::Project(ProjectKey::Appeal(submission_id));
+        let mut appeal = match Self::read_persistent::<Appeal>(&env, &appeal_key) {
+            Some(appeal) if submission.status == SubmissionStatus::UnderAppeal => appeal,
+            _ => return Err(ContractError::InvalidAppeal),
+        };
+
+        if uphold {
+            submission.status = SubmissionStatus::Rejected;
+        } else {
+            submission.status = SubmissionStatus::Approved;
+            let mut round = Self::get_round(env.clone(), submission.round_id)?;
+            appeal.compensation = Self::reinstate(&env, &mut round, &submission)?;
+        }
+        Self::write_persistent(&env, &DataKey::Submission(submission_id), &submission);
+
+        appeal.resolved_at = env.ledger().timestamp();
+        appeal.upheld = uphold;
+        Self::write_persistent(&env, &appeal_key, &appeal);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("APPL_RSLV"), submission_id),
+            (uphold, appeal.compensation),
+        );
+
+        Ok(())
+    }
+
+    // Helper function to count a submission reinstated on appeal in its round again. Returns
+    // the compensation paid, which is only owed once the round's payouts have started: the
+    // share its votes would have earned, up to the cap, paid at once out of what the round
+    // has left over.
+    fn reinstate(
+        env: &Env,
+        round: &mut Round,
+        submission: &Submission,
+    ) -> Result<i128, ContractError> {
+        let recompute = round.phase == RoundPhase::Closed && !Self::payouts_started(env, round.id);
+        if round.is_active() || recompute {
+            round.total_votes = round
+                .total_votes
+                .checked_add(submission.total_votes)
+                .ok_or(ContractError::MathOverflow)?;
+            round.tallies.set(submission.id, submission.total_votes);
+            if recompute {
+                let distributable = round.distributable;
+                Self::allocate(env, round, distributable);
+
+                // Emit event
+                env.events().publish(
+                    (symbol_short!("RECOMPUTE"), round.id),
+                    Self::allocations_hash(env, round),
+                );
+            } else {
+                Self::release_deposit(env, round, submission, None);
+            }
+            Self::write_persistent(env, &DataKey::Round(round.id), round);
+            return Ok(0);
+        }
+
+        // A cancelled round has nothing left to share
+        if round.phase == RoundPhase::Cancelled {
+            return Ok(0);
+        }
+
+        // The compensation is a payout like any other, so it waits out a halt and is held
+        // back from blacklisted addresses
+        if round.halted {
+            return Err(ContractError::DisbursementHalted);
+        }
+
+        if Self::payout_blocked(env, submission) {
+            return Err(ContractError::AddressBlocked);
+        }
+
+        let allocation = round.allocation_of(submission.id);
+        let votes = submission.total_votes;
+        let share = if votes == 0 || votes < round.config.min_votes_to_fund {
+            0
+        } else if votes >= allocation.capped_votes {
+            allocation.cap(&round.allocation_config()).unwrap_or(0)
+        } else {
+            allocation::mul_div(
+                allocation.uncapped_pool,
+                votes,
+                allocation.uncapped_votes.saturating_add(votes),
+            )
+        };
+
+        // The remainder is only known once every allocation has been paid in the token, so the
+        // appeal can't be resolved while the funding token is part way through disbursement
+        let token = Self::funding_token(env, round.id).ok_or(ContractError::FundsNotDisbursed)?;
+        let remainder_key = DataKey::Remainder(round.id, token.clone());
+        let remainder = Self::read_persistent::<i128>(env, &remainder_key)
+            .ok_or(ContractError::FundsNotDisbursed)?;
+        let amount = share.min(remainder);
+        if amount <= 0 {
+            return Ok(0);
+        }
+
+        let escrow_key = DataKey::Escrow(round.id, token.clone());
+        let escrow = Self::read_persistent::<i128>(env, &escrow_key).unwrap_or(0);
+        if escrow < amount {
+            return Err(ContractError::InsufficientFunds);
+        }
+        Self::write_escrow(env, round.id, &token, escrow - amount);
+        Self::write_persistent(env, &remainder_key, &(remainder - amount));
+        let disbursed_key = DataKey::Disbursed(round.id, token.clone());
+        let disbursed = Self::read_persistent::<i128>(env, &disbursed_key).unwrap_or(0);
+        Self::write_persistent(env, &disbursed_key, &(disbursed + amount));
+
+        // Every tranche has been released by then, but the compensation still vests on the
+        // round's schedule
+        Self::pay_or_vest(env, round, &TokenClient::new(env, &token), submission, amount)?;
+
+        Ok(amount)
+    }
+
+    // Function to get the appeal against a submission's rejection, if one was filed
+    pub fn get_appeal(env: Env, submission_id: u64) -> Option<Appeal> {
+        Self::read_persistent(&env, &DataKey::Project(ProjectKey::Appeal(submission_id)))
+    }
+
+    // Maximum number of reviewers whose attestations a round applies at close
+    const MAX_ROUND_REVIEWERS: u32 = 20;
+
+    // Function for the admin to register a trusted reviewer, whose attestations add a vote
+    // bonus to submissions. Registering a reviewer again keeps their registration.
+    pub fn add_reviewer(env: Env, addr: Address) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        let reviewer_key = DataKey::Project(ProjectKey::Reviewer(addr.clone()));
+        if !Self::has_persistent(&env, &reviewer_key) {
+            let grants_key = DataKey::Project(ProjectKey::ReviewerGrants);
+            let grant = Self::read_persistent::<u32>(&env, &grants_key).unwrap_or(0) + 1;
+            Self::write_persistent(&env, &grants_key, &grant);
+            Self::write_persistent(&env, &reviewer_key, &grant);
+        }
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("REV_ADD"), addr.clone()), addr);
+
+        Ok(())
+    }
+
+    // Function for the admin to remove a reviewer. Attestations the reviewer made in rounds
+    // that haven't closed yet are no longer applied, even if they are registered again.
+    pub fn remove_reviewer(env: Env, addr: Address) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Project(ProjectKey::Reviewer(addr.clone())));
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("REV_RMV"), addr.clone()), addr);
+
+        Ok(())
+    }
+
+    // Function to check whether an address is a registered reviewer
+    pub fn is_reviewer(env: Env, addr: Address) -> bool {
+        Self::has_persistent(&env, &DataKey::Project(ProjectKey::Reviewer(addr)))
+    }
+
+    // Function for a reviewer to attest to an approved submission, adding `weight` votes to
+    // it when its round closes. Attestations aren't counted live, so they can't be played
+    // against the running tallies, and each reviewer may attest at most the round's
+    // `attestation_budget` in total.
+    pub fn attest(
+        env: Env,
+        reviewer: Address,
+        submission_id: u64,
+        weight: u64,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the reviewer
+        reviewer.require_auth();
+        Self::extend_instance(&env);
+
+        Self::require_not_paused(&env)?;
+        Self::require_not_blocked(&env, &reviewer)?;
+
+        let grant: u32 = Self::read_persistent(
+            &env,
+            &DataKey::Project(ProjectKey::Reviewer(reviewer.clone())),
+        )
+        .ok_or(ContractError::Unauthorized)?;
+
+        if weight == 0 {
+            return Err(ContractError::InvalidAmount);
+        }
+
+        let submission = Self::get_submission(env.clone(), submission_id)?;
+        let round_id = submission.round_id;
+        let round = Self::get_round(env.clone(), round_id)?;
+        if !round.is_active() || env.ledger().timestamp() > round.voting_deadline {
+            return Err(ContractError::VotingClosed);
+        }
+
+        if submission.withdrawn {
+            return Err(ContractError::SubmissionNotInRound);
+        }
+
+        if submission.status != SubmissionStatus::Approved {
+            return Err(ContractError::SubmissionNotApproved);
+        }
+
+        // Reviewers never endorse their own projects
+        if submission.submitter == reviewer {
+            return Err(ContractError::SelfVoteNotAllowed);
+        }
+
+        // Attestations made under an earlier registration were invalidated with it
+        let attestations_key =
+            DataKey::Project(ProjectKey::Attestations(round_id, reviewer.clone()));
+        let mut attestations = match Self::read_persistent::<Attestations>(&env, &attestations_key)
+        {
+            Some(attestations) if attestations.grant == grant => attestations,
+            Some(_) => Attestations {
+                grant,
+                weights: Map::new(&env),
+            },
+            None => {
+                let attesters_key = DataKey::Project(ProjectKey::Attesters(round_id));
+                let mut attesters = Self::read_persistent::<Vec<Address>>(&env, &attesters_key)
+                    .unwrap_or(Vec::new(&env));
+                if attesters.len() >= Self::MAX_ROUND_REVIEWERS {
+                    return Err(ContractError::ExceededVoteLimit);
+                }
+                attesters.push_back(reviewer.clone());
+                Self::write_persistent(&env, &attesters_key, &attesters);
+                Attestations {
+                    grant,
+                    weights: Map::new(&env),
+                }
+            }
+        };
+
+        let attested = Self::vote_total(&attestations.weights)?
+            .checked_add(weight)
+            .ok_or(ContractError::MathOverflow)?;
+        if attested > round.config.attestation_budget {
+            return Err(ContractError::ExceededVoteLimit);
+        }
+        let previous = attestations.weights.get(submission_id).unwrap_or(0);
+        attestations.weights.set(submission_id, previous + weight);
+        Self::write_persistent(&env, &attestations_key, &attestations);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("ATTEST"), round_id, submission_id),
+            (reviewer, weight),
+        );
+
+        Ok(())
+    }
+
+    // Function to get the vote bonus a reviewer has attested to each submission of a round,
+    // leaving out attestations invalidated by the reviewer's removal
+    pub fn get_attestations(env: Env, round_id: u64, reviewer: Address) -> Map<u64, u64> {
+        let grant: Option<u32> = Self::read_persistent(
+            &env,
+            &DataKey::Project(ProjectKey::Reviewer(reviewer.clone())),
+        );
+        match Self::read_persistent::<Attestations>(
+            &env,
+            &DataKey::Project(ProjectKey::Attestations(round_id, reviewer)),
+        ) {
+            Some(attestations) if Some(attestations.grant) == grant => attestations.weights,
+            _ => Map::new(&env),
+        }
+    }
+
+    // Helper function to add the valid attestations of a closing round to its tallies, so
+    // the bonus counts like votes from then on. Attestations to submissions that are no
+    // longer approved are dropped. Returns the bonus added to each submission.
+    fn apply_attestations(env: &Env, round: &mut Round) -> Map<u64, u64> {
+        let mut bonuses = Map::new(env);
+        let attesters = Self::read_persistent::<Vec<Address>>(
+            env,
+            &DataKey::Project(ProjectKey::Attesters(round.id)),
+        )
+        .unwrap_or(Vec::new(env));
+        for reviewer in attesters.iter() {
+            for (submission_id, weight) in Self::get_attestations(env.clone(), round.id, reviewer) {
+                let Some(votes) = round.tallies.get(submission_id) else {
+                    continue;
+                };
+                round
+                    .tallies
+                    .set(submission_id, votes.saturating_add(weight));
+                round.total_votes = round.total_votes.saturating_add(weight);
+                let bonus: u64 = bonuses.get(submission_id).unwrap_or(0);
+                bonuses.set(submission_id, bonus.saturating_add(weight));
+            }
+        }
+        bonuses
+    }
+
+    // Helper function to check that a name and metadata hash are non-empty and within
+    // the length limit
+    fn validate_metadata(name: &Bytes, metadata_hash: &Bytes) -> Result<(), ContractError> {
+        for field in [name, metadata_hash] {
+            if field.is_empty() || field.len() > Self::MAX_METADATA_LEN {
+                return Err(ContractError::InvalidMetadata);
+            }
+        }
+        Ok(())
+    }
+
+    // Function to get a submission
+    pub fn get_submission(env: Env, submission_id: u64) -> Result<Submission, ContractError> {
+        Self::read_persistent::<Submission>(&env, &DataKey::Submission(submission_id))
+            .ok_or(ContractError::SubmissionNotFound)
+    }
+
+    // Function to list a round's submissions, skipping the first `start` and returning at most `limit`
+    pub fn list_submissions(env: Env, round_id: u64, start: u32, limit: u32) -> Vec<Submission> {
+        let mut submissions = Vec::new(&env);
+        let Ok(round) = Self::get_round(env.clone(), round_id) else {
+            return submissions;
+        };
+
+        let end = start
+            .saturating_add(limit.min(Self::MAX_PAGE_SIZE))
+            .min(round.submissions.len());
+        for index in start..end {
+            let submission_id = round.submissions.get_unchecked(index);
+            if let Ok(submission) = Self::get_submission(env.clone(), submission_id) {
+                submissions.push_back(submission);
+            }
+        }
+
+        submissions
+    }
+
+    // Function to list a submitter's indexed submissions across rounds, oldest first, skipping
+    // the first `start` and returning at most `limit`
+    pub fn get_submissions_by_submitter(
+        env: Env,
+        submitter: Address,
+        start: u32,
+        limit: u32,
+    ) -> Vec<Submission> {
+        let index = Self::submitter_submissions(&env, &submitter);
+
+        let mut submissions = Vec::new(&env);
+        let end = start
+            .saturating_add(limit.min(Self::MAX_PAGE_SIZE))
+            .min(index.len());
+        for position in start..end {
+            let submission_id = index.get_unchecked(position);
+            if let Ok(submission) = Self::get_submission(env.clone(), submission_id) {
+                submissions.push_back(submission);
+            }
+        }
+
+        submissions
+    }
+
+    // Function to get the total a submitter can claim in a token now across their indexed
+    // submissions
+    pub fn get_total_claimable(env: Env, submitter: Address, token: Address) -> i128 {
+        Self::submitter_submissions(&env, &submitter)
+            .iter()
+            .map(|submission_id| Self::get_claimable(env.clone(), submission_id, token.clone()))
+            .sum()
+    }
+
+    // Helper function to get the ids of a submitter's indexed submissions
+    fn submitter_submissions(env: &Env, submitter: &Address) -> Vec<u64> {
+        let index_key = DataKey::Index(IndexKey::SubmitterSubmissions(submitter.clone()));
+        Self::read_persistent(env, &index_key).unwrap_or(Vec::new(env))
+    }
+
+    // Function to list a round's top submissions by votes, highest first with ties broken by
+    // ascending id, returning at most `limit`. Withdrawn, rejected, suspended and appealed
+    // submissions are left out.
+    pub fn leaderboard(env: Env, round_id: u64, limit: u32) -> Vec<Submission> {
+        let mut leaders: Vec<Submission> = Vec::new(&env);
+        let Ok(round) = Self::get_round(env.clone(), round_id) else {
+            return leaders;
+        };
+
+        let limit = limit.min(Self::MAX_PAGE_SIZE);
+        if limit == 0 {
+            return leaders;
+        }
+        for submission_id in round.submissions.iter() {
+            let Ok(submission) = Self::get_submission(env.clone(), submission_id) else {
+                continue;
+            };
+            if submission.withdrawn
+                || matches!(
+                    submission.status,
+                    SubmissionStatus::Rejected
+                        | SubmissionStatus::Suspended
+                        | SubmissionStatus::UnderAppeal
+                )
+            {
+                continue;
+            }
+
+            // Submissions are visited in ascending id order, so a later submission only
+            // overtakes those with strictly fewer votes
+            let position = leaders
+                .iter()
+                .position(|leader| leader.total_votes < submission.total_votes)
+                .map_or(leaders.len(), |index| index as u32);
+            if position < limit {
+                leaders.insert(position, submission);
+                if leaders.len() > limit {
+                    leaders.pop_back();
+                }
+            }
+        }
+
+        leaders
+    }
+
+    // Function for voters to allocate votes to submissions
+    pub fn allocate_votes(
+        env: Env,
+        voter: Address,
+        round_id: u64,
+        allocations: Map<u64, u64>,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the voter
+        voter.require_auth();
+        Self::extend_instance(&env);
+
+        Self::require_not_paused(&env)?;
+
+        // Check if the round exists, is active and still open for voting
+        let round = Self::get_round(env.clone(), round_id)?;
+
+        // Only the round's voters may allocate votes, checked before anything is written
+        Self::require_eligible(&env, &round, &voter)?;
+
+        // Ballots in commit-reveal rounds are only applied when revealed
+        if round.config.reveal_period > 0 {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        let deadline = round.grace_deadline();
+        Self::record_ballot(env, voter, round, allocations, deadline)
+    }
+
+    // Function for voters to allocate votes in a round gated by an eligibility root, proving
+    // their address is a leaf of the round's Merkle tree. A verified voter is added to the
+    // round's voters, so later ballots need no proof. Rounds without a root fall back to the
+    // voter registry.
+    pub fn allocate_votes_with_proof(
+        env: Env,
+        voter: Address,
+        round_id: u64,
+        allocations: Map<u64, u64>,
+        proof: Vec<BytesN<32>>,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the voter
+        voter.require_auth();
+        Self::extend_instance(&env);
+
+        Self::require_not_paused(&env)?;
+
+        let round = Self::get_round(env.clone(), round_id)?;
+        match Self::read_persistent(&env, &DataKey::EligibilityRoot(round_id)) {
+            Some(root) => {
+                if !Self::verify_proof(&env, &root, &voter, &proof) {
+                    return Err(ContractError::NotEligible);
+                }
+                if Self::admit_round_voter(&env, round_id, &voter) {
+                    // Emit event
+                    env.events()
+                        .publish((symbol_short!("RVTR_ADD"), round_id), voter.clone());
+                }
+            }
+            None => Self::require_eligible(&env, &round, &voter)?,
+        }
+
+        // Ballots in commit-reveal rounds are only applied when revealed
+        if round.config.reveal_period > 0 {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        let deadline = round.grace_deadline();
+        Self::record_ballot(env, voter, round, allocations, deadline)
+    }
+
+    // Function for voters in a commit-reveal round to commit to a ballot while voting is
+    // open. The commitment is the SHA-256 hash of the allocations in XDR followed by a
+    // secret salt; committing again replaces the earlier commitment.
+    pub fn commit_vote(
+        env: Env,
+        voter: Address,
+        round_id: u64,
+        commitment: BytesN<32>,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the voter
+        voter.require_auth();
+        Self::extend_instance(&env);
+
+        Self::require_not_paused(&env)?;
+        Self::require_not_blocked(&env, &voter)?;
+
+        let mut round = Self::get_round(env.clone(), round_id)?;
+        Self::require_eligible(&env, &round, &voter)?;
+
+        if !round.is_active() {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        if round.phase != RoundPhase::Voting || round.config.reveal_period == 0 {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        if env.ledger().timestamp() > round.voting_deadline {
+            return Err(ContractError::VotingClosed);
+        }
+
+        Self::write_persistent(
+            &env,
+            &DataKey::Commitment(round_id, voter.clone()),
+            &commitment,
+        );
+        if Self::clear_abstention(&env, &mut round, &voter) {
+            Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+        }
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("VOTE_CMT"), voter), round_id);
+
+        Ok(())
+    }
+
+    // Function for voters to reveal a committed ballot between the voting deadline and the
+    // round's reveal deadline, applying it to the tallies. Commitments that are never
+    // revealed don't count towards the round.
+    pub fn reveal_vote(
+        env: Env,
+        voter: Address,
+        round_id: u64,
+        allocations: Map<u64, u64>,
+        salt: Bytes,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the voter
+        voter.require_auth();
+        Self::extend_instance(&env);
+
+        Self::require_not_paused(&env)?;
+
+        let round = Self::get_round(env.clone(), round_id)?;
+
+        if round.config.reveal_period == 0 {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        if env.ledger().timestamp() <= round.voting_deadline {
+            return Err(ContractError::VotingStillOpen);
+        }
+
+        // A revealed ballot is stored as the voter's allocation
+        let allocation_key = DataKey::VoterAllocation(round_id, voter.clone());
+        if Self::has_persistent(&env, &allocation_key) {
+            return Err(ContractError::AlreadyRevealed);
+        }
+
+        let commitment_key = DataKey::Commitment(round_id, voter.clone());
+        let commitment = Self::read_persistent::<BytesN<32>>(&env, &commitment_key)
+            .ok_or(ContractError::CommitmentMismatch)?;
+        let mut preimage = allocations.clone().to_xdr(&env);
+        preimage.append(&salt);
+        let hash: BytesN<32> = env.crypto().sha256(&preimage).into();
+        if hash != commitment {
+            return Err(ContractError::CommitmentMismatch);
+        }
+
+        env.storage().persistent().remove(&commitment_key);
+
+        let deadline = round.reveal_deadline();
+        Self::record_ballot(env, voter, round, allocations, deadline)
+    }
+
+    // Helper function to check that a voter's leaf, the SHA-256 hash of their address in
+    // XDR, hashes up to the root. Each level hashes the pair of nodes in ascending order.
+    fn verify_proof(
+        env: &Env,
+        root: &BytesN<32>,
+        voter: &Address,
+        proof: &Vec<BytesN<32>>,
+    ) -> bool {
+        let mut node: BytesN<32> = env.crypto().sha256(&voter.clone().to_xdr(env)).into();
+        for sibling in proof.iter() {
+            let (left, right) = if node < sibling {
+                (node, sibling)
+            } else {
+                (sibling, node)
+            };
+            let mut pair = Bytes::from(left);
+            pair.append(&Bytes::from(right));
+            node = env.crypto().sha256(&pair).into();
+        }
+        node == *root
+    }
+
+    // Helper function to merge a ballot into a voter's allocation in an active round, as long
+    // as ballots are still accepted at `deadline`
+    fn record_ballot(
+        env: Env,
+        voter: Address,
+        mut round: Round,
+        allocations: Map<u64, u64>,
+        deadline: u64,
+    ) -> Result<(), ContractError> {
+        let round_id = round.id;
+
+        Self::require_not_blocked(&env, &voter)?;
+
+        if !round.is_active() {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        if round.phase != RoundPhase::Voting {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        if env.ledger().timestamp() > deadline {
+            return Err(ContractError::VotingClosed);
+        }
+
+        // Ranked rounds only take rankings, quadratic funding rounds contributions and
+        // pairwise rounds comparisons
+        if matches!(
+            round.config.mechanism,
+            VotingMechanism::Ranked | VotingMechanism::QuadraticFunding | VotingMechanism::Pairwise
+        ) {
+            return Err(ContractError::InvalidAllocations);
+        }
+
+        Self::apply_carryover(&env, &round, &voter);
+        Self::validate_allocations(&env, &round, &voter, &allocations)?;
+
+        // Ballots in time-weighted rounds are weighted by when they are cast, so a voter's
+        // ballot is replaced with update_votes rather than topped up
+        let allocation_key = DataKey::VoterAllocation(round_id, voter.clone());
+        let stored_allocations = Self::read_persistent::<Map<u64, u64>>(&env, &allocation_key);
+        let first_ballot = stored_allocations.is_none();
+        if round.config.time_weighted && !first_ballot {
+            return Err(ContractError::AlreadyVoted);
+        }
+
+        // Open ballots arriving in the grace period after the voting deadline count at a
+        // discount. Revealed ballots were committed in time and count in full.
+        let late =
+            round.config.reveal_period == 0 && env.ledger().timestamp() > round.voting_deadline;
+        let mut counted = Self::weigh_ballot(&env, &round, &allocations);
+        if late {
+            counted = Self::scale_ballot(&env, &counted, Self::GRACE_WEIGHT_BPS);
+        }
+
+        // Merge the new allocations into the voter's stored allocations
+        let mut voter_allocations = stored_allocations.unwrap_or(Map::new(&env));
+        Self::drop_withdrawn_submissions(&env, &mut round, &voter, &mut voter_allocations)?;
+        for (submission_id, votes) in counted.iter() {
+            let previous = voter_allocations.get(submission_id).unwrap_or(0);
+            let merged = previous
+                .checked_add(votes)
+                .ok_or(ContractError::MathOverflow)?;
+            voter_allocations.set(submission_id, merged);
+        }
+
+        // Credits spent in earlier calls count against the same budget, so the
+        // cost is charged on the merged allocation. Late and time-weighted ballots are
+        // charged in full on top of what was spent before, while the cap applies to the
+        // merged votes.
+        let mut total_credits_spent = Self::ballot_cost(&round.config, &voter_allocations)?;
+        if late || round.config.time_weighted {
+            let spent: u64 =
+                Self::read_persistent(&env, &DataKey::VoterCredits(round_id, voter.clone()))
+                    .unwrap_or(0);
+            total_credits_spent = spent
+                .checked_add(Self::ballot_cost(&round.config, &allocations)?)
+                .ok_or(ContractError::MathOverflow)?;
+        }
+        if total_credits_spent > Self::voter_budget(&env, &round, &voter) {
+            return Err(ContractError::ExceededVoteLimit);
+        }
+
+        // Keep the votes as cast and the credits charged on each submission, charged the same
+        // way as the ballot as a whole, so a refund gives back what the voter paid
+        let previous_cast = Self::read_persistent::<Map<u64, (u64, u64)>>(
+            &env,
+            &DataKey::Credit(CreditKey::CastVotes(round_id, voter.clone())),
+        )
+        .unwrap_or(Map::new(&env));
+        let mut cast = Map::new(&env);
+        for (submission_id, votes) in voter_allocations.iter() {
+            let (raw, charged) = previous_cast.get(submission_id).unwrap_or((0, 0));
+            let added = allocations.get(submission_id).unwrap_or(0);
+            let charged = if late || round.config.time_weighted {
+                charged
+                    .checked_add(Self::entry_cost(&round.config, added)?)
+                    .ok_or(ContractError::MathOverflow)?
+            } else {
+                Self::entry_cost(&round.config, votes)?
+            };
+            let raw = raw.checked_add(added).ok_or(ContractError::MathOverflow)?;
+            cast.set(submission_id, (raw, charged));
+        }
+
+        // Store voter allocations, credits spent and the votes as cast
+        let raw_votes: u64 =
+            Self::read_persistent(&env, &DataKey::RawVotes(round_id, voter.clone())).unwrap_or(0);
+        let raw_votes = raw_votes
+            .checked_add(Self::vote_total(&allocations)?)
+            .ok_or(ContractError::MathOverflow)?;
+        Self::store_voter_allocation(&env, round_id, &voter, &voter_allocations);
+        Self::store_cast_votes(&env, round_id, &voter, &cast);
+        Self::set_credits_spent(&env, &mut round, &voter, total_credits_spent)?;
+        Self::set_raw_votes(&env, &mut round, &voter, raw_votes)?;
+
+        // Count the voter towards the round's quorum on their first ballot
+        if first_ballot {
+            round.voter_count += 1;
+            Self::record_voter_round(&env, &voter, round_id);
+        }
+
+        // Update total votes for each submission and the round
+        Self::clear_abstention(&env, &mut round, &voter);
+        Self::add_votes(&env, &mut round, &counted)?;
+        Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("VOTE_ALC"), voter.clone()),
+            VotesAllocatedEvent {
+                round_id,
+                voter: voter.clone(),
+                allocations: voter_allocations,
+                credits_spent: total_credits_spent,
+            },
+        );
+        if late {
+            env.events()
+                .publish((symbol_short!("VOTE_LATE"), voter, round_id), counted);
+        }
+
+        Ok(())
+    }
+
+    // Share of a late ballot's votes that count, in basis points
+    const GRACE_WEIGHT_BPS: u64 = 5_000;
+
+    // Share of a ballot's votes that count in a time-weighted round when cast at the voting
+    // deadline, in basis points
+    const CONVICTION_FLOOR_BPS: u64 = 5_000;
+
+    // Helper function to scale a ballot down to the votes that count, rounding each entry
+    // down and leaving out entries that round to zero
+    fn scale_ballot(env: &Env, allocations: &Map<u64, u64>, weight_bps: u64) -> Map<u64, u64> {
+        let mut counted = Map::new(env);
+        for (submission_id, votes) in allocations.iter() {
+            let scaled = (votes as u128 * weight_bps as u128 / Self::TOTAL_BPS as u128) as u64;
+            if scaled > 0 {
+                counted.set(submission_id, scaled);
+            }
+        }
+        counted
+    }
+
+    // Helper function to weigh a ballot cast now in a time-weighted round: its votes count in
+    // full when voting opens, decaying linearly to CONVICTION_FLOOR_BPS at the voting
+    // deadline. Ballots in other rounds count as cast.
+    fn weigh_ballot(env: &Env, round: &Round, allocations: &Map<u64, u64>) -> Map<u64, u64> {
+        if !round.config.time_weighted {
+            return allocations.clone();
+        }
+        let weight_bps = Self::conviction_weight_bps(round, env.ledger().timestamp());
+        Self::scale_ballot(env, allocations, weight_bps)
+    }
+
+    // Helper function to calculate the weight of a ballot cast at `timestamp` in a
+    // time-weighted round, in basis points
+    fn conviction_weight_bps(round: &Round, timestamp: u64) -> u64 {
+        let total = Self::TOTAL_BPS as u64;
+        let window = round.voting_deadline.saturating_sub(round.voting_opened_at);
+        if window == 0 {
+            return Self::CONVICTION_FLOOR_BPS;
+        }
+        let elapsed = timestamp.saturating_sub(round.voting_opened_at).min(window);
+        let decay = (total - Self::CONVICTION_FLOOR_BPS) as u128 * elapsed as u128 / window as u128;
+        total - decay as u64
+    }
+
+    // Helper function to add up the votes of a ballot
+    fn vote_total(allocations: &Map<u64, u64>) -> Result<u64, ContractError> {
+        allocations
+            .values()
+            .iter()
+            .try_fold(0u64, |total, votes| total.checked_add(votes))
+            .ok_or(ContractError::MathOverflow)
+    }
+
+    // Helper function to store the votes of a voter's ballot in a round as cast, keeping the
+    // round's running total in step. The caller is responsible for storing the round.
+    fn set_raw_votes(
+        env: &Env,
+        round: &mut Round,
+        voter: &Address,
+        raw_votes: u64,
+    ) -> Result<(), ContractError> {
+        let key = DataKey::RawVotes(round.id, voter.clone());
+        let previous: u64 = Self::read_persistent(env, &key).unwrap_or(0);
+        round.raw_votes = round
+            .raw_votes
+            .saturating_sub(previous)
+            .checked_add(raw_votes)
+            .ok_or(ContractError::MathOverflow)?;
+
+        if raw_votes == 0 {
+            env.storage().persistent().remove(&key);
+        } else {
+            Self::write_persistent(env, &key, &raw_votes);
+        }
+        Ok(())
+    }
+
+    // Function for voters to replace their allocation in a round before voting closes
+    pub fn update_votes(
+        env: Env,
+        voter: Address,
+        round_id: u64,
+        allocations: Map<u64, u64>,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the voter
+        voter.require_auth();
+        Self::extend_instance(&env);
+
+        Self::require_not_paused(&env)?;
+        Self::require_not_blocked(&env, &voter)?;
+
+        let mut round = Self::get_round(env.clone(), round_id)?;
+
+        // Only the round's voters may cast new allocations
+        Self::require_eligible(&env, &round, &voter)?;
+        if !round.is_active() || env.ledger().timestamp() > round.voting_deadline {
+            return Err(ContractError::VotingClosed);
+        }
+
+        // Ballots in commit-reveal rounds can't be replaced once revealed
+        if round.phase != RoundPhase::Voting || round.config.reveal_period > 0 {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        // Ranked rounds only take rankings, quadratic funding rounds contributions and
+        // pairwise rounds comparisons
+        if matches!(
+            round.config.mechanism,
+            VotingMechanism::Ranked | VotingMechanism::QuadraticFunding | VotingMechanism::Pairwise
+        ) {
+            return Err(ContractError::InvalidAllocations);
+        }
+
+        // Re-validate the credit budget against the new allocation alone
+        Self::apply_carryover(&env, &round, &voter);
+        Self::validate_allocations(&env, &round, &voter, &allocations)?;
+
+        let total_credits_spent = Self::ballot_cost(&round.config, &allocations)?;
+        if total_credits_spent > Self::voter_budget(&env, &round, &voter) {
+            return Err(ContractError::ExceededVoteLimit);
+        }
+
+        // Time-weighted rounds weigh the replacement by when it is cast
+        let raw_votes = Self::vote_total(&allocations)?;
+        let counted = Self::weigh_ballot(&env, &round, &allocations);
+        let mut cast = Map::new(&env);
+        for (submission_id, votes) in allocations.iter() {
+            if counted.contains_key(submission_id) {
+                cast.set(
+                    submission_id,
+                    (votes, Self::entry_cost(&round.config, votes)?),
+                );
+            }
+        }
+
+        // Take the previous allocation back out of the submission tallies
+        if !Self::withdraw_voter_allocations(&env, &mut round, &voter)? {
+            round.voter_count += 1;
+            Self::record_voter_round(&env, &voter, round_id);
+        }
+
+        // Apply the new allocation
+        Self::clear_abstention(&env, &mut round, &voter);
+        Self::add_votes(&env, &mut round, &counted)?;
+        Self::set_credits_spent(&env, &mut round, &voter, total_credits_spent)?;
+        Self::set_raw_votes(&env, &mut round, &voter, raw_votes)?;
+        Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+
+        Self::store_voter_allocation(&env, round_id, &voter, &counted);
+        Self::store_cast_votes(&env, round_id, &voter, &cast);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("VOTE_UPD"), voter.clone(), round_id),
+            counted,
+        );
+
+        Ok(())
+    }
+
+    // Function for voters in a ranked round to rank submissions, best first, replacing any
+    // earlier ranking. Rankings are scored with a modified Borda count: of `n` ranked
+    // submissions the first gets `n` votes and the last gets one, so the scores feed the
+    // same proportional funding math as credit ballots.
+    pub fn submit_ranking(
+        env: Env,
+        voter: Address,
+        round_id: u64,
+        ranking: Vec<u64>,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the voter
+        voter.require_auth();
+        Self::extend_instance(&env);
+
+        Self::require_not_paused(&env)?;
+        Self::require_not_blocked(&env, &voter)?;
+
+        let mut round = Self::get_round(env.clone(), round_id)?;
+        Self::require_eligible(&env, &round, &voter)?;
+
+        if !round.is_active() {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        if round.phase != RoundPhase::Voting || round.config.reveal_period > 0 {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        if env.ledger().timestamp() > round.voting_deadline {
+            return Err(ContractError::VotingClosed);
+        }
+
+        if round.config.mechanism != VotingMechanism::Ranked {
+            return Err(ContractError::InvalidAllocations);
+        }
+
+        // Convert the ranking to Borda scores; a repeated id collapses into one entry
+        let mut scores = Map::new(&env);
+        let len = ranking.len() as u64;
+        for (position, submission_id) in ranking.iter().enumerate() {
+            scores.set(submission_id, len - position as u64);
+        }
+        if scores.len() != ranking.len() {
+            return Err(ContractError::InvalidAllocations);
+        }
+        Self::validate_allocations(&env, &round, &voter, &scores)?;
+
+        // Take the previous ranking back out of the submission tallies
+        if !Self::withdraw_voter_allocations(&env, &mut round, &voter)? {
+            round.voter_count += 1;
+            Self::record_voter_round(&env, &voter, round_id);
+        }
+
+        Self::clear_abstention(&env, &mut round, &voter);
+        Self::add_votes(&env, &mut round, &scores)?;
+        Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+        Self::store_voter_allocation(&env, round_id, &voter, &scores);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("RANK_SUB"), voter, round_id), ranking);
+
+        Ok(())
+    }
+
+    // Function for voters in a pairwise round to record that one submission beats another.
+    // Every comparison costs a credit, so the voter's budget caps how many they make, and a
+    // voter compares each pair once. Submissions are scored with a Copeland count, kept up to
+    // date as comparisons come in: two points for every pair a submission wins by more
+    // comparisons than it loses, and one for every pair it ties. The scores feed the same
+    // proportional funding math as credit ballots.
+    pub fn submit_comparison(
+        env: Env,
+        voter: Address,
+        round_id: u64,
+        winner_id: u64,
+        loser_id: u64,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the voter
+        voter.require_auth();
+        Self::extend_instance(&env);
+
+        Self::require_not_paused(&env)?;
+        Self::require_not_blocked(&env, &voter)?;
+
+        let mut round = Self::get_round(env.clone(), round_id)?;
+        Self::require_eligible(&env, &round, &voter)?;
+
+        if !round.is_active() {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        if round.phase != RoundPhase::Voting || round.config.reveal_period > 0 {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        if env.ledger().timestamp() > round.voting_deadline {
+            return Err(ContractError::VotingClosed);
+        }
+
+        if round.config.mechanism != VotingMechanism::Pairwise || winner_id == loser_id {
+            return Err(ContractError::InvalidAllocations);
+        }
+
+        // Both submissions must be open to votes from the voter
+        let mut pair = Map::new(&env);
+        pair.set(winner_id, 1);
+        pair.set(loser_id, 1);
+        Self::validate_allocations(&env, &round, &voter, &pair)?;
+
+        // A pair is compared once per voter, whichever way round
+        let (low_id, high_id) = (winner_id.min(loser_id), winner_id.max(loser_id));
+        let comparison_key = DataKey::Ballot(BallotKey::Comparison(
+            round_id,
+            voter.clone(),
+            low_id,
+            high_id,
+        ));
+        if Self::has_persistent(&env, &comparison_key) {
+            return Err(ContractError::AlreadyVoted);
+        }
+
+        Self::apply_carryover(&env, &round, &voter);
+        let spent: u64 =
+            Self::read_persistent(&env, &DataKey::VoterCredits(round_id, voter.clone()))
+                .unwrap_or(0);
+        if spent >= Self::voter_budget(&env, &round, &voter) {
+            return Err(ContractError::ExceededVoteLimit);
+        }
+        Self::write_persistent(&env, &comparison_key, &winner_id);
+        Self::set_credits_spent(&env, &mut round, &voter, spent + 1)?;
+        Self::set_raw_votes(&env, &mut round, &voter, spent + 1)?;
+
+        // Count the voter towards the round's quorum on their first comparison
+        if spent == 0 {
+            round.voter_count += 1;
+            Self::record_voter_round(&env, &voter, round_id);
+        }
+        Self::clear_abstention(&env, &mut round, &voter);
+
+        // Move the pair's margin and the points both submissions take from it
+        let margin_key = DataKey::Ballot(BallotKey::PairMargin(round_id, low_id, high_id));
+        let previous = Self::read_persistent::<i64>(&env, &margin_key);
+        let margin = previous.unwrap_or(0) + if winner_id == low_id { 1 } else { -1 };
+        Self::write_persistent(&env, &margin_key, &margin);
+        let (low_before, high_before) = match previous {
+            Some(previous) => (
+                Self::copeland_points(previous),
+                Self::copeland_points(-previous),
+            ),
+            None => (0, 0),
+        };
+        Self::replace_points(
+            &env,
+            &mut round,
+            low_id,
+            low_before,
+            Self::copeland_points(margin),
+        )?;
+        Self::replace_points(
+            &env,
+            &mut round,
+            high_id,
+            high_before,
+            Self::copeland_points(-margin),
+        )?;
+        Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("CMP_SUB"), round_id, voter),
+            (winner_id, loser_id),
+        );
+
+        Ok(())
+    }
+
+    // Helper function to get the Copeland points a submission takes from a compared pair,
+    // given its net wins in the pair
+    fn copeland_points(margin: i64) -> u64 {
+        match margin {
+            m if m > 0 => 2,
+            0 => 1,
+            _ => 0,
+        }
+    }
+
+    // Helper function to replace the points a submission in a pairwise round takes from a
+    // pair in its tally and the round's running total. The caller is responsible for storing
+    // the round.
+    fn replace_points(
+        env: &Env,
+        round: &mut Round,
+        submission_id: u64,
+        before: u64,
+        after: u64,
+    ) -> Result<(), ContractError> {
+        let mut submission = Self::get_submission(env.clone(), submission_id)?;
+        submission.total_votes = (submission.total_votes - before)
+            .checked_add(after)
+            .ok_or(ContractError::MathOverflow)?;
+        Self::write_persistent(env, &DataKey::Submission(submission_id), &submission);
+
+        round.total_votes = (round.total_votes - before)
+            .checked_add(after)
+            .ok_or(ContractError::MathOverflow)?;
+        round.tallies.set(submission_id, submission.total_votes);
+
+        Ok(())
+    }
+
+    // Function for a donor to contribute directly to a submission in a quadratic funding
+    // round. The tokens go straight to the submission's payout address, and the round's
+    // funding matches the contributions by the quadratic funding formula: each submission's
+    // weight is the square of the sum of the square roots of its donors' contributions.
+    pub fn contribute(
+        env: Env,
+        donor: Address,
+        submission_id: u64,
+        token: Address,
+        amount: i128,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the donor
+        donor.require_auth();
+        Self::extend_instance(&env);
+
+        Self::require_not_paused(&env)?;
+        Self::require_not_blocked(&env, &donor)?;
+
+        if amount <= 0 {
+            return Err(ContractError::InvalidAmount);
+        }
+
+        let mut submission = Self::get_submission(env.clone(), submission_id)?;
+        let round_id = submission.round_id;
+        let mut round = Self::get_round(env.clone(), round_id)?;
+
+        // Only the round's voters are matched, which keeps out sybil donors
+        Self::require_eligible(&env, &round, &donor)?;
+
+        if !round.is_active() {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        if round.phase != RoundPhase::Voting {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        if env.ledger().timestamp() > round.voting_deadline {
+            return Err(ContractError::VotingClosed);
+        }
+
+        if round.config.mechanism != VotingMechanism::QuadraticFunding {
+            return Err(ContractError::InvalidAllocations);
+        }
+
+        if submission.withdrawn {
+            return Err(ContractError::SubmissionNotInRound);
+        }
+
+        if submission.status != SubmissionStatus::Approved {
+            return Err(ContractError::SubmissionNotApproved);
+        }
+
+        if !round.config.allow_self_votes && donor == submission.submitter {
+            return Err(ContractError::SelfVoteNotAllowed);
+        }
+
+        // Contributions are only comparable in a single token, set by the first one
+        let token_key = DataKey::ContributionToken(round_id);
+        match Self::read_persistent::<Address>(&env, &token_key) {
+            Some(round_token) if round_token != token => {
+                return Err(ContractError::InvalidAmount);
+            }
+            Some(_) => {}
+            None => Self::write_persistent(&env, &token_key, &token),
+        }
+
+        // Record the donor's running contribution; a first contribution counts towards the
+        // quorum like a ballot
+        let contribution_key = DataKey::Contribution(round_id, donor.clone());
+        let mut contributions =
+            match Self::read_persistent::<Map<u64, i128>>(&env, &contribution_key) {
+                Some(contributions) => contributions,
+                None => {
+                    round.voter_count += 1;
+                    Map::new(&env)
+                }
+            };
+        let previous = contributions.get(submission_id).unwrap_or(0);
+        let total = previous
+            .checked_add(amount)
+            .ok_or(ContractError::MathOverflow)?;
+        contributions.set(submission_id, total);
+        Self::write_persistent(&env, &contribution_key, &contributions);
+        Self::clear_abstention(&env, &mut round, &donor);
+
+        // Replace the donor's square root in the submission's sum, so repeated contributions
+        // count as one
+        let roots_key = DataKey::ContributionRoots(submission_id);
+        let roots = Self::read_persistent::<u128>(&env, &roots_key).unwrap_or(0)
+            - Self::integer_sqrt(previous as u128)
+            + Self::integer_sqrt(total as u128);
+        Self::write_persistent(&env, &roots_key, &roots);
+
+        // The squared sum becomes the submission's tally, so the allocation math and any
+        // threshold, cutoff or cap apply to the matching unchanged
+        let weight = roots
+            .checked_mul(roots)
+            .and_then(|weight| u64::try_from(weight).ok())
+            .ok_or(ContractError::MathOverflow)?;
+        round.total_votes = (round.total_votes - submission.total_votes)
+            .checked_add(weight)
+            .ok_or(ContractError::MathOverflow)?;
+        round.tallies.set(submission_id, weight);
+        submission.total_votes = weight;
+        Self::write_persistent(&env, &DataKey::Submission(submission_id), &submission);
+        Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+
+        // Pay the submission directly
+        TokenClient::new(&env, &token).transfer(&donor, &submission.payout_address, &amount);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("CONTRIB"), round_id, submission_id),
+            (donor, token, amount),
+        );
+
+        Ok(())
+    }
+
+    // Function to get the amounts a donor has contributed to a round's submissions
+    pub fn get_contributions(env: Env, round_id: u64, donor: Address) -> Map<u64, i128> {
+        Self::read_persistent::<Map<u64, i128>>(&env, &DataKey::Contribution(round_id, donor))
+            .unwrap_or(Map::new(&env))
+    }
+
+    // Helper function to calculate the integer square root of a value, rounded down, by
+    // Newton's method
+    fn integer_sqrt(value: u128) -> u128 {
+        if value < 2 {
+            return value;
+        }
+
+        // Starting at or above the root, each step moves down towards it
+        let mut root = value / 2;
+        let mut next = (root + value / root) / 2;
+        while next < root {
+            root = next;
+            next = (root + value / root) / 2;
+        }
+        root
+    }
+
+    // Function for voters to revoke their allocation in a round before voting closes
+    pub fn revoke_votes(env: Env, voter: Address, round_id: u64) -> Result<(), ContractError> {
+        // Require authorization from the voter
+        voter.require_auth();
+        Self::extend_instance(&env);
+
+        Self::require_not_paused(&env)?;
+
+        let mut round = Self::get_round(env.clone(), round_id)?;
+        if !round.is_active() || env.ledger().timestamp() > round.voting_deadline {
+            return Err(ContractError::VotingClosed);
+        }
+
+        if round.phase != RoundPhase::Voting {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        // Comparisons are final
+        if round.config.mechanism == VotingMechanism::Pairwise {
+            return Err(ContractError::InvalidAllocations);
+        }
+
+        // The voter no longer counts towards the quorum
+        if Self::withdraw_voter_allocations(&env, &mut round, &voter)? {
+            round.voter_count -= 1;
+        }
+        Self::set_credits_spent(&env, &mut round, &voter, 0)?;
+        Self::set_raw_votes(&env, &mut round, &voter, 0)?;
+        Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+
+        Self::store_voter_allocation(&env, round_id, &voter, &Map::new(&env));
+        Self::store_cast_votes(&env, round_id, &voter, &Map::new(&env));
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("VOTE_RVK"), voter.clone(), round_id),
+            round_id,
+        );
+
+        Ok(())
+    }
+
+    // Function for a voter to deliberately abstain in a round. Abstaining replaces any ballot
+    // or commitment the voter has in the round, and a later ballot replaces the abstention.
+    // Abstentions count towards the round's quorum.
+    pub fn abstain(env: Env, voter: Address, round_id: u64) -> Result<(), ContractError> {
+        // Require authorization from the voter
+        voter.require_auth();
+        Self::extend_instance(&env);
+
+        Self::require_not_paused(&env)?;
+        Self::require_not_blocked(&env, &voter)?;
+
+        let mut round = Self::get_round(env.clone(), round_id)?;
+        Self::require_eligible(&env, &round, &voter)?;
+
+        if !round.is_active() || env.ledger().timestamp() > round.voting_deadline {
+            return Err(ContractError::VotingClosed);
+        }
+
+        if round.phase != RoundPhase::Voting {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        // Contributions have been paid out and comparisons are final, so neither can be
+        // taken back
+        let compared = round.config.mechanism == VotingMechanism::Pairwise
+            && Self::has_persistent(&env, &DataKey::VoterCredits(round_id, voter.clone()));
+        if Self::has_abstained_in(&env, round_id, &voter)
+            || Self::has_persistent(&env, &DataKey::Contribution(round_id, voter.clone()))
+            || compared
+        {
+            return Err(ContractError::AlreadyVoted);
+        }
+
+        // Credits handed to a delegate are theirs to cast
+        if Self::has_persistent(&env, &DataKey::Delegate(round_id, voter.clone())) {
+            return Err(ContractError::InvalidDelegation);
+        }
+
+        // Take back any ballot or commitment the voter has in the round
+        if Self::withdraw_voter_allocations(&env, &mut round, &voter)? {
+            round.voter_count -= 1;
+        }
+        Self::set_credits_spent(&env, &mut round, &voter, 0)?;
+        Self::set_raw_votes(&env, &mut round, &voter, 0)?;
+        Self::store_voter_allocation(&env, round_id, &voter, &Map::new(&env));
+        Self::store_cast_votes(&env, round_id, &voter, &Map::new(&env));
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Commitment(round_id, voter.clone()));
+
+        Self::write_persistent(
+            &env,
+            &DataKey::Ballot(BallotKey::Abstained(round_id, voter.clone())),
+            &true,
+        );
+        round.abstentions += 1;
+        Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("ABSTAIN"), round_id, voter),
+            round.abstentions,
+        );
+
+        Ok(())
+    }
+
+    // Function to check whether a voter has abstained in a round
+    pub fn has_abstained(env: Env, round_id: u64, voter: Address) -> Result<bool, ContractError> {
+        Self::get_round(env.clone(), round_id)?;
+        Ok(Self::has_abstained_in(&env, round_id, &voter))
+    }
+
+    // Helper function to check whether a voter has abstained in a round
+    fn has_abstained_in(env: &Env, round_id: u64, voter: &Address) -> bool {
+        Self::has_persistent(
+            env,
+            &DataKey::Ballot(BallotKey::Abstained(round_id, voter.clone())),
+        )
+    }
+
+    // Helper function to replace a voter's abstention in a round by the ballot they cast.
+    // Returns whether the voter had abstained.
+    fn clear_abstention(env: &Env, round: &mut Round, voter: &Address) -> bool {
+        if !Self::has_abstained_in(env, round.id, voter) {
+            return false;
+        }
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Ballot(BallotKey::Abstained(
+                round.id,
+                voter.clone(),
+            )));
+        round.abstentions -= 1;
+        true
+    }
+
+    // Function for a voter who hasn't voted yet to hand their credit budget for a round to
+    // another voter. Delegation is a single hop: delegates can't delegate onwards and
+    // delegators can't receive credits.
+    pub fn delegate_votes(
+        env: Env,
+        from: Address,
+        to: Address,
+        round_id: u64,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the delegator
+        from.require_auth();
+        Self::extend_instance(&env);
+
+        Self::require_not_paused(&env)?;
+        Self::require_not_blocked(&env, &from)?;
+        Self::require_not_blocked(&env, &to)?;
+
+        let round = Self::get_round(env.clone(), round_id)?;
+        if !round.is_active() || env.ledger().timestamp() > round.voting_deadline {
+            return Err(ContractError::VotingClosed);
+        }
+
+        // Both ends of the delegation must be voters of the round
+        Self::require_eligible(&env, &round, &from)?;
+        Self::require_eligible(&env, &round, &to)?;
+
+        // Credits can only be handed over before any of them are used
+        if Self::has_persistent(&env, &DataKey::VoterAllocation(round_id, from.clone()))
+            || Self::has_persistent(&env, &DataKey::Commitment(round_id, from.clone()))
+            || Self::has_persistent(&env, &DataKey::Delegate(round_id, from.clone()))
+            || Self::has_abstained_in(&env, round_id, &from)
+        {
+            return Err(ContractError::AlreadyVoted);
+        }
+
+        // Refuse self-delegation and anything longer than one hop
+        if from == to
+            || Self::has_persistent(&env, &DataKey::Delegate(round_id, to.clone()))
+            || Self::has_persistent(&env, &DataKey::DelegatedCredits(round_id, from.clone()))
+        {
+            return Err(ContractError::InvalidDelegation);
+        }
+
+        let credits = Self::own_budget(&env, &round, &from);
+        let received_key = DataKey::DelegatedCredits(round_id, to.clone());
+        let received: u64 = Self::read_persistent(&env, &received_key).unwrap_or(0);
+        let received = received
+            .checked_add(credits)
+            .ok_or(ContractError::MathOverflow)?;
+        Self::write_persistent(&env, &received_key, &received);
+        Self::write_persistent(
+            &env,
+            &DataKey::Delegate(round_id, from.clone()),
+            &(to.clone(), credits),
+        );
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("DELEGATE"), round_id), (from, to, credits));
+
+        Ok(())
+    }
+
+    // Function for a delegator to take their credits back, as long as the delegate hasn't
+    // spent any of them
+    pub fn undelegate(env: Env, from: Address, round_id: u64) -> Result<(), ContractError> {
+        // Require authorization from the delegator
+        from.require_auth();
+        Self::extend_instance(&env);
+
+        Self::require_not_paused(&env)?;
+        Self::require_not_blocked(&env, &from)?;
+
+        let round = Self::get_round(env.clone(), round_id)?;
+        if !round.is_active() || env.ledger().timestamp() > round.voting_deadline {
+            return Err(ContractError::VotingClosed);
+        }
+
+        let delegate_key = DataKey::Delegate(round_id, from.clone());
+        let (to, credits) = Self::read_persistent::<(Address, u64)>(&env, &delegate_key)
+            .ok_or(ContractError::InvalidDelegation)?;
+
+        // The delegate's ballot must still fit their budget without these credits
+        let received_key = DataKey::DelegatedCredits(round_id, to.clone());
+        let received: u64 = Self::read_persistent(&env, &received_key).unwrap_or(0);
+        let remaining = received.saturating_sub(credits);
+        let spent: u64 =
+            Self::read_persistent(&env, &DataKey::VoterCredits(round_id, to.clone())).unwrap_or(0);
+        if spent > Self::own_budget(&env, &round, &to).saturating_add(remaining) {
+            return Err(ContractError::ExceededVoteLimit);
+        }
+
+        if remaining == 0 {
+            env.storage().persistent().remove(&received_key);
+        } else {
+            Self::write_persistent(&env, &received_key, &remaining);
+        }
+        env.storage().persistent().remove(&delegate_key);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("UNDELEG"), round_id), (from, to, credits));
+
+        Ok(())
+    }
+
+    // Function to get the delegate a voter has handed their credits in a round to, if any
+    pub fn get_delegate(env: Env, round_id: u64, voter: Address) -> Option<Address> {
+        Self::read_persistent::<(Address, u64)>(&env, &DataKey::Delegate(round_id, voter))
+            .map(|(to, _)| to)
+    }
+
+    // Helper function to check that an allocation map is non-empty, has no zero-vote
+    // entries, only targets live, approved submissions of the given round and respects
+    // the round's self-vote rule
+    fn validate_allocations(
+        env: &Env,
+        round: &Round,
+        voter: &Address,
+        allocations: &Map<u64, u64>,
+    ) -> Result<(), ContractError> {
+        if allocations.is_empty() {
+            return Err(ContractError::InvalidAllocations);
+        }
+
+        for (submission_id, votes) in allocations.iter() {
+            if votes == 0 {
+                return Err(ContractError::InvalidAllocations);
+            }
+
+            let submission = Self::get_submission(env.clone(), submission_id)?;
+            if submission.round_id != round.id || submission.withdrawn {
+                return Err(ContractError::SubmissionNotInRound);
+            }
+
+            if submission.status != SubmissionStatus::Approved {
+                return Err(ContractError::SubmissionNotApproved);
+            }
+
+            if !round.config.allow_self_votes && submission.submitter == *voter {
+                return Err(ContractError::SelfVoteNotAllowed);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Helper function to check a voter's ballot against the round's per-submission cap
+    // and calculate the credits it costs under the round's voting mechanism
+    fn ballot_cost(
+        config: &RoundConfig,
+        allocations: &Map<u64, u64>,
+    ) -> Result<u64, ContractError> {
+        let mut cost: u64 = 0;
+        for votes in allocations.values().iter() {
+            if config.max_votes_per_submission > 0 && votes > config.max_votes_per_submission {
+                return Err(ContractError::PerProjectCapExceeded);
+            }
+
+            cost = cost
+                .checked_add(Self::entry_cost(config, votes)?)
+                .ok_or(ContractError::MathOverflow)?;
+        }
+
+        Ok(cost)
+    }
+
+    // Helper function to calculate the credits one entry of a ballot costs
+    fn entry_cost(config: &RoundConfig, votes: u64) -> Result<u64, ContractError> {
+        match config.mechanism {
+            VotingMechanism::Linear
+            | VotingMechanism::Ranked
+            | VotingMechanism::QuadraticFunding
+            | VotingMechanism::Pairwise => Ok(votes),
+            VotingMechanism::Quadratic => {
+                votes.checked_mul(votes).ok_or(ContractError::MathOverflow)
+            }
+        }
+    }
+
+    // Helper function to store the votes a voter cast on each submission of their ballot in a
+    // round, and the credits charged for them. An empty map is removed.
+    fn store_cast_votes(env: &Env, round_id: u64, voter: &Address, cast: &Map<u64, (u64, u64)>) {
+        let cast_key = DataKey::Credit(CreditKey::CastVotes(round_id, voter.clone()));
+        if cast.is_empty() {
+            env.storage().persistent().remove(&cast_key);
+        } else {
+            Self::write_persistent(env, &cast_key, cast);
+        }
+    }
+
+    // Helper function to add a validated allocation to the submission tallies and the
+    // round's running total. The caller is responsible for storing the round.
+    fn add_votes(
+        env: &Env,
+        round: &mut Round,
+        allocations: &Map<u64, u64>,
+    ) -> Result<(), ContractError> {
+        for (submission_id, votes) in allocations.iter() {
+            let mut submission = Self::get_submission(env.clone(), submission_id)?;
+            submission.total_votes = submission
+                .total_votes
+                .checked_add(votes)
+                .ok_or(ContractError::MathOverflow)?;
+            Self::write_persistent(env, &DataKey::Submission(submission_id), &submission);
+
+            round.total_votes = round
+                .total_votes
+                .checked_add(votes)
+                .ok_or(ContractError::MathOverflow)?;
+            round.tallies.set(submission_id, submission.total_votes);
+        }
+
+        Ok(())
+    }
+
+    // Helper function to subtract a voter's stored allocation from the submission tallies
+    // and the round's running total. The caller is responsible for storing the round.
+    // Returns whether the voter had a stored allocation.
+    fn withdraw_voter_allocations(
+        env: &Env,
+        round: &mut Round,
+        voter: &Address,
+    ) -> Result<bool, ContractError> {
+        let allocation_key = DataKey::VoterAllocation(round.id, voter.clone());
+        let Some(previous) = Self::read_persistent::<Map<u64, u64>>(env, &allocation_key) else {
+            return Ok(false);
+        };
+
+        for (submission_id, votes) in previous.iter() {
+            let mut submission = Self::get_submission(env.clone(), submission_id)?;
+            // Withdrawn submissions already had their tally cleared
+            if submission.withdrawn {
+                continue;
+            }
+            submission.total_votes -= votes;
+            Self::write_persistent(env, &DataKey::Submission(submission_id), &submission);
+
+            // Rejected submissions' votes were taken out of the running total on review
+            if submission.status == SubmissionStatus::Approved {
+                round.total_votes -= votes;
+                round.tallies.set(submission_id, submission.total_votes);
+            }
+        }
+
+        Ok(true)
+    }
+
+    // Helper function to store a voter's allocation in a round, keeping the voter breakdown
+    // of every submission it adds, changes or drops in step. An empty allocation is removed.
+    fn store_voter_allocation(
+        env: &Env,
+        round_id: u64,
+        voter: &Address,
+        allocations: &Map<u64, u64>,
+    ) {
+        let allocation_key = DataKey::VoterAllocation(round_id, voter.clone());
+        let previous =
+            Self::read_persistent::<Map<u64, u64>>(env, &allocation_key).unwrap_or(Map::new(env));
+        for (submission_id, votes) in previous.iter() {
+            if !allocations.contains_key(submission_id) && votes > 0 {
+                Self::set_submission_voter(env, submission_id, voter, 0);
+            }
+        }
+        for (submission_id, votes) in allocations.iter() {
+            if previous.get(submission_id) != Some(votes) {
+                Self::set_submission_voter(env, submission_id, voter, votes);
+            }
+        }
+
+        if allocations.is_empty() {
+            env.storage().persistent().remove(&allocation_key);
+        } else {
+            Self::write_persistent(env, &allocation_key, allocations);
+        }
+    }
+
+    // Helper function to set a voter's votes in a submission's voter breakdown. The breakdown
+    // is split into chunks of `SUBMISSION_VOTER_CHUNK` voters, so a ballot rewrites at most
+    // two chunks per submission; a voter dropping out is replaced by the last one.
+    fn set_submission_voter(env: &Env, submission_id: u64, voter: &Address, votes: u64) {
+        let slot_key = DataKey::Index(IndexKey::SubmissionVoterSlot(submission_id, voter.clone()));
+        let count_key = DataKey::Index(IndexKey::SubmissionVoterCount(submission_id));
+        let count = Self::read_persistent::<u32>(env, &count_key).unwrap_or(0);
+
+        match (Self::read_persistent::<u32>(env, &slot_key), votes) {
+            (Some(position), 0) => {
+                // Move the last voter into the vacated position
+                let last = count - 1;
+                let mut last_chunk = Self::voter_chunk(env, submission_id, last);
+                let moved = last_chunk.pop_back_unchecked();
+                if position != last {
+                    let chunk_size = Self::SUBMISSION_VOTER_CHUNK;
+                    let offset = position % chunk_size;
+                    if position / chunk_size == last / chunk_size {
+                        last_chunk.set(offset, moved.clone());
+                    } else {
+                        let mut chunk = Self::voter_chunk(env, submission_id, position);
+                        chunk.set(offset, moved.clone());
+                        Self::write_voter_chunk(env, submission_id, position, &chunk);
+                    }
+                    Self::write_persistent(
+                        env,
+                        &DataKey::Index(IndexKey::SubmissionVoterSlot(submission_id, moved.0)),
+                        &position,
+                    );
+                }
+                Self::write_voter_chunk(env, submission_id, last, &last_chunk);
+                env.storage().persistent().remove(&slot_key);
+                Self::write_persistent(env, &count_key, &last);
+            }
+            (Some(position), votes) => {
+                let mut chunk = Self::voter_chunk(env, submission_id, position);
+                chunk.set(
+                    position % Self::SUBMISSION_VOTER_CHUNK,
+                    (voter.clone(), votes),
+                );
+                Self::write_voter_chunk(env, submission_id, position, &chunk);
+            }
+            (None, 0) => {}
+            (None, votes) => {
+                let mut chunk = Self::voter_chunk(env, submission_id, count);
+                chunk.push_back((voter.clone(), votes));
+                Self::write_voter_chunk(env, submission_id, count, &chunk);
+                Self::write_persistent(env, &slot_key, &count);
+                Self::write_persistent(env, &count_key, &(count + 1));
+            }
+        }
+    }
+
+    // Helper function to get the chunk of a submission's voter breakdown holding a position
+    fn voter_chunk(env: &Env, submission_id: u64, position: u32) -> Vec<(Address, u64)> {
+        let chunk = position / Self::SUBMISSION_VOTER_CHUNK;
+        let chunk_key = DataKey::Index(IndexKey::SubmissionVoters(submission_id, chunk));
+        Self::read_persistent(env, &chunk_key).unwrap_or(Vec::new(env))
+    }
+
+    // Helper function to store the chunk of a submission's voter breakdown holding a
+    // position, removing it once it is empty
+    fn write_voter_chunk(
+        env: &Env,
+        submission_id: u64,
+        position: u32,
+        voters: &Vec<(Address, u64)>,
+    ) {
+        let chunk = position / Self::SUBMISSION_VOTER_CHUNK;
+        let chunk_key = DataKey::Index(IndexKey::SubmissionVoters(submission_id, chunk));
+        if voters.is_empty() {
+            env.storage().persistent().remove(&chunk_key);
+        } else {
+            Self::write_persistent(env, &chunk_key, voters);
+        }
+    }
+
+    // Function to list the voters with votes on a submission and their votes, skipping the
+    // first `start` and returning at most `limit`
+    pub fn get_submission_voters(
+        env: Env,
+        submission_id: u64,
+        start: u32,
+        limit: u32,
+    ) -> Vec<(Address, u64)> {
+        let count_key = DataKey::Index(IndexKey::SubmissionVoterCount(submission_id));
+        let count = Self::read_persistent::<u32>(&env, &count_key).unwrap_or(0);
+
+        // Each chunk the page touches is read once
+        let mut voters = Vec::new(&env);
+        let end = start
+            .saturating_add(limit.min(Self::MAX_PAGE_SIZE))
+            .min(count);
+        let mut chunk = Vec::new(&env);
+        for position in start..end {
+            let offset = position % Self::SUBMISSION_VOTER_CHUNK;
+            if position == start || offset == 0 {
+                chunk = Self::voter_chunk(&env, submission_id, position);
+            }
+            voters.push_back(chunk.get_unchecked(offset));
+        }
+
+        voters
+    }
+
+    // Helper function to store the credits a voter has spent in a round, keeping the round's
+    // running total in step. The caller is responsible for storing the round.
+    fn set_credits_spent(
+        env: &Env,
+        round: &mut Round,
+        voter: &Address,
+        spent: u64,
+    ) -> Result<(), ContractError> {
+        let key = DataKey::VoterCredits(round.id, voter.clone());
+        let previous: u64 = Self::read_persistent(env, &key).unwrap_or(0);
+        round.credits_spent = round
+            .credits_spent
+            .saturating_sub(previous)
+            .checked_add(spent)
+            .ok_or(ContractError::MathOverflow)?;
+
+        if spent == 0 {
+            env.storage().persistent().remove(&key);
+        } else {
+            Self::write_persistent(env, &key, &spent);
+        }
+        Ok(())
+    }
+
+    // Helper function to remove votes on withdrawn submissions that withdraw_submission has
+    // not refunded yet from a voter's allocations, refunding the credits they cost. The caller
+    // is responsible for storing the round.
+    fn drop_withdrawn_submissions(
+        env: &Env,
+        round: &mut Round,
+        voter: &Address,
+        allocations: &mut Map<u64, u64>,
+    ) -> Result<(), ContractError> {
+        for (submission_id, votes) in allocations.iter() {
+            if Self::get_submission(env.clone(), submission_id)?.withdrawn {
+                Self::refund_voter_votes(env, round, voter, submission_id, votes)?;
+                allocations.remove(submission_id);
+            }
+        }
+        Ok(())
+    }
+
+    // Function to close voting and record the totals funding allocations are derived from.
+    // Once ballots have closed anyone may close the round, so its funds don't depend on the
+    // admin staying around; closing early or without a quorum is reserved for the admin.
+    pub fn close_voting(
+        env: Env,
+        caller: Address,
+        round_id: u64,
+        force: bool,
+        override_quorum: bool,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the admin, a round manager or the round's operator to
+        // bypass the rules
+        if force || override_quorum {
+            let action = symbol_short!("close");
+            Self::require_round_role(&env, &caller, round_id, Some(Role::RoundManager), action)?;
+        } else {
+            Self::extend_instance(&env);
+        }
+
+        Self::require_not_paused(&env)?;
+
+        let mut round = Self::get_round(env.clone(), round_id)?;
+
+        if !round.is_active() {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        if round.phase != RoundPhase::Voting {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        // Voting, its grace period and the reveal period of commit-reveal rounds may only be
+        // cut short by an explicit force. Ballots committed but not yet revealed are discarded.
+        let ballots_close = round.reveal_deadline().max(round.grace_deadline());
+        if !force && env.ledger().timestamp() <= ballots_close {
+            return Err(ContractError::VotingStillOpen);
+        }
+
+        // Enough distinct voters must have taken part, unless explicitly overridden.
+        // Abstaining counts as taking part.
+        let turnout = round.voter_count.saturating_add(round.abstentions);
+        if !override_quorum && turnout < round.config.min_voters {
+            return Err(ContractError::QuorumNotReached);
+        }
+
+        // Close the round. The vote totals are already up to date, so each submission's
+        // allocation is derived from them when the submission is paid.
+        let (fee_due, excluded, bonuses) = Self::compute_allocations(&env, &mut round);
+        let mut fee = 0;
+        if let Some(fee_due) = fee_due {
+            fee = fee_due.1;
+            Self::write_persistent(&env, &DataKey::FeeDue(round_id), &fee_due);
+        }
+        for (submission_id, bonus) in bonuses.iter() {
+            let mut submission = Self::get_submission(env.clone(), submission_id)?;
+            submission.total_votes = submission.total_votes.saturating_add(bonus);
+            Self::write_persistent(&env, &DataKey::Submission(submission_id), &submission);
+        }
+        for submission_id in excluded.iter() {
+            Self::reject_at_close(&env, submission_id);
+        }
+        Self::refund_deposits(&env, &round)?;
+
+        // Open the challenge window in which the results can be inspected before payouts
+        round.closed_at = env.ledger().timestamp();
+        round.challenge_ends = round
+            .closed_at
+            .saturating_add(round.config.challenge_period_seconds);
+        Self::set_phase(&env, &mut round, RoundPhase::Closed);
+        Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+
+        // Fix the pools of the tokens escrowed besides the funding token, whose pool is the
+        // funding amount. The allocations are shared out of each pool in the same proportions.
+        let tokens = Self::read_persistent::<Vec<Address>>(&env, &DataKey::EscrowTokens(round_id))
+            .unwrap_or(Vec::new(&env));
+        for token in tokens.iter().skip(1) {
+            let escrow =
+                Self::read_persistent::<i128>(&env, &DataKey::Escrow(round_id, token.clone()))
+                    .unwrap_or(0);
+            Self::write_persistent(&env, &DataKey::TokenPool(round_id, token), &escrow);
+        }
+
+        // Freeze the turnout figures for historical queries
+        Self::write_persistent(
+            &env,
+            &DataKey::FinalStats(round_id),
+            &Self::live_stats(&round),
+        );
+        let payload = Vec::from_array(&env, [round.distributable, fee]);
+        Self::record_audit(&env, symbol_short!("close"), &caller, round_id, payload);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("VOTE_CLSD"), round_id),
+            VotingClosedEvent {
+                round_id,
+                total_votes: round.total_votes,
+                funded_votes: round.funded_votes,
+                distributable: round.distributable,
+                fee,
+                allocations_hash: Self::allocations_hash(&env, &round),
+            },
+        );
+
+        Ok(())
+    }
+
+    // Helper function to hash the allocation table of a closed round: the XDR of the map from
+    // each approved submission to its allocation, as `get_allocations` returns it. The tallies
+    // hold every approved submission, so no submission has to be loaded.
+    fn allocations_hash(env: &Env, round: &Round) -> BytesN<32> {
+        let mut allocations = Map::<u64, i128>::new(env);
+        for (submission_id, tally) in round.tallies.iter() {
+            let funded = if round.is_funded(submission_id, tally) {
+                tally
+            } else {
+                0
+            };
+            allocations.set(
+                submission_id,
+                Self::allocation_for(round, submission_id, funded),
+            );
+        }
+        env.crypto().sha256(&allocations.to_xdr(env)).into()
+    }
+
+    // Helper function to work out the allocations of a round as if it closed now, on the
+    // round in memory: sets the protocol fee aside, adds reviewers' attestations, drops
+    // blacklisted submitters' tallies and applies the funding cutoff and cap. Writes nothing,
+    // so `close_voting` and `preview_allocations` share it and can't drift apart. Returns the
+    // fee due, the submissions dropped and the attestation bonus added to each submission.
+    fn compute_allocations(
+        env: &Env,
+        round: &mut Round,
+    ) -> (Option<FeeDue>, Vec<u64>, Map<u64, u64>) {
+        // Set the protocol fee aside; allocations share what is left of the funding
+        let mut distributable = round.funding_amount;
+        let mut fee_due = None;
+        if let Some(fee) = Self::get_fee(env.clone()) {
+            let fee_amount =
+                allocation::mul_div(round.funding_amount, fee.bps as u64, Self::TOTAL_BPS as u64);
+            if fee_amount > 0 {
+                fee_due = Some((fee.recipient, fee_amount));
+                distributable -= fee_amount;
+            }
+        }
+
+        // The running tallies hold every approved submission's votes, so no submission has to
+        // be loaded
+        let bonuses = Self::apply_attestations(env, round);
+        let excluded = Self::exclude_blacklisted(env, round);
+        Self::allocate(env, round, distributable);
+
+        (fee_due, excluded, bonuses)
+    }
+
+    // Helper function to work out the allocation totals of a round's tallies, on the round in
+    // memory, sharing out the given distributable funding
+    fn allocate(env: &Env, round: &mut Round, distributable: i128) {
+        let votes: alloc::vec::Vec<(u64, u64)> = round.tallies.iter().collect();
+        if round.config.categories.is_empty() {
+            round.set_allocation(allocation::compute(
+                &votes,
+                distributable,
+                &round.allocation_config(),
+            ));
+        } else {
+            Self::allocate_categories(env, round, &votes, distributable);
+        }
+    }
+
+    // Helper function to work out a closing round's allocations independently within each of
+    // its categories, on the round in memory. The distributable funding is split in proportion
+    // to the category budgets, the budget of a category without approved submissions going to
+    // the largest category that has some, and each category's share is then allocated like a
+    // round of its own. The round's totals add up the categories'.
+    fn allocate_categories(
+        env: &Env,
+        round: &mut Round,
+        votes: &[(u64, u64)],
+        distributable: i128,
+    ) {
+        let config = round.allocation_config();
+        let mut category_votes: alloc::vec::Vec<alloc::vec::Vec<(u64, u64)>> = round
+            .config
+            .categories
+            .iter()
+            .map(|_| alloc::vec::Vec::new())
+            .collect();
+        for &(submission_id, tally) in votes {
+            if let Some(index) = round.submission_categories.get(submission_id) {
+                category_votes[index as usize].push((submission_id, tally));
+            }
+        }
+
+        // Budgets were checked to fit a u64 at creation
+        let budgets: alloc::vec::Vec<(u64, bool)> = round
+            .config
+            .categories
+            .iter()
+            .zip(category_votes.iter())
+            .map(|(category, votes)| (category.budget as u64, !votes.is_empty()))
+            .collect();
+        let pools = allocation::split_budgets(distributable, &budgets);
+
+        let mut totals = Vec::new(env);
+        let mut funded_votes: u64 = 0;
+        for (votes, pool) in category_votes.iter().zip(pools) {
+            let allocation = allocation::compute(votes, pool, &config);
+            funded_votes = funded_votes.saturating_add(allocation.funded_votes);
+            totals.push_back(CategoryAllocation::from(allocation));
+        }
+        round.category_totals = totals;
+        round.set_allocation(Allocation {
+            distributable,
+            funded_votes,
+            cutoff_votes: 0,
+            cutoff_id: u64::MAX,
+            capped_votes: u64::MAX,
+            uncapped_pool: distributable,
+            uncapped_votes: funded_votes,
+        });
+    }
+
+    // Helper function to drop the tallies of blacklisted submitters' approved submissions from
+    // a closing round, so the share their votes would have had goes to the other submissions.
+    // Returns the submissions dropped.
+    fn exclude_blacklisted(env: &Env, round: &mut Round) -> Vec<u64> {
+        let mut excluded = Vec::new(env);
+        let blacklist = Self::read_persistent::<Vec<Address>>(env, &DataKey::Blacklist)
+            .unwrap_or(Vec::new(env));
+        for submitter in blacklist.iter() {
+            let Some(submission_id) =
+                Self::read_persistent::<u64>(env, &DataKey::Submitter(round.id, submitter))
+            else {
+                continue;
+            };
+            let Some(votes) = round.tallies.get(submission_id) else {
+                continue;
+            };
+
+            round.total_votes -= votes;
+            round.tallies.remove(submission_id);
+            excluded.push_back(submission_id);
+        }
+
+        excluded
+    }
+
+    // Helper function to reject a submission dropped when its round closed
+    fn reject_at_close(env: &Env, submission_id: u64) {
+        if let Some(mut submission) =
+            Self::read_persistent::<Submission>(env, &DataKey::Submission(submission_id))
+        {
+            submission.status = SubmissionStatus::Rejected;
+            Self::write_persistent(env, &DataKey::Submission(submission_id), &submission);
+            Self::record_rejection(env, submission_id);
+        }
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("SUB_RVW"), submission_id),
+            SubmissionStatus::Rejected,
+        );
+    }
+
+    // Function to escrow tokens for a round in the contract
+    pub fn deposit_funds(
+        env: Env,
+        from: Address,
+        round_id: u64,
+        token: Address,
+        amount: i128,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the depositor
+        from.require_auth();
+        Self::extend_instance(&env);
+
+        if amount <= 0 {
+            return Err(ContractError::InvalidAmount);
+        }
+
+        let round = Self::get_round(env.clone(), round_id)?;
+
+        match round.phase {
+            RoundPhase::Disbursed => return Err(ContractError::FundsAlreadyDisbursed),
+            RoundPhase::Cancelled => return Err(ContractError::InvalidPhase),
+            _ => {}
+        }
+
+        // The pools are fixed at close, so no new token can join them afterwards. The funding
+        // token may still be topped up to cover the funding amount.
+        let tokens = Self::read_persistent::<Vec<Address>>(&env, &DataKey::EscrowTokens(round_id))
+            .unwrap_or(Vec::new(&env));
+        if !round.is_active() && !tokens.is_empty() && !tokens.contains(&token) {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        // Pull the tokens into the contract and record them for refunds
+        Self::escrow_tokens(&env, &from, round_id, &token, amount)?;
+        Self::record_funding(
+            &env,
+            DataKey::Deposit(round_id, from.clone()),
+            DataKey::Depositors(round_id),
+            &from,
+            &token,
+            amount,
+        );
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("DEPOSIT"), round_id, from), (token, amount));
+
+        Ok(())
+    }
+
+    // Function for anyone to donate tokens to an active round, growing its funding pool on top
+    // of the admin's commitment. Donations are refunded to their donors if the round is
+    // cancelled.
+    pub fn donate(
+        env: Env,
+        donor: Address,
+        round_id: u64,
+        token: Address,
+        amount: i128,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the donor
+        donor.require_auth();
+        Self::extend_instance(&env);
+
+        if amount <= 0 {
+            return Err(ContractError::InvalidAmount);
+        }
+
+        // The pool is fixed once voting has closed
+        let mut round = Self::get_round(env.clone(), round_id)?;
+        if !round.is_active() {
+            return Err(ContractError::RoundNotActive);
+        }
+
+        // Donations in the funding token grow the funding amount; any other token's pool is
+        // its escrow when voting closes
+        if Self::is_funding_token(&env, round_id, &token) {
+            let funding = round
+                .funding_amount
+                .checked_add(amount)
+                .ok_or(ContractError::MathOverflow)?;
+            Self::rescale_categories(&env, &mut round, funding)?;
+            round.funding_amount = funding;
+            Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+        }
+
+        // Pull the tokens into the contract
+        Self::escrow_tokens(&env, &donor, round_id, &token, amount)?;
+
+        // Record the donor's running total in the token
+        Self::record_funding(
+            &env,
+            DataKey::Donation(round_id, donor.clone()),
+            DataKey::Donors(round_id),
+            &donor,
+            &token,
+            amount,
+        );
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("DONATE"), round_id, donor), (token, amount));
+
+        Ok(())
+    }
+
+    // Function to get the amounts a donor has donated to a round, per token
+    pub fn get_donation(env: Env, round_id: u64, donor: Address) -> Map<Address, i128> {
+        Self::read_persistent::<Map<Address, i128>>(&env, &DataKey::Donation(round_id, donor))
+            .unwrap_or(Map::new(&env))
+    }
+
+    // Function to get the amounts a depositor has deposited into a round, per token
+    pub fn get_deposit(env: Env, round_id: u64, depositor: Address) -> Map<Address, i128> {
+        Self::read_persistent::<Map<Address, i128>>(&env, &DataKey::Deposit(round_id, depositor))
+            .unwrap_or(Map::new(&env))
+    }
+
+    // Helper function to add an amount to a funder's running total per token in a round's
+    // refund ledger, adding the funder to the ledger's list on their first entry
+    fn record_funding(
+        env: &Env,
+        ledger_key: DataKey,
+        funders_key: DataKey,
+        funder: &Address,
+        token: &Address,
+        amount: i128,
+    ) {
+        let mut ledger =
+            Self::read_persistent::<Map<Address, i128>>(env, &ledger_key).unwrap_or(Map::new(env));
+        if ledger.is_empty() {
+            let mut funders =
+                Self::read_persistent::<Vec<Address>>(env, &funders_key).unwrap_or(Vec::new(env));
+            funders.push_back(funder.clone());
+            Self::write_persistent(env, &funders_key, &funders);
+        }
+        let total = ledger.get(token.clone()).unwrap_or(0) + amount;
+        ledger.set(token.clone(), total);
+        Self::write_persistent(env, &ledger_key, &ledger);
+    }
+
+    // Helper function to transfer tokens into the contract and add them to a round's escrow
+    fn escrow_tokens(
+        env: &Env,
+        from: &Address,
+        round_id: u64,
+        token: &Address,
+        amount: i128,
+    ) -> Result<(), ContractError> {
+        let token_client = TokenClient::new(env, token);
+        token_client.transfer(from, &env.current_contract_address(), &amount);
+
+        // Record the escrowed balance for the round
+        let escrow_key = DataKey::Escrow(round_id, token.clone());
+        let escrow = Self::read_persistent::<i128>(env, &escrow_key).unwrap_or(0);
+        let escrow = escrow
+            .checked_add(amount)
+            .ok_or(ContractError::MathOverflow)?;
+        Self::write_escrow(env, round_id, token, escrow);
+
+        // Remember which tokens the round holds so they can be refunded
+        let tokens_key = DataKey::EscrowTokens(round_id);
+        let mut tokens =
+            Self::read_persistent::<Vec<Address>>(env, &tokens_key).unwrap_or(Vec::new(env));
+        if !tokens.contains(token) {
+            tokens.push_back(token.clone());
+            Self::write_persistent(env, &tokens_key, &tokens);
+        }
+
+        Ok(())
+    }
+
+    // Function to cancel a round that has not paid anything out. Its depositors and donors are
+    // then refunded through `refund`, and anything escrowed that no funder is owed goes to the
+    // given address.
+    pub fn cancel_round(env: Env, round_id: u64, refund_to: Address) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        let mut round = Self::get_round(env.clone(), round_id)?;
+
+        match round.phase {
+            RoundPhase::Disbursed => return Err(ContractError::FundsAlreadyDisbursed),
+            RoundPhase::Cancelled => return Err(ContractError::InvalidPhase),
+            _ => {}
+        }
+
+        // Funders can only be refunded in full while nothing has been paid out
+        let tokens = Self::read_persistent::<Vec<Address>>(&env, &DataKey::EscrowTokens(round_id))
+            .unwrap_or(Vec::new(&env));
+        for token in tokens.iter() {
+            if Self::has_persistent(&env, &DataKey::Disbursed(round_id, token)) {
+                return Err(ContractError::FundsAlreadyDisbursed);
+            }
+        }
+
+        Self::set_phase(&env, &mut round, RoundPhase::Cancelled);
+        Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+        Self::write_persistent(&env, &DataKey::RefundTo(round_id), &refund_to);
+        Self::refund_deposits(&env, &round)?;
+
+        Ok(())
+    }
+
+    // Maximum number of funders refunded by a single refund call
+    const MAX_REFUND_BATCH: u32 = 20;
+
+    // Function to refund a cancelled round's depositors and then its donors their recorded
+    // contributions, resuming after the funders refunded by previous calls and processing at
+    // most `MAX_REFUND_BATCH` of them. Once every funder has been refunded, whatever is left
+    // in escrow goes to the round's refund address. Returns the number of funders refunded.
+    pub fn refund(env: Env, round_id: u64) -> Result<u32, ContractError> {
+        Self::extend_instance(&env);
+
+        let round = Self::get_round(env.clone(), round_id)?;
+
+        match round.phase {
+            RoundPhase::Cancelled => {}
+            RoundPhase::Disbursed => return Err(ContractError::FundsAlreadyDisbursed),
+            _ => return Err(ContractError::InvalidPhase),
+        }
+
+        let depositors =
+            Self::read_persistent::<Vec<Address>>(&env, &DataKey::Depositors(round_id))
+                .unwrap_or(Vec::new(&env));
+        let donors = Self::read_persistent::<Vec<Address>>(&env, &DataKey::Donors(round_id))
+            .unwrap_or(Vec::new(&env));
+        let funder_count = depositors.len() + donors.len();
+
+        // Resume after the funders already refunded
+        let cursor_key = DataKey::RefundCursor(round_id);
+        let cursor = Self::read_persistent::<u32>(&env, &cursor_key).unwrap_or(0);
+        let end = funder_count.min(cursor.saturating_add(Self::MAX_REFUND_BATCH));
+        for position in cursor..end {
+            let (funder, ledger_key) = if position < depositors.len() {
+                let depositor = depositors.get_unchecked(position);
+                (depositor.clone(), DataKey::Deposit(round_id, depositor))
+            } else {
+                let donor = donors.get_unchecked(position - depositors.len());
+                (donor.clone(), DataKey::Donation(round_id, donor))
+            };
+
+            // Remove the ledger entry before paying out so it can't be refunded twice
+            let ledger = Self::read_persistent::<Map<Address, i128>>(&env, &ledger_key)
+                .unwrap_or(Map::new(&env));
+            env.storage().persistent().remove(&ledger_key);
+            for (token, amount) in ledger.iter() {
+                Self::refund_escrow(&env, round_id, &token, &funder, amount);
+            }
+        }
+        Self::write_persistent(&env, &cursor_key, &end);
+
+        // Return the rest of the escrowed funds once every funder has been refunded
+        let refund_to = Self::read_persistent::<Address>(&env, &DataKey::RefundTo(round_id));
+        if let Some(refund_to) = refund_to.filter(|_| end == funder_count) {
+            let tokens =
+                Self::read_persistent::<Vec<Address>>(&env, &DataKey::EscrowTokens(round_id))
+                    .unwrap_or(Vec::new(&env));
+            for token in tokens.iter() {
+                let escrow_key = DataKey::Escrow(round_id, token.clone());
+                let escrow = Self::read_persistent::<i128>(&env, &escrow_key).unwrap_or(0);
+                if escrow > 0 {
+                    Self::refund_escrow(&env, round_id, &token, &refund_to, escrow);
+                }
+            }
+        }
+
+        Ok(end - cursor)
+    }
+
+    // Helper function to return part of a round's escrow in a token to an address
+    fn refund_escrow(env: &Env, round_id: u64, token: &Address, to: &Address, amount: i128) {
+        TokenClient::new(env, token).transfer(&env.current_contract_address(), to, &amount);
+
+        let escrow_key = DataKey::Escrow(round_id, token.clone());
+        let escrow = Self::read_persistent::<i128>(env, &escrow_key).unwrap_or(0);
+        Self::write_escrow(env, round_id, token, escrow - amount);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("REFUND"), round_id, to.clone()),
+            (token.clone(), amount),
+        );
+    }
+
+    // Function for the admin to correct a funded submission's allocation after voting has
+    // closed and before anything has been paid out, e.g. to disqualify a project. The
+    // allocations may not add up to more than the distributable funding afterwards.
+    pub fn override_allocation(
+        env: Env,
+        round_id: u64,
+        submission_id: u64,
+        new_amount: i128,
+        reason: Symbol,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        let admin = Self::require_admin(&env)?;
+        Self::require_no_council(&env)?;
+        Self::require_not_timelocked(&env, TimelockedOp::Override)?;
+
+        Self::apply_override(env, &admin, round_id, submission_id, new_amount, reason)
+    }
+
+    // Helper function to override a funded submission's allocation, for override_allocation or
+    // an approved council proposal
+    fn apply_override(
+        env: Env,
+        actor: &Address,
+        round_id: u64,
+        submission_id: u64,
+        new_amount: i128,
+        reason: Symbol,
+    ) -> Result<(), ContractError> {
+        let mut round = Self::get_round(env.clone(), round_id)?;
+
+        // Allocations are only computed once voting has been closed
+        if round.is_active() {
+            return Err(ContractError::AllocationsNotComputed);
+        }
+
+        match round.phase {
+            RoundPhase::Closed => {}
+            RoundPhase::Disbursed => return Err(ContractError::FundsAlreadyDisbursed),
+            _ => return Err(ContractError::InvalidPhase),
+        }
+
+        // Nothing may have gone out in any of the round's tokens, fee included
+        if Self::payouts_started(&env, round_id) {
+            return Err(ContractError::FundsAlreadyDisbursed);
+        }
+
+        let submission = Self::get_submission(env.clone(), submission_id)?;
+        if submission.round_id != round_id {
+            return Err(ContractError::SubmissionNotInRound);
+        }
+
+        // Only a funded submission has an allocation to correct, and never to below nothing
+        let votes = Self::funded_votes_of(&round, &submission);
+        if votes == 0 || new_amount < 0 {
+            return Err(ContractError::InvalidAmount);
+        }
+
+        let old_amount = Self::allocation_for(&round, submission_id, votes);
+        round.overrides.set(submission_id, new_amount);
+
+        // The tallies hold every approved submission's votes, so the allocations can be
+        // totalled without loading the submissions
+        let mut total: i128 = 0;
+        for (id, tally) in round.tallies.iter() {
+            let funded = if round.is_funded(id, tally) { tally } else { 0 };
+            total = total
+                .checked_add(Self::allocation_for(&round, id, funded))
+                .ok_or(ContractError::InvalidAmount)?;
+        }
+        if total > round.distributable {
+            return Err(ContractError::InvalidAmount);
+        }
+
+        Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+
+        let payload = Vec::from_array(&env, [submission_id.into(), old_amount, new_amount]);
+        Self::record_audit(&env, symbol_short!("override"), actor, round_id, payload);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("OVERRIDE"), round_id, submission_id),
+            (old_amount, new_amount, reason),
+        );
+
+        Ok(())
+    }
+
+    // Helper function to check whether anything of a round has gone out in any of its tokens,
+    // fee included
+    fn payouts_started(env: &Env, round_id: u64) -> bool {
+        Self::read_persistent::<Vec<Address>>(env, &DataKey::EscrowTokens(round_id))
+            .unwrap_or(Vec::new(env))
+            .iter()
+            .any(|token| Self::has_persistent(env, &DataKey::Disbursed(round_id, token)))
+    }
+
+    // Helper function to check that a closed round's challenge window has elapsed
+    fn require_challenge_elapsed(env: &Env, round: &Round) -> Result<(), ContractError> {
+        if env.ledger().timestamp() < round.challenge_ends {
+            return Err(ContractError::ChallengePeriodActive);
+        }
+
+        Ok(())
+    }
+
+    // Function for the admin to extend a closed round's challenge window once, to the given
+    // time. The window can't be shortened, and can only be extended while it is running.
+    pub fn extend_challenge_period(
+        env: Env,
+        round_id: u64,
+        challenge_ends: u64,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        let mut round = Self::get_round(env.clone(), round_id)?;
+
+        // The window only opens when voting closes
+        if round.is_active() {
+            return Err(ContractError::AllocationsNotComputed);
+        }
+
+        if round.phase != RoundPhase::Closed
+            || round.challenge_extended
+            || env.ledger().timestamp() >= round.challenge_ends
+        {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        if challenge_ends <= round.challenge_ends {
+            return Err(ContractError::InvalidDeadline);
+        }
+
+        let previous = round.challenge_ends;
+        round.challenge_ends = challenge_ends;
+        round.challenge_extended = true;
+        Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("CHAL_EXT"), round_id),
+            (previous, challenge_ends),
+        );
+
+        Ok(())
+    }
+
+    // Function to get when a closed round's payouts open, once its challenge window elapses
+    pub fn get_disbursement_unlock_time(env: Env, round_id: u64) -> Result<u64, ContractError> {
+        let round = Self::get_round(env.clone(), round_id)?;
+
+        if !matches!(round.phase, RoundPhase::Closed | RoundPhase::Disbursed) {
+            return Err(ContractError::AllocationsNotComputed);
+        }
+
+        Ok(round.challenge_ends)
+    }
+
+    // Function to freeze a closed round's payouts, both pushed by disburse_funds and pulled by
+    // claims, e.g. when a payout address turns out to be compromised mid-run. Payouts already
+    // transferred are untouched, and submitters may correct their payout table while halted.
+    pub fn halt_disbursement(env: Env, round_id: u64) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        let mut round = Self::get_round(env.clone(), round_id)?;
+
+        // Only a closed round has payouts to halt
+        if round.is_active() {
+            return Err(ContractError::AllocationsNotComputed);
+        }
+
+        if !matches!(round.phase, RoundPhase::Closed | RoundPhase::Disbursed) {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        if round.halted {
+            return Err(ContractError::DisbursementHalted);
+        }
+
+        round.halted = true;
+        Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("HALTED"), round_id), round_id);
+
+        Ok(())
+    }
+
+    // Function to let a halted round's payouts carry on where they stopped
+    pub fn resume_disbursement(env: Env, round_id: u64) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        let mut round = Self::get_round(env.clone(), round_id)?;
+
+        if !round.halted {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        round.halted = false;
+        Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("RESUMED"), round_id), round_id);
+
+        Ok(())
+    }
+
+    // Function to disburse funds to submissions in batches. Pays the approved submissions at
+    // positions `start..start + count` of the round's submission list in the given token,
+    // skipping any already paid; the token is finalized once every vote's allocation has been
+    // paid in it, and the round is marked disbursed once its funding token is
+    pub fn disburse_funds(
+        env: Env,
+        caller: Address,
+        round_id: u64,
+        token_address: Address,
+        start: u32,
+        count: u32,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the admin, a treasurer or the round's operator
+        let action = symbol_short!("disburse");
+        Self::require_round_role(&env, &caller, round_id, Some(Role::Treasurer), action)?;
+        Self::require_no_council(&env)?;
+
+        Self::disburse(env, &caller, round_id, token_address, start, count)
+    }
+
+    // Helper function to disburse a batch of a round's allocations, for disburse_funds or an
+    // approved council proposal
+    fn disburse(
+        env: Env,
+        actor: &Address,
+        round_id: u64,
+        token_address: Address,
+        start: u32,
+        count: u32,
+    ) -> Result<(), ContractError> {
+        Self::require_not_paused(&env)?;
+
+        let mut round = Self::get_round(env.clone(), round_id)?;
+
+        // Allocations are only computed once voting has been closed
+        if round.is_active() {
+            return Err(ContractError::AllocationsNotComputed);
+        }
+
+        if !matches!(round.phase, RoundPhase::Closed | RoundPhase::Disbursed) {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        if round.halted {
+            return Err(ContractError::DisbursementHalted);
+        }
+
+        Self::require_challenge_elapsed(&env, &round)?;
+
+        if Self::token_finalized(&env, round_id, &token_address) {
+            return Err(ContractError::FundsAlreadyDisbursed);
+        }
+
+        // The token's escrow must still cover everything not yet paid out of its pool
+        let pool = Self::token_pool(&env, &round, &token_address)?;
+        let disbursed = Self::read_persistent::<i128>(
+            &env,
+            &DataKey::Disbursed(round_id, token_address.clone()),
+        )
+        .unwrap_or(0);
+        let escrow = Self::check_escrow(&env, round_id, &token_address, pool - disbursed)?;
+
+        // Initialize token client
+        let token_client = TokenClient::new(&env, &token_address);
+
+        // Pay the protocol fee with the first batch
+        let mut batch_disbursed = Self::pay_fee(&env, &round, &token_client, pool)?;
+        let mut batch_votes = 0u64;
+
+        // Disburse funds to each approved submission's payout address
+        for submission_id in round
+            .submissions
+            .iter()
+            .skip(start as usize)
+            .take(count as usize)
+        {
+            let submission = Self::get_submission(env.clone(), submission_id)?;
+            let votes = Self::funded_votes_of(&round, &submission);
+            if votes == 0 {
+                continue;
+            }
+
+            // Never pay a submission twice across overlapping batches
+            if Self::has_persistent(
+                &env,
+                &DataKey::Claimed(submission_id, token_address.clone()),
+            ) {
+                continue;
+            }
+
+            // Hold back payouts to blacklisted addresses without stalling the others
+            if Self::payout_blocked(&env, &submission) {
+                continue;
+            }
+
+            // Only the released tranches are paid; a submission's votes count as paid for
+            // once its whole allocation has been
+            let allocation =
+                Self::token_share(&round, pool, Self::allocation_of(&round, &submission))?;
+            let (amount, complete) =
+                Self::pay_unlocked(&env, &round, &token_client, &submission, allocation)?;
+            batch_disbursed += amount;
+            if complete {
+                batch_votes += votes;
+            }
+        }
+
+        // Release the disbursed amount from the round's escrow
+        Self::write_escrow(&env, round_id, &token_address, escrow - batch_disbursed);
+        let payload = Vec::from_array(&env, [start.into(), count.into(), batch_disbursed]);
+        Self::record_audit(&env, symbol_short!("disburse"), actor, round_id, payload);
+
+        // Finalize the token once every allocation has been paid in it
+        if let Some(remainder) = Self::record_payout(
+            &env,
+            &round,
+            &token_address,
+            pool,
+            batch_disbursed,
+            batch_votes,
+        ) {
+            if Self::is_funding_token(&env, round_id, &token_address) {
+                Self::set_phase(&env, &mut round, RoundPhase::Disbursed);
+                Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+            }
+
+            // Emit event
+            env.events().publish(
+                (symbol_short!("FUND_DISB"), round_id),
+                FundsDisbursedEvent {
+                    round_id,
+                    token: token_address,
+                    pool,
+                    disbursed: pool - remainder,
+                    remainder,
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    // Helper function to get the token a round's funding amount is denominated in: the first
+    // token escrowed for it
+    fn funding_token(env: &Env, round_id: u64) -> Option<Address> {
+        Self::read_persistent::<Vec<Address>>(env, &DataKey::EscrowTokens(round_id))
+            .and_then(|tokens| tokens.get(0))
+    }
+
+    // Helper function to check whether a token is a round's funding token, which any token is
+    // while nothing has been escrowed yet
+    fn is_funding_token(env: &Env, round_id: u64, token: &Address) -> bool {
+        Self::funding_token(env, round_id).is_none_or(|funding_token| funding_token == *token)
+    }
+
+    // Helper function to get the pool a closed round shares out in a token: the funding amount
+    // for its funding token, otherwise the token's escrow when voting closed
+    fn token_pool(env: &Env, round: &Round, token: &Address) -> Result<i128, ContractError> {
+        if Self::is_funding_token(env, round.id, token) {
+            return Ok(round.funding_amount);
+        }
+
+        Self::read_persistent::<i128>(env, &DataKey::TokenPool(round.id, token.clone()))
+            .filter(|pool| *pool > 0)
+            .ok_or(ContractError::InsufficientFunds)
+    }
+
+    // Helper function to scale an amount of a round's funding to the same share of another
+    // token's pool, widening to u128. Amounts of realistic tokens are far too small for the
+    // product to overflow, but should two huge amounts meet it fails with MathOverflow.
+    fn token_share(round: &Round, pool: i128, amount: i128) -> Result<i128, ContractError> {
+        if pool == round.funding_amount {
+            return Ok(amount);
+        }
+
+        let product = (amount as u128)
+            .checked_mul(pool as u128)
+            .ok_or(ContractError::MathOverflow)?;
+        Ok(product
+            .checked_div(round.funding_amount as u128)
+            .unwrap_or(0) as i128)
+    }
+
+    // Helper function to check whether a token's payouts have been completed or committed to
+    // claims, after which it can't be disbursed again
+    fn token_finalized(env: &Env, round_id: u64, token: &Address) -> bool {
+        Self::has_persistent(env, &DataKey::Remainder(round_id, token.clone()))
+            || Self::has_persistent(env, &DataKey::ClaimDeadline(round_id, token.clone()))
+    }
+
+    // Helper function to transfer a closed round's protocol fee to the fee recipient, as the
+    // same share of the token's pool, unless it has already been paid in the token. Returns
+    // the amount paid.
+    fn pay_fee(
+        env: &Env,
+        round: &Round,
+        token_client: &TokenClient,
+        pool: i128,
+    ) -> Result<i128, ContractError> {
+        let Some((recipient, amount)) =
+            Self::read_persistent::<(Address, i128)>(env, &DataKey::FeeDue(round.id))
+        else {
+            return Ok(0);
+        };
+
+        // Anything paid out in the token means its first batch, and so the fee, is done
+        if Self::has_persistent(
+            env,
+            &DataKey::Disbursed(round.id, token_client.address.clone()),
+        ) {
+            return Ok(0);
+        }
+
+        let amount = Self::token_share(round, pool, amount)?;
+        if amount > 0 {
+            token_client.transfer(&env.current_contract_address(), &recipient, &amount);
+        }
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("FEE"), round.id, recipient),
+            (token_client.address.clone(), amount),
+        );
+
+        Ok(amount)
+    }
+
+    // Helper function to calculate a submission's share of a closed round's distributable
+    // funding
+    fn allocation_of(round: &Round, submission: &Submission) -> i128 {
+        Self::allocation_for(
+            round,
+            submission.id,
+            Self::funded_votes_of(round, submission),
+        )
+    }
+
+    // Helper function to calculate the allocation of a submission funded for the given votes:
+    // the admin's override if there is one, otherwise its share by the allocation math
+    fn allocation_for(round: &Round, submission_id: u64, votes: u64) -> i128 {
+        if votes == 0 {
+            return 0;
+        }
+        if let Some(amount) = round.overrides.get(submission_id) {
+            return amount;
+        }
+
+        round
+            .allocation_of(submission_id)
+            .share(&round.allocation_config(), votes)
+    }
+
+    // Helper function to get the votes a submission is funded for in a closed round: its
+    // tally if it is approved, was counted at close and made the cutoff, otherwise 0. A
+    // submission reinstated on appeal after payouts started is compensated instead.
+    fn funded_votes_of(round: &Round, submission: &Submission) -> u64 {
+        if submission.status != SubmissionStatus::Approved
+            || submission.withdrawn
+            || !round.tallies.contains_key(submission.id)
+            || !round.is_funded(submission.id, submission.total_votes)
+        {
+            return 0;
+        }
+
+        submission.total_votes
+    }
+
+    // Helper function to add a payout in a token to the round's paid totals for it. Once every
+    // vote has been paid for, whatever is left of the token's pool is recorded as its rounding
+    // remainder and returned.
+    fn record_payout(
+        env: &Env,
+        round: &Round,
+        token: &Address,
+        pool: i128,
+        amount: i128,
+        votes: u64,
+    ) -> Option<i128> {
+        let disbursed_key = DataKey::Disbursed(round.id, token.clone());
+        let disbursed = Self::read_persistent::<i128>(env, &disbursed_key).unwrap_or(0) + amount;
+        Self::write_persistent(env, &disbursed_key, &disbursed);
+
+        let votes_paid_key = DataKey::VotesPaid(round.id, token.clone());
+        let votes_paid = Self::read_persistent::<u64>(env, &votes_paid_key).unwrap_or(0) + votes;
+        Self::write_persistent(env, &votes_paid_key, &votes_paid);
+
+        if votes_paid != round.funded_votes {
+            return None;
+        }
+
+        let remainder = pool - disbursed;
+        Self::write_persistent(
+            env,
+            &DataKey::Remainder(round.id, token.clone()),
+            &remainder,
+        );
+        Some(remainder)
+    }
+
+    // Function to lock a closed round's allocations in a token so each submitter can claim
+    // their own payout in it until the claim deadline. Finalizing the funding token marks the
+    // round disbursed.
+    pub fn finalize_disbursement(
+        env: Env,
+        round_id: u64,
+        token: Address,
+        claim_deadline: u64,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        if claim_deadline <= env.ledger().timestamp() {
+            return Err(ContractError::InvalidDeadline);
+        }
+
+        let mut round = Self::get_round(env.clone(), round_id)?;
+
+        // Allocations are only computed once voting has been closed
+        if round.is_active() {
+            return Err(ContractError::AllocationsNotComputed);
+        }
+
+        if !matches!(round.phase, RoundPhase::Closed | RoundPhase::Disbursed) {
+            return Err(ContractError::InvalidPhase);
+        }
+
+        if round.halted {
+            return Err(ContractError::DisbursementHalted);
+        }
+
+        Self::require_challenge_elapsed(&env, &round)?;
+
+        if Self::token_finalized(&env, round_id, &token) {
+            return Err(ContractError::FundsAlreadyDisbursed);
+        }
+
+        // Batched disbursement may already have paid part of the allocations
+        let pool = Self::token_pool(&env, &round, &token)?;
+        let disbursed =
+            Self::read_persistent::<i128>(&env, &DataKey::Disbursed(round_id, token.clone()))
+                .unwrap_or(0);
+        let escrow = Self::check_escrow(&env, round_id, &token, pool - disbursed)?;
+
+        // Pay the protocol fee if batched disbursement has not already
+        let fee_paid = Self::pay_fee(&env, &round, &TokenClient::new(&env, &token), pool)?;
+        Self::write_escrow(&env, round_id, &token, escrow - fee_paid);
+        Self::record_payout(&env, &round, &token, pool, fee_paid, 0);
+
+        Self::write_persistent(
+            &env,
+            &DataKey::ClaimDeadline(round_id, token.clone()),
+            &claim_deadline,
+        );
+
+        // The allocations are committed to claims, so the round can't be disbursed again
+        if Self::is_funding_token(&env, round_id, &token) {
+            Self::set_phase(&env, &mut round, RoundPhase::Disbursed);
+            Self::write_persistent(&env, &DataKey::Round(round_id), &round);
+        }
+
+        // Emit event
+        env.events()
+            .publish((symbol_short!("FUND_FIN"), round_id), token);
+
+        Ok(())
+    }
+
+    // Function for a submitter to claim their submission's allocation in a token finalized
+    // for its round
+    pub fn claim(env: Env, submission_id: u64, token: Address) -> Result<i128, ContractError> {
+        let submission = Self::get_submission(env.clone(), submission_id)?;
+
+        // Require authorization from the submitter
+        submission.submitter.require_auth();
+        Self::extend_instance(&env);
+
+        let round_id = submission.round_id;
+        if !Self::has_persistent(&env, &DataKey::ClaimDeadline(round_id, token.clone())) {
+            return Err(ContractError::FundsNotDisbursed);
+        }
+
+        if Self::has_persistent(&env, &DataKey::Claimed(submission_id, token.clone())) {
+            return Err(ContractError::AlreadyClaimed);
+        }
+
+        if Self::claim_window_closed(&env, round_id, &token) {
+            return Err(ContractError::ClaimExpired);
+        }
+
+        if Self::payout_blocked(&env, &submission) {
+            return Err(ContractError::AddressBlocked);
+        }
+
+        let round = Self::get_round(env.clone(), round_id)?;
+        if round.halted {
+            return Err(ContractError::DisbursementHalted);
+        }
 
-// Define the main contract structure
-#[contract]
-pub struct RetroPGFContract;
+        Self::require_challenge_elapsed(&env, &round)?;
 
-#[contractimpl]
-impl RetroPGFContract {
-    pub fn initialize(env: Env, admin: Address) {
-        let admin_key = symbol_short!("ADMIN");
-        env.storage().instance().set(&admin_key, &admin);
-    }
+        // Only the part of the allocation released so far can be claimed
+        let pool = Self::token_pool(&env, &round, &token)?;
+        let allocation = Self::token_share(&round, pool, Self::allocation_of(&round, &submission))?;
+        let token_client = TokenClient::new(&env, &token);
+        let (amount, complete) =
+            Self::pay_unlocked(&env, &round, &token_client, &submission, allocation)?;
+        if amount == 0 {
+            return Err(ContractError::InvalidAmount);
+        }
+
+        // Release the claimed amount from the round's escrow
+        let escrow_key = DataKey::Escrow(round_id, token.clone());
+        let escrow = Self::read_persistent::<i128>(&env, &escrow_key).unwrap_or(0);
+        Self::write_escrow(&env, round_id, &token, escrow - amount);
+        let votes = if complete {
+            Self::funded_votes_of(&round, &submission)
+        } else {
+            0
+        };
+        Self::record_payout(&env, &round, &token, pool, amount, votes);
 
-    // Each voter has a fixed number of votes to allocate
-    const VOTE_CREDITS: u64 = 20;
+        // Emit event
+        env.events().publish(
+            (symbol_short!("CLAIM"), round_id, submission_id),
+            (submission.payout_address, token, amount),
+        );
 
-    pub fn set_voter(env: Env, voter: Address) {
-        let voter_key = symbol_short!("VOTER");
-        env.storage().instance().set(&voter_key, &voter);
+        Ok(amount)
     }
 
-    // Function to create a new round
-    pub fn create_round(env: Env, funding_amount: u64, deadline: u64) -> Result<u64, ContractError> {
-        let admin_key = symbol_short!("ADMIN");
-        let admin = env
-            .storage()
-            .instance()
-            .get::<Symbol, Address>(&admin_key)
-            .ok_or(ContractError::Unauthorized)?;
+    // Function to get the amount a submission can still claim in a token, or 0 if there is
+    // nothing to claim
+    pub fn get_claimable(env: Env, submission_id: u64, token: Address) -> i128 {
+        let Ok(submission) = Self::get_submission(env.clone(), submission_id) else {
+            return 0;
+        };
+
+        let round_id = submission.round_id;
+        if !Self::has_persistent(&env, &DataKey::ClaimDeadline(round_id, token.clone()))
+            || Self::has_persistent(&env, &DataKey::Claimed(submission_id, token.clone()))
+            || Self::claim_window_closed(&env, round_id, &token)
+        {
+            return 0;
+        }
+
+        let Ok(round) = Self::get_round(env.clone(), round_id) else {
+            return 0;
+        };
+        let Ok(pool) = Self::token_pool(&env, &round, &token) else {
+            return 0;
+        };
+        let Ok(allocation) =
+            Self::token_share(&round, pool, Self::allocation_of(&round, &submission))
+        else {
+            return 0;
+        };
+        let paid =
+            Self::read_persistent::<i128>(&env, &DataKey::Paid(submission_id, token)).unwrap_or(0);
+        Self::unlocked_share(&env, &round, allocation) - paid
+    }
 
+    // Function to recover the allocations left unclaimed in a token after its claim deadline.
+    // Returns the amount recovered.
+    pub fn sweep_unclaimed(
+        env: Env,
+        round_id: u64,
+        token: Address,
+        to: Address,
+    ) -> Result<i128, ContractError> {
         // Require authorization from the admin
-        admin.require_auth();
+        Self::require_admin(&env)?;
+        Self::require_no_council(&env)?;
 
-        // Generate a new round ID
-        let next_round_id_key = symbol_short!("NEXT_RND");
-        let mut round_id = env
-            .storage()
-            .instance()
-            .get::<Symbol, u64>(&next_round_id_key)
-            .unwrap_or(0);
+        Self::sweep(env, round_id, token, to)
+    }
 
-        round_id += 1;
-        env.storage()
-            .instance()
-            .set(&next_round_id_key, &round_id);
+    // Helper function to recover a token's unclaimed allocations, for sweep_unclaimed or an
+    // approved council proposal
+    fn sweep(env: Env, round_id: u64, token: Address, to: Address) -> Result<i128, ContractError> {
+        if !Self::has_persistent(&env, &DataKey::ClaimDeadline(round_id, token.clone())) {
+            return Err(ContractError::FundsNotDisbursed);
+        }
 
-        // Create a new round
-        let round = Round {
-            id: round_id,
-            funding_amount,
-            deadline,
-            is_active: true,
-            submissions: Vec::new(&env),
-            funds_disbursed: false,
-        };
+        if !Self::claim_window_closed(&env, round_id, &token) {
+            return Err(ContractError::ClaimWindowOpen);
+        }
 
-        // Store the round
-        env.storage()
-            .persistent()
-            .set(&Self::round_key(round_id), &round);
+        // Everything not yet paid out of the token's pool is recovered, including the
+        // rounding remainder
+        let round = Self::get_round(env.clone(), round_id)?;
+        let pool = Self::token_pool(&env, &round, &token)?;
+        let disbursed_key = DataKey::Disbursed(round_id, token.clone());
+        let disbursed = Self::read_persistent::<i128>(&env, &disbursed_key).unwrap_or(0);
+        let recovered = pool - disbursed;
+
+        if recovered > 0 {
+            TokenClient::new(&env, &token).transfer(
+                &env.current_contract_address(),
+                &to,
+                &recovered,
+            );
+
+            // Release the recovered amount from the round's escrow
+            let escrow_key = DataKey::Escrow(round_id, token.clone());
+            let escrow = Self::read_persistent::<i128>(&env, &escrow_key).unwrap_or(0);
+            Self::write_escrow(&env, round_id, &token, escrow - recovered);
+            Self::write_persistent(&env, &disbursed_key, &(disbursed + recovered));
+            Self::write_persistent(&env, &DataKey::Remainder(round_id, token.clone()), &0i128);
+        }
 
         // Emit event
         env.events()
-            .publish((symbol_short!("RND_CREAT"), round_id), round_id);
+            .publish((symbol_short!("SWEEP"), round_id, to), (token, recovered));
 
-        Ok(round_id)
+        Ok(recovered)
     }
 
-    // Helper function to get a round
-    fn get_round(env: Env, round_id: u64) -> Result<Round, ContractError> {
-        env.storage()
-            .persistent()
-            .get::<(Symbol, u64), Round>(&Self::round_key(round_id))
-            .ok_or(ContractError::RoundNotFound)
+    // Helper function to check whether the claim deadline of a token finalized for a round
+    // has passed
+    fn claim_window_closed(env: &Env, round_id: u64, token: &Address) -> bool {
+        Self::read_persistent::<u64>(env, &DataKey::ClaimDeadline(round_id, token.clone()))
+            .is_some_and(|deadline| env.ledger().timestamp() > deadline)
     }
 
-    // Helper function to generate storage key for rounds
-    fn round_key(round_id: u64) -> (Symbol, u64) {
-        (symbol_short!("ROUND"), round_id)
+    // Helper function to check that both the round's escrow and the contract's balance in
+    // the token cover the required amount. Returns the round's escrow.
+    fn check_escrow(
+        env: &Env,
+        round_id: u64,
+        token: &Address,
+        required: i128,
+    ) -> Result<i128, ContractError> {
+        let escrow = Self::read_persistent::<i128>(env, &DataKey::Escrow(round_id, token.clone()))
+            .unwrap_or(0);
+
+        if escrow < required {
+            return Err(ContractError::InsufficientFunds);
+        }
+
+        let contract_balance =
+            TokenClient::new(env, token).balance(&env.current_contract_address());
+        if contract_balance < required {
+            return Err(ContractError::InsufficientFunds);
+        }
+
+        Ok(escrow)
     }
 
-    // Function to submit a project to a round
-    pub fn submit_project(
+    // Function to release the next milestone tranche of a closed round, unlocking its share of
+    // every funded submission's allocation for disburse_funds and claim. The first tranche is
+    // released up front, so tranches are released from index 1, one at a time and in order.
+    pub fn release_tranche(
         env: Env,
         round_id: u64,
-    ) -> Result<u64, ContractError> {
-        // Check if the round exists and is active
-        let mut round = Self::get_round(env.clone(), round_id)?;
+        tranche_index: u32,
+    ) -> Result<(), ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
 
-        if !round.is_active {
-            return Err(ContractError::RoundNotActive);
-        }
+        let round = Self::get_round(env.clone(), round_id)?;
 
-        // Check if the submission deadline has not passed
-        let current_timestamp = env.ledger().timestamp();
-        if current_timestamp > round.deadline {
-            return Err(ContractError::SubmissionDeadlinePassed);
+        // Allocations are only computed once voting has been closed
+        if round.is_active() {
+            return Err(ContractError::AllocationsNotComputed);
         }
 
-        // Generate a new submission ID
-        let next_submission_id_key = symbol_short!("NEXT_SUB");
-        let mut submission_id = env
-            .storage()
-            .instance()
-            .get::<Symbol, u64>(&next_submission_id_key)
-            .unwrap_or(0);
-
-        submission_id += 1;
-        env.storage()
-            .instance()
-            .set(&next_submission_id_key, &submission_id);
+        if !matches!(round.phase, RoundPhase::Closed | RoundPhase::Disbursed) {
+            return Err(ContractError::InvalidPhase);
+        }
 
-        let voter_key = symbol_short!("VOTER");
-        // Create a new submission
-        let submission = Submission {
-            id: submission_id,
-            round_id,            
-            submitter: env
-                .storage()
-                .instance()
-                .get::<Symbol, Address>(&voter_key)
-                .unwrap(),
-            total_votes: 0,
-        };
+        if tranche_index >= round.config.tranches.len() {
+            return Err(ContractError::InvalidAmount);
+        }
 
-        // Store the submission
-        env.storage()
-            .persistent()
-            .set(&Self::submission_key(submission_id), &submission);
+        // Only the tranche after the last one released can be released
+        if tranche_index != Self::tranches_released(&env, round_id) {
+            return Err(ContractError::InvalidPhase);
+        }
 
-        // Add submission ID to the round
-        round.submissions.push_back(submission_id);
-        env.storage()
-            .persistent()
-            .set(&Self::round_key(round_id), &round);
+        Self::write_persistent(
+            &env,
+            &DataKey::TranchesReleased(round_id),
+            &(tranche_index + 1),
+        );
 
         // Emit event
         env.events()
-            .publish((symbol_short!("PROJ_SUB"), submission_id), submission_id);
+            .publish((symbol_short!("TRANCHE"), round_id), tranche_index);
 
-        Ok(submission_id)
+        Ok(())
     }
 
-    // Helper function to get a submission
-    fn get_submission(env: Env, submission_id: u64) -> Result<Submission, ContractError> {
-        env.storage()
-            .persistent()
-            .get::<(Symbol, u64), Submission>(&Self::submission_key(submission_id))
-            .ok_or(ContractError::SubmissionNotFound)
+    // Helper function to get how many of a round's milestone tranches have been released
+    fn tranches_released(env: &Env, round_id: u64) -> u32 {
+        Self::read_persistent::<u32>(env, &DataKey::TranchesReleased(round_id)).unwrap_or(1)
     }
 
-    // Helper function to generate storage key for submissions
-    fn submission_key(submission_id: u64) -> (Symbol, u64) {
-        (symbol_short!("SUBMISSN"), submission_id)
+    // Helper function to get the part of an allocation unlocked by a round's released
+    // milestone tranches; all of it once every tranche is released or if the round has none
+    fn unlocked_share(env: &Env, round: &Round, allocation: i128) -> i128 {
+        let tranches = &round.config.tranches;
+        let released = Self::tranches_released(env, round.id);
+        if released >= tranches.len() {
+            return allocation;
+        }
+
+        let bps: u32 = tranches.iter().take(released as usize).sum();
+        allocation::mul_div(allocation, bps as u64, Self::TOTAL_BPS as u64)
     }
 
-    // Function for voters to allocate votes to submissions
-    pub fn allocate_votes(
-        env: Env,
-        round_id: u64,
-        allocations: Map<u64, u64>,
-    ) -> Result<(), ContractError> {
-        let voter_key = symbol_short!("VOTER");
-        let voter = env
-            .storage()
-            .instance()
-            .get::<Symbol, Address>(&voter_key)
-            .unwrap();
+    // Helper function to pay the part of a submission's allocation in a token that has been
+    // unlocked but not yet paid, recording the payment first. Returns the amount paid and
+    // whether the whole allocation has now been paid.
+    fn pay_unlocked(
+        env: &Env,
+        round: &Round,
+        token_client: &TokenClient,
+        submission: &Submission,
+        allocation: i128,
+    ) -> Result<(i128, bool), ContractError> {
+        let token = token_client.address.clone();
+        let paid_key = DataKey::Paid(submission.id, token.clone());
+        let paid = Self::read_persistent::<i128>(env, &paid_key).unwrap_or(0);
+        let amount = Self::unlocked_share(env, round, allocation) - paid;
+        let complete = paid + amount == allocation;
 
-        // Calculate total votes allocated
-        let mut total_votes_allocated: u64 = 0;
-        for vote in allocations.values().iter() {
-            total_votes_allocated += vote;
+        if complete {
+            Self::write_persistent(env, &DataKey::Claimed(submission.id, token), &true);
+        } else if amount > 0 {
+            Self::write_persistent(env, &paid_key, &(paid + amount));
         }
 
-        if total_votes_allocated > Self::VOTE_CREDITS {
-            return Err(ContractError::ExceededVoteLimit);
+        if amount > 0 {
+            Self::pay_or_vest(env, round, token_client, submission, amount)?;
         }
 
-        // Store voter allocations
-        env.storage().persistent().set(
-            &Self::voter_allocation_key(round_id, &voter),
-            &allocations,
-        );
+        Ok((amount, complete))
+    }
 
-        // Update total votes for each submission
-        for (submission_id, votes) in allocations.iter() {
-            let mut submission = Self::get_submission(env.clone(), submission_id)?;
-            submission.total_votes += votes;
-            env.storage()
-                .persistent()
-                .set(&Self::submission_key(submission_id), &submission);
+    // Helper function to pay a submission's allocation at once, or to start it vesting if
+    // the round has a vesting schedule
+    fn pay_or_vest(
+        env: &Env,
+        round: &Round,
+        token_client: &TokenClient,
+        submission: &Submission,
+        amount: i128,
+    ) -> Result<(), ContractError> {
+        if round.config.duration_seconds == 0 {
+            return Self::pay_submission(env, token_client, submission, amount);
         }
 
+        // The tokens stay in the contract until they are claimed with claim_vested. Later
+        // milestone tranches join the position on its original schedule.
+        let token = token_client.address.clone();
+        let vesting_key = DataKey::Vesting(submission.id, token.clone());
+        let mut position = Self::read_persistent::<VestingPosition>(env, &vesting_key).unwrap_or(
+            VestingPosition {
+                total: 0,
+                claimed: 0,
+                start: env.ledger().timestamp(),
+            },
+        );
+        position.total += amount;
+        Self::write_persistent(env, &vesting_key, &position);
+        Self::adjust_obligations(env, &token, amount);
+
         // Emit event
-        env.events()
-            .publish((symbol_short!("VOTE_ALC"), voter.clone()), voter.clone());
+        env.events().publish(
+            (symbol_short!("VEST"), submission.round_id, submission.id),
+            (token, amount),
+        );
 
         Ok(())
     }
 
-    // Helper function to generate storage key for voter allocations
-    fn voter_allocation_key(round_id: u64, voter: &Address) -> (Symbol, u64, Address) {
-        (symbol_short!("VOTR_ALC"), round_id, voter.clone())
+    // Function for a submitter to claim the portion of their submission's vesting payouts
+    // vested since their last claim. Returns the amount transferred in each token.
+    pub fn claim_vested(env: Env, submission_id: u64) -> Result<Map<Address, i128>, ContractError> {
+        let submission = Self::get_submission(env.clone(), submission_id)?;
+
+        // Require authorization from the submitter
+        submission.submitter.require_auth();
+        Self::extend_instance(&env);
+
+        let round = Self::get_round(env.clone(), submission.round_id)?;
+        if round.halted {
+            return Err(ContractError::DisbursementHalted);
+        }
+
+        if Self::payout_blocked(&env, &submission) {
+            return Err(ContractError::AddressBlocked);
+        }
+
+        let positions = Self::vesting_positions(&env, &submission);
+        if positions.is_empty() {
+            return Err(ContractError::FundsNotDisbursed);
+        }
+
+        let now = env.ledger().timestamp();
+        let mut transferred = Map::new(&env);
+        for (token, mut position) in positions.iter() {
+            let amount = Self::vested_amount(&round, &position, now) - position.claimed;
+            if amount > 0 {
+                // Record the claim before paying out
+                position.claimed += amount;
+                Self::write_persistent(
+                    &env,
+                    &DataKey::Vesting(submission_id, token.clone()),
+                    &position,
+                );
+                Self::pay_submission(&env, &TokenClient::new(&env, &token), &submission, amount)?;
+                Self::adjust_obligations(&env, &token, -amount);
+
+                // Emit event
+                env.events().publish(
+                    (
+                        symbol_short!("VEST_CLM"),
+                        submission.round_id,
+                        submission_id,
+                    ),
+                    (token.clone(), amount),
+                );
+            }
+            transferred.set(token, amount);
+        }
+
+        Ok(transferred)
     }
 
-    // Function to close voting and calculate funding allocations
-    pub fn close_voting(env: Env, round_id: u64) -> Result<(), ContractError> {
-        let admin_key = symbol_short!("ADMIN");
-        let admin = env
-            .storage()
-            .instance()
-            .get::<Symbol, Address>(&admin_key)
-            .ok_or(ContractError::Unauthorized)?;
+    // Function to preview how much of each of a submission's vesting payouts has vested in
+    // total at the given time, claimed or not
+    pub fn get_vested(env: Env, submission_id: u64, at: u64) -> Map<Address, i128> {
+        let mut vested = Map::new(&env);
+        let Ok(submission) = Self::get_submission(env.clone(), submission_id) else {
+            return vested;
+        };
+        let Ok(round) = Self::get_round(env.clone(), submission.round_id) else {
+            return vested;
+        };
 
-        // Require authorization from the admin
-        admin.require_auth();
+        for (token, position) in Self::vesting_positions(&env, &submission).iter() {
+            vested.set(token, Self::vested_amount(&round, &position, at));
+        }
 
-        let mut round = Self::get_round(env.clone(), round_id)?;
+        vested
+    }
 
-        if !round.is_active {
-            return Err(ContractError::RoundNotActive);
+    // Helper function to get a submission's vesting positions by token
+    fn vesting_positions(env: &Env, submission: &Submission) -> Map<Address, VestingPosition> {
+        let tokens =
+            Self::read_persistent::<Vec<Address>>(env, &DataKey::EscrowTokens(submission.round_id))
+                .unwrap_or(Vec::new(env));
+
+        let mut positions = Map::new(env);
+        for token in tokens.iter() {
+            let key = DataKey::Vesting(submission.id, token.clone());
+            if let Some(position) = Self::read_persistent::<VestingPosition>(env, &key) {
+                positions.set(token, position);
+            }
         }
 
-        // Close the round
-        round.is_active = false;
-        env.storage()
-            .persistent()
-            .set(&Self::round_key(round_id), &round);
+        positions
+    }
 
-        // Calculate total votes
-        let mut total_votes = 0u64;
-        for submission_id in round.submissions.iter() {
-            let submission = Self::get_submission(env.clone(), submission_id)?;
-            total_votes += submission.total_votes;
+    // Helper function to calculate how much of a vesting position has vested at the given
+    // time: nothing before the cliff, then linearly up to all of it once the duration has
+    // passed
+    fn vested_amount(round: &Round, position: &VestingPosition, at: u64) -> i128 {
+        let elapsed = at.saturating_sub(position.start);
+        if elapsed < round.config.cliff_seconds {
+            return 0;
+        }
+        if elapsed >= round.config.duration_seconds {
+            return position.total;
         }
 
-        // Calculate funding allocations
-        let mut allocations = Map::new(&env);
-        for submission_id in round.submissions.iter() {
-            let submission = Self::get_submission(env.clone(), submission_id)?;
-            let allocation = if total_votes > 0 {
-                (submission.total_votes * round.funding_amount) / total_votes
+        // Both factors fit in 64 bits, so the product can't overflow an i128
+        position.total * elapsed as i128 / round.config.duration_seconds as i128
+    }
+
+    // Helper function to transfer a submission's allocation from the contract to its payout
+    // address, or across its payout split with the rounding remainder going to the first
+    // recipient
+    fn pay_submission(
+        env: &Env,
+        token_client: &TokenClient,
+        submission: &Submission,
+        amount: i128,
+    ) -> Result<(), ContractError> {
+        let Some(splits) = Self::get_payout_splits(env.clone(), submission.id) else {
+            Self::transfer_payout(
+                env,
+                token_client,
+                submission,
+                &submission.payout_address,
+                amount,
+            );
+            return Ok(());
+        };
+
+        let mut shares = Vec::new(env);
+        let mut total_shared: i128 = 0;
+        for (recipient, bps) in splits.iter() {
+            let share = amount
+                .checked_mul(bps as i128)
+                .ok_or(ContractError::MathOverflow)?
+                / Self::TOTAL_BPS as i128;
+            shares.push_back((recipient, share));
+            total_shared += share;
+        }
+
+        for (index, (recipient, share)) in shares.iter().enumerate() {
+            let share = if index == 0 {
+                share + (amount - total_shared)
             } else {
-                0
+                share
             };
-            allocations.set(submission_id, allocation);
+            if share > 0 {
+                Self::transfer_payout(env, token_client, submission, &recipient, share);
+            }
         }
 
-        // Store funding allocations
-        env.storage()
-            .persistent()
-            .set(&Self::allocations_key(round_id), &allocations);
+        Ok(())
+    }
+
+    // Helper function to transfer part of a submission's payout from the contract and
+    // report it in a per-transfer event
+    fn transfer_payout(
+        env: &Env,
+        token_client: &TokenClient,
+        submission: &Submission,
+        recipient: &Address,
+        amount: i128,
+    ) {
+        token_client.transfer(&env.current_contract_address(), recipient, &amount);
+
+        // Emit event
+        env.events().publish(
+            (symbol_short!("PAYOUT"), submission.round_id, submission.id),
+            (
+                submission.submitter.clone(),
+                recipient.clone(),
+                token_client.address.clone(),
+                amount,
+            ),
+        );
+    }
+
+    // Function to withdraw the rounding remainder left in a token's escrow after disbursement
+    pub fn withdraw_remainder(
+        env: Env,
+        round_id: u64,
+        token: Address,
+        to: Address,
+    ) -> Result<i128, ContractError> {
+        // Require authorization from the admin
+        Self::require_admin(&env)?;
+
+        Self::get_round(env.clone(), round_id)?;
+
+        // The remainder is only known once every allocation has been paid in the token
+        let remainder_key = DataKey::Remainder(round_id, token.clone());
+        let remainder = Self::read_persistent::<i128>(&env, &remainder_key)
+            .ok_or(ContractError::FundsNotDisbursed)?;
+
+        if remainder == 0 {
+            return Err(ContractError::InvalidAmount);
+        }
+
+        let escrow_key = DataKey::Escrow(round_id, token.clone());
+        let escrow = Self::read_persistent::<i128>(&env, &escrow_key).unwrap_or(0);
+
+        if escrow < remainder {
+            return Err(ContractError::InsufficientFunds);
+        }
+
+        // Transfer the remainder out of the contract
+        let token_client = TokenClient::new(&env, &token);
+        token_client.transfer(&env.current_contract_address(), &to, &remainder);
+
+        Self::write_escrow(&env, round_id, &token, escrow - remainder);
+        Self::write_persistent(&env, &remainder_key, &0i128);
+
+        // The remainder no longer counts as unpaid funding for sweep_unclaimed
+        let disbursed_key = DataKey::Disbursed(round_id, token.clone());
+        let disbursed = Self::read_persistent::<i128>(&env, &disbursed_key).unwrap_or(0);
+        Self::write_persistent(&env, &disbursed_key, &(disbursed + remainder));
 
         // Emit event
         env.events()
-            .publish((symbol_short!("VOTE_CLSD"), round_id), round_id);
+            .publish((symbol_short!("RMDR_WD"), round_id, to), (token, remainder));
 
-        Ok(())
+        Ok(remainder)
     }
 
-    // Function to disburse funds to submissions based on allocations
-    pub fn disburse_funds(env: Env, round_id: u64, token_address: Address) -> Result<(), ContractError> {
-        let admin_key = symbol_short!("ADMIN");
-        let admin = env
-            .storage()
-            .instance()
-            .get::<Symbol, Address>(&admin_key)
-            .ok_or(ContractError::Unauthorized)?;
+    // Function to get the funding allocations of a closed round's approved submissions
+    pub fn get_allocations(env: Env, round_id: u64) -> Result<Map<u64, i128>, ContractError> {
+        let round = Self::get_round(env.clone(), round_id)?;
 
-        // Require authorization from the admin
-        admin.require_auth();
+        if !matches!(round.phase, RoundPhase::Closed | RoundPhase::Disbursed) {
+            return Err(ContractError::AllocationsNotComputed);
+        }
+
+        let mut allocations = Map::new(&env);
+        for submission_id in round.submissions.iter() {
+            let submission = Self::get_submission(env.clone(), submission_id)?;
+            if submission.status == SubmissionStatus::Approved {
+                allocations.set(submission_id, Self::allocation_of(&round, &submission));
+            }
+        }
 
+        Ok(allocations)
+    }
+
+    // Function to preview the allocations of an active round's approved submissions if voting
+    // closed now. Runs the same computation as `close_voting` on the current tallies without
+    // writing anything.
+    pub fn preview_allocations(env: Env, round_id: u64) -> Result<Map<u64, i128>, ContractError> {
         let mut round = Self::get_round(env.clone(), round_id)?;
 
-        if round.funds_disbursed {
-            return Err(ContractError::FundsAlreadyDisbursed);
+        if !round.is_active() {
+            return Err(ContractError::RoundNotActive);
         }
 
-        // Get funding allocations
-        let allocations = env
-            .storage()
-            .persistent()
-            .get::<(Symbol, u64), Map<u64, u64>>(&Self::allocations_key(round_id))
-            .ok_or(ContractError::VotingClosed)?;
+        Self::compute_allocations(&env, &mut round);
 
-        // Initialize token client
-        let token_client = TokenClient::new(&env, &token_address);
+        let mut allocations = Map::new(&env);
+        if round.config.categories.is_empty() {
+            let votes: alloc::vec::Vec<(u64, u64)> = round.tallies.iter().collect();
+            let (table, _) =
+                allocation::allocate(&votes, round.distributable, &round.allocation_config());
+            for (submission_id, amount) in table {
+                allocations.set(submission_id, amount);
+            }
+        } else {
+            for (submission_id, tally) in round.tallies.iter() {
+                let funded = if round.is_funded(submission_id, tally) {
+                    tally
+                } else {
+                    0
+                };
+                allocations.set(
+                    submission_id,
+                    Self::allocation_for(&round, submission_id, funded),
+                );
+            }
+        }
 
-        // Check the contract's token balance
-        let contract_balance = token_client.balance(&admin);
+        Ok(allocations)
+    }
 
-        if contract_balance < round.funding_amount as i128 {
-            return Err(ContractError::InsufficientFunds);
+    // Function to get the results of one of a closed round's categories: its budget, the
+    // funding its submissions shared after any budget rolled into it, and their allocations
+    pub fn get_category_results(
+        env: Env,
+        round_id: u64,
+        category: Symbol,
+    ) -> Result<CategoryResults, ContractError> {
+        let round = Self::get_round(env.clone(), round_id)?;
+
+        if !matches!(round.phase, RoundPhase::Closed | RoundPhase::Disbursed) {
+            return Err(ContractError::AllocationsNotComputed);
         }
 
-        // Disburse funds to submitters
-        for (submission_id, amount) in allocations.iter() {
-            let submission = Self::get_submission(env.clone(), submission_id)?;
-            // Convert amount to i128
-            let amount_i128 = amount as i128;
-
-            // Transfer tokens from the contract to the submitter
-            token_client.transfer(
-                &admin,        // From: The contract's own address
-                &submission.submitter, // To: The submitter's address
-                &amount_i128,          // Amount: The allocation amount as i128
-            );
+        let index = Self::category_index(&round, Some(category))?.unwrap_or(0);
+        let budget = round.config.categories.get_unchecked(index);
+        let totals = round.category_totals.get_unchecked(index);
+
+        // The tallies hold every approved submission's votes, so no submission has to be loaded
+        let mut allocations = Map::new(&env);
+        for (submission_id, tally) in round.tallies.iter() {
+            if round.submission_categories.get(submission_id) == Some(index) {
+                let funded = if round.is_funded(submission_id, tally) {
+                    tally
+                } else {
+                    0
+                };
+                allocations.set(
+                    submission_id,
+                    Self::allocation_for(&round, submission_id, funded),
+                );
+            }
         }
 
-        // Mark funds as disbursed
-        round.funds_disbursed = true;
-        env.storage()
-            .persistent()
-            .set(&Self::round_key(round_id), &round);
+        Ok(CategoryResults {
+            name: budget.name,
+            budget: budget.budget,
+            distributable: totals.distributable,
+            funded_votes: totals.funded_votes,
+            allocations,
+        })
+    }
 
-        // Emit event
-        env.events()
-            .publish((symbol_short!("FUND_DISB"), round_id), round_id);
+    // Function to get the allocations of a closed round's approved submissions in one of its
+    // tokens, in the same proportions as its funding allocations
+    pub fn get_token_allocations(
+        env: Env,
+        round_id: u64,
+        token: Address,
+    ) -> Result<Map<u64, i128>, ContractError> {
+        let funding_allocations = Self::get_allocations(env.clone(), round_id)?;
+        let round = Self::get_round(env.clone(), round_id)?;
+        let pool = Self::token_pool(&env, &round, &token)?;
 
-        Ok(())
+        let mut allocations = Map::new(&env);
+        for (submission_id, allocation) in funding_allocations.iter() {
+            allocations.set(submission_id, Self::token_share(&round, pool, allocation)?);
+        }
+
+        Ok(allocations)
+    }
+
+    // Function to get a voter's allocation in a round
+    pub fn get_voter_allocation(env: Env, round_id: u64, voter: Address) -> Option<Map<u64, u64>> {
+        Self::read_persistent::<Map<u64, u64>>(&env, &DataKey::VoterAllocation(round_id, voter))
     }
 
-    // Helper function to generate storage key for allocations
-    fn allocations_key(round_id: u64) -> (Symbol, u64) {
-        (symbol_short!("FUND_ALC"), round_id)
+    // Function to list the rounds a voter has cast a ballot in with their stored allocation
+    // in each, in order of the voter's first ballot, skipping the first `start` and returning
+    // at most `limit`. Rounds whose ballot was revoked stay in the history with an empty map.
+    pub fn get_voter_history(
+        env: Env,
+        voter: Address,
+        start: u32,
+        limit: u32,
+    ) -> Vec<(u64, Map<u64, u64>)> {
+        let history_key = DataKey::Index(IndexKey::VoterRounds(voter.clone()));
+        let round_ids =
+            Self::read_persistent::<Vec<u64>>(&env, &history_key).unwrap_or(Vec::new(&env));
+
+        let mut history = Vec::new(&env);
+        let end = start
+            .saturating_add(limit.min(Self::MAX_PAGE_SIZE))
+            .min(round_ids.len());
+        for index in start..end {
+            let round_id = round_ids.get_unchecked(index);
+            let allocation = Self::get_voter_allocation(env.clone(), round_id, voter.clone())
+                .unwrap_or(Map::new(&env));
+            history.push_back((round_id, allocation));
+        }
+
+        history
+    }
+
+    // Helper function to add a round to a voter's history on their first ballot in it. A
+    // voter who revoked their ballot and votes again is already listed.
+    fn record_voter_round(env: &Env, voter: &Address, round_id: u64) {
+        let history_key = DataKey::Index(IndexKey::VoterRounds(voter.clone()));
+        let mut round_ids =
+            Self::read_persistent::<Vec<u64>>(env, &history_key).unwrap_or(Vec::new(env));
+        if !round_ids.contains(round_id) {
+            round_ids.push_back(round_id);
+            Self::write_persistent(env, &history_key, &round_ids);
+        }
     }
 }
+
+mod test;
@@ -0,0 +1,8615 @@
+#![cfg(test)]
+
+use super::*;
+use soroban_sdk::testutils::storage::{Instance as _, Persistent as _};
+use soroban_sdk::testutils::{Address as _, Events, Ledger, MockAuth, MockAuthInvoke};
+use soroban_sdk::{token, vec, IntoVal, TryFromVal};
+use testutils::{round_config, TestFixture};
+
+fn setup() -> (Env, RetroPGFContractClient<'static>, Address) {
+    let fixture = TestFixture::new();
+    (fixture.env, fixture.client, fixture.admin)
+}
+
+// Approve every submission of the round and open voting
+fn open_voting(client: &RetroPGFContractClient, round_id: &u64) {
+    let admin = client.get_admin();
+    for submission_id in client.get_round(round_id).submissions.iter() {
+        client.review_submission(&admin, &submission_id, &true);
+    }
+    client.start_voting(round_id);
+}
+
+fn round_name(env: &Env) -> Bytes {
+    Bytes::from_slice(env, b"RPGF Round 3")
+}
+
+fn round_hash(env: &Env) -> Bytes {
+    Bytes::from_slice(
+        env,
+        b"bafybeihkoviema7g3gxyt6la7vd5ho32ictqbilu3wnlo3rs7ewhnp7lly",
+    )
+}
+
+fn project_name(env: &Env) -> Bytes {
+    Bytes::from_slice(env, b"project")
+}
+
+fn project_hash(env: &Env) -> Bytes {
+    Bytes::from_slice(
+        env,
+        b"bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi",
+    )
+}
+
+#[test]
+fn test_voter_registry() {
+    let (env, client, _admin) = setup();
+    let voter = Address::generate(&env);
+
+    assert!(!client.is_voter(&voter));
+    client.add_voter(&voter);
+    assert!(client.is_voter(&voter));
+    client.remove_voter(&voter);
+    assert!(!client.is_voter(&voter));
+
+    assert_eq!(
+        client.try_remove_voter(&voter),
+        Err(Ok(ContractError::NotAVoter))
+    );
+}
+
+#[test]
+fn test_unregistered_voter_cannot_allocate() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_id, 10);
+    assert_eq!(
+        client.try_allocate_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::NotAVoter))
+    );
+}
+
+#[test]
+fn test_removed_voter_keeps_existing_allocations() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_id, 10);
+    client.allocate_votes(&voter, &round_id, &allocations);
+
+    client.remove_voter(&voter);
+    assert_eq!(
+        client.try_allocate_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::NotAVoter))
+    );
+
+    // The removed voter's earlier ballot still counts towards the payout
+    client.close_voting(&admin, &round_id, &true, &false);
+    let allocations = client.get_allocations(&round_id);
+    assert_eq!(allocations.get(submission_id), Some(1000));
+}
+
+#[test]
+fn test_allocate_votes_requires_voter_auth() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    let attacker = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_id, 10);
+
+    // The attacker signs the call but passes the registered voter's address
+    env.mock_auths(&[MockAuth {
+        address: &attacker,
+        invoke: &MockAuthInvoke {
+            contract: &client.address,
+            fn_name: "allocate_votes",
+            args: (voter.clone(), round_id, allocations.clone()).into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    assert!(client
+        .try_allocate_votes(&voter, &round_id, &allocations)
+        .is_err());
+
+    // With the voter's own signature the ballot goes through
+    env.mock_auths(&[MockAuth {
+        address: &voter,
+        invoke: &MockAuthInvoke {
+            contract: &client.address,
+            fn_name: "allocate_votes",
+            args: (voter.clone(), round_id, allocations.clone()).into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    client.allocate_votes(&voter, &round_id, &allocations);
+}
+
+#[test]
+fn test_vote_credits_are_cumulative_across_calls() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_id, 12);
+    client.allocate_votes(&voter, &round_id, &allocations);
+
+    // A second pass may only spend what is left of the budget
+    assert_eq!(
+        client.try_allocate_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::ExceededVoteLimit))
+    );
+
+    allocations.set(submission_id, 8);
+    client.allocate_votes(&voter, &round_id, &allocations);
+
+    let stored = client.get_voter_allocation(&round_id, &voter).unwrap();
+    assert_eq!(stored.get(submission_id), Some(20));
+
+    allocations.set(submission_id, 1);
+    assert_eq!(
+        client.try_allocate_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::ExceededVoteLimit))
+    );
+}
+
+#[test]
+fn test_update_and_revoke_votes() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let first = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    let second = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(first, 20);
+    client.allocate_votes(&voter, &round_id, &allocations);
+
+    // Moving the whole budget to another submission frees up the old votes
+    let mut updated = Map::new(&env);
+    updated.set(first, 5);
+    updated.set(second, 15);
+    client.update_votes(&voter, &round_id, &updated);
+    assert_eq!(client.get_submission(&first).total_votes, 5);
+    assert_eq!(client.get_submission(&second).total_votes, 15);
+
+    updated.set(second, 16);
+    assert_eq!(
+        client.try_update_votes(&voter, &round_id, &updated),
+        Err(Ok(ContractError::ExceededVoteLimit))
+    );
+
+    client.revoke_votes(&voter, &round_id);
+    assert_eq!(client.get_submission(&first).total_votes, 0);
+    assert_eq!(client.get_submission(&second).total_votes, 0);
+
+    // The full budget is available again after a revocation
+    client.allocate_votes(&voter, &round_id, &allocations);
+    assert_eq!(client.get_submission(&first).total_votes, 20);
+}
+
+#[test]
+fn test_update_and_revoke_fail_after_close() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_id, 10);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    client.close_voting(&admin, &round_id, &true, &false);
+
+    assert_eq!(
+        client.try_update_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::VotingClosed))
+    );
+    assert_eq!(
+        client.try_revoke_votes(&voter, &round_id),
+        Err(Ok(ContractError::VotingClosed))
+    );
+}
+
+#[test]
+fn test_allocations_are_validated() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let other_round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    let other_submission_id = client.submit_project(
+        &Address::generate(&env),
+        &other_round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+    open_voting(&client, &other_round_id);
+
+    let mut allocations = Map::new(&env);
+    assert_eq!(
+        client.try_allocate_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::InvalidAllocations))
+    );
+
+    allocations.set(submission_id, 0);
+    assert_eq!(
+        client.try_allocate_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::InvalidAllocations))
+    );
+
+    allocations.set(submission_id, 5);
+    allocations.set(other_submission_id, 5);
+    assert_eq!(
+        client.try_allocate_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::SubmissionNotInRound))
+    );
+
+    let mut allocations = Map::new(&env);
+    allocations.set(99, 5);
+    assert_eq!(
+        client.try_allocate_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::SubmissionNotFound))
+    );
+
+    // Nothing was written by the rejected ballots
+    assert_eq!(client.get_submission(&other_submission_id).total_votes, 0);
+}
+
+#[test]
+fn test_allocate_votes_checks_round_state() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_id, 5);
+
+    assert_eq!(
+        client.try_allocate_votes(&voter, &42, &allocations),
+        Err(Ok(ContractError::RoundNotFound))
+    );
+
+    // Voting is still open exactly at the deadline
+    env.ledger().with_mut(|li| li.timestamp = 2000);
+    client.allocate_votes(&voter, &round_id, &allocations);
+
+    env.ledger().with_mut(|li| li.timestamp = 2001);
+    assert_eq!(
+        client.try_allocate_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::VotingClosed))
+    );
+    assert_eq!(
+        client.try_update_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::VotingClosed))
+    );
+
+    client.close_voting(&admin, &round_id, &true, &false);
+    assert_eq!(
+        client.try_allocate_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::RoundNotActive))
+    );
+}
+
+#[test]
+fn test_distinct_submitters_receive_distinct_payouts() {
+    let fixture = TestFixture::new();
+    let voter = fixture.add_voter();
+
+    let round_id = fixture.create_active_round(1000, 1000);
+    let (alice, alice_submission) = fixture.submit_project(round_id);
+    let (bob, bob_submission) = fixture.submit_project(round_id);
+    fixture.open_voting(round_id);
+
+    fixture.vote(
+        &voter,
+        round_id,
+        &fixture.ballot(&[(alice_submission, 15), (bob_submission, 5)]),
+    );
+
+    fixture.close(round_id);
+    fixture
+        .client
+        .disburse_funds(&fixture.admin, &round_id, &fixture.token, &0, &10);
+
+    assert_eq!(fixture.balance(&alice), 750);
+    assert_eq!(fixture.balance(&bob), 250);
+}
+
+#[test]
+fn test_disburse_requires_round_escrow() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let voter = fixture.add_voter();
+
+    let round_id = fixture.create_round(1000, 1000, 2000, &round_config(&fixture.env));
+    let other_round_id = fixture.create_round(1000, 1000, 2000, &round_config(&fixture.env));
+    let submission_id = fixture.submit_n_projects(round_id, 1).get_unchecked(0);
+    fixture.open_voting(round_id);
+
+    assert_eq!(
+        client.try_deposit_funds(&fixture.funder, &round_id, &fixture.token, &0),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+
+    // Funds escrowed for another round do not count towards this one
+    fixture.fund(round_id, 600);
+    fixture.fund(other_round_id, 1000);
+    assert_eq!(fixture.balance(&client.address), 1600);
+
+    fixture.vote(&voter, round_id, &fixture.ballot(&[(submission_id, 10)]));
+    fixture.close(round_id);
+
+    assert_eq!(
+        client.try_disburse_funds(&fixture.admin, &round_id, &fixture.token, &0, &10),
+        Err(Ok(ContractError::InsufficientFunds))
+    );
+
+    fixture.fund(round_id, 400);
+    client.disburse_funds(&fixture.admin, &round_id, &fixture.token, &0, &10);
+    assert_eq!(fixture.balance(&client.address), 1000);
+}
+
+#[test]
+fn test_allocation_math_does_not_overflow() {
+    let fixture = TestFixture::new();
+    let funding = i128::MAX / 2;
+
+    let round_id = fixture.create_round(funding, 1000, 2000, &round_config(&fixture.env));
+    let submission_ids = fixture.submit_n_projects(round_id, 2);
+    let (first, second) = (
+        submission_ids.get_unchecked(0),
+        submission_ids.get_unchecked(1),
+    );
+    fixture.open_voting(round_id);
+
+    // 15 voters cast 300 votes in total, split 2:1
+    for _ in 0..15 {
+        let voter = fixture.add_voter();
+        fixture.vote(
+            &voter,
+            round_id,
+            &fixture.ballot(&[(first, 14), (second, 6)]),
+        );
+    }
+
+    fixture.close(round_id);
+
+    let allocations = fixture.client.get_allocations(&round_id);
+    let expected_first = funding / 300 * 210 + funding % 300 * 210 / 300;
+    let expected_second = funding / 300 * 90 + funding % 300 * 90 / 300;
+    assert_eq!(allocations.get(first), Some(expected_first));
+    assert_eq!(allocations.get(second), Some(expected_second));
+    assert!(expected_first + expected_second <= funding);
+}
+
+fn stored_remainder(
+    env: &Env,
+    client: &RetroPGFContractClient,
+    round_id: u64,
+    token: &Address,
+) -> i128 {
+    env.as_contract(&client.address, || {
+        env.storage()
+            .persistent()
+            .get::<DataKey, i128>(&DataKey::Remainder(round_id, token.clone()))
+            .unwrap()
+    })
+}
+
+#[test]
+fn test_allocations_plus_remainder_equal_funding() {
+    let distributions: [&[u64]; 5] = [&[], &[1, 1, 1], &[7, 3, 5], &[20], &[13, 2, 2, 1, 1]];
+
+    for votes in distributions {
+        let fixture = TestFixture::new();
+        let funding = 1001i128;
+        let round_id = fixture.create_active_round(funding, 1000);
+        let submission_ids = fixture.submit_n_projects(round_id, votes.len() as u32);
+        fixture.open_voting(round_id);
+
+        for (submission_id, vote) in submission_ids.iter().zip(votes.iter()) {
+            let voter = fixture.add_voter();
+            fixture.vote(&voter, round_id, &fixture.ballot(&[(submission_id, *vote)]));
+        }
+
+        fixture.close(round_id);
+
+        // The remainder is known once every allocation has been paid
+        fixture
+            .client
+            .disburse_funds(&fixture.admin, &round_id, &fixture.token, &0, &10);
+        let total: i128 = fixture
+            .client
+            .get_allocations(&round_id)
+            .values()
+            .iter()
+            .sum();
+        assert_eq!(
+            total + stored_remainder(&fixture.env, &fixture.client, round_id, &fixture.token),
+            funding
+        );
+    }
+}
+
+#[test]
+fn test_withdraw_remainder_after_disbursement() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let token = &fixture.token;
+
+    let round_id = fixture.create_active_round(1000, 1000);
+    let submission_ids = fixture.submit_n_projects(round_id, 3);
+    fixture.open_voting(round_id);
+
+    for submission_id in submission_ids.iter() {
+        let voter = fixture.add_voter();
+        fixture.vote(&voter, round_id, &fixture.ballot(&[(submission_id, 1)]));
+    }
+    fixture.close(round_id);
+
+    let treasury = Address::generate(&fixture.env);
+    assert_eq!(
+        client.try_withdraw_remainder(&round_id, token, &treasury),
+        Err(Ok(ContractError::FundsNotDisbursed))
+    );
+
+    client.disburse_funds(&fixture.admin, &round_id, token, &0, &10);
+    assert_eq!(client.withdraw_remainder(&round_id, token, &treasury), 1);
+    assert_eq!(fixture.balance(&treasury), 1);
+    assert_eq!(fixture.balance(&client.address), 0);
+
+    assert_eq!(
+        client.try_withdraw_remainder(&round_id, token, &treasury),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+}
+
+#[test]
+fn test_initialize_twice_fails() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let client = RetroPGFContractClient::new(&env, &env.register_contract(None, RetroPGFContract));
+    let admin = Address::generate(&env);
+    let attacker = Address::generate(&env);
+
+    assert_eq!(client.try_get_admin(), Err(Ok(ContractError::AdminNotSet)));
+    client.initialize(&admin);
+
+    assert_eq!(
+        client.try_initialize(&attacker),
+        Err(Ok(ContractError::AlreadyInitialized))
+    );
+
+    assert_eq!(client.get_admin(), admin);
+}
+
+#[test]
+fn test_update_config() {
+    let (env, client, admin) = setup();
+
+    // Initialization stores the defaults: no fee, no pause
+    let mut config = client.get_config();
+    assert_eq!(
+        config,
+        Config {
+            admin: admin.clone(),
+            default_vote_credits: 100,
+            fee_bps: 0,
+            fee_recipient: FeeRecipient::None,
+            paused: false,
+            timelock_delay: 0,
+            timelocked: Vec::new(&env),
+        }
+    );
+
+    // Every field is validated, and the admin only changes hands through transfer_admin
+    let fee_recipient = Address::generate(&env);
+    let invalid = [
+        (
+            Config {
+                admin: Address::generate(&env),
+                ..config.clone()
+            },
+            ContractError::Unauthorized,
+        ),
+        (
+            Config {
+                default_vote_credits: 0,
+                ..config.clone()
+            },
+            ContractError::InvalidAmount,
+        ),
+        (
+            Config {
+                fee_bps: 1001,
+                fee_recipient: FeeRecipient::Account(fee_recipient.clone()),
+                ..config.clone()
+            },
+            ContractError::InvalidAmount,
+        ),
+        (
+            Config {
+                fee_bps: 500,
+                ..config.clone()
+            },
+            ContractError::InvalidAmount,
+        ),
+    ];
+    for (invalid_config, error) in invalid {
+        assert_eq!(client.try_update_config(&invalid_config), Err(Ok(error)));
+    }
+
+    config.default_vote_credits = 50;
+    config.fee_bps = 500;
+    config.fee_recipient = FeeRecipient::Account(fee_recipient.clone());
+    config.paused = true;
+    client.update_config(&config);
+    assert_eq!(env.auths()[0].0, admin);
+    assert_eq!(
+        event_data::<Config>(&env, (symbol_short!("CFG_UPD"),)),
+        Some(config.clone())
+    );
+    assert_eq!(client.get_config(), config);
+    assert_eq!(
+        client.get_fee(),
+        Some(FeeConfig {
+            bps: 500,
+            recipient: fee_recipient
+        })
+    );
+    assert!(client.is_paused());
+    client.unpause(&admin);
+    assert!(!client.get_config().paused);
+
+    // Rounds created without their own credits take the default
+    let mut round_config = round_config(&env);
+    round_config.vote_credits = 0;
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config,
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    assert_eq!(client.get_vote_credits(&round_id), 50);
+}
+
+#[test]
+fn test_two_step_admin_transfer() {
+    let (env, client, admin) = setup();
+    let new_admin = Address::generate(&env);
+
+    assert_eq!(
+        client.try_accept_admin(),
+        Err(Ok(ContractError::Unauthorized))
+    );
+
+    client.transfer_admin(&new_admin);
+    // The old admin stays in charge until the nominee accepts
+    assert_eq!(client.get_admin(), admin);
+
+    client.accept_admin();
+    assert_eq!(env.auths()[0].0, new_admin);
+    assert_eq!(client.get_admin(), new_admin);
+
+    // The pending nomination is consumed by the handover
+    assert_eq!(
+        client.try_accept_admin(),
+        Err(Ok(ContractError::Unauthorized))
+    );
+}
+
+#[test]
+fn test_public_getters() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    let submitter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    assert_eq!(
+        client.try_get_round(&1),
+        Err(Ok(ContractError::RoundNotFound))
+    );
+    assert_eq!(
+        client.try_get_submission(&1),
+        Err(Ok(ContractError::SubmissionNotFound))
+    );
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submission_id = client.submit_project(
+        &submitter,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let round = client.get_round(&round_id);
+    assert_eq!(round.funding_amount, 1000);
+    assert_eq!(round.submissions, Vec::from_array(&env, [submission_id]));
+
+    let submission = client.get_submission(&submission_id);
+    assert_eq!(submission.round_id, round_id);
+    assert_eq!(submission.submitter, submitter);
+
+    assert_eq!(client.get_voter_allocation(&round_id, &voter), None);
+    assert_eq!(
+        client.try_get_allocations(&round_id),
+        Err(Ok(ContractError::AllocationsNotComputed))
+    );
+
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_id, 4);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    assert_eq!(
+        client.get_voter_allocation(&round_id, &voter),
+        Some(allocations)
+    );
+
+    client.close_voting(&admin, &round_id, &true, &false);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Closed);
+    assert_eq!(
+        client.get_allocations(&round_id).get(submission_id),
+        Some(1000)
+    );
+}
+
+#[test]
+fn test_list_rounds_and_submissions() {
+    let (env, client, admin) = setup();
+    assert_eq!(client.list_rounds(&0, &10).len(), 0);
+
+    for _ in 0..5 {
+        client.create_round(
+            &admin,
+            &1000,
+            &1000,
+            &2000,
+            &round_config(&env),
+            &round_name(&env),
+            &round_hash(&env),
+            &None,
+        );
+    }
+    let rounds = client.list_rounds(&1, &3);
+    assert_eq!(rounds.len(), 3);
+    assert_eq!(rounds.get_unchecked(0).id, 2);
+    assert_eq!(rounds.get_unchecked(2).id, 4);
+    assert_eq!(client.list_rounds(&4, &10).len(), 1);
+    assert_eq!(client.list_rounds(&5, &10).len(), 0);
+    assert_eq!(client.list_rounds(&u64::MAX, &10).len(), 0);
+
+    for _ in 0..60 {
+        client.submit_project(
+            &Address::generate(&env),
+            &1,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None,
+        );
+    }
+    // Page size is capped
+    assert_eq!(client.list_submissions(&1, &0, &100).len(), 50);
+    let page = client.list_submissions(&1, &55, &10);
+    assert_eq!(page.len(), 5);
+    assert_eq!(page.get_unchecked(0).id, 56);
+    assert_eq!(client.list_submissions(&1, &60, &10).len(), 0);
+    assert_eq!(client.list_submissions(&2, &0, &10).len(), 0);
+    assert_eq!(client.list_submissions(&99, &0, &10).len(), 0);
+}
+
+#[test]
+fn test_separate_submission_and_voting_deadlines() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let admin = &fixture.admin;
+    let voter = fixture.add_voter();
+
+    assert_eq!(
+        client.try_create_round(
+            admin,
+            &1000,
+            &2000,
+            &1000,
+            &round_config(&fixture.env),
+            &round_name(&fixture.env),
+            &round_hash(&fixture.env),
+            &None
+        ),
+        Err(Ok(ContractError::InvalidDeadline))
+    );
+
+    let round_id = fixture.create_active_round(1000, 1000);
+    let (_, submission_id) = fixture.submit_project(round_id);
+
+    // Submissions close at the submission deadline, voting stays open
+    fixture.pass_submission_deadline(round_id);
+    assert_eq!(
+        client.try_submit_project(
+            &Address::generate(&fixture.env),
+            &round_id,
+            &project_name(&fixture.env),
+            &project_hash(&fixture.env),
+            &None,
+            &None
+        ),
+        Err(Ok(ContractError::SubmissionDeadlinePassed))
+    );
+    fixture.open_voting(round_id);
+    fixture.vote(&voter, round_id, &fixture.ballot(&[(submission_id, 5)]));
+
+    // Closing early needs the force flag
+    assert_eq!(
+        client.try_close_voting(admin, &round_id, &false, &false),
+        Err(Ok(ContractError::VotingStillOpen))
+    );
+
+    fixture.pass_voting_deadline(round_id);
+    client.close_voting(admin, &round_id, &false, &false);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Closed);
+}
+
+#[test]
+fn test_round_phase_transitions() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token_admin.mint(&funder, &1000);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Submission);
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+
+    // Votes are only accepted in the voting phase
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_id, 5);
+    assert_eq!(
+        client.try_allocate_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::InvalidPhase))
+    );
+    assert_eq!(
+        client.try_close_voting(&admin, &round_id, &true, &false),
+        Err(Ok(ContractError::InvalidPhase))
+    );
+
+    open_voting(&client, &round_id);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Voting);
+    assert_eq!(
+        client.try_submit_project(
+            &Address::generate(&env),
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None
+        ),
+        Err(Ok(ContractError::InvalidPhase))
+    );
+    assert_eq!(
+        client.try_start_voting(&round_id),
+        Err(Ok(ContractError::InvalidPhase))
+    );
+    assert_eq!(
+        client.try_disburse_funds(&admin, &round_id, &token.address(), &0, &10),
+        Err(Ok(ContractError::AllocationsNotComputed))
+    );
+    client.allocate_votes(&voter, &round_id, &allocations);
+
+    client.close_voting(&admin, &round_id, &true, &false);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Closed);
+
+    client.disburse_funds(&admin, &round_id, &token.address(), &0, &10);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Disbursed);
+    assert_eq!(
+        client.try_disburse_funds(&admin, &round_id, &token.address(), &0, &10),
+        Err(Ok(ContractError::FundsAlreadyDisbursed))
+    );
+
+    // One event per transition: Voting, Closed, Disbursed
+    let phase_events = env
+        .events()
+        .all()
+        .iter()
+        .filter(|(_, topics, _)| {
+            Symbol::try_from_val(&env, &topics.get_unchecked(0)) == Ok(symbol_short!("PHASE"))
+        })
+        .count();
+    assert_eq!(phase_events, 3);
+}
+
+#[test]
+fn test_cancel_round_refunds_escrow() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let token_client = token::TokenClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token_admin.mint(&funder, &1000);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &600);
+    client.deposit_funds(&funder, &round_id, &token.address(), &400);
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let refund_to = Address::generate(&env);
+    client.cancel_round(&round_id, &refund_to);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Cancelled);
+    assert_eq!(
+        client.get_deposit(&round_id, &funder),
+        Map::from_array(&env, [(token.address(), 1000)])
+    );
+
+    // The depositor gets back exactly what they deposited
+    assert_eq!(client.refund(&round_id), 1);
+    assert_eq!(token_client.balance(&funder), 1000);
+    assert_eq!(token_client.balance(&refund_to), 0);
+    assert_eq!(token_client.balance(&client.address), 0);
+    assert_eq!(client.get_deposit(&round_id, &funder).len(), 0);
+
+    // A cancelled round accepts nothing and can never be disbursed
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_id, 5);
+    assert_eq!(
+        client.try_allocate_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::RoundNotActive))
+    );
+    assert_eq!(
+        client.try_submit_project(
+            &Address::generate(&env),
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None
+        ),
+        Err(Ok(ContractError::RoundNotActive))
+    );
+    assert_eq!(
+        client.try_close_voting(&admin, &round_id, &true, &false),
+        Err(Ok(ContractError::RoundNotActive))
+    );
+    assert_eq!(
+        client.try_disburse_funds(&admin, &round_id, &token.address(), &0, &10),
+        Err(Ok(ContractError::InvalidPhase))
+    );
+    assert_eq!(
+        client.try_cancel_round(&round_id, &refund_to),
+        Err(Ok(ContractError::InvalidPhase))
+    );
+}
+
+#[test]
+fn test_cannot_cancel_disbursed_round() {
+    let (env, client, admin) = setup();
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token_admin.mint(&funder, &1000);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    open_voting(&client, &round_id);
+    client.close_voting(&admin, &round_id, &true, &false);
+    client.disburse_funds(&admin, &round_id, &token.address(), &0, &10);
+
+    assert_eq!(
+        client.try_cancel_round(&round_id, &admin),
+        Err(Ok(ContractError::FundsAlreadyDisbursed))
+    );
+    assert_eq!(
+        client.try_refund(&round_id),
+        Err(Ok(ContractError::FundsAlreadyDisbursed))
+    );
+}
+
+#[test]
+fn test_update_round() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    env.ledger().with_mut(|li| li.timestamp = 500);
+
+    // Extend the submission window and grow the pool
+    client.update_round(&round_id, &Some(1500), &None, &Some(2000));
+    let round = client.get_round(&round_id);
+    assert_eq!(round.submission_deadline, 1500);
+    assert_eq!(round.voting_deadline, 2000);
+    assert_eq!(round.funding_amount, 2000);
+
+    assert_eq!(
+        client.try_update_round(&round_id, &Some(400), &None, &None),
+        Err(Ok(ContractError::InvalidDeadline))
+    );
+    assert_eq!(
+        client.try_update_round(&round_id, &None, &Some(1200), &None),
+        Err(Ok(ContractError::InvalidDeadline))
+    );
+
+    // Shrinking is fine before any votes, but not after
+    client.update_round(&round_id, &None, &None, &Some(1500));
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_id, 5);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    assert_eq!(
+        client.try_update_round(&round_id, &None, &None, &Some(1000)),
+        Err(Ok(ContractError::RoundLocked))
+    );
+    client.update_round(&round_id, &None, &Some(3000), &Some(1800));
+
+    client.close_voting(&admin, &round_id, &true, &false);
+    assert_eq!(
+        client.try_update_round(&round_id, &None, &Some(4000), &None),
+        Err(Ok(ContractError::RoundNotActive))
+    );
+}
+
+#[test]
+fn test_per_round_vote_credits() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let config = |vote_credits| RoundConfig {
+        vote_credits,
+        ..round_config(&env)
+    };
+    // Rounds created without their own credits take the configured default
+    let default_round = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config(0),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    assert_eq!(
+        client.get_vote_credits(&default_round),
+        client.get_config().default_vote_credits
+    );
+
+    let small_round = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config(5),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let large_round = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config(100),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    assert_eq!(client.get_vote_credits(&small_round), 5);
+    assert_eq!(client.get_vote_credits(&large_round), 100);
+
+    let small_submission = client.submit_project(
+        &Address::generate(&env),
+        &small_round,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    let large_submission = client.submit_project(
+        &Address::generate(&env),
+        &large_round,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &small_round);
+    open_voting(&client, &large_round);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(small_submission, 6);
+    assert_eq!(
+        client.try_allocate_votes(&voter, &small_round, &allocations),
+        Err(Ok(ContractError::ExceededVoteLimit))
+    );
+
+    let mut allocations = Map::new(&env);
+    allocations.set(large_submission, 100);
+    client.allocate_votes(&voter, &large_round, &allocations);
+}
+
+#[test]
+fn test_quadratic_voting_costs() {
+    let (env, client, admin) = setup();
+    let concentrated = Address::generate(&env);
+    let spread = Address::generate(&env);
+    client.add_voter(&concentrated);
+    client.add_voter(&spread);
+
+    let config = RoundConfig {
+        mechanism: VotingMechanism::Quadratic,
+        ..round_config(&env)
+    };
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config,
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let mut submission_ids = Vec::new(&env);
+    for _ in 0..5 {
+        submission_ids.push_back(client.submit_project(
+            &Address::generate(&env),
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None,
+        ));
+    }
+    open_voting(&client, &round_id);
+    let first = submission_ids.get_unchecked(0);
+
+    // 5 votes on one submission would cost 25 credits
+    let mut allocations = Map::new(&env);
+    allocations.set(first, 5);
+    assert_eq!(
+        client.try_allocate_votes(&concentrated, &round_id, &allocations),
+        Err(Ok(ContractError::ExceededVoteLimit))
+    );
+
+    // Concentrating the budget buys 4 votes for 16 credits...
+    allocations.set(first, 4);
+    client.allocate_votes(&concentrated, &round_id, &allocations);
+
+    // ...and topping it up is charged on the merged total (5² - 4² = 9 > 4 left)
+    allocations.set(first, 1);
+    assert_eq!(
+        client.try_allocate_votes(&concentrated, &round_id, &allocations),
+        Err(Ok(ContractError::ExceededVoteLimit))
+    );
+
+    // Spreading the same 20 credits buys 2 votes on each of 5 submissions
+    let mut allocations = Map::new(&env);
+    for submission_id in submission_ids.iter() {
+        allocations.set(submission_id, 2);
+    }
+    client.allocate_votes(&spread, &round_id, &allocations);
+
+    let concentrated_votes: u64 = client
+        .get_voter_allocation(&round_id, &concentrated)
+        .unwrap()
+        .values()
+        .iter()
+        .sum();
+    let spread_votes: u64 = client
+        .get_voter_allocation(&round_id, &spread)
+        .unwrap()
+        .values()
+        .iter()
+        .sum();
+    assert_eq!(concentrated_votes, 4);
+    assert_eq!(spread_votes, 10);
+
+    // Funding stays proportional to votes, not credits
+    client.close_voting(&admin, &round_id, &true, &false);
+    let allocations = client.get_allocations(&round_id);
+    assert_eq!(allocations.get(first), Some(6 * 1000 / 14));
+}
+
+#[test]
+fn test_per_submission_vote_cap() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let config = RoundConfig {
+        max_votes_per_submission: 8,
+        ..round_config(&env)
+    };
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config,
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    assert_eq!(client.get_round_config(&round_id), config);
+
+    let first = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    let second = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(first, 9);
+    assert_eq!(
+        client.try_allocate_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::PerProjectCapExceeded))
+    );
+
+    allocations.set(first, 8);
+    allocations.set(second, 8);
+    client.allocate_votes(&voter, &round_id, &allocations);
+
+    // The cap applies to the voter's running total on a submission
+    let mut allocations = Map::new(&env);
+    allocations.set(first, 1);
+    assert_eq!(
+        client.try_allocate_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::PerProjectCapExceeded))
+    );
+
+    allocations.set(first, 9);
+    assert_eq!(
+        client.try_update_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::PerProjectCapExceeded))
+    );
+}
+
+#[test]
+fn test_self_votes() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let strict_round = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let config = RoundConfig {
+        allow_self_votes: true,
+        ..round_config(&env)
+    };
+    let open_round = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config,
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+
+    let strict_submission = client.submit_project(
+        &voter,
+        &strict_round,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    let open_submission = client.submit_project(
+        &voter,
+        &open_round,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &strict_round);
+    open_voting(&client, &open_round);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(strict_submission, 10);
+    assert_eq!(
+        client.try_allocate_votes(&voter, &strict_round, &allocations),
+        Err(Ok(ContractError::SelfVoteNotAllowed))
+    );
+
+    let mut allocations = Map::new(&env);
+    allocations.set(open_submission, 10);
+    client.allocate_votes(&voter, &open_round, &allocations);
+    assert_eq!(client.get_submission(&open_submission).total_votes, 10);
+}
+
+#[test]
+fn test_quorum_of_distinct_voters() {
+    let (env, client, admin) = setup();
+    let config = RoundConfig {
+        min_voters: 2,
+        ..round_config(&env)
+    };
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config,
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let first = Address::generate(&env);
+    let second = Address::generate(&env);
+    client.add_voter(&first);
+    client.add_voter(&second);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_id, 5);
+
+    // Repeat ballots from the same voter only count once
+    client.allocate_votes(&first, &round_id, &allocations);
+    client.allocate_votes(&first, &round_id, &allocations);
+    client.update_votes(&first, &round_id, &allocations);
+    assert_eq!(client.get_voter_count(&round_id), 1);
+    assert_eq!(
+        client.try_close_voting(&admin, &round_id, &true, &false),
+        Err(Ok(ContractError::QuorumNotReached))
+    );
+
+    client.allocate_votes(&second, &round_id, &allocations);
+    assert_eq!(client.get_voter_count(&round_id), 2);
+
+    // A revoked ballot no longer counts
+    client.revoke_votes(&second, &round_id);
+    assert_eq!(client.get_voter_count(&round_id), 1);
+    client.update_votes(&second, &round_id, &allocations);
+    assert_eq!(client.get_voter_count(&round_id), 2);
+
+    client.close_voting(&admin, &round_id, &true, &false);
+}
+
+#[test]
+fn test_quorum_override() {
+    let (env, client, admin) = setup();
+    let config = RoundConfig {
+        min_voters: 3,
+        ..round_config(&env)
+    };
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config,
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    assert_eq!(
+        client.try_close_voting(&admin, &round_id, &true, &false),
+        Err(Ok(ContractError::QuorumNotReached))
+    );
+    client.close_voting(&admin, &round_id, &true, &true);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Closed);
+}
+
+#[test]
+fn test_submission_metadata() {
+    let (env, client, admin) = setup();
+    let submitter = Address::generate(&env);
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+
+    // Hashes must be non-empty and at most 64 bytes
+    assert_eq!(
+        client.try_submit_project(
+            &submitter,
+            &round_id,
+            &project_name(&env),
+            &Bytes::new(&env),
+            &None,
+            &None
+        ),
+        Err(Ok(ContractError::InvalidMetadata))
+    );
+    assert_eq!(
+        client.try_submit_project(
+            &submitter,
+            &round_id,
+            &project_name(&env),
+            &Bytes::from_array(&env, &[7; 65]),
+            &None,
+            &None
+        ),
+        Err(Ok(ContractError::InvalidMetadata))
+    );
+
+    let submission_id = client.submit_project(
+        &submitter,
+        &round_id,
+        &project_name(&env),
+        &Bytes::from_array(&env, &[7; 64]),
+        &None,
+        &None,
+    );
+    let submission = client.get_submission(&submission_id);
+    assert_eq!(submission.name, project_name(&env));
+    assert_eq!(submission.metadata_hash, Bytes::from_array(&env, &[7; 64]));
+
+    // Only the submitter may update the metadata
+    let new_name = Bytes::from_slice(&env, b"renamed");
+    assert_eq!(
+        client.try_update_submission_metadata(
+            &Address::generate(&env),
+            &submission_id,
+            &new_name,
+            &project_hash(&env)
+        ),
+        Err(Ok(ContractError::Unauthorized))
+    );
+    open_voting(&client, &round_id);
+    client.update_submission_metadata(&submitter, &submission_id, &new_name, &project_hash(&env));
+    let submission = client.get_submission(&submission_id);
+    assert_eq!(submission.name, new_name);
+    assert_eq!(submission.metadata_hash, project_hash(&env));
+
+    // Metadata is frozen once voting closes
+    client.close_voting(&admin, &round_id, &true, &false);
+    assert_eq!(
+        client.try_update_submission_metadata(
+            &submitter,
+            &submission_id,
+            &project_name(&env),
+            &project_hash(&env)
+        ),
+        Err(Ok(ContractError::RoundNotActive))
+    );
+}
+
+#[test]
+fn test_round_metadata() {
+    let (env, client, admin) = setup();
+
+    assert_eq!(
+        client.try_create_round(
+            &admin,
+            &1000,
+            &1000,
+            &2000,
+            &round_config(&env),
+            &Bytes::new(&env),
+            &round_hash(&env),
+            &None
+        ),
+        Err(Ok(ContractError::InvalidMetadata))
+    );
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let round = client.list_rounds(&0, &10).get(0).unwrap();
+    assert_eq!(round.name, round_name(&env));
+    assert_eq!(round.metadata_hash, round_hash(&env));
+
+    let new_name = Bytes::from_slice(&env, b"RPGF Round 3 - Developer Tooling");
+    client.set_round_metadata(&round_id, &new_name, &round_hash(&env));
+    assert_eq!(client.get_round(&round_id).name, new_name);
+    assert_eq!(
+        client.try_set_round_metadata(&round_id, &new_name, &Bytes::from_array(&env, &[1; 65])),
+        Err(Ok(ContractError::InvalidMetadata))
+    );
+
+    // Metadata is frozen once the round is no longer active
+    open_voting(&client, &round_id);
+    client.close_voting(&admin, &round_id, &true, &false);
+    assert_eq!(
+        client.try_set_round_metadata(&round_id, &round_name(&env), &round_hash(&env)),
+        Err(Ok(ContractError::RoundNotActive))
+    );
+}
+
+#[test]
+fn test_one_submission_per_submitter() {
+    let (env, client, admin) = setup();
+    let submitter = Address::generate(&env);
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let other_round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+
+    client.submit_project(
+        &submitter,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    assert_eq!(
+        client.try_submit_project(
+            &submitter,
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None
+        ),
+        Err(Ok(ContractError::AlreadySubmitted))
+    );
+
+    // The same address may still enter another round
+    client.submit_project(
+        &submitter,
+        &other_round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+}
+
+#[test]
+fn test_submission_limit() {
+    let (env, client, admin) = setup();
+    let config = RoundConfig {
+        max_submissions: 2,
+        ..round_config(&env)
+    };
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config,
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+
+    for _ in 0..2 {
+        client.submit_project(
+            &Address::generate(&env),
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None,
+        );
+    }
+    assert_eq!(
+        client.try_submit_project(
+            &Address::generate(&env),
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None
+        ),
+        Err(Ok(ContractError::SubmissionLimitReached))
+    );
+}
+
+#[test]
+fn test_withdraw_submission() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    let team = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let dropped = client.submit_project(
+        &team,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    let kept = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(dropped, 15);
+    client.allocate_votes(&voter, &round_id, &allocations);
+
+    // Only the submitter may withdraw
+    assert_eq!(
+        client.try_withdraw_submission(&Address::generate(&env), &dropped),
+        Err(Ok(ContractError::Unauthorized))
+    );
+    client.withdraw_submission(&team, &dropped);
+    assert!(client.get_submission(&dropped).withdrawn);
+    assert_eq!(client.get_voter_allocation(&round_id, &voter), None);
+    assert_eq!(client.get_voter_credits(&round_id, &voter).remaining, 20);
+    assert_eq!(client.get_round(&round_id).credits_spent, 0);
+    assert_eq!(client.get_submission(&dropped).total_votes, 0);
+    assert_eq!(
+        client.get_round(&round_id).submissions,
+        Vec::from_array(&env, [kept])
+    );
+    assert_eq!(
+        client.try_withdraw_submission(&team, &dropped),
+        Err(Ok(ContractError::SubmissionNotInRound))
+    );
+    assert_eq!(
+        client.try_allocate_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::SubmissionNotInRound))
+    );
+
+    // The credits spent on the withdrawn submission are available again
+    let mut allocations = Map::new(&env);
+    allocations.set(kept, 20);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    assert_eq!(
+        client.get_voter_allocation(&round_id, &voter),
+        Some(allocations)
+    );
+
+    client.close_voting(&admin, &round_id, &true, &false);
+    let fund_allocations = client.get_allocations(&round_id);
+    assert_eq!(fund_allocations.get(dropped), None);
+    assert_eq!(fund_allocations.get(kept), Some(1000));
+}
+
+#[test]
+fn test_withdrawal_refunds_credits() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let config = RoundConfig {
+        grace_seconds: 100,
+        ..round_config(&fixture.env)
+    };
+    let round_id = fixture.create_round(1000, 1000, 2000, &config);
+    let (team, dropped) = fixture.submit_project(round_id);
+    let (_, kept) = fixture.submit_project(round_id);
+    fixture.set_time(1000);
+    fixture.open_voting(round_id);
+
+    // Withdrawing refunds the ten most recent voters at once
+    let voters: alloc::vec::Vec<Address> = (0..11).map(|_| fixture.add_voter()).collect();
+    for voter in &voters {
+        fixture.vote(voter, round_id, &fixture.ballot(&[(dropped, 15)]));
+    }
+    client.withdraw_submission(&team, &dropped);
+    assert_eq!(
+        client.get_voter_credits(&round_id, &voters[10]).remaining,
+        20
+    );
+    assert_eq!(client.get_voter_credits(&round_id, &voters[0]).remaining, 5);
+    let stats = client.get_round_stats(&round_id);
+    assert_eq!((stats.raw_votes, stats.credits_spent), (15, 15));
+
+    // The others are refunded on their next ballot, even a late one
+    fixture.set_time(2050);
+    fixture.vote(&voters[0], round_id, &fixture.ballot(&[(kept, 10)]));
+    assert_eq!(
+        client.get_voter_credits(&round_id, &voters[0]).remaining,
+        10
+    );
+    assert_eq!(
+        client.get_voter_allocation(&round_id, &voters[0]),
+        Some(fixture.ballot(&[(kept, 5)]))
+    );
+    let stats = client.get_round_stats(&round_id);
+    assert_eq!((stats.raw_votes, stats.credits_spent), (10, 10));
+    assert_eq!(client.refund_suspended_votes(&dropped, &10), 0);
+}
+
+#[test]
+fn test_submission_review() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let approved = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    let rejected = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    let pending = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    assert_eq!(
+        client.get_submission(&pending).status,
+        SubmissionStatus::Pending
+    );
+
+    client.review_submission(&admin, &approved, &true);
+    client.review_submission(&admin, &rejected, &false);
+    assert_eq!(
+        client.get_submission(&approved).status,
+        SubmissionStatus::Approved
+    );
+    assert_eq!(
+        client.get_submission(&rejected).status,
+        SubmissionStatus::Rejected
+    );
+    let event = env.events().all().last().unwrap();
+    assert_eq!(event.1, (symbol_short!("SUB_RVW"), rejected).into_val(&env));
+    assert_eq!(
+        SubmissionStatus::try_from_val(&env, &event.2).unwrap(),
+        SubmissionStatus::Rejected
+    );
+    client.start_voting(&round_id);
+
+    // Only approved submissions accept votes
+    for submission_id in [rejected, pending] {
+        let mut allocations = Map::new(&env);
+        allocations.set(submission_id, 5);
+        assert_eq!(
+            client.try_allocate_votes(&voter, &round_id, &allocations),
+            Err(Ok(ContractError::SubmissionNotApproved))
+        );
+    }
+
+    let mut allocations = Map::new(&env);
+    allocations.set(approved, 5);
+    client.allocate_votes(&voter, &round_id, &allocations);
+
+    // A submission rejected after receiving votes is left out of the allocations
+    client.review_submission(&admin, &approved, &false);
+    client.close_voting(&admin, &round_id, &true, &false);
+    assert!(client.get_allocations(&round_id).is_empty());
+    assert_eq!(
+        client.try_review_submission(&admin, &approved, &true),
+        Err(Ok(ContractError::RoundNotActive))
+    );
+}
+
+#[test]
+fn test_payout_address() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let token_client = token::TokenClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token_admin.mint(&funder, &1000);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    let hot_wallet = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let submission_id = client.submit_project(
+        &hot_wallet,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    assert_eq!(
+        client.get_submission(&submission_id).payout_address,
+        hot_wallet
+    );
+
+    // Only the submitter may redirect the payout
+    assert_eq!(
+        client.try_set_payout_address(&Address::generate(&env), &submission_id, &treasury),
+        Err(Ok(ContractError::Unauthorized))
+    );
+    client.set_payout_address(&hot_wallet, &submission_id, &treasury);
+    assert_eq!(
+        client.get_submission(&submission_id).payout_address,
+        treasury
+    );
+
+    open_voting(&client, &round_id);
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_id, 20);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    client.close_voting(&admin, &round_id, &true, &false);
+
+    // The payout address is locked once voting closes
+    assert_eq!(
+        client.try_set_payout_address(&hot_wallet, &submission_id, &hot_wallet),
+        Err(Ok(ContractError::RoundNotActive))
+    );
+
+    client.disburse_funds(&admin, &round_id, &token.address(), &0, &10);
+    assert_eq!(token_client.balance(&treasury), 1000);
+    assert_eq!(token_client.balance(&hot_wallet), 0);
+
+    let payout_topics = (symbol_short!("PAYOUT"), round_id, submission_id).into_val(&env);
+    let event = env
+        .events()
+        .all()
+        .iter()
+        .find(|event| event.1 == payout_topics)
+        .unwrap();
+    assert_eq!(
+        <(Address, Address, Address, i128)>::try_from_val(&env, &event.2).unwrap(),
+        (hot_wallet, treasury, token.address(), 1000)
+    );
+}
+
+#[test]
+fn test_payout_splits() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let token_client = token::TokenClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token_admin.mint(&funder, &1000);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+
+    // Splits must sum to 10000 basis points
+    let mut splits = Map::new(&env);
+    splits.set(Address::generate(&env), 5000);
+    splits.set(Address::generate(&env), 4000);
+    assert_eq!(
+        client.try_set_payout_splits(&submission_id, &splits),
+        Err(Ok(ContractError::InvalidAllocations))
+    );
+
+    // The number of recipients is capped
+    let mut too_many = Map::new(&env);
+    for _ in 0..11 {
+        too_many.set(Address::generate(&env), 10_000 / 11);
+    }
+    assert_eq!(
+        client.try_set_payout_splits(&submission_id, &too_many),
+        Err(Ok(ContractError::InvalidAllocations))
+    );
+
+    let mut splits = Map::new(&env);
+    for bps in [3333, 3333, 3334] {
+        splits.set(Address::generate(&env), bps);
+    }
+    client.set_payout_splits(&submission_id, &splits);
+    assert_eq!(
+        client.get_payout_splits(&submission_id),
+        Some(splits.clone())
+    );
+
+    open_voting(&client, &round_id);
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_id, 20);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    client.close_voting(&admin, &round_id, &true, &false);
+    client.disburse_funds(&admin, &round_id, &token.address(), &0, &10);
+
+    // Each recipient gets their share, with the rounding remainder going to the first
+    let recipients = splits.keys();
+    let expected = [333 + 1, 333, 333];
+    for (index, recipient) in recipients.iter().enumerate() {
+        assert_eq!(token_client.balance(&recipient), expected[index]);
+    }
+    assert_eq!(token_client.balance(&client.address), 0);
+}
+
+#[test]
+fn test_claim_payouts() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let token_client = token::TokenClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token_admin.mint(&funder, &1000);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let alice_submission = client.submit_project(
+        &alice,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    let bob_submission = client.submit_project(
+        &bob,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(alice_submission, 15);
+    allocations.set(bob_submission, 5);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    client.close_voting(&admin, &round_id, &true, &false);
+
+    // Nothing can be claimed before the round is finalized
+    assert_eq!(client.get_claimable(&alice_submission, &token.address()), 0);
+    assert_eq!(
+        client.try_claim(&alice_submission, &token.address()),
+        Err(Ok(ContractError::FundsNotDisbursed))
+    );
+
+    client.finalize_disbursement(&round_id, &token.address(), &5000);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Disbursed);
+    assert_eq!(
+        client.try_disburse_funds(&admin, &round_id, &token.address(), &0, &10),
+        Err(Ok(ContractError::FundsAlreadyDisbursed))
+    );
+    assert_eq!(
+        client.get_claimable(&alice_submission, &token.address()),
+        750
+    );
+    assert_eq!(client.get_claimable(&bob_submission, &token.address()), 250);
+
+    assert_eq!(client.claim(&alice_submission, &token.address()), 750);
+    assert_eq!(token_client.balance(&alice), 750);
+    assert_eq!(client.get_claimable(&alice_submission, &token.address()), 0);
+    assert_eq!(
+        client.try_claim(&alice_submission, &token.address()),
+        Err(Ok(ContractError::AlreadyClaimed))
+    );
+
+    client.claim(&bob_submission, &token.address());
+    assert_eq!(token_client.balance(&bob), 250);
+    assert_eq!(token_client.balance(&client.address), 0);
+}
+
+#[test]
+fn test_claim_requires_submitter_auth() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token_admin.mint(&funder, &1000);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    let submitter = Address::generate(&env);
+    let submission_id = client.submit_project(
+        &submitter,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_id, 20);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    client.close_voting(&admin, &round_id, &true, &false);
+    client.finalize_disbursement(&round_id, &token.address(), &5000);
+
+    client.claim(&submission_id, &token.address());
+    assert_eq!(env.auths()[0].0, submitter);
+}
+
+#[test]
+fn test_batched_disbursement() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let token_client = token::TokenClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token_admin.mint(&funder, &1000);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    let mut submitters = Vec::new(&env);
+    let mut allocations = Map::new(&env);
+    for votes in [10, 6, 4] {
+        let submitter = Address::generate(&env);
+        let submission_id = client.submit_project(
+            &submitter,
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None,
+        );
+        submitters.push_back(submitter);
+        allocations.set(submission_id, votes);
+    }
+    open_voting(&client, &round_id);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    client.close_voting(&admin, &round_id, &true, &false);
+
+    // The first batch pays two submissions and leaves the round open for the rest
+    client.disburse_funds(&admin, &round_id, &token.address(), &0, &2);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Closed);
+    assert_eq!(token_client.balance(&submitters.get(0).unwrap()), 500);
+    assert_eq!(token_client.balance(&submitters.get(1).unwrap()), 300);
+    assert_eq!(token_client.balance(&submitters.get(2).unwrap()), 0);
+
+    // An overlapping batch only pays the submission that is still outstanding
+    client.disburse_funds(&admin, &round_id, &token.address(), &1, &5);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Disbursed);
+    assert_eq!(token_client.balance(&submitters.get(1).unwrap()), 300);
+    assert_eq!(token_client.balance(&submitters.get(2).unwrap()), 200);
+    assert_eq!(token_client.balance(&client.address), 0);
+
+    assert_eq!(
+        client.try_disburse_funds(&admin, &round_id, &token.address(), &0, &10),
+        Err(Ok(ContractError::FundsAlreadyDisbursed))
+    );
+}
+
+#[test]
+fn test_sweep_unclaimed() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let token_client = token::TokenClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token_admin.mint(&funder, &1000);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    let alice_submission = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    let bob_submission = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(alice_submission, 15);
+    allocations.set(bob_submission, 5);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    env.ledger().with_mut(|li| li.timestamp = 3000);
+    client.close_voting(&admin, &round_id, &false, &false);
+
+    // The claim deadline must lie in the future
+    assert_eq!(
+        client.try_finalize_disbursement(&round_id, &token.address(), &3000),
+        Err(Ok(ContractError::InvalidDeadline))
+    );
+    client.finalize_disbursement(&round_id, &token.address(), &4000);
+    client.claim(&alice_submission, &token.address());
+
+    let treasury = Address::generate(&env);
+    assert_eq!(
+        client.try_sweep_unclaimed(&round_id, &token.address(), &treasury),
+        Err(Ok(ContractError::ClaimWindowOpen))
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = 4001);
+    assert_eq!(client.get_claimable(&bob_submission, &token.address()), 0);
+    assert_eq!(
+        client.sweep_unclaimed(&round_id, &token.address(), &treasury),
+        250
+    );
+    assert_eq!(token_client.balance(&treasury), 250);
+    assert_eq!(token_client.balance(&client.address), 0);
+    assert_eq!(
+        client.try_claim(&bob_submission, &token.address()),
+        Err(Ok(ContractError::ClaimExpired))
+    );
+
+    // Swept allocations are only recovered once
+    assert_eq!(
+        client.sweep_unclaimed(&round_id, &token.address(), &treasury),
+        0
+    );
+    assert_eq!(token_client.balance(&treasury), 250);
+}
+
+#[test]
+fn test_multi_token_disbursement() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let usdc = env.register_stellar_asset_contract_v2(admin.clone());
+    let usdc_client = token::TokenClient::new(&env, &usdc.address());
+    let bonus = env.register_stellar_asset_contract_v2(admin.clone());
+    let bonus_client = token::TokenClient::new(&env, &bonus.address());
+    let funder = Address::generate(&env);
+    token::StellarAssetClient::new(&env, &usdc.address()).mint(&funder, &1000);
+    token::StellarAssetClient::new(&env, &bonus.address()).mint(&funder, &501);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+
+    // The first token escrowed is the funding token; the other is shared out alongside it
+    client.deposit_funds(&funder, &round_id, &usdc.address(), &600);
+    client.deposit_funds(&funder, &round_id, &bonus.address(), &501);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let alice_submission = client.submit_project(
+        &alice,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    let bob_submission = client.submit_project(
+        &bob,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(alice_submission, 15);
+    allocations.set(bob_submission, 5);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    client.close_voting(&admin, &round_id, &true, &false);
+
+    assert_eq!(
+        client.get_token_allocations(&round_id, &usdc.address()),
+        Map::from_array(&env, [(alice_submission, 750), (bob_submission, 250)])
+    );
+    assert_eq!(
+        client.get_token_allocations(&round_id, &bonus.address()),
+        Map::from_array(&env, [(alice_submission, 375), (bob_submission, 125)])
+    );
+
+    // A token with nothing escrowed for the round has nothing to disburse, and can't be
+    // added once the pools are fixed
+    let other = env.register_stellar_asset_contract_v2(admin.clone());
+    token::StellarAssetClient::new(&env, &other.address()).mint(&funder, &100);
+    assert_eq!(
+        client.try_disburse_funds(&admin, &round_id, &other.address(), &0, &10),
+        Err(Ok(ContractError::InsufficientFunds))
+    );
+    assert_eq!(
+        client.try_deposit_funds(&funder, &round_id, &other.address(), &100),
+        Err(Ok(ContractError::InvalidPhase))
+    );
+
+    // Each token's escrow is checked on its own: the short funding token doesn't hold up
+    // the fully escrowed one
+    assert_eq!(
+        client.try_disburse_funds(&admin, &round_id, &usdc.address(), &0, &10),
+        Err(Ok(ContractError::InsufficientFunds))
+    );
+    client.disburse_funds(&admin, &round_id, &bonus.address(), &0, &10);
+    assert_eq!(bonus_client.balance(&alice), 375);
+    assert_eq!(bonus_client.balance(&bob), 125);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Closed);
+
+    let summary = env.events().all().last().unwrap();
+    assert_eq!(
+        summary.1,
+        (symbol_short!("FUND_DISB"), round_id).into_val(&env)
+    );
+    assert_eq!(
+        FundsDisbursedEvent::try_from_val(&env, &summary.2).unwrap(),
+        FundsDisbursedEvent {
+            round_id,
+            token: bonus.address(),
+            pool: 501,
+            disbursed: 500,
+            remainder: 1,
+        }
+    );
+    let payout_topics = (symbol_short!("PAYOUT"), round_id, alice_submission).into_val(&env);
+    let event = env
+        .events()
+        .all()
+        .iter()
+        .find(|event| event.1 == payout_topics)
+        .unwrap();
+    assert_eq!(
+        <(Address, Address, Address, i128)>::try_from_val(&env, &event.2).unwrap(),
+        (alice.clone(), alice.clone(), bonus.address(), 375)
+    );
+    assert_eq!(
+        client.try_disburse_funds(&admin, &round_id, &bonus.address(), &0, &10),
+        Err(Ok(ContractError::FundsAlreadyDisbursed))
+    );
+    assert_eq!(
+        client.withdraw_remainder(&round_id, &bonus.address(), &admin),
+        1
+    );
+    assert_eq!(bonus_client.balance(&client.address), 0);
+
+    // Topping up the funding token lets it be disbursed, which completes the round
+    client.deposit_funds(&funder, &round_id, &usdc.address(), &400);
+    client.disburse_funds(&admin, &round_id, &usdc.address(), &0, &10);
+    assert_eq!(usdc_client.balance(&alice), 750);
+    assert_eq!(usdc_client.balance(&bob), 250);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Disbursed);
+    assert_eq!(
+        stored_remainder(&env, &client, round_id, &usdc.address()),
+        0
+    );
+}
+
+#[test]
+fn test_protocol_fee() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let fee_recipient = Address::generate(&env);
+    assert_eq!(
+        client.try_set_fee(&1001, &fee_recipient),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+    client.set_fee(&500, &fee_recipient);
+    assert_eq!(
+        client.get_fee(),
+        Some(FeeConfig {
+            bps: 500,
+            recipient: fee_recipient.clone()
+        })
+    );
+
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let token_client = token::TokenClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token_admin.mint(&funder, &1000);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let alice_submission = client.submit_project(
+        &alice,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    let bob_submission = client.submit_project(
+        &bob,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(alice_submission, 15);
+    allocations.set(bob_submission, 5);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    client.close_voting(&admin, &round_id, &true, &false);
+    let closed =
+        event_data::<VotingClosedEvent>(&env, (symbol_short!("VOTE_CLSD"), round_id)).unwrap();
+    assert_eq!((closed.distributable, closed.fee), (950, 50));
+
+    // Allocations share the funding left after the 5% fee
+    let fund_allocations = client.get_allocations(&round_id);
+    assert_eq!(fund_allocations.get(alice_submission), Some(712));
+    assert_eq!(fund_allocations.get(bob_submission), Some(237));
+
+    client.disburse_funds(&admin, &round_id, &token.address(), &0, &10);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Disbursed);
+    assert_eq!(
+        stored_remainder(&env, &client, round_id, &token.address()),
+        1
+    );
+    assert_eq!(token_client.balance(&fee_recipient), 50);
+    assert_eq!(token_client.balance(&alice), 712);
+    assert_eq!(token_client.balance(&bob), 237);
+
+    client.withdraw_remainder(&round_id, &token.address(), &admin);
+    assert_eq!(token_client.balance(&client.address), 0);
+}
+
+#[test]
+fn test_disbursement_events() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token_admin.mint(&funder, &1000);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    let mut allocations = Map::new(&env);
+    for votes in [10, 5, 5] {
+        let submission_id = client.submit_project(
+            &Address::generate(&env),
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None,
+        );
+        allocations.set(submission_id, votes);
+    }
+    open_voting(&client, &round_id);
+    client.allocate_votes(&voter, &round_id, &allocations);
+
+    // Closing publishes the totals the allocations are derived from
+    client.close_voting(&admin, &round_id, &true, &false);
+    let event = env.events().all().last().unwrap();
+    assert_eq!(
+        event.1,
+        (symbol_short!("VOTE_CLSD"), round_id).into_val(&env)
+    );
+    let closed = VotingClosedEvent::try_from_val(&env, &event.2).unwrap();
+    assert_eq!((closed.total_votes, closed.distributable), (20, 1000));
+    let fund_allocations = client.get_allocations(&round_id);
+
+    // Every transfer gets its own event, followed by a summary
+    client.disburse_funds(&admin, &round_id, &token.address(), &0, &10);
+    let events = env.events().all();
+    for (submission_id, amount) in fund_allocations.iter() {
+        let payout_topics = (symbol_short!("PAYOUT"), round_id, submission_id).into_val(&env);
+        let event = events
+            .iter()
+            .find(|event| event.1 == payout_topics)
+            .unwrap();
+        let (_, _, _, paid) =
+            <(Address, Address, Address, i128)>::try_from_val(&env, &event.2).unwrap();
+        assert_eq!(paid, amount);
+    }
+
+    let summary = events.last().unwrap();
+    assert_eq!(
+        summary.1,
+        (symbol_short!("FUND_DISB"), round_id).into_val(&env)
+    );
+    assert_eq!(
+        FundsDisbursedEvent::try_from_val(&env, &summary.2).unwrap(),
+        FundsDisbursedEvent {
+            round_id,
+            token: token.address(),
+            pool: 1000,
+            disbursed: 1000,
+            remainder: 0,
+        }
+    );
+}
+
+#[test]
+fn test_mis_ordered_calls_return_exact_errors() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token_admin.mint(&funder, &1000);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_id, 20);
+
+    // Still accepting submissions
+    assert_eq!(
+        client.try_allocate_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::InvalidPhase))
+    );
+    assert_eq!(
+        client.try_close_voting(&admin, &round_id, &true, &false),
+        Err(Ok(ContractError::InvalidPhase))
+    );
+
+    // Voting open, allocations not computed yet
+    open_voting(&client, &round_id);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    assert_eq!(
+        client.try_get_allocations(&round_id),
+        Err(Ok(ContractError::AllocationsNotComputed))
+    );
+    assert_eq!(
+        client.try_disburse_funds(&admin, &round_id, &token.address(), &0, &10),
+        Err(Ok(ContractError::AllocationsNotComputed))
+    );
+    assert_eq!(
+        client.try_finalize_disbursement(&round_id, &token.address(), &5000),
+        Err(Ok(ContractError::AllocationsNotComputed))
+    );
+    assert_eq!(
+        client.try_withdraw_remainder(&round_id, &token.address(), &admin),
+        Err(Ok(ContractError::FundsNotDisbursed))
+    );
+
+    // Closed, not yet disbursed
+    client.close_voting(&admin, &round_id, &true, &false);
+    assert_eq!(
+        client.try_allocate_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::RoundNotActive))
+    );
+    assert_eq!(
+        client.try_update_votes(&voter, &round_id, &allocations),
+        Err(Ok(ContractError::VotingClosed))
+    );
+    assert_eq!(
+        client.try_claim(&submission_id, &token.address()),
+        Err(Ok(ContractError::FundsNotDisbursed))
+    );
+
+    // Disbursed
+    client.disburse_funds(&admin, &round_id, &token.address(), &0, &10);
+    assert_eq!(
+        client.try_disburse_funds(&admin, &round_id, &token.address(), &0, &10),
+        Err(Ok(ContractError::FundsAlreadyDisbursed))
+    );
+    assert_eq!(
+        client.try_close_voting(&admin, &round_id, &true, &false),
+        Err(Ok(ContractError::RoundNotActive))
+    );
+
+    // The numeric codes are part of the public interface
+    assert_eq!(ContractError::VotingClosed as u32, 8);
+    assert_eq!(ContractError::FundsAlreadyDisbursed as u32, 9);
+    assert_eq!(ContractError::FundsNotDisbursed as u32, 18);
+    assert_eq!(ContractError::InvalidPhase as u32, 22);
+    assert_eq!(ContractError::AllocationsNotComputed as u32, 34);
+}
+
+#[test]
+fn test_create_round_validation() {
+    let (env, client, admin) = setup();
+    env.ledger().with_mut(|li| li.timestamp = 1000);
+
+    assert_eq!(
+        client.try_create_round(
+            &admin,
+            &0,
+            &2000,
+            &3000,
+            &round_config(&env),
+            &round_name(&env),
+            &round_hash(&env),
+            &None
+        ),
+        Err(Ok(ContractError::InvalidFundingAmount))
+    );
+
+    // The submission deadline must lie in the future
+    for submission_deadline in [999, 1000] {
+        assert_eq!(
+            client.try_create_round(
+                &admin,
+                &1000,
+                &submission_deadline,
+                &3000,
+                &round_config(&env),
+                &round_name(&env),
+                &round_hash(&env),
+                &None
+            ),
+            Err(Ok(ContractError::InvalidDeadline))
+        );
+    }
+
+    // Rejected rounds don't consume an id
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1001,
+        &3000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    assert_eq!(round_id, 1);
+    assert_eq!(
+        client.try_update_round(&round_id, &None, &None, &Some(0)),
+        Err(Ok(ContractError::InvalidFundingAmount))
+    );
+}
+
+#[test]
+fn test_vote_tallies_error_instead_of_wrapping() {
+    let (env, client, admin) = setup();
+    let config = RoundConfig {
+        vote_credits: u64::MAX,
+        ..round_config(&env)
+    };
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config,
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let first = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    let second = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    client.add_voter(&alice);
+    client.add_voter(&bob);
+
+    let mut max_first = Map::new(&env);
+    max_first.set(first, u64::MAX);
+    client.allocate_votes(&alice, &round_id, &max_first);
+
+    // Topping up a maxed-out ballot would wrap the voter's own allocation
+    let mut one_more = Map::new(&env);
+    one_more.set(first, 1);
+    assert_eq!(
+        client.try_allocate_votes(&alice, &round_id, &one_more),
+        Err(Ok(ContractError::MathOverflow))
+    );
+
+    // A second voter would wrap the submission's tally
+    assert_eq!(
+        client.try_allocate_votes(&bob, &round_id, &one_more),
+        Err(Ok(ContractError::MathOverflow))
+    );
+    assert_eq!(
+        client.try_update_votes(&bob, &round_id, &one_more),
+        Err(Ok(ContractError::MathOverflow))
+    );
+    assert_eq!(client.get_submission(&first).total_votes, u64::MAX);
+
+    // Tallies that fit individually but not together would wrap the round's total
+    let mut max_second = Map::new(&env);
+    max_second.set(second, u64::MAX);
+    assert_eq!(
+        client.try_allocate_votes(&bob, &round_id, &max_second),
+        Err(Ok(ContractError::MathOverflow))
+    );
+    assert_eq!(client.get_submission(&second).total_votes, 0);
+    assert_eq!(client.get_round(&round_id).total_votes, u64::MAX);
+}
+
+// A submission-phase round with one approved submission holding 5 votes, as release 1
+// stored it
+fn release_1_round(env: &Env) -> RoundV1 {
+    RoundV1 {
+        id: 1,
+        name: round_name(env),
+        metadata_hash: round_hash(env),
+        funding_amount: 1000,
+        submission_deadline: 1000,
+        voting_deadline: 2000,
+        config: RoundConfigV1 {
+            vote_credits: 20,
+            mechanism: VotingMechanism::Linear,
+            max_votes_per_submission: 0,
+            allow_self_votes: false,
+            min_voters: 0,
+            max_submissions: 0,
+            inherit_global_voters: true,
+            token_gate: TokenGate::None,
+            reveal_period: 0,
+            grace_seconds: 0,
+            top_n: 0,
+            min_votes_to_fund: 0,
+            max_allocation: 0,
+            max_match_bps: 0,
+            cliff_seconds: 0,
+            duration_seconds: 0,
+            tranches: Vec::new(env),
+            challenge_period_seconds: 0,
+        },
+        phase: RoundPhase::Submission,
+        submissions: Vec::from_array(env, [1]),
+        voter_count: 1,
+        total_votes: 5,
+        distributable: 0,
+        credits_spent: 0,
+        tallies: Map::from_array(env, [(1, 5)]),
+        funded_votes: 0,
+        cutoff_votes: 0,
+        cutoff_id: 0,
+        capped_votes: 0,
+        uncapped_pool: 0,
+        uncapped_votes: 0,
+        halted: false,
+        overrides: Map::new(env),
+        closed_at: 0,
+        challenge_ends: 0,
+        challenge_extended: false,
+    }
+}
+
+#[test]
+fn test_migrate_legacy_storage() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let contract_id = env.register_contract(None, RetroPGFContract);
+    let client = RetroPGFContractClient::new(&env, &contract_id);
+
+    let admin = Address::generate(&env);
+    let voter = Address::generate(&env);
+    let submitter = Address::generate(&env);
+    let round = Round {
+        id: 1,
+        name: round_name(&env),
+        metadata_hash: round_hash(&env),
+        funding_amount: 1000,
+        submission_deadline: 1000,
+        voting_deadline: 2000,
+        config: round_config(&env),
+        phase: RoundPhase::Submission,
+        submissions: Vec::from_array(&env, [1]),
+        voter_count: 1,
+        total_votes: 5,
+        distributable: 0,
+        credits_spent: 0,
+        tallies: Map::from_array(&env, [(1, 5)]),
+        funded_votes: 0,
+        cutoff_votes: 0,
+        cutoff_id: 0,
+        capped_votes: 0,
+        uncapped_pool: 0,
+        uncapped_votes: 0,
+        halted: false,
+        overrides: Map::new(&env),
+        closed_at: 0,
+        challenge_ends: 0,
+        challenge_extended: false,
+        voting_opened_at: 0,
+        raw_votes: 5,
+        abstentions: 0,
+        submission_categories: Map::new(&env),
+        category_totals: Vec::new(&env),
+    };
+    let submission = SubmissionV1 {
+        id: 1,
+        round_id: 1,
+        submitter: submitter.clone(),
+        payout_address: submitter.clone(),
+        name: project_name(&env),
+        metadata_hash: project_hash(&env),
+        total_votes: 5,
+        status: SubmissionStatus::Approved,
+        withdrawn: false,
+    };
+    let mut ballot = Map::new(&env);
+    ballot.set(1u64, 5u64);
+
+    // Write entries under the legacy tuple keys
+    env.as_contract(&contract_id, || {
+        let instance = env.storage().instance();
+        instance.set(&symbol_short!("ADMIN"), &admin);
+        instance.set(&symbol_short!("NEXT_RND"), &1u64);
+        instance.set(&symbol_short!("NEXT_SUB"), &1u64);
+        let persistent = env.storage().persistent();
+        persistent.set(&(symbol_short!("ROUND"), 1u64), &release_1_round(&env));
+        persistent.set(&(symbol_short!("SUBMISSN"), 1u64), &submission);
+        persistent.set(&(symbol_short!("SUBMITTR"), 1u64, submitter.clone()), &1u64);
+        persistent.set(&(symbol_short!("VOTER"), voter.clone()), &true);
+        persistent.set(&(symbol_short!("VOTR_ALC"), 1u64, voter.clone()), &ballot);
+    });
+
+    // The legacy admin can't be overwritten before migrating
+    assert_eq!(
+        client.try_initialize(&Address::generate(&env)),
+        Err(Ok(ContractError::AlreadyInitialized))
+    );
+
+    client.migrate_storage(&Vec::from_array(&env, [voter.clone()]));
+    assert_eq!(client.get_admin(), admin);
+    assert_eq!(client.get_round(&1), round);
+    assert_eq!(client.get_submission(&1), submission.upgrade());
+    assert!(client.is_voter(&voter));
+    assert_eq!(client.get_voter_allocation(&1, &voter), Some(ballot));
+    assert_eq!(
+        client.try_submit_project(
+            &submitter,
+            &1,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None
+        ),
+        Err(Ok(ContractError::AlreadySubmitted))
+    );
+    env.as_contract(&contract_id, || {
+        assert!(!env
+            .storage()
+            .persistent()
+            .has(&(symbol_short!("ROUND"), 1u64)));
+    });
+
+    // New ids continue after the migrated ones
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    assert_eq!(round_id, 2);
+
+    // Migration is one-shot
+    assert_eq!(
+        client.try_migrate_storage(&Vec::new(&env)),
+        Err(Ok(ContractError::AlreadyInitialized))
+    );
+}
+
+#[test]
+fn test_migrate_amounts_to_i128() {
+    let fixture = TestFixture::new();
+    let env = &fixture.env;
+    let client = &fixture.client;
+    let other = env
+        .register_stellar_asset_contract_v2(fixture.admin.clone())
+        .address();
+    let fee_recipient = Address::generate(env);
+
+    // A closed round whose funding amounts release 1 stored as u64s
+    let mut legacy = release_1_round(env);
+    legacy.phase = RoundPhase::Closed;
+    legacy.distributable = 950;
+    legacy.funded_votes = 5;
+    legacy.uncapped_pool = 950;
+    legacy.uncapped_votes = 5;
+    legacy.config.max_allocation = 900;
+    legacy.overrides = Map::from_array(env, [(1, 900)]);
+    env.as_contract(&client.address, || {
+        let persistent = env.storage().persistent();
+        env.storage().instance().set(&DataKey::NextRoundId, &1u64);
+        env.storage()
+            .instance()
+            .set(&DataKey::StorageVersion, &1u32);
+        persistent.set(&DataKey::Round(1), &legacy);
+        persistent.set(&DataKey::FeeDue(1), &(fee_recipient.clone(), 50u64));
+        persistent.set(
+            &DataKey::EscrowTokens(1),
+            &Vec::from_array(env, [fixture.token.clone(), other.clone()]),
+        );
+        persistent.set(&DataKey::TokenPool(1, other.clone()), &500u64);
+        persistent.set(&DataKey::Remainder(1, fixture.token.clone()), &7u64);
+    });
+
+    client.migrate();
+    assert_eq!(client.storage_version(), RetroPGFContract::VERSION);
+
+    let round = client.get_round(&1);
+    assert_eq!(round.funding_amount, 1000);
+    assert_eq!(round.distributable, 950);
+    assert_eq!(round.uncapped_pool, 950);
+    assert_eq!(round.config.max_allocation, 900);
+    assert_eq!(round.overrides, Map::from_array(env, [(1, 900i128)]));
+    assert_eq!(round.tallies, Map::from_array(env, [(1, 5u64)]));
+    assert_eq!(stored_remainder(env, client, 1, &fixture.token), 7);
+    env.as_contract(&client.address, || {
+        let persistent = env.storage().persistent();
+        assert_eq!(
+            persistent.get::<DataKey, (Address, i128)>(&DataKey::FeeDue(1)),
+            Some((fee_recipient.clone(), 50))
+        );
+        assert_eq!(
+            persistent.get::<DataKey, i128>(&DataKey::TokenPool(1, other.clone())),
+            Some(500)
+        );
+    });
+}
+
+#[test]
+fn test_migrate_settings_to_config() {
+    let (env, client, admin) = setup();
+    let fee_recipient = Address::generate(&env);
+
+    // Release 2 stored the admin, fee and pause flag on their own keys
+    env.as_contract(&client.address, || {
+        let instance = env.storage().instance();
+        instance.remove(&DataKey::Config);
+        instance.set(&DataKey::StorageVersion, &2u32);
+        instance.set(&DataKeyV2::Admin, &admin);
+        instance.set(
+            &DataKeyV2::FeeConfig,
+            &FeeConfig {
+                bps: 250,
+                recipient: fee_recipient.clone(),
+            },
+        );
+        instance.set(&DataKeyV2::Paused, &true);
+    });
+    assert_eq!(client.try_get_config(), Err(Ok(ContractError::AdminNotSet)));
+    assert_eq!(
+        client.try_initialize(&Address::generate(&env)),
+        Err(Ok(ContractError::AlreadyInitialized))
+    );
+
+    client.migrate();
+    assert_eq!(env.auths()[0].0, admin);
+    assert_eq!(
+        client.get_config(),
+        Config {
+            admin: admin.clone(),
+            default_vote_credits: 100,
+            fee_bps: 250,
+            fee_recipient: FeeRecipient::Account(fee_recipient),
+            paused: true,
+            timelock_delay: 0,
+            timelocked: Vec::new(&env),
+        }
+    );
+    env.as_contract(&client.address, || {
+        let instance = env.storage().instance();
+        assert!(!instance.has(&DataKeyV2::Admin));
+        assert!(!instance.has(&DataKeyV2::FeeConfig));
+        assert!(!instance.has(&DataKeyV2::Paused));
+    });
+}
+
+#[test]
+fn test_migrate_submissions() {
+    let fixture = TestFixture::new();
+    let (env, client) = (&fixture.env, &fixture.client);
+    let round_id = fixture.create_active_round(1000, 100);
+    let (submitter, submission_id) = fixture.submit_project(round_id);
+
+    // Release 2 stored submissions without a continuation link
+    let legacy = SubmissionV1 {
+        id: submission_id,
+        round_id,
+        submitter: submitter.clone(),
+        payout_address: submitter.clone(),
+        name: project_name(env),
+        metadata_hash: project_hash(env),
+        total_votes: 0,
+        status: SubmissionStatus::Pending,
+        withdrawn: false,
+    };
+    env.as_contract(&client.address, || {
+        env.storage()
+            .instance()
+            .set(&DataKey::StorageVersion, &2u32);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Submission(submission_id), &legacy);
+    });
+    assert!(client.try_get_submission(&submission_id).is_err());
+
+    client.migrate();
+    let submission = client.get_submission(&submission_id);
+    assert_eq!(submission.previous_submission_id, 0);
+    assert_eq!(submission, legacy.upgrade());
+    assert_eq!(
+        client.get_submission_lineage(&submission_id),
+        Vec::from_array(env, [submission_id])
+    );
+}
+
+#[test]
+fn test_bump_round_extends_ttls() {
+    let (env, client, admin) = setup();
+    let submitter = Address::generate(&env);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submission_id = client.submit_project(
+        &submitter,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+
+    let ttl = |key: DataKey| {
+        env.as_contract(&client.address, || env.storage().persistent().get_ttl(&key))
+    };
+    let bump = RetroPGFContract::PERSISTENT_BUMP_AMOUNT;
+    assert_eq!(ttl(DataKey::Round(round_id)), bump);
+
+    // Five days pass without anyone touching the round
+    let elapsed = 5 * RetroPGFContract::DAY_IN_LEDGERS;
+    env.ledger().with_mut(|li| li.sequence_number += elapsed);
+    assert_eq!(ttl(DataKey::Round(round_id)), bump - elapsed);
+    assert_eq!(ttl(DataKey::Submission(submission_id)), bump - elapsed);
+
+    client.bump_round(&round_id);
+    assert_eq!(ttl(DataKey::Round(round_id)), bump);
+    assert_eq!(ttl(DataKey::Submission(submission_id)), bump);
+    assert_eq!(ttl(DataKey::Submitter(round_id, submitter)), bump);
+    env.as_contract(&client.address, || {
+        assert_eq!(
+            env.storage().instance().get_ttl(),
+            RetroPGFContract::INSTANCE_BUMP_AMOUNT
+        );
+    });
+
+    // Reads extend entries too
+    env.ledger().with_mut(|li| li.sequence_number += elapsed);
+    client.get_submission(&submission_id);
+    assert_eq!(ttl(DataKey::Submission(submission_id)), bump);
+    assert_eq!(
+        client.try_bump_round(&99),
+        Err(Ok(ContractError::RoundNotFound))
+    );
+}
+
+#[test]
+fn test_round_total_votes_track_ballots() {
+    let (env, client, admin) = setup();
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let mut submission_ids = Vec::new(&env);
+    for _ in 0..3 {
+        submission_ids.push_back(client.submit_project(
+            &Address::generate(&env),
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None,
+        ));
+    }
+    let (first, second, third) = (
+        submission_ids.get(0).unwrap(),
+        submission_ids.get(1).unwrap(),
+        submission_ids.get(2).unwrap(),
+    );
+    open_voting(&client, &round_id);
+    let total_votes = || client.get_round(&round_id).total_votes;
+
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    client.add_voter(&alice);
+    client.add_voter(&bob);
+
+    let mut ballot = Map::new(&env);
+    ballot.set(first, 6);
+    ballot.set(second, 4);
+    client.allocate_votes(&alice, &round_id, &ballot);
+    assert_eq!(total_votes(), 10);
+
+    let mut ballot = Map::new(&env);
+    ballot.set(third, 5);
+    client.allocate_votes(&bob, &round_id, &ballot);
+    ballot.set(third, 8);
+    client.update_votes(&bob, &round_id, &ballot);
+    assert_eq!(total_votes(), 18);
+
+    // Rejected submissions' votes stop counting and come back on re-approval
+    client.review_submission(&admin, &second, &false);
+    assert_eq!(total_votes(), 14);
+    client.review_submission(&admin, &second, &true);
+    assert_eq!(total_votes(), 18);
+    client.review_submission(&admin, &second, &false);
+
+    // Revoking a ballot on a rejected submission doesn't count its votes twice
+    client.revoke_votes(&alice, &round_id);
+    assert_eq!(total_votes(), 8);
+    client.allocate_votes(&alice, &round_id, &Map::from_array(&env, [(first, 2)]));
+    assert_eq!(total_votes(), 10);
+
+    client.close_voting(&admin, &round_id, &true, &false);
+    let round = client.get_round(&round_id);
+    assert_eq!((round.total_votes, round.distributable), (10, 1000));
+
+    let allocations = client.get_allocations(&round_id);
+    assert_eq!(allocations.get(first), Some(200));
+    assert_eq!(allocations.get(second), None);
+    assert_eq!(allocations.get(third), Some(800));
+}
+
+#[test]
+fn test_pause_blocks_state_changes() {
+    let (env, client, admin) = setup();
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    assert!(!client.is_paused());
+    client.pause(&admin);
+    assert!(client.is_paused());
+    assert_eq!(
+        env.events().all().last().unwrap().1,
+        (symbol_short!("PAUSED"),).into_val(&env)
+    );
+
+    let ballot = Map::from_array(&env, [(submission_id, 5)]);
+    assert_eq!(
+        client.try_submit_project(
+            &Address::generate(&env),
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None
+        ),
+        Err(Ok(ContractError::ContractPaused))
+    );
+    assert_eq!(
+        client.try_allocate_votes(&voter, &round_id, &ballot),
+        Err(Ok(ContractError::ContractPaused))
+    );
+    assert_eq!(
+        client.try_update_votes(&voter, &round_id, &ballot),
+        Err(Ok(ContractError::ContractPaused))
+    );
+    assert_eq!(
+        client.try_revoke_votes(&voter, &round_id),
+        Err(Ok(ContractError::ContractPaused))
+    );
+    assert_eq!(
+        client.try_undelegate(&voter, &round_id),
+        Err(Ok(ContractError::ContractPaused))
+    );
+    assert_eq!(
+        client.try_close_voting(&admin, &round_id, &true, &false),
+        Err(Ok(ContractError::ContractPaused))
+    );
+    assert_eq!(
+        client.try_disburse_funds(&admin, &round_id, &token.address(), &0, &10),
+        Err(Ok(ContractError::ContractPaused))
+    );
+
+    // Getters keep working while paused
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Voting);
+    assert_eq!(client.get_submission(&submission_id).total_votes, 0);
+
+    client.unpause(&admin);
+    assert!(!client.is_paused());
+    client.allocate_votes(&voter, &round_id, &ballot);
+    client.close_voting(&admin, &round_id, &true, &false);
+}
+
+#[test]
+fn test_pause_requires_pauser() {
+    let (env, client, admin) = setup();
+    let pauser = Address::generate(&env);
+
+    client.pause(&admin);
+    assert_eq!(env.auths()[0].0, admin);
+    client.unpause(&admin);
+
+    assert_eq!(
+        client.try_pause(&pauser),
+        Err(Ok(ContractError::Unauthorized))
+    );
+    client.grant_role(&pauser, &Role::Pauser);
+    client.pause(&pauser);
+    assert_eq!(env.auths()[0].0, pauser);
+    client.unpause(&pauser);
+    assert!(!client.is_paused());
+}
+
+#[test]
+fn test_roles() {
+    let (env, client, admin) = setup();
+    let manager = Address::generate(&env);
+    let treasurer = Address::generate(&env);
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let funder = Address::generate(&env);
+    token::StellarAssetClient::new(&env, &token.address()).mint(&funder, &1000);
+
+    // Roles have to be granted, and only the admin can grant them
+    assert!(!client.has_role(&manager, &Role::RoundManager));
+    assert_eq!(
+        client.try_create_round(
+            &manager,
+            &1000,
+            &1000,
+            &2000,
+            &round_config(&env),
+            &round_name(&env),
+            &round_hash(&env),
+            &None
+        ),
+        Err(Ok(ContractError::Unauthorized))
+    );
+    client.grant_role(&manager, &Role::RoundManager);
+    assert_eq!(env.auths()[0].0, admin);
+    assert_eq!(
+        env.events().all().last().unwrap().1,
+        (symbol_short!("ROLE_GRNT"), manager.clone()).into_val(&env)
+    );
+    client.grant_role(&treasurer, &Role::Treasurer);
+    assert!(client.has_role(&manager, &Role::RoundManager));
+    assert!(!client.has_role(&manager, &Role::Treasurer));
+
+    // The round manager runs the round
+    let round_id = client.create_round(
+        &manager,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    assert_eq!(env.auths()[0].0, manager);
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+    client.allocate_votes(
+        &voter,
+        &round_id,
+        &Map::from_array(&env, [(submission_id, 5)]),
+    );
+    client.close_voting(&manager, &round_id, &true, &false);
+    assert_eq!(env.auths()[0].0, manager);
+
+    // Only the treasurer (or the admin) moves funds
+    assert_eq!(
+        client.try_disburse_funds(&manager, &round_id, &token.address(), &0, &10),
+        Err(Ok(ContractError::Unauthorized))
+    );
+    client.disburse_funds(&treasurer, &round_id, &token.address(), &0, &10);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Disbursed);
+
+    // Revoked roles stop working
+    client.revoke_role(&manager, &Role::RoundManager);
+    assert_eq!(
+        env.events().all().last().unwrap().1,
+        (symbol_short!("ROLE_RVK"), manager.clone()).into_val(&env)
+    );
+    assert!(!client.has_role(&manager, &Role::RoundManager));
+    assert_eq!(
+        client.try_create_round(
+            &manager,
+            &1000,
+            &1000,
+            &2000,
+            &round_config(&env),
+            &round_name(&env),
+            &round_hash(&env),
+            &None
+        ),
+        Err(Ok(ContractError::Unauthorized))
+    );
+}
+
+#[test]
+fn test_version_and_migrate() {
+    let (env, client, admin) = setup();
+
+    assert_eq!(client.version(), RetroPGFContract::VERSION);
+    assert_eq!(client.storage_version(), RetroPGFContract::VERSION);
+
+    // Instances set up before the layout was versioned hold the first release's layout
+    env.as_contract(&client.address, || {
+        env.storage().instance().remove(&DataKey::StorageVersion);
+    });
+    assert_eq!(client.storage_version(), 1);
+
+    client.migrate();
+    assert_eq!(env.auths()[0].0, admin);
+    assert_eq!(client.storage_version(), RetroPGFContract::VERSION);
+    let event = env.events().all().last().unwrap();
+    assert_eq!(event.1, (symbol_short!("STORE_MIG"),).into_val(&env));
+    assert_eq!(
+        <(u32, u32)>::try_from_val(&env, &event.2).unwrap(),
+        (1, RetroPGFContract::VERSION)
+    );
+
+    // Storage written by a newer release is left alone
+    env.as_contract(&client.address, || {
+        env.storage()
+            .instance()
+            .set(&DataKey::StorageVersion, &(RetroPGFContract::VERSION + 1));
+    });
+    assert_eq!(
+        client.try_migrate(),
+        Err(Ok(ContractError::UnsupportedVersion))
+    );
+}
+
+#[test]
+fn test_upgrade_requires_admin() {
+    let (env, client, _admin) = setup();
+    let attacker = Address::generate(&env);
+    let new_wasm_hash = BytesN::from_array(&env, &[7; 32]);
+
+    env.mock_auths(&[MockAuth {
+        address: &attacker,
+        invoke: &MockAuthInvoke {
+            contract: &client.address,
+            fn_name: "upgrade",
+            args: (new_wasm_hash.clone(),).into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    assert!(client.try_upgrade(&new_wasm_hash).is_err());
+}
+
+#[test]
+fn test_batch_voter_registration() {
+    let (env, client, admin) = setup();
+    let existing = Address::generate(&env);
+    client.add_voter(&existing);
+
+    // Duplicates, in the list or already registered, are skipped
+    let first = Address::generate(&env);
+    let second = Address::generate(&env);
+    let batch = Vec::from_array(
+        &env,
+        [
+            first.clone(),
+            existing.clone(),
+            second.clone(),
+            first.clone(),
+        ],
+    );
+    assert_eq!(client.add_voters(&batch), 4);
+    assert_eq!(env.auths()[0].0, admin);
+    assert!(client.is_voter(&first) && client.is_voter(&second));
+    let event = env.events().all().last().unwrap();
+    assert_eq!(event.1, (symbol_short!("VTRS_ADD"),).into_val(&env));
+    assert_eq!(u32::try_from_val(&env, &event.2).unwrap(), 2);
+
+    // Long lists are processed up to the cap so the caller can resume
+    let mut voters = Vec::new(&env);
+    for _ in 0..RetroPGFContract::MAX_VOTER_BATCH + 5 {
+        voters.push_back(Address::generate(&env));
+    }
+    let processed = client.add_voters(&voters);
+    assert_eq!(processed, RetroPGFContract::MAX_VOTER_BATCH);
+    assert!(!client.is_voter(&voters.last().unwrap()));
+    assert_eq!(client.add_voters(&voters.slice(processed..)), 5);
+    assert!(client.is_voter(&voters.last().unwrap()));
+
+    let unknown = Address::generate(&env);
+    let batch = Vec::from_array(&env, [first.clone(), unknown, existing.clone()]);
+    assert_eq!(client.remove_voters(&batch), 3);
+    assert!(!client.is_voter(&first) && !client.is_voter(&existing));
+    assert!(client.is_voter(&second));
+    let event = env.events().all().last().unwrap();
+    assert_eq!(event.1, (symbol_short!("VTRS_RMV"),).into_val(&env));
+    assert_eq!(u32::try_from_val(&env, &event.2).unwrap(), 2);
+}
+
+#[test]
+fn test_round_scoped_voters() {
+    let (env, client, admin) = setup();
+    let config = RoundConfig {
+        inherit_global_voters: false,
+        ..round_config(&env)
+    };
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config,
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let other_round = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let core_dev = Address::generate(&env);
+    let badge_holder = Address::generate(&env);
+    client.add_voter(&badge_holder);
+    client.add_round_voter(&round_id, &core_dev);
+    client.add_round_voter(&round_id, &core_dev);
+    assert_eq!(env.auths()[0].0, admin);
+
+    // The round doesn't inherit the global registry
+    assert!(client.is_round_voter(&round_id, &core_dev));
+    assert!(!client.is_round_voter(&round_id, &badge_holder));
+    assert!(!client.is_round_voter(&other_round, &core_dev));
+    assert!(client.is_round_voter(&other_round, &badge_holder));
+
+    let ballot = Map::from_array(&env, [(submission_id, 5)]);
+    assert_eq!(
+        client.try_allocate_votes(&badge_holder, &round_id, &ballot),
+        Err(Ok(ContractError::NotAVoter))
+    );
+    client.allocate_votes(&core_dev, &round_id, &ballot);
+
+    // Round voters are paged in registration order
+    let mut voters = Vec::from_array(&env, [core_dev.clone()]);
+    for _ in 0..3 {
+        let voter = Address::generate(&env);
+        client.add_round_voter(&round_id, &voter);
+        voters.push_back(voter);
+    }
+    assert_eq!(client.get_round_voters(&round_id, &0, &10), voters);
+    assert_eq!(
+        client.get_round_voters(&round_id, &1, &2),
+        voters.slice(1..3)
+    );
+    assert_eq!(client.get_round_voters(&round_id, &10, &10).len(), 0);
+
+    client.remove_round_voter(&round_id, &core_dev);
+    assert!(!client.is_round_voter(&round_id, &core_dev));
+    assert_eq!(
+        client.get_round_voters(&round_id, &0, &10),
+        voters.slice(1..)
+    );
+    assert_eq!(
+        client.try_remove_round_voter(&round_id, &core_dev),
+        Err(Ok(ContractError::NotAVoter))
+    );
+
+    // A closed round's electorate is fixed
+    client.close_voting(&admin, &round_id, &true, &false);
+    assert_eq!(
+        client.try_add_round_voter(&round_id, &Address::generate(&env)),
+        Err(Ok(ContractError::RoundNotActive))
+    );
+}
+
+#[test]
+fn test_weighted_voters() {
+    let (env, client, admin) = setup();
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let whale = Address::generate(&env);
+    let voter = Address::generate(&env);
+    client.add_voter_with_credits(&whale, &50);
+    client.add_voter(&voter);
+    assert_eq!(env.auths()[0].0, admin);
+    assert!(client.is_voter(&whale));
+    assert_eq!(
+        client.try_add_voter_with_credits(&Address::generate(&env), &0),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+
+    // Unweighted voters get the round's base credits
+    assert_eq!(
+        client.get_voter_credits(&round_id, &voter),
+        CreditBalance {
+            total: 20,
+            remaining: 20
+        }
+    );
+
+    // Weighted voters spend their personal budget
+    client.allocate_votes(
+        &whale,
+        &round_id,
+        &Map::from_array(&env, [(submission_id, 30)]),
+    );
+    assert_eq!(
+        client.get_voter_credits(&round_id, &whale),
+        CreditBalance {
+            total: 50,
+            remaining: 20
+        }
+    );
+    assert_eq!(
+        client.try_allocate_votes(
+            &whale,
+            &round_id,
+            &Map::from_array(&env, [(submission_id, 21)])
+        ),
+        Err(Ok(ContractError::ExceededVoteLimit))
+    );
+    assert_eq!(
+        client.try_allocate_votes(
+            &voter,
+            &round_id,
+            &Map::from_array(&env, [(submission_id, 30)])
+        ),
+        Err(Ok(ContractError::ExceededVoteLimit))
+    );
+    client.update_votes(
+        &whale,
+        &round_id,
+        &Map::from_array(&env, [(submission_id, 50)]),
+    );
+    assert_eq!(client.get_voter_credits(&round_id, &whale).remaining, 0);
+
+    // Registering again without a weight restores the default
+    client.add_voter(&whale);
+    assert_eq!(client.get_voter_credits(&round_id, &whale).total, 20);
+    assert_eq!(
+        client.try_get_voter_credits(&99, &whale),
+        Err(Ok(ContractError::RoundNotFound))
+    );
+}
+
+// Hash two nodes in ascending order, as the contract does when verifying a proof
+fn hash_pair(env: &Env, a: &BytesN<32>, b: &BytesN<32>) -> BytesN<32> {
+    let (left, right) = if a < b { (a, b) } else { (b, a) };
+    let mut pair = Bytes::from(left.clone());
+    pair.append(&Bytes::from(right.clone()));
+    env.crypto().sha256(&pair).into()
+}
+
+#[test]
+fn test_merkle_eligibility() {
+    use soroban_sdk::xdr::ToXdr;
+
+    let (env, client, admin) = setup();
+    let voters: [Address; 4] = core::array::from_fn(|_| Address::generate(&env));
+    let leaves: [BytesN<32>; 4] =
+        core::array::from_fn(|i| env.crypto().sha256(&voters[i].clone().to_xdr(&env)).into());
+    let left = hash_pair(&env, &leaves[0], &leaves[1]);
+    let right = hash_pair(&env, &leaves[2], &leaves[3]);
+    let root = hash_pair(&env, &left, &right);
+
+    let config = RoundConfig {
+        inherit_global_voters: false,
+        ..round_config(&env)
+    };
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config,
+        &round_name(&env),
+        &round_hash(&env),
+        &Some(root),
+    );
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+    let ballot = Map::from_array(&env, [(submission_id, 5)]);
+
+    // A valid proof admits the voter to the round
+    let proof = Vec::from_array(&env, [leaves[3].clone(), left.clone()]);
+    client.allocate_votes_with_proof(&voters[2], &round_id, &ballot, &proof);
+    assert!(client.is_round_voter(&round_id, &voters[2]));
+    client.update_votes(&voters[2], &round_id, &ballot);
+    assert_eq!(client.get_submission(&submission_id).total_votes, 5);
+
+    // Proofs for another leaf or from outsiders are rejected
+    assert_eq!(
+        client.try_allocate_votes_with_proof(&voters[1], &round_id, &ballot, &proof),
+        Err(Ok(ContractError::NotEligible))
+    );
+    let outsider = Address::generate(&env);
+    client.add_voter(&outsider);
+    assert_eq!(
+        client.try_allocate_votes_with_proof(&outsider, &round_id, &ballot, &proof),
+        Err(Ok(ContractError::NotEligible))
+    );
+    assert_eq!(
+        client.try_allocate_votes(&voters[0], &round_id, &ballot),
+        Err(Ok(ContractError::NotAVoter))
+    );
+
+    // Rounds without a root fall back to the registry
+    let open_round = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let other_submission = client.submit_project(
+        &Address::generate(&env),
+        &open_round,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &open_round);
+    let ballot = Map::from_array(&env, [(other_submission, 5)]);
+    assert_eq!(
+        client.try_allocate_votes_with_proof(&voters[2], &open_round, &ballot, &proof),
+        Err(Ok(ContractError::NotAVoter))
+    );
+    client.allocate_votes_with_proof(&outsider, &open_round, &ballot, &Vec::new(&env));
+    assert_eq!(client.get_submission(&other_submission).total_votes, 5);
+}
+
+#[test]
+fn test_token_gated_voting() {
+    let (env, client, admin) = setup();
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let config = RoundConfig {
+        token_gate: TokenGate::Holding(token.address(), 50),
+        ..round_config(&env)
+    };
+    assert_eq!(
+        client.try_create_round(
+            &admin,
+            &1000,
+            &1000,
+            &2000,
+            &RoundConfig {
+                token_gate: TokenGate::Holding(token.address(), 0),
+                ..config.clone()
+            },
+            &round_name(&env),
+            &round_hash(&env),
+            &None
+        ),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config,
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+    let ballot = Map::from_array(&env, [(submission_id, 5)]);
+
+    // Holders at the threshold may vote without being registered
+    let holder = Address::generate(&env);
+    token_admin.mint(&holder, &50);
+    client.allocate_votes(&holder, &round_id, &ballot);
+    client.update_votes(&holder, &round_id, &ballot);
+
+    // Registration doesn't stand in for the balance
+    let registered = Address::generate(&env);
+    client.add_voter(&registered);
+    token_admin.mint(&registered, &49);
+    assert_eq!(
+        client.try_allocate_votes(&registered, &round_id, &ballot),
+        Err(Ok(ContractError::NotEligible))
+    );
+    assert_eq!(client.get_voter_allocation(&round_id, &registered), None);
+
+    // A token contract that doesn't exist rejects the voter cleanly
+    let missing_token = RoundConfig {
+        token_gate: TokenGate::Holding(Address::generate(&env), 50),
+        ..config
+    };
+    let other_round = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &missing_token,
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let other_submission = client.submit_project(
+        &Address::generate(&env),
+        &other_round,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &other_round);
+    assert_eq!(
+        client.try_allocate_votes(
+            &holder,
+            &other_round,
+            &Map::from_array(&env, [(other_submission, 5)])
+        ),
+        Err(Ok(ContractError::NotEligible))
+    );
+    assert_eq!(client.get_round(&other_round).voter_count, 0);
+}
+
+// Hash a ballot and salt the way `reveal_vote` checks a commitment
+fn ballot_commitment(env: &Env, allocations: &Map<u64, u64>, salt: &Bytes) -> BytesN<32> {
+    use soroban_sdk::xdr::ToXdr;
+
+    let mut preimage = allocations.clone().to_xdr(env);
+    preimage.append(salt);
+    env.crypto().sha256(&preimage).into()
+}
+
+#[test]
+fn test_commit_reveal_voting() {
+    let (env, client, admin) = setup();
+    let config = RoundConfig {
+        reveal_period: 500,
+        ..round_config(&env)
+    };
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config,
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let voter = Address::generate(&env);
+    let late = Address::generate(&env);
+    client.add_voter(&voter);
+    client.add_voter(&late);
+    let ballot = Map::from_array(&env, [(submission_id, 5)]);
+    let salt = Bytes::from_slice(&env, b"secret");
+
+    // Ballots are committed rather than cast during voting
+    assert_eq!(
+        client.try_allocate_votes(&voter, &round_id, &ballot),
+        Err(Ok(ContractError::InvalidPhase))
+    );
+    client.commit_vote(&voter, &round_id, &ballot_commitment(&env, &ballot, &salt));
+    client.commit_vote(&late, &round_id, &ballot_commitment(&env, &ballot, &salt));
+    assert_eq!(client.get_submission(&submission_id).total_votes, 0);
+    assert_eq!(
+        client.try_reveal_vote(&voter, &round_id, &ballot, &salt),
+        Err(Ok(ContractError::VotingStillOpen))
+    );
+
+    // Reveals are checked against the commitment once voting ends
+    env.ledger().with_mut(|li| li.timestamp = 2001);
+    assert_eq!(
+        client.try_commit_vote(&voter, &round_id, &ballot_commitment(&env, &ballot, &salt)),
+        Err(Ok(ContractError::VotingClosed))
+    );
+    assert_eq!(
+        client.try_reveal_vote(
+            &voter,
+            &round_id,
+            &ballot,
+            &Bytes::from_slice(&env, b"guess")
+        ),
+        Err(Ok(ContractError::CommitmentMismatch))
+    );
+    assert_eq!(
+        client.try_reveal_vote(&admin, &round_id, &ballot, &salt),
+        Err(Ok(ContractError::CommitmentMismatch))
+    );
+    client.reveal_vote(&voter, &round_id, &ballot, &salt);
+    assert_eq!(client.get_submission(&submission_id).total_votes, 5);
+    assert_eq!(
+        client.try_reveal_vote(&voter, &round_id, &ballot, &salt),
+        Err(Ok(ContractError::AlreadyRevealed))
+    );
+
+    // The round can only close after the reveal period, discarding unrevealed ballots
+    assert_eq!(
+        client.try_close_voting(&admin, &round_id, &false, &false),
+        Err(Ok(ContractError::VotingStillOpen))
+    );
+    env.ledger().with_mut(|li| li.timestamp = 2501);
+    assert_eq!(
+        client.try_reveal_vote(&late, &round_id, &ballot, &salt),
+        Err(Ok(ContractError::VotingClosed))
+    );
+    client.close_voting(&admin, &round_id, &false, &false);
+    let round = client.get_round(&round_id);
+    assert_eq!((round.voter_count, round.total_votes), (1, 5));
+}
+
+#[test]
+fn test_vote_delegation() {
+    let (env, client, admin) = setup();
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let [alice, bob, carol, dave] = core::array::from_fn(|_| Address::generate(&env));
+    for voter in [&alice, &bob, &carol, &dave] {
+        client.add_voter(voter);
+    }
+
+    client.delegate_votes(&alice, &bob, &round_id);
+    assert_eq!(env.auths()[0].0, alice);
+    assert_eq!(client.get_delegate(&round_id, &alice), Some(bob.clone()));
+    let event = env.events().all().last().unwrap();
+    assert_eq!(
+        event.1,
+        (symbol_short!("DELEGATE"), round_id).into_val(&env)
+    );
+    assert_eq!(
+        <(Address, Address, u64)>::try_from_val(&env, &event.2).unwrap(),
+        (alice.clone(), bob.clone(), 20)
+    );
+    assert_eq!(client.get_voter_credits(&round_id, &bob).total, 40);
+    assert_eq!(client.get_voter_credits(&round_id, &alice).total, 0);
+
+    // Chains longer than one hop and self-delegation are refused
+    for (from, to) in [(&bob, &carol), (&carol, &alice), (&carol, &carol)] {
+        assert_eq!(
+            client.try_delegate_votes(from, to, &round_id),
+            Err(Ok(ContractError::InvalidDelegation))
+        );
+    }
+
+    // Voters who already voted or delegated can't delegate
+    client.allocate_votes(
+        &dave,
+        &round_id,
+        &Map::from_array(&env, [(submission_id, 5)]),
+    );
+    assert_eq!(
+        client.try_delegate_votes(&dave, &carol, &round_id),
+        Err(Ok(ContractError::AlreadyVoted))
+    );
+    assert_eq!(
+        client.try_delegate_votes(&alice, &carol, &round_id),
+        Err(Ok(ContractError::AlreadyVoted))
+    );
+
+    // Delegated credits can be reclaimed only while unspent
+    client.allocate_votes(
+        &bob,
+        &round_id,
+        &Map::from_array(&env, [(submission_id, 25)]),
+    );
+    assert_eq!(
+        client.try_undelegate(&alice, &round_id),
+        Err(Ok(ContractError::ExceededVoteLimit))
+    );
+    client.update_votes(
+        &bob,
+        &round_id,
+        &Map::from_array(&env, [(submission_id, 20)]),
+    );
+    client.undelegate(&alice, &round_id);
+    assert_eq!(client.get_delegate(&round_id, &alice), None);
+    assert_eq!(client.get_voter_credits(&round_id, &bob).total, 20);
+    assert_eq!(client.get_voter_credits(&round_id, &alice).total, 20);
+    assert_eq!(
+        client.try_undelegate(&alice, &round_id),
+        Err(Ok(ContractError::InvalidDelegation))
+    );
+
+    // Only the voters' own unspent credits are banked
+    client.delegate_votes(&carol, &bob, &round_id);
+    assert_eq!(client.get_voter_credits(&round_id, &bob).remaining, 20);
+    client.close_voting(&admin, &round_id, &true, &false);
+    for voter in [&bob, &carol] {
+        assert_eq!(
+            client.try_bank_credits(&round_id, voter),
+            Err(Ok(ContractError::InvalidAmount))
+        );
+    }
+    assert_eq!(client.bank_credits(&round_id, &alice), 20);
+    assert_eq!(client.bank_credits(&round_id, &dave), 15);
+}
+
+#[test]
+fn test_ranked_choice_rounds() {
+    let (env, client, admin) = setup();
+    let config = RoundConfig {
+        mechanism: VotingMechanism::Ranked,
+        ..round_config(&env)
+    };
+    let round_id = client.create_round(
+        &admin,
+        &600,
+        &1000,
+        &2000,
+        &config,
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let [first, second, third] = core::array::from_fn(|_| {
+        client.submit_project(
+            &Address::generate(&env),
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None,
+        )
+    });
+    open_voting(&client, &round_id);
+
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    client.add_voter(&alice);
+    client.add_voter(&bob);
+
+    // Mixing mechanisms and malformed rankings are rejected
+    assert_eq!(
+        client.try_allocate_votes(&alice, &round_id, &Map::from_array(&env, [(first, 1)])),
+        Err(Ok(ContractError::InvalidAllocations))
+    );
+    for ranking in [
+        Vec::new(&env),
+        Vec::from_array(&env, [first, second, first]),
+    ] {
+        assert_eq!(
+            client.try_submit_ranking(&alice, &round_id, &ranking),
+            Err(Ok(ContractError::InvalidAllocations))
+        );
+    }
+    assert_eq!(
+        client.try_submit_ranking(&alice, &round_id, &Vec::from_array(&env, [first, 99])),
+        Err(Ok(ContractError::SubmissionNotFound))
+    );
+
+    // Rankings are scored n, n - 1, ..., 1 and can be replaced
+    client.submit_ranking(&alice, &round_id, &Vec::from_array(&env, [third, first]));
+    client.submit_ranking(
+        &alice,
+        &round_id,
+        &Vec::from_array(&env, [first, second, third]),
+    );
+    client.submit_ranking(&bob, &round_id, &Vec::from_array(&env, [first, third]));
+    let votes = [first, second, third].map(|id| client.get_submission(&id).total_votes);
+    assert_eq!(votes, [5, 2, 2]);
+    assert_eq!(client.get_round(&round_id).voter_count, 2);
+
+    client.close_voting(&admin, &round_id, &true, &false);
+    let allocations = client.get_allocations(&round_id);
+    assert_eq!(allocations.get(first), Some(333));
+    assert_eq!(allocations.get(second), Some(133));
+    assert_eq!(allocations.get(third), Some(133));
+
+    // Credit rounds don't take rankings
+    let credit_round = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let credit_submission = client.submit_project(
+        &Address::generate(&env),
+        &credit_round,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &credit_round);
+    assert_eq!(
+        client.try_submit_ranking(
+            &alice,
+            &credit_round,
+            &Vec::from_array(&env, [credit_submission])
+        ),
+        Err(Ok(ContractError::InvalidAllocations))
+    );
+}
+
+#[test]
+fn test_round_stats() {
+    let (env, client, admin) = setup();
+    let config = RoundConfig {
+        mechanism: VotingMechanism::Quadratic,
+        ..round_config(&env)
+    };
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config,
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let [first, second] = core::array::from_fn(|_| {
+        client.submit_project(
+            &Address::generate(&env),
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None,
+        )
+    });
+    open_voting(&client, &round_id);
+    assert_eq!(
+        client.get_round_stats(&round_id),
+        RoundStats {
+            voter_count: 0,
+            credits_spent: 0,
+            votes_cast: 0,
+            raw_votes: 0,
+            abstentions: 0,
+            submission_count: 2,
+        }
+    );
+
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    client.add_voter(&alice);
+    client.add_voter(&bob);
+    client.allocate_votes(&alice, &round_id, &Map::from_array(&env, [(first, 3)]));
+    client.allocate_votes(&alice, &round_id, &Map::from_array(&env, [(second, 1)]));
+    client.allocate_votes(&bob, &round_id, &Map::from_array(&env, [(first, 4)]));
+    assert_eq!(
+        client.get_round_stats(&round_id),
+        RoundStats {
+            voter_count: 2,
+            credits_spent: 26,
+            votes_cast: 8,
+            raw_votes: 8,
+            abstentions: 0,
+            submission_count: 2,
+        }
+    );
+
+    // Updates and revocations keep the figures in step
+    client.update_votes(&bob, &round_id, &Map::from_array(&env, [(second, 2)]));
+    client.revoke_votes(&alice, &round_id);
+    let stats = client.get_round_stats(&round_id);
+    assert_eq!(
+        (
+            stats.voter_count,
+            stats.credits_spent,
+            stats.votes_cast,
+            stats.raw_votes
+        ),
+        (1, 4, 2, 2)
+    );
+
+    // The figures are frozen when voting closes
+    client.close_voting(&admin, &round_id, &true, &false);
+    let frozen = env.as_contract(&client.address, || {
+        env.storage()
+            .persistent()
+            .get::<_, RoundStats>(&DataKey::FinalStats(round_id))
+    });
+    assert_eq!(frozen, Some(stats.clone()));
+    assert_eq!(client.get_round_stats(&round_id), stats);
+}
+
+#[test]
+fn test_leaderboard() {
+    let (env, client, admin) = setup();
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    assert_eq!(client.leaderboard(&round_id, &10).len(), 0);
+    assert_eq!(client.leaderboard(&99, &10).len(), 0);
+
+    let ids: [u64; 4] = core::array::from_fn(|_| {
+        client.submit_project(
+            &Address::generate(&env),
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None,
+        )
+    });
+    open_voting(&client, &round_id);
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+    client.allocate_votes(
+        &voter,
+        &round_id,
+        &Map::from_array(&env, [(ids[1], 3), (ids[2], 5), (ids[3], 3)]),
+    );
+
+    // Live tallies, highest first and ties by ascending id
+    let standings = |limit: u32| {
+        let mut ids = Vec::new(&env);
+        for submission in client.leaderboard(&round_id, &limit).iter() {
+            ids.push_back(submission.id);
+        }
+        ids
+    };
+    assert_eq!(
+        standings(10),
+        Vec::from_array(&env, [ids[2], ids[1], ids[3], ids[0]])
+    );
+    assert_eq!(standings(2), Vec::from_array(&env, [ids[2], ids[1]]));
+    assert_eq!(standings(0).len(), 0);
+
+    // Final tallies once the round is closed
+    client.update_votes(&voter, &round_id, &Map::from_array(&env, [(ids[3], 6)]));
+    client.close_voting(&admin, &round_id, &true, &false);
+    assert_eq!(
+        standings(3),
+        Vec::from_array(&env, [ids[3], ids[0], ids[1]])
+    );
+}
+
+#[test]
+fn test_has_voted_and_remaining_credits() {
+    let (env, client, admin) = setup();
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let voter = Address::generate(&env);
+    let stranger = Address::generate(&env);
+    client.add_voter(&voter);
+    assert!(!client.has_voted(&round_id, &voter));
+    assert_eq!(client.remaining_credits(&round_id, &voter), 20);
+    assert_eq!(client.remaining_credits(&round_id, &stranger), 0);
+
+    client.allocate_votes(
+        &voter,
+        &round_id,
+        &Map::from_array(&env, [(submission_id, 13)]),
+    );
+    assert!(client.has_voted(&round_id, &voter));
+    assert_eq!(client.remaining_credits(&round_id, &voter), 7);
+
+    client.revoke_votes(&voter, &round_id);
+    assert!(!client.has_voted(&round_id, &voter));
+    assert_eq!(client.remaining_credits(&round_id, &voter), 20);
+
+    assert_eq!(
+        client.try_has_voted(&99, &voter),
+        Err(Ok(ContractError::RoundNotFound))
+    );
+    assert_eq!(
+        client.try_remaining_credits(&99, &voter),
+        Err(Ok(ContractError::RoundNotFound))
+    );
+}
+
+#[test]
+fn test_grace_period_discounts_late_ballots() {
+    let (env, client, admin) = setup();
+    let config = RoundConfig {
+        grace_seconds: 100,
+        ..round_config(&env)
+    };
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config,
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+    let [on_time, late, last, too_late] = core::array::from_fn(|_| Address::generate(&env));
+    for voter in [&on_time, &late, &last, &too_late] {
+        client.add_voter(voter);
+    }
+    let ballot = Map::from_array(&env, [(submission_id, 5)]);
+
+    // Ballots at the deadline count in full
+    env.ledger().with_mut(|li| li.timestamp = 2000);
+    client.allocate_votes(&on_time, &round_id, &ballot);
+    assert_eq!(client.get_submission(&submission_id).total_votes, 5);
+
+    // Ballots in the grace period count half, rounded down, and are charged in full
+    env.ledger().with_mut(|li| li.timestamp = 2001);
+    client.allocate_votes(&late, &round_id, &ballot);
+    assert_eq!(client.get_submission(&submission_id).total_votes, 7);
+    assert_eq!(client.remaining_credits(&round_id, &late), 15);
+    let event = env.events().all().last().unwrap();
+    assert_eq!(
+        event.1,
+        (symbol_short!("VOTE_LATE"), late.clone(), round_id).into_val(&env)
+    );
+    assert_eq!(
+        Map::<u64, u64>::try_from_val(&env, &event.2).unwrap(),
+        Map::from_array(&env, [(submission_id, 2)])
+    );
+    client.allocate_votes(
+        &late,
+        &round_id,
+        &Map::from_array(&env, [(submission_id, 15)]),
+    );
+    assert_eq!(
+        client.get_voter_allocation(&round_id, &late),
+        Some(Map::from_array(&env, [(submission_id, 9)]))
+    );
+    assert_eq!(client.remaining_credits(&round_id, &late), 0);
+
+    env.ledger().with_mut(|li| li.timestamp = 2100);
+    client.allocate_votes(
+        &last,
+        &round_id,
+        &Map::from_array(&env, [(submission_id, 1)]),
+    );
+    assert_eq!(client.get_submission(&submission_id).total_votes, 14);
+    assert_eq!(client.get_round(&round_id).voter_count, 3);
+    assert_eq!(
+        client.try_close_voting(&admin, &round_id, &false, &false),
+        Err(Ok(ContractError::VotingStillOpen))
+    );
+
+    // Ballots after the grace period are rejected
+    env.ledger().with_mut(|li| li.timestamp = 2101);
+    assert_eq!(
+        client.try_allocate_votes(&too_late, &round_id, &ballot),
+        Err(Ok(ContractError::VotingClosed))
+    );
+    client.close_voting(&admin, &round_id, &false, &false);
+}
+
+#[test]
+fn test_anyone_can_close_after_deadline() {
+    let (env, client, admin) = setup();
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+    client.allocate_votes(
+        &voter,
+        &round_id,
+        &Map::from_array(&env, [(submission_id, 5)]),
+    );
+
+    // Early and quorum-less closes stay with the admin and round managers
+    let stranger = Address::generate(&env);
+    assert_eq!(
+        client.try_close_voting(&stranger, &round_id, &false, &false),
+        Err(Ok(ContractError::VotingStillOpen))
+    );
+    env.ledger().with_mut(|li| li.timestamp = 2001);
+    for (force, override_quorum) in [(true, false), (false, true)] {
+        assert_eq!(
+            client.try_close_voting(&stranger, &round_id, &force, &override_quorum),
+            Err(Ok(ContractError::Unauthorized))
+        );
+    }
+
+    // Past the deadline anyone may close, without any authorization
+    client.close_voting(&stranger, &round_id, &false, &false);
+    assert!(env.auths().is_empty());
+    let event = env.events().all().last().unwrap();
+    assert_eq!(
+        event.1,
+        (symbol_short!("VOTE_CLSD"), round_id).into_val(&env)
+    );
+    let closed = VotingClosedEvent::try_from_val(&env, &event.2).unwrap();
+    assert_eq!((closed.total_votes, closed.distributable), (5, 1000));
+    assert_eq!(
+        client.get_allocations(&round_id).get(submission_id),
+        Some(1000)
+    );
+    assert_eq!(
+        client.try_close_voting(&stranger, &round_id, &false, &false),
+        Err(Ok(ContractError::RoundNotActive))
+    );
+}
+
+#[test]
+fn test_top_n_cutoff_funding() {
+    let (env, client, admin) = setup();
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_client = token::TokenClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token::StellarAssetClient::new(&env, &token.address()).mint(&funder, &1000);
+
+    let config = RoundConfig {
+        top_n: 3,
+        ..round_config(&env)
+    };
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config,
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    let submitters: [Address; 5] = core::array::from_fn(|_| Address::generate(&env));
+    let mut ids = Vec::new(&env);
+    for submitter in submitters.iter() {
+        ids.push_back(client.submit_project(
+            submitter,
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None,
+        ));
+    }
+    open_voting(&client, &round_id);
+
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+    let ballot = Map::from_array(
+        &env,
+        [
+            (ids.get(0).unwrap(), 4),
+            (ids.get(1).unwrap(), 9),
+            (ids.get(2).unwrap(), 4),
+            (ids.get(4).unwrap(), 3),
+        ],
+    );
+    client.allocate_votes(&voter, &round_id, &ballot);
+    let mut tallies = ballot.clone();
+    tallies.set(ids.get(3).unwrap(), 0);
+    assert_eq!(client.get_round(&round_id).tallies, tallies);
+
+    // Replacing the ballot keeps the tallies in step
+    let ballot = Map::from_array(
+        &env,
+        [
+            (ids.get(0).unwrap(), 3),
+            (ids.get(1).unwrap(), 6),
+            (ids.get(2).unwrap(), 3),
+            (ids.get(4).unwrap(), 5),
+        ],
+    );
+    client.update_votes(&voter, &round_id, &ballot);
+    tallies = ballot.clone();
+    tallies.set(ids.get(3).unwrap(), 0);
+    assert_eq!(client.get_round(&round_id).tallies, tallies);
+    client.close_voting(&admin, &round_id, &true, &false);
+
+    // The first and third submissions tie on 3 votes and the earlier one makes the cut;
+    // the top three share the funding and the rest are listed as not funded
+    assert_eq!(client.get_round(&round_id).funded_votes, 14);
+    let allocations = client.get_allocations(&round_id);
+    assert_eq!(allocations.len(), 5);
+    assert_eq!(allocations.get(ids.get(1).unwrap()), Some(428));
+    assert_eq!(allocations.get(ids.get(4).unwrap()), Some(357));
+    assert_eq!(allocations.get(ids.get(0).unwrap()), Some(214));
+    assert_eq!(allocations.get(ids.get(2).unwrap()), Some(0));
+    assert_eq!(allocations.get(ids.get(3).unwrap()), Some(0));
+
+    // Paying the funded submissions completes the round, leaving only the rounding remainder
+    client.disburse_funds(&admin, &round_id, &token.address(), &0, &10);
+    assert_eq!(client.get_round(&round_id).phase, RoundPhase::Disbursed);
+    assert_eq!(token_client.balance(&submitters[1]), 428);
+    assert_eq!(token_client.balance(&submitters[2]), 0);
+    assert_eq!(
+        stored_remainder(&env, &client, round_id, &token.address()),
+        1
+    );
+}
+
+#[test]
+fn test_top_n_cutoff_scales_to_hundreds_of_submissions() {
+    let (env, client, admin) = setup();
+    env.budget().reset_unlimited();
+    let config = RoundConfig {
+        vote_credits: 1000,
+        top_n: 10,
+        ..round_config(&env)
+    };
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config,
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    for _ in 0..300 {
+        client.submit_project(
+            &Address::generate(&env),
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None,
+        );
+    }
+    open_voting(&client, &round_id);
+
+    // Spread distinct vote counts across every submission
+    let ids = client.get_round(&round_id).submissions;
+    for chunk in 0..15 {
+        let voter = Address::generate(&env);
+        client.add_voter(&voter);
+        let mut ballot = Map::new(&env);
+        for index in chunk * 20..(chunk + 1) * 20 {
+            ballot.set(ids.get(index).unwrap(), (index as u64 * 7) % 40 + 1);
+        }
+        client.allocate_votes(&voter, &round_id, &ballot);
+    }
+
+    // Closing ranks the running tallies within a single transaction's budget
+    env.budget().reset_default();
+    client.close_voting(&admin, &round_id, &true, &false);
+    assert!(env.budget().cpu_instruction_cost() < 100_000_000);
+
+    let allocations = client.get_allocations(&round_id);
+    assert_eq!(allocations.len(), 300);
+    let funded = allocations
+        .values()
+        .iter()
+        .filter(|amount| *amount > 0)
+        .count();
+    assert_eq!(funded, 10);
+}
+
+#[test]
+fn test_min_votes_to_fund() {
+    for (min_votes_to_fund, expected) in [(5u64, [625i128, 375, 0, 0]), (50, [0, 0, 0, 0])] {
+        let (env, client, admin) = setup();
+        let token = env.register_stellar_asset_contract_v2(admin.clone());
+        let funder = Address::generate(&env);
+        token::StellarAssetClient::new(&env, &token.address()).mint(&funder, &1000);
+
+        let config = RoundConfig {
+            min_votes_to_fund,
+            ..round_config(&env)
+        };
+        let round_id = client.create_round(
+            &admin,
+            &1000,
+            &1000,
+            &2000,
+            &config,
+            &round_name(&env),
+            &round_hash(&env),
+            &None,
+        );
+        client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+        let ids: [u64; 4] = core::array::from_fn(|_| {
+            client.submit_project(
+                &Address::generate(&env),
+                &round_id,
+                &project_name(&env),
+                &project_hash(&env),
+                &None,
+                &None,
+            )
+        });
+        open_voting(&client, &round_id);
+
+        let voter = Address::generate(&env);
+        client.add_voter(&voter);
+        client.allocate_votes(
+            &voter,
+            &round_id,
+            &Map::from_array(&env, [(ids[0], 10), (ids[1], 6), (ids[2], 3), (ids[3], 1)]),
+        );
+        client.close_voting(&admin, &round_id, &true, &false);
+
+        // Submissions below the threshold are zeroed and their share goes to the rest
+        let allocations = client.get_allocations(&round_id);
+        for (submission_id, amount) in ids.iter().zip(expected.iter()) {
+            assert_eq!(allocations.get(*submission_id), Some(*amount));
+        }
+
+        // The funding is conserved, with everything in the remainder if nobody qualifies
+        client.disburse_funds(&admin, &round_id, &token.address(), &0, &10);
+        assert_eq!(client.get_round(&round_id).phase, RoundPhase::Disbursed);
+        let total: i128 = allocations.values().iter().sum();
+        assert_eq!(
+            total + stored_remainder(&env, &client, round_id, &token.address()),
+            1000
+        );
+    }
+}
+
+#[test]
+fn test_max_allocation_redistributes_excess() {
+    // A single pass, two passes to stabilize, and a cap too low to absorb the funding
+    let cases: [(i128, [i128; 4], i128); 3] = [
+        (400, [400, 360, 180, 60], 0),
+        (300, [300, 300, 300, 100], 0),
+        (200, [200, 200, 200, 200], 200),
+    ];
+
+    for (max_allocation, expected, remainder) in cases {
+        let (env, client, admin) = setup();
+        let token = env.register_stellar_asset_contract_v2(admin.clone());
+        let token_client = token::TokenClient::new(&env, &token.address());
+        let funder = Address::generate(&env);
+        token::StellarAssetClient::new(&env, &token.address()).mint(&funder, &1000);
+
+        let config = RoundConfig {
+            max_allocation,
+            ..round_config(&env)
+        };
+        let round_id = client.create_round(
+            &admin,
+            &1000,
+            &1000,
+            &2000,
+            &config,
+            &round_name(&env),
+            &round_hash(&env),
+            &None,
+        );
+        client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+        let submitters: [Address; 4] = core::array::from_fn(|_| Address::generate(&env));
+        let mut ids = Vec::new(&env);
+        for submitter in submitters.iter() {
+            ids.push_back(client.submit_project(
+                submitter,
+                &round_id,
+                &project_name(&env),
+                &project_hash(&env),
+                &None,
+                &None,
+            ));
+        }
+        open_voting(&client, &round_id);
+
+        let voter = Address::generate(&env);
+        client.add_voter(&voter);
+        let mut ballot = Map::new(&env);
+        for (submission_id, votes) in ids.iter().zip([10, 6, 3, 1]) {
+            ballot.set(submission_id, votes);
+        }
+        client.allocate_votes(&voter, &round_id, &ballot);
+        client.close_voting(&admin, &round_id, &true, &false);
+
+        // No allocation exceeds the cap and the excess went to the uncapped submissions
+        let allocations = client.get_allocations(&round_id);
+        for (submission_id, amount) in ids.iter().zip(expected.iter()) {
+            assert_eq!(allocations.get(submission_id), Some(*amount));
+        }
+
+        // Paying everyone conserves the funding, leaving what the caps couldn't absorb
+        client.disburse_funds(&admin, &round_id, &token.address(), &0, &10);
+        assert_eq!(client.get_round(&round_id).phase, RoundPhase::Disbursed);
+        for (submitter, amount) in submitters.iter().zip(expected.iter()) {
+            assert_eq!(token_client.balance(submitter), *amount);
+        }
+        assert_eq!(
+            stored_remainder(&env, &client, round_id, &token.address()),
+            remainder
+        );
+    }
+}
+
+#[test]
+fn test_donations_grow_the_funding_pool() {
+    let (env, client, admin) = setup();
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let token_client = token::TokenClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    token_admin.mint(&funder, &1000);
+    token_admin.mint(&alice, &500);
+    token_admin.mint(&bob, &500);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    client.donate(&alice, &round_id, &token.address(), &300);
+    let event = env.events().all().last().unwrap();
+    assert_eq!(
+        event.1,
+        (symbol_short!("DONATE"), round_id, alice.clone()).into_val(&env)
+    );
+    assert_eq!(
+        <(Address, i128)>::try_from_val(&env, &event.2).unwrap(),
+        (token.address(), 300)
+    );
+    client.donate(&alice, &round_id, &token.address(), &100);
+    client.donate(&bob, &round_id, &token.address(), &200);
+    assert_eq!(
+        client.try_donate(&bob, &round_id, &token.address(), &0),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+
+    // Donations add to the pool and are recorded per donor
+    assert_eq!(client.get_round(&round_id).funding_amount, 1600);
+    assert_eq!(
+        client.get_donation(&round_id, &alice),
+        Map::from_array(&env, [(token.address(), 400)])
+    );
+    assert_eq!(client.get_donation(&round_id, &funder).len(), 0);
+
+    // The whole pool is allocated once voting closes, after which donations are rejected
+    let submitter = Address::generate(&env);
+    client.submit_project(
+        &submitter,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+    client.allocate_votes(
+        &voter,
+        &round_id,
+        &Map::from_array(
+            &env,
+            [(client.get_round(&round_id).submissions.get(0).unwrap(), 5)],
+        ),
+    );
+    client.close_voting(&admin, &round_id, &true, &false);
+    assert_eq!(
+        client.try_donate(&bob, &round_id, &token.address(), &100),
+        Err(Ok(ContractError::RoundNotActive))
+    );
+    client.disburse_funds(&admin, &round_id, &token.address(), &0, &10);
+    assert_eq!(token_client.balance(&submitter), 1600);
+}
+
+#[test]
+fn test_cancel_round_refunds_donors() {
+    let (env, client, admin) = setup();
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let other = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_client = token::TokenClient::new(&env, &token.address());
+    let other_client = token::TokenClient::new(&env, &other.address());
+    let funder = Address::generate(&env);
+    let donor = Address::generate(&env);
+    token::StellarAssetClient::new(&env, &token.address()).mint(&funder, &1000);
+    token::StellarAssetClient::new(&env, &token.address()).mint(&donor, &300);
+    token::StellarAssetClient::new(&env, &other.address()).mint(&donor, &50);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    client.donate(&donor, &round_id, &token.address(), &300);
+    client.donate(&donor, &round_id, &other.address(), &50);
+
+    // The depositor and the donor each get back their own contributions in every token
+    let refund_to = Address::generate(&env);
+    client.cancel_round(&round_id, &refund_to);
+    assert_eq!(client.refund(&round_id), 2);
+    assert_eq!(token_client.balance(&donor), 300);
+    assert_eq!(other_client.balance(&donor), 50);
+    assert_eq!(token_client.balance(&funder), 1000);
+    assert_eq!(token_client.balance(&refund_to), 0);
+    assert_eq!(other_client.balance(&refund_to), 0);
+    assert_eq!(token_client.balance(&client.address), 0);
+    assert_eq!(client.get_donation(&round_id, &donor).len(), 0);
+    assert_eq!(
+        client.try_donate(&donor, &round_id, &token.address(), &100),
+        Err(Ok(ContractError::RoundNotActive))
+    );
+}
+
+#[test]
+fn test_quadratic_funding_matches_contributions() {
+    // Hand-computed matches of a 1000 pool. Contributions of 4 and 9 root to 2 + 3 = 5 for a
+    // weight of 25; 16 from one donor and 1 from each of four donors both weigh 4² = 16.
+    // Without a cap the weights split the pool 25:16:16 with 2 left over; capped at 40% the
+    // first gets 400 and the other two share the remaining 600.
+    let cases: [(u32, [i128; 3], i128); 2] = [(0, [438, 280, 280], 2), (4_000, [400, 300, 300], 0)];
+
+    for (max_match_bps, expected, remainder) in cases {
+        let (env, client, admin) = setup();
+        let token = env.register_stellar_asset_contract_v2(admin.clone());
+        let token_admin = token::StellarAssetClient::new(&env, &token.address());
+        let token_client = token::TokenClient::new(&env, &token.address());
+        let funder = Address::generate(&env);
+        token_admin.mint(&funder, &1000);
+
+        let config = RoundConfig {
+            mechanism: VotingMechanism::QuadraticFunding,
+            max_match_bps,
+            ..round_config(&env)
+        };
+        let round_id = client.create_round(
+            &admin,
+            &1000,
+            &1000,
+            &2000,
+            &config,
+            &round_name(&env),
+            &round_hash(&env),
+            &None,
+        );
+        client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+        let submitters: [Address; 3] = core::array::from_fn(|_| Address::generate(&env));
+        let mut ids = Vec::new(&env);
+        for submitter in submitters.iter() {
+            ids.push_back(client.submit_project(
+                submitter,
+                &round_id,
+                &project_name(&env),
+                &project_hash(&env),
+                &None,
+                &None,
+            ));
+        }
+        open_voting(&client, &round_id);
+
+        let contributions: [(u64, i128); 7] = [
+            (ids.get(0).unwrap(), 4),
+            (ids.get(0).unwrap(), 9),
+            (ids.get(1).unwrap(), 16),
+            (ids.get(2).unwrap(), 1),
+            (ids.get(2).unwrap(), 1),
+            (ids.get(2).unwrap(), 1),
+            (ids.get(2).unwrap(), 1),
+        ];
+        for (submission_id, amount) in contributions {
+            let donor = Address::generate(&env);
+            client.add_voter(&donor);
+            token_admin.mint(&donor, &amount);
+            client.contribute(&donor, &submission_id, &token.address(), &amount);
+        }
+        assert_eq!(client.get_voter_count(&round_id), 7);
+        assert_eq!(client.get_submission(&ids.get(0).unwrap()).total_votes, 25);
+
+        // Contributions go straight to the submissions
+        assert_eq!(token_client.balance(&submitters[0]), 13);
+        assert_eq!(token_client.balance(&submitters[2]), 4);
+
+        client.close_voting(&admin, &round_id, &true, &false);
+        let allocations = client.get_allocations(&round_id);
+        for (submission_id, amount) in ids.iter().zip(expected.iter()) {
+            assert_eq!(allocations.get(submission_id), Some(*amount));
+        }
+
+        client.disburse_funds(&admin, &round_id, &token.address(), &0, &10);
+        assert_eq!(token_client.balance(&submitters[0]), 13 + expected[0]);
+        assert_eq!(token_client.balance(&submitters[1]), 16 + expected[1]);
+        assert_eq!(
+            stored_remainder(&env, &client, round_id, &token.address()),
+            remainder
+        );
+    }
+}
+
+#[test]
+fn test_quadratic_funding_contributions_are_validated() {
+    let (env, client, admin) = setup();
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let other = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let config = RoundConfig {
+        mechanism: VotingMechanism::QuadraticFunding,
+        ..round_config(&env)
+    };
+    assert_eq!(
+        client.try_create_round(
+            &admin,
+            &1000,
+            &1000,
+            &2000,
+            &RoundConfig {
+                max_match_bps: 10_001,
+                ..config.clone()
+            },
+            &round_name(&env),
+            &round_hash(&env),
+            &None,
+        ),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config,
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submitter = Address::generate(&env);
+    let submission_id = client.submit_project(
+        &submitter,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+
+    let donor = Address::generate(&env);
+    client.add_voter(&donor);
+    token_admin.mint(&donor, &100);
+    assert_eq!(
+        client.try_contribute(&donor, &submission_id, &token.address(), &10),
+        Err(Ok(ContractError::InvalidPhase))
+    );
+    open_voting(&client, &round_id);
+
+    // A donor contributing twice is matched on their total, not on each contribution
+    client.contribute(&donor, &submission_id, &token.address(), &4);
+    client.contribute(&donor, &submission_id, &token.address(), &5);
+    assert_eq!(client.get_submission(&submission_id).total_votes, 9);
+    assert_eq!(
+        client.get_contributions(&round_id, &donor),
+        Map::from_array(&env, [(submission_id, 9)])
+    );
+    assert_eq!(client.get_voter_count(&round_id), 1);
+    let event = env.events().all().last().unwrap();
+    assert_eq!(
+        event.1,
+        (symbol_short!("CONTRIB"), round_id, submission_id).into_val(&env)
+    );
+    assert_eq!(
+        <(Address, Address, i128)>::try_from_val(&env, &event.2).unwrap(),
+        (donor.clone(), token.address(), 5)
+    );
+
+    assert_eq!(
+        client.try_contribute(&donor, &submission_id, &token.address(), &0),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+    assert_eq!(
+        client.try_contribute(&donor, &submission_id, &other.address(), &10),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+    assert_eq!(
+        client.try_contribute(
+            &Address::generate(&env),
+            &submission_id,
+            &token.address(),
+            &10
+        ),
+        Err(Ok(ContractError::NotAVoter))
+    );
+    client.add_voter(&submitter);
+    assert_eq!(
+        client.try_contribute(&submitter, &submission_id, &token.address(), &10),
+        Err(Ok(ContractError::SelfVoteNotAllowed))
+    );
+
+    // Quadratic funding rounds take no credit ballots
+    assert_eq!(
+        client.try_allocate_votes(
+            &donor,
+            &round_id,
+            &Map::from_array(&env, [(submission_id, 1)])
+        ),
+        Err(Ok(ContractError::InvalidAllocations))
+    );
+
+    // Floors of the integer square root
+    for (value, root) in [
+        (0u128, 0u128),
+        (1, 1),
+        (2, 1),
+        (3, 1),
+        (4, 2),
+        (8, 2),
+        (99, 9),
+    ] {
+        assert_eq!(RetroPGFContract::integer_sqrt(value), root);
+    }
+    assert_eq!(RetroPGFContract::integer_sqrt(u128::MAX), u64::MAX as u128);
+}
+
+#[test]
+fn test_vesting_payouts() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let token_client = token::TokenClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token_admin.mint(&funder, &2000);
+
+    // The cliff can't outlast the schedule
+    let vesting = |cliff_seconds, duration_seconds| RoundConfig {
+        cliff_seconds,
+        duration_seconds,
+        ..round_config(&env)
+    };
+    assert_eq!(
+        client.try_create_round(
+            &admin,
+            &1000,
+            &1000,
+            &2000,
+            &vesting(1001, 1000),
+            &round_name(&env),
+            &round_hash(&env),
+            &None,
+        ),
+        Err(Ok(ContractError::InvalidDeadline))
+    );
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &vesting(200, 1000),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let alice_submission = client.submit_project(
+        &alice,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    let bob_submission = client.submit_project(
+        &bob,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(alice_submission, 15);
+    allocations.set(bob_submission, 5);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    assert_eq!(
+        client.try_claim_vested(&alice_submission),
+        Err(Ok(ContractError::FundsNotDisbursed))
+    );
+    client.close_voting(&admin, &round_id, &true, &false);
+
+    // Disbursing starts the payouts vesting instead of transferring them
+    env.ledger().with_mut(|li| li.timestamp = 10_000);
+    client.disburse_funds(&admin, &round_id, &token.address(), &0, &10);
+    assert_eq!(token_client.balance(&alice), 0);
+    assert_eq!(token_client.balance(&client.address), 1000);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Disbursed);
+
+    let vested = |at| {
+        client
+            .get_vested(&alice_submission, &at)
+            .get(token.address())
+    };
+    assert_eq!(vested(10_199), Some(0));
+    assert_eq!(vested(10_200), Some(150));
+    assert_eq!(vested(10_500), Some(375));
+    assert_eq!(vested(11_000), Some(750));
+    assert_eq!(vested(20_000), Some(750));
+
+    // Nothing is claimable before the cliff
+    env.ledger().with_mut(|li| li.timestamp = 10_100);
+    assert_eq!(
+        client.claim_vested(&alice_submission),
+        Map::from_array(&env, [(token.address(), 0)])
+    );
+    assert_eq!(token_client.balance(&alice), 0);
+
+    // Halfway through, half has vested
+    env.ledger().with_mut(|li| li.timestamp = 10_500);
+    assert_eq!(
+        client.claim_vested(&alice_submission),
+        Map::from_array(&env, [(token.address(), 375)])
+    );
+    assert_eq!(token_client.balance(&alice), 375);
+    let event = env.events().all().last().unwrap();
+    assert_eq!(
+        event.1,
+        (symbol_short!("VEST_CLM"), round_id, alice_submission).into_val(&env)
+    );
+    assert_eq!(
+        <(Address, i128)>::try_from_val(&env, &event.2).unwrap(),
+        (token.address(), 375)
+    );
+
+    // Once fully vested the position drains, and later claims transfer nothing
+    env.ledger().with_mut(|li| li.timestamp = 12_000);
+    assert_eq!(
+        client.claim_vested(&alice_submission),
+        Map::from_array(&env, [(token.address(), 375)])
+    );
+    assert_eq!(
+        client.claim_vested(&alice_submission),
+        Map::from_array(&env, [(token.address(), 0)])
+    );
+    client.claim_vested(&bob_submission);
+    assert_eq!(token_client.balance(&alice), 750);
+    assert_eq!(token_client.balance(&bob), 250);
+    assert_eq!(token_client.balance(&client.address), 0);
+
+    // In the claim model, claiming starts the payout vesting
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &13_000,
+        &14_000,
+        &vesting(0, 100),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    let submission_id = client.submit_project(
+        &alice,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_id, 20);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    client.close_voting(&admin, &round_id, &true, &false);
+    client.finalize_disbursement(&round_id, &token.address(), &20_000);
+
+    assert_eq!(client.claim(&submission_id, &token.address()), 1000);
+    assert_eq!(token_client.balance(&alice), 750);
+    assert_eq!(
+        client.get_vested(&submission_id, &12_050),
+        Map::from_array(&env, [(token.address(), 500)])
+    );
+    env.ledger().with_mut(|li| li.timestamp = 12_100);
+    client.claim_vested(&submission_id);
+    assert_eq!(token_client.balance(&alice), 1750);
+}
+
+#[test]
+fn test_milestone_tranches() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let token_client = token::TokenClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token_admin.mint(&funder, &2000);
+
+    // Tranches must each release something and add up to the whole allocation
+    let milestones = |tranches: &[u32]| RoundConfig {
+        tranches: Vec::from_slice(&env, tranches),
+        ..round_config(&env)
+    };
+    for tranches in [&[5000, 4000][..], &[0, 10_000]] {
+        assert_eq!(
+            client.try_create_round(
+                &admin,
+                &1000,
+                &1000,
+                &2000,
+                &milestones(tranches),
+                &round_name(&env),
+                &round_hash(&env),
+                &None,
+            ),
+            Err(Ok(ContractError::InvalidAmount))
+        );
+    }
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &milestones(&[5000, 3000, 2000]),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let alice_submission = client.submit_project(
+        &alice,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    let bob_submission = client.submit_project(
+        &bob,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(alice_submission, 15);
+    allocations.set(bob_submission, 5);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    assert_eq!(
+        client.try_release_tranche(&round_id, &1),
+        Err(Ok(ContractError::AllocationsNotComputed))
+    );
+    client.close_voting(&admin, &round_id, &true, &false);
+
+    // Only the up-front tranche is paid, however often the round is disbursed
+    client.disburse_funds(&admin, &round_id, &token.address(), &0, &10);
+    client.disburse_funds(&admin, &round_id, &token.address(), &0, &10);
+    assert_eq!(token_client.balance(&alice), 375);
+    assert_eq!(token_client.balance(&bob), 125);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Closed);
+
+    // Tranches are released once each, in order
+    for tranche_index in [0, 2] {
+        assert_eq!(
+            client.try_release_tranche(&round_id, &tranche_index),
+            Err(Ok(ContractError::InvalidPhase))
+        );
+    }
+    assert_eq!(
+        client.try_release_tranche(&round_id, &3),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+    client.release_tranche(&round_id, &1);
+    let event = env.events().all().last().unwrap();
+    assert_eq!(event.1, (symbol_short!("TRANCHE"), round_id).into_val(&env));
+    assert_eq!(u32::try_from_val(&env, &event.2).unwrap(), 1);
+    assert_eq!(
+        client.try_release_tranche(&round_id, &1),
+        Err(Ok(ContractError::InvalidPhase))
+    );
+
+    client.disburse_funds(&admin, &round_id, &token.address(), &0, &10);
+    assert_eq!(token_client.balance(&alice), 600);
+    assert_eq!(token_client.balance(&bob), 200);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Closed);
+
+    // The last tranche pays out the rest of each allocation and completes the round
+    client.release_tranche(&round_id, &2);
+    client.disburse_funds(&admin, &round_id, &token.address(), &0, &10);
+    assert_eq!(token_client.balance(&alice), 750);
+    assert_eq!(token_client.balance(&bob), 250);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Disbursed);
+
+    // In the claim model, each claim pays the tranches released since the last one
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &milestones(&[5000, 5000]),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    let submission_id = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    let submitter = client.get_submission(&submission_id).submitter;
+    open_voting(&client, &round_id);
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_id, 20);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    client.close_voting(&admin, &round_id, &true, &false);
+    client.finalize_disbursement(&round_id, &token.address(), &5000);
+
+    assert_eq!(client.get_claimable(&submission_id, &token.address()), 500);
+    assert_eq!(client.claim(&submission_id, &token.address()), 500);
+    assert_eq!(client.get_claimable(&submission_id, &token.address()), 0);
+    assert_eq!(
+        client.try_claim(&submission_id, &token.address()),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+
+    client.release_tranche(&round_id, &1);
+    assert_eq!(client.get_claimable(&submission_id, &token.address()), 500);
+    assert_eq!(client.claim(&submission_id, &token.address()), 500);
+    assert_eq!(token_client.balance(&submitter), 1000);
+    assert_eq!(
+        client.try_claim(&submission_id, &token.address()),
+        Err(Ok(ContractError::AlreadyClaimed))
+    );
+    assert_eq!(token_client.balance(&client.address), 0);
+}
+
+#[test]
+fn test_refund_in_batches() {
+    let (env, client, admin) = setup();
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let token_client = token::TokenClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token_admin.mint(&funder, &1000);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    let donors: [Address; 21] = core::array::from_fn(|_| Address::generate(&env));
+    for donor in donors.iter() {
+        token_admin.mint(donor, &10);
+        client.donate(donor, &round_id, &token.address(), &10);
+    }
+
+    // Escrow no funder is owed, such as a legacy deposit, goes to the refund address last
+    token_admin.mint(&client.address, &50);
+    env.as_contract(&client.address, || {
+        let escrow_key = DataKey::Escrow(round_id, token.address());
+        env.storage().persistent().set(&escrow_key, &1260i128);
+    });
+
+    // Only cancelled rounds are refunded
+    assert_eq!(
+        client.try_refund(&round_id),
+        Err(Ok(ContractError::InvalidPhase))
+    );
+    let refund_to = Address::generate(&env);
+    client.cancel_round(&round_id, &refund_to);
+
+    // The depositor and the first 19 donors fit in the first batch
+    assert_eq!(client.refund(&round_id), 20);
+    assert_eq!(token_client.balance(&funder), 1000);
+    assert_eq!(token_client.balance(&donors[18]), 10);
+    assert_eq!(token_client.balance(&donors[19]), 0);
+    assert_eq!(token_client.balance(&refund_to), 0);
+
+    assert_eq!(client.refund(&round_id), 2);
+    assert!(donors.iter().all(|donor| token_client.balance(donor) == 10));
+    assert_eq!(token_client.balance(&refund_to), 50);
+    assert_eq!(token_client.balance(&client.address), 0);
+    let event = env.events().all().last().unwrap();
+    assert_eq!(
+        event.1,
+        (symbol_short!("REFUND"), round_id, refund_to.clone()).into_val(&env)
+    );
+    assert_eq!(
+        <(Address, i128)>::try_from_val(&env, &event.2).unwrap(),
+        (token.address(), 50)
+    );
+
+    // Nobody is refunded twice
+    assert_eq!(client.refund(&round_id), 0);
+    assert_eq!(token_client.balance(&funder), 1000);
+    assert_eq!(token_client.balance(&refund_to), 50);
+}
+
+#[test]
+fn test_halt_disbursement() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let token_client = token::TokenClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token_admin.mint(&funder, &2000);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let alice_submission = client.submit_project(
+        &alice,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    let bob_submission = client.submit_project(
+        &bob,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(alice_submission, 15);
+    allocations.set(bob_submission, 5);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    assert_eq!(
+        client.try_halt_disbursement(&round_id),
+        Err(Ok(ContractError::AllocationsNotComputed))
+    );
+    client.close_voting(&admin, &round_id, &true, &false);
+    client.disburse_funds(&admin, &round_id, &token.address(), &0, &1);
+    assert_eq!(token_client.balance(&alice), 750);
+
+    // Halting mid-run freezes the remaining transfers
+    client.halt_disbursement(&round_id);
+    assert!(client.get_round(&round_id).halted);
+    let event = env.events().all().last().unwrap();
+    assert_eq!(event.1, (symbol_short!("HALTED"), round_id).into_val(&env));
+    assert_eq!(
+        client.try_disburse_funds(&admin, &round_id, &token.address(), &0, &10),
+        Err(Ok(ContractError::DisbursementHalted))
+    );
+    assert_eq!(
+        client.try_finalize_disbursement(&round_id, &token.address(), &5000),
+        Err(Ok(ContractError::DisbursementHalted))
+    );
+    assert_eq!(
+        client.try_halt_disbursement(&round_id),
+        Err(Ok(ContractError::DisbursementHalted))
+    );
+
+    // The payout table can be fixed while halted, and only then
+    let wallet = Address::generate(&env);
+    client.set_payout_address(&bob, &bob_submission, &wallet);
+
+    client.resume_disbursement(&round_id);
+    let event = env.events().all().last().unwrap();
+    assert_eq!(event.1, (symbol_short!("RESUMED"), round_id).into_val(&env));
+    assert_eq!(
+        client.try_resume_disbursement(&round_id),
+        Err(Ok(ContractError::InvalidPhase))
+    );
+    assert_eq!(
+        client.try_set_payout_address(&bob, &bob_submission, &bob),
+        Err(Ok(ContractError::RoundNotActive))
+    );
+
+    // Resuming pays the rest without touching what was already transferred
+    client.disburse_funds(&admin, &round_id, &token.address(), &0, &10);
+    assert_eq!(token_client.balance(&alice), 750);
+    assert_eq!(token_client.balance(&wallet), 250);
+    assert_eq!(token_client.balance(&bob), 0);
+
+    // Claims are frozen as well
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    let submission_id = client.submit_project(
+        &alice,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_id, 20);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    client.close_voting(&admin, &round_id, &true, &false);
+    client.finalize_disbursement(&round_id, &token.address(), &5000);
+
+    client.halt_disbursement(&round_id);
+    assert_eq!(
+        client.try_claim(&submission_id, &token.address()),
+        Err(Ok(ContractError::DisbursementHalted))
+    );
+    client.resume_disbursement(&round_id);
+    assert_eq!(client.claim(&submission_id, &token.address()), 1000);
+}
+
+#[test]
+fn test_override_allocation() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let token_client = token::TokenClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token_admin.mint(&funder, &1000);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    let submitters: [Address; 3] = core::array::from_fn(|_| Address::generate(&env));
+    let submission_ids: [u64; 3] = core::array::from_fn(|i| {
+        client.submit_project(
+            &submitters[i],
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None,
+        )
+    });
+    open_voting(&client, &round_id);
+
+    let mut allocations = Map::new(&env);
+    for (submission_id, votes) in submission_ids.iter().zip([10, 6, 4]) {
+        allocations.set(*submission_id, votes);
+    }
+    client.allocate_votes(&voter, &round_id, &allocations);
+    let reason = symbol_short!("DISQUAL");
+    assert_eq!(
+        client.try_override_allocation(&round_id, &submission_ids[2], &0, &reason),
+        Err(Ok(ContractError::AllocationsNotComputed))
+    );
+    client.close_voting(&admin, &round_id, &true, &false);
+
+    // Every override is reported with the old and new amounts and the reason
+    client.override_allocation(&round_id, &submission_ids[2], &0, &reason);
+    let event = env.events().all().last().unwrap();
+    assert_eq!(
+        event.1,
+        (symbol_short!("OVERRIDE"), round_id, submission_ids[2]).into_val(&env)
+    );
+    assert_eq!(
+        <(i128, i128, Symbol)>::try_from_val(&env, &event.2).unwrap(),
+        (200, 0, reason.clone())
+    );
+
+    // The allocations may not add up to more than the pool
+    let reason = symbol_short!("COURT");
+    assert_eq!(
+        client.try_override_allocation(&round_id, &submission_ids[0], &800, &reason),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+    client.override_allocation(&round_id, &submission_ids[0], &700, &reason);
+    assert_eq!(
+        client.get_allocations(&round_id),
+        Map::from_array(
+            &env,
+            [
+                (submission_ids[0], 700),
+                (submission_ids[1], 300),
+                (submission_ids[2], 0)
+            ]
+        )
+    );
+
+    // Once anything has been paid out the allocations are final
+    client.disburse_funds(&admin, &round_id, &token.address(), &0, &1);
+    assert_eq!(
+        client.try_override_allocation(&round_id, &submission_ids[1], &100, &reason),
+        Err(Ok(ContractError::FundsAlreadyDisbursed))
+    );
+    client.disburse_funds(&admin, &round_id, &token.address(), &1, &10);
+    assert_eq!(token_client.balance(&submitters[0]), 700);
+    assert_eq!(token_client.balance(&submitters[1]), 300);
+    assert_eq!(token_client.balance(&submitters[2]), 0);
+    assert_eq!(
+        stored_remainder(&env, &client, round_id, &token.address()),
+        0
+    );
+    assert_eq!(
+        client.try_override_allocation(&round_id, &submission_ids[1], &100, &reason),
+        Err(Ok(ContractError::FundsAlreadyDisbursed))
+    );
+}
+
+#[test]
+fn test_challenge_period() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let token_client = token::TokenClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token_admin.mint(&funder, &1000);
+
+    let mut config = round_config(&env);
+    config.challenge_period_seconds = 100;
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config,
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+    let alice = Address::generate(&env);
+    let submission_id = client.submit_project(
+        &alice,
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_id, 10);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    assert_eq!(
+        client.try_get_disbursement_unlock_time(&round_id),
+        Err(Ok(ContractError::AllocationsNotComputed))
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = 2001);
+    client.close_voting(&admin, &round_id, &true, &false);
+    assert_eq!(client.get_round(&round_id).closed_at, 2001);
+    assert_eq!(client.get_disbursement_unlock_time(&round_id), 2101);
+
+    // Nothing can be paid out while the window runs
+    assert_eq!(
+        client.try_disburse_funds(&admin, &round_id, &token.address(), &0, &10),
+        Err(Ok(ContractError::ChallengePeriodActive))
+    );
+    assert_eq!(
+        client.try_finalize_disbursement(&round_id, &token.address(), &5000),
+        Err(Ok(ContractError::ChallengePeriodActive))
+    );
+
+    // The window can be extended once, but never shortened
+    assert_eq!(
+        client.try_extend_challenge_period(&round_id, &2050),
+        Err(Ok(ContractError::InvalidDeadline))
+    );
+    client.extend_challenge_period(&round_id, &2200);
+    let event = env.events().all().last().unwrap();
+    assert_eq!(
+        event.1,
+        (symbol_short!("CHAL_EXT"), round_id).into_val(&env)
+    );
+    assert_eq!(
+        <(u64, u64)>::try_from_val(&env, &event.2).unwrap(),
+        (2101, 2200)
+    );
+    assert_eq!(client.get_disbursement_unlock_time(&round_id), 2200);
+    assert_eq!(
+        client.try_extend_challenge_period(&round_id, &2300),
+        Err(Ok(ContractError::InvalidPhase))
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = 2199);
+    assert_eq!(
+        client.try_disburse_funds(&admin, &round_id, &token.address(), &0, &10),
+        Err(Ok(ContractError::ChallengePeriodActive))
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = 2200);
+    client.disburse_funds(&admin, &round_id, &token.address(), &0, &10);
+    assert_eq!(token_client.balance(&alice), 1000);
+}
+
+#[test]
+fn test_blacklist() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    let spammer = Address::generate(&env);
+    client.add_voter(&voter);
+    client.add_voter(&spammer);
+
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let token_client = token::TokenClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token_admin.mint(&funder, &1000);
+
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    client.deposit_funds(&funder, &round_id, &token.address(), &1000);
+
+    // Blacklisted addresses can't submit
+    client.blacklist(&spammer);
+    assert!(client.is_blacklisted(&spammer));
+    let event = env.events().all().last().unwrap();
+    assert_eq!(
+        event.1,
+        (symbol_short!("BLOCKED"), spammer.clone()).into_val(&env)
+    );
+    assert_eq!(
+        client.try_submit_project(
+            &spammer,
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None
+        ),
+        Err(Ok(ContractError::AddressBlocked))
+    );
+
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let carol = Address::generate(&env);
+    let submission_ids = [&alice, &bob, &carol].map(|submitter| {
+        client.submit_project(
+            submitter,
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None,
+        )
+    });
+    let wallet = Address::generate(&env);
+    client.set_payout_address(&carol, &submission_ids[2], &wallet);
+    open_voting(&client, &round_id);
+
+    // Nor vote
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_ids[0], 1);
+    assert_eq!(
+        client.try_allocate_votes(&spammer, &round_id, &allocations),
+        Err(Ok(ContractError::AddressBlocked))
+    );
+    allocations.set(submission_ids[1], 1);
+    allocations.set(submission_ids[2], 3);
+    client.allocate_votes(&voter, &round_id, &allocations);
+    assert_eq!(
+        client.try_update_votes(&spammer, &round_id, &allocations),
+        Err(Ok(ContractError::AddressBlocked))
+    );
+
+    // Nor hand their credits to a voter, take them from one, commit a ballot or attest
+    let friend = Address::generate(&env);
+    client.add_voter(&friend);
+    assert_eq!(
+        client.try_delegate_votes(&spammer, &friend, &round_id),
+        Err(Ok(ContractError::AddressBlocked))
+    );
+    assert_eq!(
+        client.try_delegate_votes(&friend, &spammer, &round_id),
+        Err(Ok(ContractError::AddressBlocked))
+    );
+    assert_eq!(
+        client.try_commit_vote(&spammer, &round_id, &BytesN::from_array(&env, &[0; 32])),
+        Err(Ok(ContractError::AddressBlocked))
+    );
+    client.add_reviewer(&spammer);
+    assert_eq!(
+        client.try_attest(&spammer, &submission_ids[0], &1),
+        Err(Ok(ContractError::AddressBlocked))
+    );
+
+    // A submitter blacklisted after the votes are in loses them at close, and their share
+    // goes to the other submissions
+    client.blacklist(&bob);
+    client.close_voting(&admin, &round_id, &true, &false);
+    assert_eq!(
+        client.get_submission(&submission_ids[1]).status,
+        SubmissionStatus::Rejected
+    );
+    let allocations = client.get_allocations(&round_id);
+    assert_eq!(allocations.get(submission_ids[0]), Some(250));
+    assert_eq!(allocations.get(submission_ids[1]), None);
+    assert_eq!(allocations.get(submission_ids[2]), Some(750));
+
+    // A blacklisted payout address only holds back its own payout
+    client.blacklist(&wallet);
+    client.disburse_funds(&admin, &round_id, &token.address(), &0, &10);
+    assert_eq!(token_client.balance(&alice), 250);
+    assert_eq!(token_client.balance(&wallet), 0);
+    assert_eq!(client.get_round(&round_id).phase, RoundPhase::Closed);
+
+    client.finalize_disbursement(&round_id, &token.address(), &5000);
+    assert_eq!(
+        client.try_claim(&submission_ids[2], &token.address()),
+        Err(Ok(ContractError::AddressBlocked))
+    );
+
+    client.unblacklist(&wallet);
+    assert!(!client.is_blacklisted(&wallet));
+    let event = env.events().all().last().unwrap();
+    assert_eq!(
+        event.1,
+        (symbol_short!("UNBLOCKED"), wallet.clone()).into_val(&env)
+    );
+    assert_eq!(client.claim(&submission_ids[2], &token.address()), 750);
+    assert_eq!(token_client.balance(&wallet), 750);
+
+    // Nor rank submissions or contribute to them
+    for mechanism in [VotingMechanism::Ranked, VotingMechanism::QuadraticFunding] {
+        let config = RoundConfig {
+            mechanism,
+            ..round_config(&env)
+        };
+        let round_id = client.create_round(
+            &admin,
+            &1000,
+            &1000,
+            &2000,
+            &config,
+            &round_name(&env),
+            &round_hash(&env),
+            &None,
+        );
+        let submission_id = client.submit_project(
+            &alice,
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None,
+        );
+        open_voting(&client, &round_id);
+        let result = if mechanism == VotingMechanism::Ranked {
+            let ranking = Vec::from_array(&env, [submission_id]);
+            client.try_submit_ranking(&spammer, &round_id, &ranking)
+        } else {
+            client.try_contribute(&spammer, &submission_id, &token.address(), &10)
+        };
+        assert_eq!(result, Err(Ok(ContractError::AddressBlocked)));
+    }
+}
+
+#[test]
+fn test_submissions_by_submitter() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let token_admin = token::StellarAssetClient::new(&env, &token.address());
+    let funder = Address::generate(&env);
+    token_admin.mint(&funder, &2000);
+
+    let round_ids: [u64; 3] = core::array::from_fn(|_| {
+        client.create_round(
+            &admin,
+            &1000,
+            &1000,
+            &2000,
+            &round_config(&env),
+            &round_name(&env),
+            &round_hash(&env),
+            &None,
+        )
+    });
+    client.deposit_funds(&funder, &round_ids[0], &token.address(), &1000);
+    client.deposit_funds(&funder, &round_ids[1], &token.address(), &1000);
+
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let alice_submissions = round_ids.map(|round_id| {
+        client.submit_project(
+            &alice,
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None,
+        )
+    });
+    let bob_submission = client.submit_project(
+        &bob,
+        &round_ids[0],
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+
+    // Withdrawn submissions drop out of the index
+    client.withdraw_submission(&alice, &alice_submissions[2]);
+    let submissions = client.get_submissions_by_submitter(&alice, &0, &10);
+    assert_eq!(submissions.len(), 2);
+    assert_eq!(submissions.get_unchecked(0).id, alice_submissions[0]);
+    assert_eq!(submissions.get_unchecked(1).id, alice_submissions[1]);
+    let page = client.get_submissions_by_submitter(&alice, &1, &10);
+    assert_eq!(page.len(), 1);
+    assert_eq!(page.get_unchecked(0).id, alice_submissions[1]);
+    assert_eq!(client.get_submissions_by_submitter(&bob, &0, &10).len(), 1);
+
+    for (index, round_id) in round_ids.iter().take(2).enumerate() {
+        open_voting(&client, round_id);
+        let mut allocations = Map::new(&env);
+        allocations.set(alice_submissions[index], 1);
+        if index == 0 {
+            allocations.set(bob_submission, 1);
+        }
+        client.allocate_votes(&voter, round_id, &allocations);
+        client.close_voting(&admin, round_id, &true, &false);
+    }
+
+    // Nothing is claimable until the token is finalized for claims
+    assert_eq!(client.get_total_claimable(&alice, &token.address()), 0);
+    client.finalize_disbursement(&round_ids[0], &token.address(), &5000);
+    client.finalize_disbursement(&round_ids[1], &token.address(), &5000);
+    assert_eq!(client.get_total_claimable(&alice, &token.address()), 1500);
+    assert_eq!(client.get_total_claimable(&bob, &token.address()), 500);
+
+    client.claim(&alice_submissions[1], &token.address());
+    assert_eq!(client.get_total_claimable(&alice, &token.address()), 500);
+}
+
+#[test]
+fn test_voter_history() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+
+    let round_ids: [u64; 3] = core::array::from_fn(|_| {
+        client.create_round(
+            &admin,
+            &1000,
+            &1000,
+            &2000,
+            &round_config(&env),
+            &round_name(&env),
+            &round_hash(&env),
+            &None,
+        )
+    });
+    let submission_ids = round_ids.map(|round_id| {
+        let submitter = Address::generate(&env);
+        let submission_id = client.submit_project(
+            &submitter,
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None,
+        );
+        open_voting(&client, &round_id);
+        submission_id
+    });
+
+    // Rounds are listed in the order the voter first voted in them
+    for index in [2, 0] {
+        let mut allocations = Map::new(&env);
+        allocations.set(submission_ids[index], 3);
+        client.allocate_votes(&voter, &round_ids[index], &allocations);
+    }
+    let history = client.get_voter_history(&voter, &0, &10);
+    assert_eq!(history.len(), 2);
+    assert_eq!(
+        history.get_unchecked(0),
+        (
+            round_ids[2],
+            Map::from_array(&env, [(submission_ids[2], 3)])
+        )
+    );
+    assert_eq!(
+        history.get_unchecked(1),
+        (
+            round_ids[0],
+            Map::from_array(&env, [(submission_ids[0], 3)])
+        )
+    );
+
+    // Revoked ballots stay listed with an empty allocation, and voting again doesn't list
+    // the round twice
+    client.revoke_votes(&voter, &round_ids[2]);
+    let history = client.get_voter_history(&voter, &0, &10);
+    assert_eq!(history.get_unchecked(0), (round_ids[2], Map::new(&env)));
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_ids[2], 2);
+    client.allocate_votes(&voter, &round_ids[2], &allocations);
+    let history = client.get_voter_history(&voter, &0, &10);
+    assert_eq!(history.len(), 2);
+    assert_eq!(
+        history.get_unchecked(0),
+        (
+            round_ids[2],
+            Map::from_array(&env, [(submission_ids[2], 2)])
+        )
+    );
+
+    let page = client.get_voter_history(&voter, &1, &1);
+    assert_eq!(page.len(), 1);
+    assert_eq!(page.get_unchecked(0).0, round_ids[0]);
+    assert!(client
+        .get_voter_history(&Address::generate(&env), &0, &10)
+        .is_empty());
+}
+
+#[test]
+fn test_submission_voters() {
+    let (env, client, admin) = setup();
+    env.budget().reset_unlimited();
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &round_config(&env),
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let alice_submission = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    let bob_submission = client.submit_project(
+        &Address::generate(&env),
+        &round_id,
+        &project_name(&env),
+        &project_hash(&env),
+        &None,
+        &None,
+    );
+    open_voting(&client, &round_id);
+
+    // Enough voters to spill into a second chunk
+    let voters: [Address; 52] = core::array::from_fn(|_| Address::generate(&env));
+    for (index, voter) in voters.iter().enumerate() {
+        client.add_voter(voter);
+        let mut allocations = Map::new(&env);
+        allocations.set(alice_submission, 1 + index as u64 % 3);
+        client.allocate_votes(voter, &round_id, &allocations);
+    }
+    let page = client.get_submission_voters(&alice_submission, &0, &100);
+    assert_eq!(page.len(), 50);
+    assert_eq!(page.get_unchecked(0), (voters[0].clone(), 1));
+    let page = client.get_submission_voters(&alice_submission, &49, &10);
+    assert_eq!(page.len(), 3);
+    assert_eq!(page.get_unchecked(0), (voters[49].clone(), 2));
+    assert_eq!(page.get_unchecked(2), (voters[51].clone(), 1));
+
+    // Merged ballots and updates adjust the voter's entry in place
+    let mut allocations = Map::new(&env);
+    allocations.set(alice_submission, 1);
+    client.allocate_votes(&voters[1], &round_id, &allocations);
+    assert_eq!(
+        client.get_submission_voters(&alice_submission, &1, &1),
+        vec![&env, (voters[1].clone(), 3)]
+    );
+    let mut allocations = Map::new(&env);
+    allocations.set(bob_submission, 2);
+    client.update_votes(&voters[2], &round_id, &allocations);
+    assert_eq!(
+        client.get_submission_voters(&bob_submission, &0, &10),
+        vec![&env, (voters[2].clone(), 2)]
+    );
+
+    // A voter dropping out of a submission is replaced by the last voter
+    assert_eq!(
+        client.get_submission_voters(&alice_submission, &2, &1),
+        vec![&env, (voters[51].clone(), 1)]
+    );
+    client.revoke_votes(&voters[0], &round_id);
+    let page = client.get_submission_voters(&alice_submission, &0, &100);
+    assert_eq!(page.len(), 50);
+    assert_eq!(page.get_unchecked(0), (voters[50].clone(), 3));
+    assert!(!page.iter().any(|(voter, _)| voter == voters[0]));
+    assert!(client
+        .get_submission_voters(&alice_submission, &50, &10)
+        .is_empty());
+}
+
+#[test]
+fn test_preview_allocations() {
+    let (env, client, admin) = setup();
+    let voter = Address::generate(&env);
+    client.add_voter(&voter);
+    client.set_fee(&500, &Address::generate(&env));
+
+    let mut config = round_config(&env);
+    config.top_n = 2;
+    let round_id = client.create_round(
+        &admin,
+        &1000,
+        &1000,
+        &2000,
+        &config,
+        &round_name(&env),
+        &round_hash(&env),
+        &None,
+    );
+    let submission_ids: [u64; 3] = core::array::from_fn(|_| {
+        client.submit_project(
+            &Address::generate(&env),
+            &round_id,
+            &project_name(&env),
+            &project_hash(&env),
+            &None,
+            &None,
+        )
+    });
+    open_voting(&client, &round_id);
+
+    let mut allocations = Map::new(&env);
+    allocations.set(submission_ids[0], 1);
+    allocations.set(submission_ids[1], 2);
+    allocations.set(submission_ids[2], 3);
+    client.allocate_votes(&voter, &round_id, &allocations);
+
+    // The preview applies the fee and cutoff without touching the round
+    let round = client.get_round(&round_id);
+    let preview = client.preview_allocations(&round_id);
+    assert_eq!(preview.get(submission_ids[0]), Some(0));
+    assert_eq!(preview.get(submission_ids[1]), Some(380));
+    assert_eq!(preview.get(submission_ids[2]), Some(570));
+    assert_eq!(client.get_round(&round_id), round);
+
+    // And matches what closing computes
+    client.close_voting(&admin, &round_id, &true, &false);
+    assert_eq!(client.get_allocations(&round_id), preview);
+    assert_eq!(
+        client.try_preview_allocations(&round_id),
+        Err(Ok(ContractError::RoundNotActive))
+    );
+}
+
+// Data of the event with the given topics published by the last invocation, if any
+fn event_data<T: TryFromVal<Env, Val>>(
+    env: &Env,
+    topics: impl IntoVal<Env, Vec<Val>>,
+) -> Option<T> {
+    let topics = topics.into_val(env);
+    env.events()
+        .all()
+        .iter()
+        .find(|event| event.1 == topics)
+        .and_then(|event| T::try_from_val(env, &event.2).ok())
+}
+
+#[test]
+fn test_full_round_lifecycle() {
+    let fixture = TestFixture::new();
+    let env = &fixture.env;
+    let client = &fixture.client;
+    let admin = &fixture.admin;
+    let token = &fixture.token;
+
+    // Creation
+    let round_id = fixture.create_round(1000, 1000, 2000, &round_config(env));
+    assert_eq!(
+        event_data::<RoundCreatedEvent>(env, (symbol_short!("RND_CREAT"), round_id)),
+        Some(RoundCreatedEvent {
+            round_id,
+            creator: admin.clone(),
+            funding_amount: 1000,
+            submission_deadline: 1000,
+            voting_deadline: 2000,
+            mechanism: VotingMechanism::Linear,
+        })
+    );
+    let round = client.get_round(&round_id);
+    assert_eq!(round.funding_amount, 1000);
+    assert_eq!(round.submission_deadline, 1000);
+    assert_eq!(round.voting_deadline, 2000);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Submission);
+    assert_eq!(client.get_round_config(&round_id), round_config(env));
+    assert_eq!(client.get_vote_credits(&round_id), 20);
+
+    fixture.fund(round_id, 1000);
+    assert_eq!(
+        event_data::<(Address, i128)>(
+            env,
+            (symbol_short!("DEPOSIT"), round_id, fixture.funder.clone())
+        ),
+        Some((token.clone(), 1000))
+    );
+
+    // Submissions are accepted until the submission deadline
+    let (alice, alice_submission) = fixture.submit_project(round_id);
+    let (bob, bob_submission) = fixture.submit_project(round_id);
+    let (carol, carol_submission) = fixture.submit_project(round_id);
+    assert_eq!(
+        event_data::<ProjectSubmittedEvent>(env, (symbol_short!("PROJ_SUB"), carol_submission)),
+        Some(ProjectSubmittedEvent {
+            submission_id: carol_submission,
+            round_id,
+            submitter: carol.clone(),
+            previous_submission_id: 0,
+        })
+    );
+    assert_eq!(client.get_submission(&alice_submission).submitter, alice);
+
+    fixture.pass_submission_deadline(round_id);
+    assert_eq!(
+        client.try_submit_project(
+            &Address::generate(env),
+            &round_id,
+            &project_name(env),
+            &project_hash(env),
+            &None,
+            &None
+        ),
+        Err(Ok(ContractError::SubmissionDeadlinePassed))
+    );
+
+    fixture.open_voting(round_id);
+    assert_eq!(
+        event_data::<(RoundPhase, RoundPhase)>(env, (symbol_short!("PHASE"), round_id)),
+        Some((RoundPhase::Submission, RoundPhase::Voting))
+    );
+
+    // Ballots may not spend more than the round's credits
+    let voter = fixture.add_voter();
+    assert_eq!(
+        client.try_allocate_votes(
+            &voter,
+            &round_id,
+            &fixture.ballot(&[(alice_submission, 18), (bob_submission, 3)])
+        ),
+        Err(Ok(ContractError::ExceededVoteLimit))
+    );
+    fixture.vote(
+        &voter,
+        round_id,
+        &fixture.ballot(&[(alice_submission, 18), (bob_submission, 2)]),
+    );
+    assert_eq!(
+        event_data::<VotesAllocatedEvent>(env, (symbol_short!("VOTE_ALC"), voter.clone())),
+        Some(VotesAllocatedEvent {
+            round_id,
+            voter: voter.clone(),
+            allocations: fixture.ballot(&[(alice_submission, 18), (bob_submission, 2)]),
+            credits_spent: 20,
+        })
+    );
+    assert_eq!(client.remaining_credits(&round_id, &voter), 0);
+
+    // Voting closes at the deadline and the skewed votes are allocated proportionally
+    fixture.pass_voting_deadline(round_id);
+    client.close_voting(admin, &round_id, &false, &false);
+    let allocations = client.get_allocations(&round_id);
+    assert_eq!(allocations.get(alice_submission), Some(900));
+    assert_eq!(allocations.get(bob_submission), Some(100));
+    assert_eq!(allocations.get(carol_submission), Some(0));
+    let allocations_hash: BytesN<32> = env.crypto().sha256(&allocations.to_xdr(env)).into();
+    assert_eq!(
+        event_data::<VotingClosedEvent>(env, (symbol_short!("VOTE_CLSD"), round_id)),
+        Some(VotingClosedEvent {
+            round_id,
+            total_votes: 20,
+            funded_votes: 20,
+            distributable: 1000,
+            fee: 0,
+            allocations_hash,
+        })
+    );
+
+    // Disbursement pays the submitters from the round's escrow
+    client.disburse_funds(admin, &round_id, token, &0, &10);
+    assert_eq!(
+        event_data::<FundsDisbursedEvent>(env, (symbol_short!("FUND_DISB"), round_id)),
+        Some(FundsDisbursedEvent {
+            round_id,
+            token: token.clone(),
+            pool: 1000,
+            disbursed: 1000,
+            remainder: 0,
+        })
+    );
+    assert_eq!(fixture.balance(&alice), 900);
+    assert_eq!(fixture.balance(&bob), 100);
+    assert_eq!(fixture.balance(&carol), 0);
+    assert_eq!(fixture.balance(&client.address), 0);
+    assert_eq!(client.get_round_phase(&round_id), RoundPhase::Disbursed);
+    assert_eq!(
+        client.try_disburse_funds(admin, &round_id, token, &0, &10),
+        Err(Ok(ContractError::FundsAlreadyDisbursed))
+    );
+}
+
+#[test]
+fn test_close_with_zero_votes() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let round_id = fixture.create_active_round(1000, 1000);
+    let submission_ids = fixture.submit_n_projects(round_id, 2);
+    fixture.open_voting(round_id);
+
+    fixture.close(round_id);
+    let closed =
+        event_data::<VotingClosedEvent>(&fixture.env, (symbol_short!("VOTE_CLSD"), round_id))
+            .unwrap();
+    assert_eq!((closed.total_votes, closed.distributable), (0, 1000));
+    let allocations = client.get_allocations(&round_id);
+    for submission_id in submission_ids.iter() {
+        assert_eq!(allocations.get(submission_id), Some(0));
+    }
+
+    // Nothing is paid out and the whole funding is left to withdraw
+    client.disburse_funds(&fixture.admin, &round_id, &fixture.token, &0, &10);
+    let treasury = Address::generate(&fixture.env);
+    assert_eq!(
+        client.withdraw_remainder(&round_id, &fixture.token, &treasury),
+        1000
+    );
+    assert_eq!(fixture.balance(&treasury), 1000);
+}
+
+// Stroops in one lumen; Stellar assets, lumens included, have 7 decimals
+const STROOPS_PER_XLM: i128 = 10_000_000;
+
+#[test]
+fn test_round_paying_lumens() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    assert_eq!(fixture.token_client().decimals(), 7);
+
+    // 250,000 XLM shared by three projects
+    let funding = 250_000 * STROOPS_PER_XLM;
+    let round_id = fixture.create_active_round(funding, 1000);
+    assert_eq!(fixture.balance(&client.address), funding);
+    let (alice, alice_submission) = fixture.submit_project(round_id);
+    let (bob, bob_submission) = fixture.submit_project(round_id);
+    let (carol, carol_submission) = fixture.submit_project(round_id);
+    fixture.open_voting(round_id);
+
+    let voter = fixture.add_voter();
+    fixture.vote(
+        &voter,
+        round_id,
+        &fixture.ballot(&[
+            (alice_submission, 10),
+            (bob_submission, 7),
+            (carol_submission, 3),
+        ]),
+    );
+    fixture.pass_voting_deadline(round_id);
+    client.close_voting(&fixture.admin, &round_id, &false, &false);
+    client.disburse_funds(&fixture.admin, &round_id, &fixture.token, &0, &10);
+
+    assert_eq!(fixture.balance(&alice), 125_000 * STROOPS_PER_XLM);
+    assert_eq!(fixture.balance(&bob), 87_500 * STROOPS_PER_XLM);
+    assert_eq!(fixture.balance(&carol), 37_500 * STROOPS_PER_XLM);
+    assert_eq!(fixture.balance(&client.address), 0);
+
+    // A round can distribute the whole supply of 50 billion XLM
+    let supply = 50_000_000_000 * STROOPS_PER_XLM;
+    let round_id = fixture.create_active_round(supply, 1000);
+    let submission_ids = fixture.submit_n_projects(round_id, 2);
+    fixture.open_voting(round_id);
+    fixture.vote(
+        &fixture.add_voter(),
+        round_id,
+        &fixture.ballot(&[
+            (submission_ids.get_unchecked(0), 2),
+            (submission_ids.get_unchecked(1), 1),
+        ]),
+    );
+    fixture.close(round_id);
+    client.disburse_funds(&fixture.admin, &round_id, &fixture.token, &0, &10);
+
+    let paid: i128 = client.get_allocations(&round_id).values().iter().sum();
+    let remainder = stored_remainder(&fixture.env, client, round_id, &fixture.token);
+    assert_eq!(paid + remainder, supply);
+    assert_eq!(fixture.balance(&client.address), remainder);
+}
+
+#[test]
+fn test_time_weighted_votes() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let config = RoundConfig {
+        time_weighted: true,
+        ..round_config(&fixture.env)
+    };
+
+    // Commit-reveal ballots can't be weighted by when they were cast
+    assert_eq!(
+        client.try_create_round(
+            &fixture.admin,
+            &1000,
+            &1000,
+            &3000,
+            &RoundConfig {
+                reveal_period: 100,
+                ..config.clone()
+            },
+            &round_name(&fixture.env),
+            &round_hash(&fixture.env),
+            &None
+        ),
+        Err(Ok(ContractError::InvalidDeadline))
+    );
+
+    // Voting opens at 1000 and closes at 3000
+    let round_id = fixture.create_round(1000, 1000, 3000, &config);
+    let (_, early) = fixture.submit_project(round_id);
+    let (_, late) = fixture.submit_project(round_id);
+    fixture.set_time(1000);
+    fixture.open_voting(round_id);
+    let alice = fixture.add_voter();
+    let bob = fixture.add_voter();
+    let carol = fixture.add_voter();
+
+    // Ballots count in full at the open, decaying linearly to half at the deadline
+    fixture.vote(&alice, round_id, &fixture.ballot(&[(early, 10)]));
+    fixture.set_time(2000);
+    fixture.vote(&bob, round_id, &fixture.ballot(&[(late, 10)]));
+    fixture.set_time(3000);
+    fixture.vote(&carol, round_id, &fixture.ballot(&[(late, 10)]));
+    assert_eq!(client.get_submission(&early).total_votes, 10);
+    assert_eq!(client.get_submission(&late).total_votes, 12);
+    assert_eq!(
+        client.get_voter_allocation(&round_id, &bob),
+        Some(fixture.ballot(&[(late, 7)]))
+    );
+
+    // A ballot is replaced rather than topped up, and the replacement is weighted by when it
+    // is cast
+    assert_eq!(
+        client.try_allocate_votes(&alice, &round_id, &fixture.ballot(&[(late, 5)])),
+        Err(Ok(ContractError::AlreadyVoted))
+    );
+    client.update_votes(&alice, &round_id, &fixture.ballot(&[(early, 10)]));
+    assert_eq!(client.get_submission(&early).total_votes, 5);
+    assert_eq!(client.remaining_credits(&round_id, &alice), 10);
+
+    // The statistics report the weighted votes alongside the votes and credits as cast
+    let stats = client.get_round_stats(&round_id);
+    assert_eq!(
+        (stats.votes_cast, stats.raw_votes, stats.credits_spent),
+        (17, 30, 30)
+    );
+    let leaders = client.leaderboard(&round_id, &2);
+    assert_eq!(leaders.get(0).unwrap().id, late);
+    assert_eq!(leaders.get(1).unwrap().id, early);
+}
+
+#[test]
+fn test_carryover_credits() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+
+    // Alice and Bob leave 15 and 4 of their 20 credits unspent; Carol doesn't vote at all
+    let first = fixture.create_round(1000, 1000, 2000, &round_config(&fixture.env));
+    let (_, project) = fixture.submit_project(first);
+    fixture.set_time(1000);
+    fixture.open_voting(first);
+    let alice = fixture.add_voter();
+    let bob = fixture.add_voter();
+    let carol = fixture.add_voter();
+    fixture.vote(&alice, first, &fixture.ballot(&[(project, 5)]));
+    fixture.vote(&bob, first, &fixture.ballot(&[(project, 16)]));
+    assert_eq!(
+        client.try_bank_credits(&first, &alice),
+        Err(Ok(ContractError::InvalidPhase))
+    );
+    fixture.close(first);
+
+    // Unused credits are banked once per round, and only by the round's voters
+    assert_eq!(client.bank_credits(&first, &alice), 15);
+    assert_eq!(
+        client.try_bank_credits(&first, &alice),
+        Err(Ok(ContractError::AlreadyClaimed))
+    );
+    assert_eq!(client.bank_credits(&first, &bob), 4);
+    assert_eq!(client.bank_credits(&first, &carol), 20);
+    assert_eq!(
+        client.try_bank_credits(&first, &Address::generate(&fixture.env)),
+        Err(Ok(ContractError::NotAVoter))
+    );
+    assert_eq!(client.get_banked_credits(&alice), 15);
+
+    // Removing a voter keeps their bank
+    client.remove_voter(&alice);
+    assert_eq!(client.get_banked_credits(&alice), 15);
+    client.add_voter(&alice);
+
+    // The next round adds 80% of the bank, capped at its 10 default credits
+    let config = RoundConfig {
+        vote_credits: 10,
+        carryover_bps: 8_000,
+        ..round_config(&fixture.env)
+    };
+    assert_eq!(
+        client.try_create_round(
+            &fixture.admin,
+            &1000,
+            &3000,
+            &4000,
+            &RoundConfig {
+                carryover_bps: 10_001,
+                ..config.clone()
+            },
+            &round_name(&fixture.env),
+            &round_hash(&fixture.env),
+            &None
+        ),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+    let second = fixture.create_round(1000, 3000, 4000, &config);
+    let (_, project) = fixture.submit_project(second);
+    fixture.set_time(3000);
+    fixture.open_voting(second);
+
+    // Banked credits carry over when the voter first votes in the round
+    assert_eq!(client.get_voter_credits(&second, &alice).total, 10);
+    fixture.vote(&alice, second, &fixture.ballot(&[(project, 20)]));
+    fixture.vote(&bob, second, &fixture.ballot(&[(project, 13)]));
+    assert_eq!(client.get_voter_credits(&second, &alice).total, 20);
+    assert_eq!(client.get_voter_credits(&second, &bob).total, 13);
+    assert_eq!(
+        client.try_update_votes(&bob, &second, &fixture.ballot(&[(project, 14)])),
+        Err(Ok(ContractError::ExceededVoteLimit))
+    );
+
+    // The whole bank is consumed, even the part over the cap
+    assert_eq!(client.get_banked_credits(&alice), 0);
+    assert_eq!(client.get_banked_credits(&bob), 0);
+    assert_eq!(client.get_banked_credits(&carol), 20);
+    fixture.close(second);
+    assert_eq!(
+        client.try_bank_credits(&second, &alice),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+}
+
+#[test]
+fn test_stake_for_credits() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let alice = fixture.add_voter();
+    let bob = fixture.add_voter();
+    fixture.mint(&alice, 5000);
+    fixture.mint(&bob, 1000);
+
+    // Rounds only take stakes when they set a stake unit
+    let unstaked = fixture.create_active_round(1000, 1000);
+    assert_eq!(
+        client.try_stake_for_credits(&alice, &unstaked, &fixture.token, &100),
+        Err(Ok(ContractError::InvalidAllocations))
+    );
+
+    // Every 100 tokens locked earn a credit, up to 30 credits per voter
+    let config = RoundConfig {
+        stake_unit: 100,
+        max_stake_credits: 30,
+        ..round_config(&fixture.env)
+    };
+    let round_id = fixture.create_round(1000, 2000, 3000, &config);
+    fixture.fund(round_id, 1000);
+    let (_, project) = fixture.submit_project(round_id);
+    assert_eq!(
+        client.stake_for_credits(&alice, &round_id, &fixture.token, &250),
+        2
+    );
+    assert_eq!(
+        client.stake_for_credits(&alice, &round_id, &fixture.token, &50),
+        3
+    );
+    assert_eq!(
+        client.try_stake_for_credits(&alice, &round_id, &fixture.token, &2800),
+        Err(Ok(ContractError::ExceededVoteLimit))
+    );
+    assert_eq!(client.get_voter_credits(&round_id, &alice).total, 23);
+    assert_eq!(client.get_stake(&round_id, &alice), 300);
+    assert_eq!(fixture.balance(&alice), 4700);
+    assert_eq!(
+        client.stake_for_credits(&bob, &round_id, &fixture.token, &1000),
+        10
+    );
+
+    // Stakes are in a single token, and only the round's voters may stake
+    let other = fixture
+        .env
+        .register_stellar_asset_contract_v2(fixture.admin.clone())
+        .address();
+    assert_eq!(
+        client.try_stake_for_credits(&alice, &round_id, &other, &100),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+    let stranger = Address::generate(&fixture.env);
+    fixture.mint(&stranger, 100);
+    assert_eq!(
+        client.try_stake_for_credits(&stranger, &round_id, &fixture.token, &100),
+        Err(Ok(ContractError::NotAVoter))
+    );
+
+    // The stake stays locked while ballots are live, past the voting deadline until close
+    fixture.set_time(2000);
+    fixture.open_voting(round_id);
+    fixture.vote(&alice, round_id, &fixture.ballot(&[(project, 23)]));
+    assert_eq!(
+        client.try_unstake(&alice, &round_id),
+        Err(Ok(ContractError::InvalidPhase))
+    );
+    fixture.set_time(3001);
+    assert_eq!(
+        client.try_unstake(&alice, &round_id),
+        Err(Ok(ContractError::InvalidPhase))
+    );
+
+    // Closing unlocks the stake, without touching the funding pool
+    client.close_voting(&fixture.admin, &round_id, &false, &false);
+    assert_eq!(
+        client.try_stake_for_credits(&alice, &round_id, &fixture.token, &100),
+        Err(Ok(ContractError::RoundNotActive))
+    );
+    assert_eq!(client.unstake(&alice, &round_id), 300);
+    assert_eq!(fixture.balance(&alice), 5000);
+    assert_eq!(client.get_stake(&round_id, &alice), 0);
+    assert_eq!(
+        client.try_unstake(&alice, &round_id),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+    assert_eq!(client.get_voter_credits(&round_id, &alice).total, 23);
+
+    // Credits earned by staking are not banked once the stake is taken back
+    assert_eq!(client.unstake(&bob, &round_id), 1000);
+    assert_eq!(client.bank_credits(&round_id, &bob), 20);
+    assert_eq!(
+        client.try_bank_credits(&round_id, &alice),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+    client.disburse_funds(&fixture.admin, &round_id, &fixture.token, &0, &10);
+    assert_eq!(
+        fixture.balance(&client.get_submission(&project).payout_address),
+        1000
+    );
+}
+
+#[test]
+fn test_abstain() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let env = &fixture.env;
+    let config = RoundConfig {
+        min_voters: 2,
+        ..round_config(env)
+    };
+    let round_id = fixture.create_round(1000, 1000, 2000, &config);
+    let (_, project) = fixture.submit_project(round_id);
+    fixture.set_time(1000);
+    fixture.open_voting(round_id);
+    let alice = fixture.add_voter();
+    let bob = fixture.add_voter();
+
+    // An abstention is recorded once
+    client.abstain(&alice, &round_id);
+    let event = env.events().all().last().unwrap();
+    assert_eq!(
+        event.1,
+        (symbol_short!("ABSTAIN"), round_id, alice.clone()).into_val(env)
+    );
+    assert_eq!(u32::try_from_val(env, &event.2).unwrap(), 1);
+    assert!(client.has_abstained(&round_id, &alice));
+    assert!(!client.has_voted(&round_id, &alice));
+    assert_eq!(
+        client.try_abstain(&alice, &round_id),
+        Err(Ok(ContractError::AlreadyVoted))
+    );
+    assert_eq!(
+        client.try_abstain(&Address::generate(env), &round_id),
+        Err(Ok(ContractError::NotAVoter))
+    );
+
+    // Abstaining replaces a ballot, freeing its credits
+    fixture.vote(&bob, round_id, &fixture.ballot(&[(project, 5)]));
+    client.abstain(&bob, &round_id);
+    assert_eq!(client.get_submission(&project).total_votes, 0);
+    assert_eq!(client.remaining_credits(&round_id, &bob), 20);
+    assert!(!client.has_voted(&round_id, &bob));
+    let stats = client.get_round_stats(&round_id);
+    assert_eq!((stats.voter_count, stats.abstentions), (0, 2));
+
+    // And a ballot replaces an abstention
+    fixture.vote(&alice, round_id, &fixture.ballot(&[(project, 4)]));
+    assert!(!client.has_abstained(&round_id, &alice));
+    let stats = client.get_round_stats(&round_id);
+    assert_eq!((stats.voter_count, stats.abstentions), (1, 1));
+
+    // Abstentions count towards the quorum, and are frozen with the statistics at close
+    fixture.pass_voting_deadline(round_id);
+    assert_eq!(
+        client.try_abstain(&alice, &round_id),
+        Err(Ok(ContractError::VotingClosed))
+    );
+    client.close_voting(&fixture.admin, &round_id, &false, &false);
+    let stats = client.get_round_stats(&round_id);
+    assert_eq!(
+        (stats.voter_count, stats.abstentions, stats.votes_cast),
+        (1, 1, 4)
+    );
+}
+
+#[test]
+fn test_pairwise_comparisons() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let config = RoundConfig {
+        vote_credits: 2,
+        mechanism: VotingMechanism::Pairwise,
+        ..round_config(&fixture.env)
+    };
+    let round_id = fixture.create_round(600, 1000, 2000, &config);
+    fixture.fund(round_id, 600);
+    let (_, a) = fixture.submit_project(round_id);
+    let (_, b) = fixture.submit_project(round_id);
+    let (_, c) = fixture.submit_project(round_id);
+    let linear = fixture.create_active_round(1000, 1000);
+    let (_, elsewhere) = fixture.submit_project(linear);
+    fixture.set_time(1000);
+    fixture.open_voting(round_id);
+    fixture.open_voting(linear);
+    let alice = fixture.add_voter();
+    let bob = fixture.add_voter();
+
+    // Pairwise rounds only take comparisons of two of their own submissions
+    assert_eq!(
+        client.try_allocate_votes(&alice, &round_id, &fixture.ballot(&[(a, 1)])),
+        Err(Ok(ContractError::InvalidAllocations))
+    );
+    assert_eq!(
+        client.try_submit_comparison(&alice, &linear, &elsewhere, &elsewhere),
+        Err(Ok(ContractError::InvalidAllocations))
+    );
+    assert_eq!(
+        client.try_submit_comparison(&alice, &round_id, &a, &a),
+        Err(Ok(ContractError::InvalidAllocations))
+    );
+    assert_eq!(
+        client.try_submit_comparison(&alice, &round_id, &a, &elsewhere),
+        Err(Ok(ContractError::SubmissionNotInRound))
+    );
+
+    // A pair won takes two points and a pair tied one each
+    client.submit_comparison(&alice, &round_id, &a, &b);
+    client.submit_comparison(&alice, &round_id, &a, &c);
+    client.submit_comparison(&bob, &round_id, &b, &a);
+    client.submit_comparison(&bob, &round_id, &c, &b);
+    assert_eq!(client.get_submission(&a).total_votes, 3);
+    assert_eq!(client.get_submission(&b).total_votes, 1);
+    assert_eq!(client.get_submission(&c).total_votes, 2);
+    assert_eq!(client.get_voter_count(&round_id), 2);
+
+    // Each pair is compared once per voter, and every comparison costs a credit
+    assert_eq!(
+        client.try_submit_comparison(&alice, &round_id, &a, &b),
+        Err(Ok(ContractError::AlreadyVoted))
+    );
+    assert_eq!(
+        client.try_submit_comparison(&alice, &round_id, &b, &a),
+        Err(Ok(ContractError::AlreadyVoted))
+    );
+    assert_eq!(
+        client.try_submit_comparison(&alice, &round_id, &b, &c),
+        Err(Ok(ContractError::ExceededVoteLimit))
+    );
+    assert_eq!(
+        client.try_revoke_votes(&alice, &round_id),
+        Err(Ok(ContractError::InvalidAllocations))
+    );
+
+    // The scores are funded proportionally
+    fixture.pass_voting_deadline(round_id);
+    client.close_voting(&fixture.admin, &round_id, &false, &false);
+    let allocations = client.get_allocations(&round_id);
+    assert_eq!(allocations.get(a), Some(300));
+    assert_eq!(allocations.get(b), Some(100));
+    assert_eq!(allocations.get(c), Some(200));
+}
+
+#[test]
+fn test_reviewer_attestations() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let env = &fixture.env;
+    let config = RoundConfig {
+        attestation_budget: 10,
+        ..round_config(env)
+    };
+    let round_id = fixture.create_round(1000, 1000, 2000, &config);
+    fixture.fund(round_id, 1000);
+    let (submitter, a) = fixture.submit_project(round_id);
+    let (_, b) = fixture.submit_project(round_id);
+    fixture.set_time(1000);
+    fixture.open_voting(round_id);
+    let voter = fixture.add_voter();
+    fixture.vote(&voter, round_id, &fixture.ballot(&[(a, 5), (b, 5)]));
+
+    // Only registered reviewers attest, and never to their own projects
+    let carol = Address::generate(env);
+    let dave = Address::generate(env);
+    assert_eq!(
+        client.try_attest(&carol, &a, &5),
+        Err(Ok(ContractError::Unauthorized))
+    );
+    client.add_reviewer(&carol);
+    client.add_reviewer(&dave);
+    client.add_reviewer(&submitter);
+    assert!(client.is_reviewer(&carol));
+    assert_eq!(
+        client.try_attest(&submitter, &a, &5),
+        Err(Ok(ContractError::SelfVoteNotAllowed))
+    );
+
+    // Each reviewer attests at most the round's budget
+    client.attest(&carol, &a, &6);
+    let event = env.events().all().last().unwrap();
+    assert_eq!(
+        event.1,
+        (symbol_short!("ATTEST"), round_id, a).into_val(env)
+    );
+    assert_eq!(
+        <(Address, u64)>::try_from_val(env, &event.2).unwrap(),
+        (carol.clone(), 6)
+    );
+    assert_eq!(
+        client.try_attest(&carol, &b, &5),
+        Err(Ok(ContractError::ExceededVoteLimit))
+    );
+    assert_eq!(
+        client.try_attest(&carol, &b, &0),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+    client.attest(&carol, &b, &4);
+    assert_eq!(
+        client.get_attestations(&round_id, &carol),
+        fixture.ballot(&[(a, 6), (b, 4)])
+    );
+
+    // Attestations don't count before close
+    assert_eq!(client.get_submission(&a).total_votes, 5);
+    assert_eq!(client.leaderboard(&round_id, &2).get(0).unwrap().id, a);
+
+    // Removing a reviewer invalidates their attestations, even once registered again
+    client.attest(&dave, &b, &10);
+    client.remove_reviewer(&dave);
+    assert!(!client.is_reviewer(&dave));
+    client.add_reviewer(&dave);
+    assert!(client.get_attestations(&round_id, &dave).is_empty());
+
+    // Closing adds the attestations to the tallies the funding is shared by
+    fixture.pass_voting_deadline(round_id);
+    client.close_voting(&fixture.admin, &round_id, &false, &false);
+    assert_eq!(client.get_submission(&a).total_votes, 11);
+    assert_eq!(client.get_submission(&b).total_votes, 9);
+    let allocations = client.get_allocations(&round_id);
+    assert_eq!(allocations.get(a), Some(550));
+    assert_eq!(allocations.get(b), Some(450));
+    assert_eq!(
+        client.try_attest(&carol, &a, &1),
+        Err(Ok(ContractError::VotingClosed))
+    );
+}
+
+#[test]
+fn test_submission_lineage() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let env = &fixture.env;
+    let first = fixture.create_active_round(1000, 100);
+    let (submitter, original) = fixture.submit_project(first);
+    let submit = |submitter: &Address, round_id: u64, previous: Option<u64>| {
+        client.try_submit_project(
+            submitter,
+            &round_id,
+            &project_name(env),
+            &project_hash(env),
+            &previous,
+            &None,
+        )
+    };
+
+    // A continuation must come from a later round
+    assert_eq!(
+        submit(&Address::generate(env), first, Some(original)),
+        Err(Ok(ContractError::InvalidLineage))
+    );
+    let second = fixture.create_active_round(1000, 100);
+    assert_eq!(
+        submit(&submitter, second, Some(99)),
+        Err(Ok(ContractError::SubmissionNotFound))
+    );
+
+    // Only the earlier submission's submitter or payout address may continue it
+    assert_eq!(
+        submit(&Address::generate(env), second, Some(original)),
+        Err(Ok(ContractError::InvalidLineage))
+    );
+    let continued = submit(&submitter, second, Some(original)).unwrap().unwrap();
+    assert_eq!(
+        client.get_submission(&continued).previous_submission_id,
+        original
+    );
+    assert_eq!(
+        event_data::<ProjectSubmittedEvent>(env, (symbol_short!("PROJ_SUB"), continued)),
+        Some(ProjectSubmittedEvent {
+            submission_id: continued,
+            round_id: second,
+            submitter: submitter.clone(),
+            previous_submission_id: original,
+        })
+    );
+
+    let payout = Address::generate(env);
+    client.set_payout_address(&submitter, &continued, &payout);
+    let third = fixture.create_active_round(1000, 100);
+    let latest = submit(&payout, third, Some(continued)).unwrap().unwrap();
+
+    // The lineage runs from the newest submission back to the original
+    assert_eq!(
+        client.get_submission_lineage(&latest),
+        Vec::from_array(env, [latest, continued, original])
+    );
+    assert_eq!(
+        client.get_submission_lineage(&original),
+        Vec::from_array(env, [original])
+    );
+    assert_eq!(
+        client.try_get_submission_lineage(&99),
+        Err(Ok(ContractError::SubmissionNotFound))
+    );
+}
+
+#[test]
+fn test_project_registry() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let env = &fixture.env;
+    let owner = Address::generate(env);
+    let payout = Address::generate(env);
+
+    // Projects are registered once with their details
+    assert_eq!(
+        client.try_register_project(&owner, &Bytes::new(env), &project_hash(env), &payout),
+        Err(Ok(ContractError::InvalidMetadata))
+    );
+    let project_id =
+        client.register_project(&owner, &project_name(env), &project_hash(env), &payout);
+    assert_eq!(
+        client.get_project(&project_id),
+        Project {
+            id: project_id,
+            owner: owner.clone(),
+            name: project_name(env),
+            metadata_hash: project_hash(env),
+            payout: payout.clone(),
+        }
+    );
+    assert_eq!(
+        client.try_get_project(&99),
+        Err(Ok(ContractError::ProjectNotFound))
+    );
+
+    // Only the owner enters the project, which copies its details into the submission
+    let first = fixture.create_active_round(1000, 100);
+    assert_eq!(
+        client.try_enter_round(&Address::generate(env), &project_id, &first, &None),
+        Err(Ok(ContractError::Unauthorized))
+    );
+    assert_eq!(
+        client.try_enter_round(&owner, &99, &first, &None),
+        Err(Ok(ContractError::ProjectNotFound))
+    );
+    let entered = client.enter_round(&owner, &project_id, &first, &None);
+    let submission = client.get_submission(&entered);
+    assert_eq!(submission.submitter, owner);
+    assert_eq!(submission.payout_address, payout);
+    assert_eq!(submission.name, project_name(env));
+    assert_eq!(submission.project_id, project_id);
+    assert_eq!(
+        client.try_enter_round(&owner, &project_id, &first, &None),
+        Err(Ok(ContractError::AlreadySubmitted))
+    );
+
+    // Updates apply to later entries, not to submissions already made
+    let new_payout = Address::generate(env);
+    let new_hash = Bytes::from_slice(
+        env,
+        b"bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku",
+    );
+    assert_eq!(
+        client.try_update_project(
+            &Address::generate(env),
+            &project_id,
+            &project_name(env),
+            &new_hash,
+            &new_payout
+        ),
+        Err(Ok(ContractError::Unauthorized))
+    );
+    client.update_project(
+        &owner,
+        &project_id,
+        &project_name(env),
+        &new_hash,
+        &new_payout,
+    );
+    assert_eq!(client.get_project(&project_id).payout, new_payout);
+    assert_eq!(client.get_submission(&entered), submission);
+
+    let second = fixture.create_active_round(1000, 100);
+    let reentered = client.enter_round(&owner, &project_id, &second, &None);
+    let submission = client.get_submission(&reentered);
+    assert_eq!(submission.payout_address, new_payout);
+    assert_eq!(submission.metadata_hash, new_hash);
+
+    // Entered submissions are paid like any other
+    fixture.open_voting(first);
+    fixture.vote(
+        &fixture.add_voter(),
+        first,
+        &fixture.ballot(&[(entered, 5)]),
+    );
+    fixture.close(first);
+    client.disburse_funds(&fixture.admin, &first, &fixture.token, &0, &10);
+    assert_eq!(fixture.balance(&payout), 1000);
+    assert_eq!(fixture.balance(&new_payout), 0);
+}
+
+#[test]
+fn test_submission_deposit() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let env = &fixture.env;
+    let deposit_token = env
+        .register_stellar_asset_contract_v2(fixture.admin.clone())
+        .address();
+    let deposit_asset = token::StellarAssetClient::new(env, &deposit_token);
+    let balance = |addr: &Address| token::TokenClient::new(env, &deposit_token).balance(addr);
+    let submitter = |funds: i128| {
+        let submitter = Address::generate(env);
+        deposit_asset.mint(&submitter, &funds);
+        submitter
+    };
+    let submit = |submitter: &Address, round_id: u64| {
+        client.submit_project(
+            submitter,
+            &round_id,
+            &project_name(env),
+            &project_hash(env),
+            &None,
+            &None,
+        )
+    };
+
+    // Deposits must be positive
+    let config = RoundConfig {
+        submission_deposit: SubmissionDeposit::Token(deposit_token.clone(), 0),
+        ..round_config(env)
+    };
+    assert_eq!(
+        client.try_create_round(
+            &fixture.admin,
+            &1000,
+            &1000,
+            &2000,
+            &config,
+            &round_name(env),
+            &round_hash(env),
+            &None,
+        ),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+    let config = RoundConfig {
+        submission_deposit: SubmissionDeposit::Token(deposit_token.clone(), 50),
+        ..round_config(env)
+    };
+    let round_id = fixture.create_round(1000, 1000, 2000, &config);
+    fixture.fund(round_id, 1000);
+
+    // Submitters who can't pay the deposit can't submit
+    let poor = submitter(49);
+    assert_eq!(
+        client.try_submit_project(
+            &poor,
+            &round_id,
+            &project_name(env),
+            &project_hash(env),
+            &None,
+            &None
+        ),
+        Err(Ok(ContractError::InsufficientFunds))
+    );
+    assert_eq!(balance(&poor), 49);
+    let owner = submitter(49);
+    let project_id =
+        client.register_project(&owner, &project_name(env), &project_hash(env), &owner);
+    assert_eq!(
+        client.try_enter_round(&owner, &project_id, &round_id, &None),
+        Err(Ok(ContractError::InsufficientFunds))
+    );
+
+    // The deposit is locked with the submission
+    let approved = submitter(100);
+    let withdrawn = submitter(100);
+    let spam = submitter(100);
+    let rejected = submitter(100);
+    let pending = submitter(100);
+    let approved_id = submit(&approved, round_id);
+    let withdrawn_id = submit(&withdrawn, round_id);
+    let spam_id = submit(&spam, round_id);
+    let rejected_id = submit(&rejected, round_id);
+    let pending_id = submit(&pending, round_id);
+    assert_eq!(balance(&approved), 50);
+    assert_eq!(client.get_submission_deposit(&approved_id), 50);
+    assert_eq!(balance(&client.address), 250);
+
+    // It is returned on approval and on withdrawal before review
+    client.review_submission(&fixture.admin, &approved_id, &true);
+    assert_eq!(balance(&approved), 100);
+    assert_eq!(client.get_submission_deposit(&approved_id), 0);
+    assert_eq!(
+        event_data::<(Address, i128)>(env, (symbol_short!("DEP_RFND"), approved_id)),
+        Some((approved.clone(), 50))
+    );
+    client.withdraw_submission(&withdrawn, &withdrawn_id);
+    assert_eq!(balance(&withdrawn), 100);
+
+    // Spam forfeits it to the fee recipient; a plain rejection holds it until close
+    let fee_recipient = Address::generate(env);
+    client.set_fee(&100, &fee_recipient);
+    client.reject_as_spam(&spam_id);
+    assert_eq!(
+        client.get_submission(&spam_id).status,
+        SubmissionStatus::Rejected
+    );
+    assert_eq!(balance(&spam), 50);
+    assert_eq!(balance(&fee_recipient), 50);
+    assert_eq!(
+        event_data::<(Address, i128)>(env, (symbol_short!("DEP_SLSH"), spam_id)),
+        Some((fee_recipient.clone(), 50))
+    );
+    client.review_submission(&fixture.admin, &rejected_id, &false);
+    assert_eq!(client.get_submission_deposit(&rejected_id), 50);
+
+    // A submitter blacklisted before the round closes is not in good standing
+    let sanctioned = submitter(100);
+    let sanctioned_id = submit(&sanctioned, round_id);
+    client.blacklist(&sanctioned);
+
+    // The rest are returned when the round closes
+    fixture.set_time(1000);
+    client.start_voting(&round_id);
+    fixture.vote(
+        &fixture.add_voter(),
+        round_id,
+        &fixture.ballot(&[(approved_id, 5)]),
+    );
+    fixture.close(round_id);
+    assert_eq!(balance(&rejected), 100);
+    assert_eq!(balance(&pending), 100);
+    assert_eq!(client.get_submission_deposit(&pending_id), 0);
+    assert_eq!(balance(&sanctioned), 50);
+    assert_eq!(balance(&fee_recipient), 100);
+    assert_eq!(
+        event_data::<(Address, i128)>(env, (symbol_short!("DEP_SLSH"), sanctioned_id)),
+        Some((fee_recipient.clone(), 50))
+    );
+    assert_eq!(balance(&client.address), 0);
+
+    // Without a fee recipient, spam deposits go to the admin, and cancelling a round
+    // returns the deposits it holds
+    client.update_config(&Config {
+        fee_bps: 0,
+        fee_recipient: FeeRecipient::None,
+        ..client.get_config()
+    });
+    let round_id = fixture.create_round(1000, 3000, 4000, &config);
+    let spam = submitter(50);
+    let pending = submitter(50);
+    let spam_id = submit(&spam, round_id);
+    submit(&pending, round_id);
+    client.reject_as_spam(&spam_id);
+    assert_eq!(balance(&fixture.admin), 50);
+    assert_eq!(balance(&pending), 0);
+    client.cancel_round(&round_id, &fixture.admin);
+    assert_eq!(balance(&pending), 50);
+    assert_eq!(balance(&client.address), 0);
+}
+
+#[test]
+fn test_transfer_submission() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let env = &fixture.env;
+    let round_id = fixture.create_active_round(1000, 100);
+    let (alice, alice_submission) = fixture.submit_project(round_id);
+    let (bob, bob_submission) = fixture.submit_project(round_id);
+    let carol = Address::generate(env);
+
+    // Only the submitter hands the submission over, and not to another submitter of the round
+    assert_eq!(
+        client.try_transfer_submission(&bob, &alice_submission, &carol),
+        Err(Ok(ContractError::Unauthorized))
+    );
+    assert_eq!(
+        client.try_transfer_submission(&alice, &alice_submission, &bob),
+        Err(Ok(ContractError::AlreadySubmitted))
+    );
+
+    // The payout follows the submission unless it was redirected
+    client.transfer_submission(&alice, &alice_submission, &carol);
+    let submission = client.get_submission(&alice_submission);
+    assert_eq!(submission.submitter, carol);
+    assert_eq!(submission.payout_address, carol);
+    assert_eq!(
+        event_data::<(Address, Address)>(env, (symbol_short!("SUB_XFER"), alice_submission)),
+        Some((alice.clone(), carol.clone()))
+    );
+    let treasury = Address::generate(env);
+    client.set_payout_address(&bob, &bob_submission, &treasury);
+    client.transfer_submission(&bob, &bob_submission, &alice);
+    assert_eq!(client.get_submission(&bob_submission).submitter, alice);
+    assert_eq!(
+        client.get_submission(&bob_submission).payout_address,
+        treasury
+    );
+
+    // The new submitter manages the submission and it moves between the indexes
+    assert_eq!(
+        client.try_set_payout_address(&alice, &alice_submission, &alice),
+        Err(Ok(ContractError::Unauthorized))
+    );
+    client.set_payout_address(&carol, &alice_submission, &carol);
+    let ids = |submitter: &Address| {
+        let mut ids = Vec::new(env);
+        for submission in client
+            .get_submissions_by_submitter(submitter, &0, &10)
+            .iter()
+        {
+            ids.push_back(submission.id);
+        }
+        ids
+    };
+    assert_eq!(ids(&carol), Vec::from_array(env, [alice_submission]));
+    assert_eq!(ids(&alice), Vec::from_array(env, [bob_submission]));
+    assert!(ids(&bob).is_empty());
+
+    // Ownership is fixed once voting closes
+    fixture.open_voting(round_id);
+    fixture.vote(
+        &fixture.add_voter(),
+        round_id,
+        &fixture.ballot(&[(alice_submission, 5)]),
+    );
+    fixture.close(round_id);
+    assert_eq!(
+        client.try_transfer_submission(&carol, &alice_submission, &bob),
+        Err(Ok(ContractError::RoundNotActive))
+    );
+}
+
+#[test]
+fn test_category_budgets() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let env = &fixture.env;
+    let infra = Symbol::new(env, "Infrastructure");
+    let education = Symbol::new(env, "Education");
+    let community = Symbol::new(env, "Community");
+    let categories = |budgets: [(&Symbol, i128); 3]| {
+        let mut categories = Vec::new(env);
+        for (name, budget) in budgets {
+            categories.push_back(CategoryBudget {
+                name: name.clone(),
+                budget,
+            });
+        }
+        categories
+    };
+    let create = |categories: Vec<CategoryBudget>| {
+        client.try_create_round(
+            &fixture.admin,
+            &1000,
+            &1000,
+            &2000,
+            &RoundConfig {
+                categories,
+                ..round_config(env)
+            },
+            &round_name(env),
+            &round_hash(env),
+            &None,
+        )
+    };
+
+    // Budgets split the whole funding between distinct categories
+    assert_eq!(
+        create(categories([
+            (&infra, 600),
+            (&education, 300),
+            (&community, 50)
+        ])),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+    assert_eq!(
+        create(categories([
+            (&infra, 600),
+            (&infra, 300),
+            (&community, 100)
+        ])),
+        Err(Ok(ContractError::InvalidCategory))
+    );
+    let round_id = create(categories([
+        (&infra, 600),
+        (&education, 300),
+        (&community, 100),
+    ]))
+    .unwrap()
+    .unwrap();
+    fixture.fund(round_id, 1000);
+
+    // Changing the funding scales the budgets with it, the rounding dust going to the
+    // largest category
+    let scaled = create(categories([
+        (&infra, 600),
+        (&education, 300),
+        (&community, 100),
+    ]))
+    .unwrap()
+    .unwrap();
+    client.update_round(&scaled, &None, &None, &Some(2000));
+    let donor = Address::generate(env);
+    fixture.mint(&donor, 1);
+    client.donate(&donor, &scaled, &fixture.token, &1);
+    let budgets: alloc::vec::Vec<i128> = client
+        .get_round(&scaled)
+        .config
+        .categories
+        .iter()
+        .map(|category| category.budget)
+        .collect();
+    assert_eq!(budgets, [1201, 600, 200]);
+
+    // Every submission is filed under one of the round's categories
+    let submit = |category: Option<Symbol>| {
+        client.try_submit_project(
+            &Address::generate(env),
+            &round_id,
+            &project_name(env),
+            &project_hash(env),
+            &None,
+            &category,
+        )
+    };
+    assert_eq!(submit(None), Err(Ok(ContractError::InvalidCategory)));
+    assert_eq!(
+        submit(Some(Symbol::new(env, "Art"))),
+        Err(Ok(ContractError::InvalidCategory))
+    );
+    let a = submit(Some(infra.clone())).unwrap().unwrap();
+    let b = submit(Some(infra.clone())).unwrap().unwrap();
+    let c = submit(Some(education.clone())).unwrap().unwrap();
+    let linear = fixture.create_active_round(1000, 100);
+    assert_eq!(
+        client.try_submit_project(
+            &Address::generate(env),
+            &linear,
+            &project_name(env),
+            &project_hash(env),
+            &None,
+            &Some(infra.clone()),
+        ),
+        Err(Ok(ContractError::InvalidCategory))
+    );
+
+    // Each category is allocated on its own, and Community's unused budget goes to
+    // Infrastructure, the largest category
+    fixture.set_time(1000);
+    fixture.open_voting(round_id);
+    fixture.vote(
+        &fixture.add_voter(),
+        round_id,
+        &fixture.ballot(&[(a, 3), (b, 1), (c, 1)]),
+    );
+    assert_eq!(
+        client.try_get_category_results(&round_id, &infra),
+        Err(Ok(ContractError::AllocationsNotComputed))
+    );
+    assert_eq!(
+        client.preview_allocations(&round_id),
+        Map::from_array(env, [(a, 525), (b, 175), (c, 300)])
+    );
+    fixture.close(round_id);
+    assert_eq!(
+        client.get_allocations(&round_id),
+        Map::from_array(env, [(a, 525), (b, 175), (c, 300)])
+    );
+    assert_eq!(
+        client.get_category_results(&round_id, &infra),
+        CategoryResults {
+            name: infra.clone(),
+            budget: 600,
+            distributable: 700,
+            funded_votes: 4,
+            allocations: Map::from_array(env, [(a, 525), (b, 175)]),
+        }
+    );
+    let results = client.get_category_results(&round_id, &community);
+    assert_eq!(results.distributable, 0);
+    assert!(results.allocations.is_empty());
+    assert_eq!(
+        client.try_get_category_results(&round_id, &Symbol::new(env, "Art")),
+        Err(Ok(ContractError::InvalidCategory))
+    );
+
+    client.disburse_funds(&fixture.admin, &round_id, &fixture.token, &0, &10);
+    let paid: i128 = client.get_allocations(&round_id).values().iter().sum();
+    assert_eq!(paid, 1000);
+}
+
+#[test]
+fn test_flag_submission() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let env = &fixture.env;
+    let config = RoundConfig {
+        flag_threshold: 2,
+        ..round_config(env)
+    };
+    let round_id = fixture.create_round(1000, 100, 200, &config);
+    fixture.fund(round_id, 1000);
+    let (_, flagged) = fixture.submit_project(round_id);
+    let (_, other) = fixture.submit_project(round_id);
+    fixture.open_voting(round_id);
+    let alice = fixture.add_voter();
+    let bob = fixture.add_voter();
+    let carol = fixture.add_voter();
+    fixture.vote(
+        &alice,
+        round_id,
+        &fixture.ballot(&[(flagged, 3), (other, 2)]),
+    );
+    fixture.vote(&bob, round_id, &fixture.ballot(&[(flagged, 4)]));
+    let plagiarism = symbol_short!("plagiary");
+
+    // Only registered voters flag, once per submission
+    assert_eq!(
+        client.try_flag_submission(&Address::generate(env), &flagged, &plagiarism),
+        Err(Ok(ContractError::NotAVoter))
+    );
+    client.flag_submission(&carol, &flagged, &plagiarism);
+    assert_eq!(
+        event_data::<Symbol>(env, (symbol_short!("SUB_FLAG"), flagged, carol.clone())),
+        Some(plagiarism.clone())
+    );
+    assert_eq!(
+        client.try_flag_submission(&carol, &flagged, &plagiarism),
+        Err(Ok(ContractError::AlreadyFlagged))
+    );
+    assert_eq!(client.get_flag_count(&flagged), 1);
+    assert_eq!(client.get_flag(&flagged, &carol), Some(plagiarism.clone()));
+    assert_eq!(
+        client.get_submission(&flagged).status,
+        SubmissionStatus::Approved
+    );
+    assert_eq!(
+        client.try_refund_suspended_votes(&flagged, &10),
+        Err(Ok(ContractError::InvalidPhase))
+    );
+
+    // Reaching the threshold suspends the submission and takes its votes out of the count
+    client.flag_submission(&alice, &flagged, &symbol_short!("malware"));
+    assert_eq!(
+        event_data::<u32>(env, (symbol_short!("SUB_SUSP"), flagged)),
+        Some(2)
+    );
+    assert_eq!(
+        client.get_submission(&flagged).status,
+        SubmissionStatus::Suspended
+    );
+    let round = client.get_round(&round_id);
+    assert_eq!(round.total_votes, 2);
+    assert_eq!(round.tallies.get(flagged), None);
+    assert_eq!(
+        client.try_allocate_votes(&carol, &round_id, &fixture.ballot(&[(flagged, 1)])),
+        Err(Ok(ContractError::SubmissionNotApproved))
+    );
+    assert_eq!(
+        client.try_flag_submission(&bob, &flagged, &plagiarism),
+        Err(Ok(ContractError::SubmissionNotApproved))
+    );
+    assert!(client
+        .leaderboard(&round_id, &10)
+        .iter()
+        .all(|submission| submission.id != flagged));
+
+    // Refunds take the votes off the voters' ballots, most recent voter first
+    assert_eq!(client.refund_suspended_votes(&flagged, &1), 1);
+    assert_eq!(
+        event_data::<u64>(env, (symbol_short!("VOTE_RFND"), flagged, bob.clone())),
+        Some(4)
+    );
+    assert_eq!(client.get_voter_allocation(&round_id, &bob), None);
+    assert_eq!(client.remaining_credits(&round_id, &bob), 20);
+    assert_eq!(client.refund_suspended_votes(&flagged, &10), 1);
+    assert_eq!(client.refund_suspended_votes(&flagged, &10), 0);
+    assert_eq!(
+        client.get_voter_allocation(&round_id, &alice),
+        Some(fixture.ballot(&[(other, 2)]))
+    );
+    assert_eq!(client.remaining_credits(&round_id, &alice), 18);
+    assert_eq!(client.get_submission(&flagged).total_votes, 0);
+    assert!(client.get_submission_voters(&flagged, &0, &10).is_empty());
+    assert_eq!(client.get_round(&round_id).credits_spent, 2);
+
+    // Approving the submission again reopens it to votes and to a fresh set of flags
+    client.review_submission(&fixture.admin, &flagged, &true);
+    assert_eq!(client.get_flag_count(&flagged), 0);
+    fixture.vote(&bob, round_id, &fixture.ballot(&[(flagged, 5)]));
+    assert_eq!(client.get_round(&round_id).total_votes, 7);
+    client.flag_submission(&bob, &flagged, &plagiarism);
+    assert_eq!(
+        client.get_submission(&flagged).status,
+        SubmissionStatus::Approved
+    );
+
+    // Flagging stops once the round closes
+    fixture.pass_voting_deadline(round_id);
+    fixture.close(round_id);
+    assert_eq!(
+        client.try_flag_submission(&carol, &other, &plagiarism),
+        Err(Ok(ContractError::RoundNotActive))
+    );
+}
+
+#[test]
+fn test_refund_weighted_votes() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let config = RoundConfig {
+        time_weighted: true,
+        flag_threshold: 2,
+        ..round_config(&fixture.env)
+    };
+    let round_id = fixture.create_round(1000, 1000, 3000, &config);
+    let (_, flagged) = fixture.submit_project(round_id);
+    let (_, other) = fixture.submit_project(round_id);
+    fixture.set_time(1000);
+    fixture.open_voting(round_id);
+    let alice = fixture.add_voter();
+    let bob = fixture.add_voter();
+
+    // Halfway through voting the ballot counts at 75% but is charged in full
+    fixture.set_time(2000);
+    fixture.vote(
+        &alice,
+        round_id,
+        &fixture.ballot(&[(flagged, 10), (other, 4)]),
+    );
+    assert_eq!(client.get_submission(&flagged).total_votes, 7);
+    assert_eq!(client.remaining_credits(&round_id, &alice), 6);
+    client.flag_submission(&alice, &flagged, &symbol_short!("plagiary"));
+    client.flag_submission(&bob, &flagged, &symbol_short!("plagiary"));
+
+    // The refund gives back the credits charged and the votes as cast
+    assert_eq!(client.refund_suspended_votes(&flagged, &10), 1);
+    assert_eq!(
+        client.get_voter_allocation(&round_id, &alice),
+        Some(fixture.ballot(&[(other, 3)]))
+    );
+    assert_eq!(client.remaining_credits(&round_id, &alice), 16);
+    let stats = client.get_round_stats(&round_id);
+    assert_eq!(
+        (stats.votes_cast, stats.raw_votes, stats.credits_spent),
+        (3, 4, 4)
+    );
+}
+
+#[test]
+fn test_appeal() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let env = &fixture.env;
+    let evidence = Bytes::from_slice(env, b"bafybeievidence");
+    let config = RoundConfig {
+        appeal_window_seconds: 100,
+        ..round_config(env)
+    };
+    let round_id = fixture.create_round(1000, 1000, 2000, &config);
+    fixture.fund(round_id, 1000);
+    let (alice, first) = fixture.submit_project(round_id);
+    let (_, second) = fixture.submit_project(round_id);
+    let (carol, rejected) = fixture.submit_project(round_id);
+    fixture.open_voting(round_id);
+    let voter = fixture.add_voter();
+    fixture.vote(
+        &voter,
+        round_id,
+        &fixture.ballot(&[(first, 5), (second, 3), (rejected, 2)]),
+    );
+    fixture.set_time(10);
+    client.review_submission(&fixture.admin, &rejected, &false);
+
+    // Only the submitter appeals, with evidence, and only a rejection
+    assert_eq!(
+        client.try_appeal(&alice, &rejected, &evidence),
+        Err(Ok(ContractError::Unauthorized))
+    );
+    assert_eq!(
+        client.try_appeal(&carol, &rejected, &Bytes::new(env)),
+        Err(Ok(ContractError::InvalidMetadata))
+    );
+    assert_eq!(
+        client.try_appeal(&alice, &first, &evidence),
+        Err(Ok(ContractError::InvalidAppeal))
+    );
+
+    // The window runs from the latest rejection and includes its last second
+    fixture.set_time(111);
+    assert_eq!(
+        client.try_appeal(&carol, &rejected, &evidence),
+        Err(Ok(ContractError::InvalidAppeal))
+    );
+    client.review_submission(&fixture.admin, &rejected, &false);
+    fixture.set_time(211);
+    client.appeal(&carol, &rejected, &evidence);
+    assert_eq!(
+        event_data::<Bytes>(env, (symbol_short!("APPEAL"), rejected)),
+        Some(evidence.clone())
+    );
+    assert_eq!(
+        client.get_submission(&rejected).status,
+        SubmissionStatus::UnderAppeal
+    );
+    assert_eq!(client.get_appeal(&rejected).unwrap().filed_at, 211);
+
+    // A submission is appealed once, and the appeal is settled with resolve_appeal
+    assert_eq!(
+        client.try_appeal(&carol, &rejected, &evidence),
+        Err(Ok(ContractError::InvalidAppeal))
+    );
+    assert_eq!(
+        client.try_review_submission(&fixture.admin, &rejected, &true),
+        Err(Ok(ContractError::InvalidAppeal))
+    );
+    assert_eq!(
+        client.try_resolve_appeal(&first, &false),
+        Err(Ok(ContractError::InvalidAppeal))
+    );
+
+    // The round closes without the appealed submission
+    fixture.pass_voting_deadline(round_id);
+    fixture.close(round_id);
+    assert_eq!(client.get_allocations(&round_id).get(rejected), None);
+    assert_eq!(client.get_allocations(&round_id).get(first), Some(625));
+
+    // Overturning the rejection before payouts recomputes the allocations with it
+    client.resolve_appeal(&rejected, &false);
+    let allocations = client.get_allocations(&round_id);
+    assert_eq!(allocations.get(first), Some(500));
+    assert_eq!(allocations.get(second), Some(300));
+    assert_eq!(allocations.get(rejected), Some(200));
+    assert!(event_data::<BytesN<32>>(env, (symbol_short!("RECOMPUTE"), round_id)).is_some());
+    let appeal = client.get_appeal(&rejected).unwrap();
+    assert!(!appeal.upheld);
+    assert_eq!(appeal.compensation, 0);
+    assert_eq!(
+        client.get_submission(&rejected).status,
+        SubmissionStatus::Approved
+    );
+    client.disburse_funds(&fixture.admin, &round_id, &fixture.token, &0, &10);
+    assert_eq!(fixture.balance(&carol), 200);
+
+    // After payouts an overturned rejection is compensated from the remainder, while an
+    // upheld one is final
+    let now = env.ledger().timestamp();
+    let config = RoundConfig {
+        appeal_window_seconds: 100,
+        max_allocation: 100,
+        ..round_config(env)
+    };
+    let round_id = fixture.create_round(1000, now + 100, now + 200, &config);
+    fixture.fund(round_id, 1000);
+    let ids = fixture.submit_n_projects(round_id, 2);
+    let (dave, compensated) = fixture.submit_project(round_id);
+    let (erin, upheld) = fixture.submit_project(round_id);
+    fixture.open_voting(round_id);
+    fixture.vote(
+        &fixture.add_voter(),
+        round_id,
+        &fixture.ballot(&[
+            (ids.get_unchecked(0), 1),
+            (ids.get_unchecked(1), 1),
+            (compensated, 2),
+            (upheld, 1),
+        ]),
+    );
+    client.review_submission(&fixture.admin, &compensated, &false);
+    client.review_submission(&fixture.admin, &upheld, &false);
+    client.appeal(&dave, &compensated, &evidence);
+    client.appeal(&erin, &upheld, &evidence);
+
+    client.resolve_appeal(&upheld, &true);
+    assert_eq!(
+        event_data::<(bool, i128)>(env, (symbol_short!("APPL_RSLV"), upheld)),
+        Some((true, 0))
+    );
+    assert_eq!(
+        client.get_submission(&upheld).status,
+        SubmissionStatus::Rejected
+    );
+    assert_eq!(
+        client.try_review_submission(&fixture.admin, &upheld, &true),
+        Err(Ok(ContractError::InvalidAppeal))
+    );
+    assert_eq!(
+        client.try_resolve_appeal(&upheld, &false),
+        Err(Ok(ContractError::InvalidAppeal))
+    );
+
+    fixture.pass_voting_deadline(round_id);
+    fixture.close(round_id);
+
+    // The compensation waits until the round is fully disbursed, isn't paid while payouts
+    // are halted and is held back from blacklisted addresses
+    client.disburse_funds(&fixture.admin, &round_id, &fixture.token, &0, &1);
+    assert_eq!(
+        client.try_resolve_appeal(&compensated, &false),
+        Err(Ok(ContractError::FundsNotDisbursed))
+    );
+    client.disburse_funds(&fixture.admin, &round_id, &fixture.token, &1, &10);
+    client.halt_disbursement(&round_id);
+    assert_eq!(
+        client.try_resolve_appeal(&compensated, &false),
+        Err(Ok(ContractError::DisbursementHalted))
+    );
+    client.resume_disbursement(&round_id);
+    client.blacklist(&dave);
+    assert_eq!(
+        client.try_resolve_appeal(&compensated, &false),
+        Err(Ok(ContractError::AddressBlocked))
+    );
+    assert_eq!(
+        client.get_submission(&compensated).status,
+        SubmissionStatus::UnderAppeal
+    );
+    client.unblacklist(&dave);
+    client.resolve_appeal(&compensated, &false);
+    assert_eq!(client.get_appeal(&compensated).unwrap().compensation, 100);
+    assert_eq!(fixture.balance(&dave), 100);
+    assert_eq!(client.get_claimable(&compensated, &fixture.token), 0);
+    assert_eq!(
+        client.withdraw_remainder(&round_id, &fixture.token, &fixture.admin),
+        700
+    );
+}
+
+#[test]
+fn test_council() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let env = &fixture.env;
+    let members = [
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    ];
+    let [first, second, third] = members.clone();
+    let council = Vec::from_array(env, members);
+
+    // The council needs distinct members and takes at most all their approvals
+    assert_eq!(
+        client.try_set_council(&council, &0, &100),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+    assert_eq!(
+        client.try_set_council(&council, &4, &100),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+    assert_eq!(
+        client.try_set_council(&vec![env, first.clone(), first.clone()], &1, &100),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+    client.set_council(&council, &2, &100);
+    assert_eq!(client.get_council().unwrap().term, 1);
+    assert_eq!(
+        client.try_set_council(&council, &1, &100),
+        Err(Ok(ContractError::Unauthorized))
+    );
+
+    // Treasury actions no longer go through a single key
+    let round_id = fixture.create_active_round(1000, 100);
+    let (submitter, submission_id) = fixture.submit_project(round_id);
+    fixture.open_voting(round_id);
+    fixture.vote(
+        &fixture.add_voter(),
+        round_id,
+        &fixture.ballot(&[(submission_id, 1)]),
+    );
+    fixture.close(round_id);
+    assert_eq!(
+        client.try_disburse_funds(&fixture.admin, &round_id, &fixture.token, &0, &10),
+        Err(Ok(ContractError::Unauthorized))
+    );
+    let reason = symbol_short!("audit");
+    assert_eq!(
+        client.try_override_allocation(&round_id, &submission_id, &900, &reason),
+        Err(Ok(ContractError::Unauthorized))
+    );
+    let action = ProposalAction::Override(round_id, submission_id, 900, reason);
+    assert_eq!(
+        client.try_propose(&fixture.admin, &action),
+        Err(Ok(ContractError::Unauthorized))
+    );
+
+    // The approval that meets the threshold executes the proposal
+    let override_id = client.propose(&first, &action);
+    assert_eq!(
+        client.get_allocations(&round_id).get(submission_id),
+        Some(1000)
+    );
+    assert_eq!(
+        client.try_approve_proposal(&first, &override_id),
+        Err(Ok(ContractError::AlreadyVoted))
+    );
+    client.approve_proposal(&second, &override_id);
+    assert_eq!(
+        event_data::<Vec<Address>>(env, (symbol_short!("EXECUTED"), override_id)),
+        Some(vec![env, first.clone(), second.clone()])
+    );
+    assert!(client.get_proposal(&override_id).executed);
+    assert_eq!(
+        client.get_allocations(&round_id).get(submission_id),
+        Some(900)
+    );
+    assert_eq!(
+        client.try_approve_proposal(&third, &override_id),
+        Err(Ok(ContractError::InvalidProposal))
+    );
+
+    // Proposals expire after the council's proposal lifetime
+    let disburse = ProposalAction::Disburse(round_id, fixture.token.clone(), 0, 10);
+    let expired_id = client.propose(&first, &disburse);
+    assert_eq!(client.get_proposal(&expired_id).expires_at, 100);
+    fixture.set_time(101);
+    assert_eq!(
+        client.try_approve_proposal(&second, &expired_id),
+        Err(Ok(ContractError::InvalidProposal))
+    );
+    let disburse_id = client.propose(&third, &disburse);
+    client.approve_proposal(&first, &disburse_id);
+    assert_eq!(fixture.balance(&submitter), 900);
+
+    // Replacing the council lapses the previous council's open proposals
+    let pending_id = client.propose(&first, &disburse);
+    let replace_id = client.propose(
+        &first,
+        &ProposalAction::SetCouncil(vec![env, first.clone(), second.clone()], 1, 50),
+    );
+    client.approve_proposal(&second, &replace_id);
+    let council = client.get_council().unwrap();
+    assert_eq!((council.term, council.threshold), (2, 1));
+    assert_eq!(
+        client.try_approve_proposal(&second, &pending_id),
+        Err(Ok(ContractError::InvalidProposal))
+    );
+    assert_eq!(
+        client.try_propose(&third, &disburse),
+        Err(Ok(ContractError::Unauthorized))
+    );
+
+    // Every proposal stays listed
+    assert_eq!(client.get_proposals(&0, &10).len(), 5);
+    let page = client.get_proposals(&1, &2);
+    assert_eq!(page.get_unchecked(0).id, expired_id);
+    assert_eq!(page.len(), 2);
+    assert!(!page.get_unchecked(0).executed);
+}
+
+#[test]
+fn test_timelock() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let env = &fixture.env;
+    let recipient = Address::generate(env);
+
+    // Listing an operation moves it behind the delay, including further config changes
+    let config = Config {
+        timelock_delay: 100,
+        timelocked: vec![env, TimelockedOp::SetFee, TimelockedOp::UpdateConfig],
+        ..client.get_config()
+    };
+    client.update_config(&config);
+    assert_eq!(
+        client.try_set_fee(&100, &recipient),
+        Err(Ok(ContractError::Unauthorized))
+    );
+    assert_eq!(
+        client.try_update_config(&config),
+        Err(Ok(ContractError::Unauthorized))
+    );
+
+    // A scheduled operation can only be executed once its delay has elapsed
+    let fee_id = client.schedule(&TimelockAction::SetFee(100, recipient.clone()));
+    assert_eq!(client.get_scheduled(&fee_id).ready_at, 100);
+    fixture.set_time(99);
+    assert_eq!(
+        client.try_execute(&fee_id),
+        Err(Ok(ContractError::InvalidProposal))
+    );
+
+    // The queue survives a migration
+    client.migrate();
+    fixture.set_time(100);
+    client.execute(&fee_id);
+    assert_eq!(client.get_config().fee_bps, 100);
+    assert_eq!(
+        client.try_get_scheduled(&fee_id),
+        Err(Ok(ContractError::InvalidProposal))
+    );
+    assert_eq!(
+        client.try_execute(&fee_id),
+        Err(Ok(ContractError::InvalidProposal))
+    );
+
+    // Cancelled operations never run
+    let unlock = Config {
+        timelocked: Vec::new(env),
+        ..client.get_config()
+    };
+    let unlock_id = client.schedule(&TimelockAction::UpdateConfig(unlock.clone()));
+    client.cancel_scheduled(&unlock_id);
+    fixture.set_time(300);
+    assert_eq!(
+        client.try_execute(&unlock_id),
+        Err(Ok(ContractError::InvalidProposal))
+    );
+    assert_eq!(
+        client.try_cancel_scheduled(&unlock_id),
+        Err(Ok(ContractError::InvalidProposal))
+    );
+
+    // Unlisted operations still take effect immediately, or through the queue
+    let unlock_id = client.schedule(&TimelockAction::UpdateConfig(unlock.clone()));
+    fixture.advance_time(100);
+    client.execute(&unlock_id);
+    assert_eq!(client.get_config(), unlock);
+    client.set_fee(&0, &recipient);
+    assert_eq!(client.get_config().fee_bps, 0);
+}
+
+#[test]
+fn test_round_operator() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let env = &fixture.env;
+    let operator = Address::generate(env);
+    let round_id = fixture.create_active_round(1000, 100);
+    let other_round = fixture.create_active_round(1000, 100);
+    let (submitter, submission_id) = fixture.submit_project(round_id);
+    let (_, other_submission) = fixture.submit_project(other_round);
+
+    // Operators are set by the admin on existing rounds
+    assert_eq!(
+        client.try_set_round_operator(&99, &operator),
+        Err(Ok(ContractError::RoundNotFound))
+    );
+    assert_eq!(
+        client.try_review_submission(&operator, &submission_id, &true),
+        Err(Ok(ContractError::Unauthorized))
+    );
+    client.set_round_operator(&round_id, &operator);
+    assert_eq!(client.get_round_operator(&round_id), Some(operator.clone()));
+
+    // The operator reviews, closes and disburses their own round, each action tagged with them
+    client.review_submission(&operator, &submission_id, &true);
+    assert_eq!(
+        client.try_review_submission(&operator, &other_submission, &true),
+        Err(Ok(ContractError::Unauthorized))
+    );
+    client.start_voting(&round_id);
+    fixture.vote(
+        &fixture.add_voter(),
+        round_id,
+        &fixture.ballot(&[(submission_id, 1)]),
+    );
+    client.close_voting(&operator, &round_id, &true, &false);
+    client.disburse_funds(&operator, &round_id, &fixture.token, &0, &10);
+    assert_eq!(fixture.balance(&submitter), 1000);
+    let topics: Vec<Val> = (symbol_short!("OPERATOR"), round_id, operator.clone()).into_val(env);
+    let actions: alloc::vec::Vec<Symbol> = env
+        .events()
+        .all()
+        .iter()
+        .filter(|event| event.1 == topics)
+        .map(|event| Symbol::try_from_val(env, &event.2).unwrap())
+        .collect();
+    assert_eq!(
+        actions,
+        [
+            symbol_short!("review"),
+            symbol_short!("close"),
+            symbol_short!("disburse")
+        ]
+    );
+
+    // The admin keeps every right, and removing the operator revokes theirs
+    client.remove_round_operator(&round_id);
+    assert_eq!(client.get_round_operator(&round_id), None);
+    assert_eq!(
+        client.try_remove_round_operator(&round_id),
+        Err(Ok(ContractError::Unauthorized))
+    );
+    client.set_round_operator(&other_round, &operator);
+    client.remove_round_operator(&other_round);
+    assert_eq!(
+        client.try_review_submission(&operator, &other_submission, &true),
+        Err(Ok(ContractError::Unauthorized))
+    );
+    client.review_submission(&fixture.admin, &other_submission, &true);
+    assert_eq!(
+        client.get_submission(&other_submission).status,
+        SubmissionStatus::Approved
+    );
+}
+
+#[test]
+fn test_audit_log() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let env = &fixture.env;
+    let admin = &fixture.admin;
+
+    // Each administrative action appends an entry with its actor, round and figures
+    let fee_recipient = Address::generate(env);
+    client.set_fee(&100, &fee_recipient);
+    fixture.set_time(10);
+    let round_id = fixture.create_active_round(1000, 100);
+    let (_, submission_id) = fixture.submit_project(round_id);
+    fixture.open_voting(round_id);
+    fixture.vote(
+        &fixture.add_voter(),
+        round_id,
+        &fixture.ballot(&[(submission_id, 1)]),
+    );
+    fixture.close(round_id);
+    client.override_allocation(&round_id, &submission_id, &900, &symbol_short!("audit"));
+    client.disburse_funds(admin, &round_id, &fixture.token, &0, &10);
+
+    let log = client.get_audit_log(&0, &10);
+    assert_eq!(log.total, 5);
+    let actions: alloc::vec::Vec<Symbol> = log.entries.iter().map(|entry| entry.action).collect();
+    assert_eq!(
+        actions,
+        [
+            symbol_short!("fee"),
+            symbol_short!("create"),
+            symbol_short!("close"),
+            symbol_short!("override"),
+            symbol_short!("disburse"),
+        ]
+    );
+    assert_eq!(
+        log.entries.get_unchecked(1),
+        AuditEntry {
+            action: symbol_short!("create"),
+            actor: admin.clone(),
+            round_id,
+            timestamp: 10,
+            payload: vec![env, 1000],
+        }
+    );
+    assert_eq!(log.entries.get_unchecked(2).payload, vec![env, 990, 10]);
+    assert_eq!(
+        log.entries.get_unchecked(3).payload,
+        vec![env, submission_id as i128, 990, 900]
+    );
+    assert_eq!(log.entries.get_unchecked(4).payload, vec![env, 0, 10, 910]);
+
+    // Pages run across the log's chunks and stop at its end
+    for _ in 0..50 {
+        fixture.create_round(1000, 1000, 2000, &round_config(env));
+    }
+    let page = client.get_audit_log(&48, &5);
+    assert_eq!(page.total, 55);
+    assert_eq!(page.entries.len(), 5);
+    assert!(page
+        .entries
+        .iter()
+        .all(|entry| entry.action == symbol_short!("create")));
+    assert_eq!(page.entries.get_unchecked(2).round_id, 47);
+    assert_eq!(client.get_audit_log(&53, &10).entries.len(), 2);
+    assert_eq!(client.get_audit_log(&60, &10).entries.len(), 0);
+}
+
+#[test]
+fn test_emergency_withdraw() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let env = &fixture.env;
+    let recovery = Address::generate(env);
+
+    // A closed round's escrow stays owed until it is paid out
+    let round_id = fixture.create_active_round(1000, 100);
+    let (submitter, submission_id) = fixture.submit_project(round_id);
+    fixture.open_voting(round_id);
+    fixture.vote(
+        &fixture.add_voter(),
+        round_id,
+        &fixture.ballot(&[(submission_id, 1)]),
+    );
+    fixture.close(round_id);
+    assert_eq!(client.get_obligations(&fixture.token), 1000);
+
+    // Tokens sent to the contract outside any round are not owed to anyone
+    fixture.mint(&client.address, 50);
+    assert_eq!(
+        client.try_schedule_emergency_withdraw(&fixture.token, &0, &recovery),
+        Err(Ok(ContractError::InvalidAmount))
+    );
+    let drain_id = client.schedule_emergency_withdraw(&fixture.token, &60, &recovery);
+    let stuck_id = client.schedule_emergency_withdraw(&fixture.token, &50, &recovery);
+    assert_eq!(
+        event_data::<(i128, Address, u64)>(
+            env,
+            (symbol_short!("EMRG_SCHD"), stuck_id, fixture.token.clone())
+        ),
+        Some((50, recovery.clone(), 7 * 24 * 60 * 60))
+    );
+
+    // Withdrawals wait out the delay and leave the rounds' obligations covered
+    assert_eq!(
+        client.try_execute_emergency_withdraw(&stuck_id),
+        Err(Ok(ContractError::InvalidProposal))
+    );
+    fixture.set_time(7 * 24 * 60 * 60);
+    assert_eq!(
+        client.try_execute_emergency_withdraw(&drain_id),
+        Err(Ok(ContractError::InsufficientFunds))
+    );
+    client.execute_emergency_withdraw(&stuck_id);
+    assert_eq!(fixture.balance(&recovery), 50);
+    assert_eq!(fixture.balance(&client.address), 1000);
+    assert_eq!(
+        client.try_get_emergency_withdrawal(&stuck_id),
+        Err(Ok(ContractError::InvalidProposal))
+    );
+    let log = client.get_audit_log(&0, &10);
+    assert_eq!(
+        log.entries.get_unchecked(log.total as u32 - 1).action,
+        symbol_short!("emrg_exec")
+    );
+
+    // Payouts release the obligations they settle
+    client.disburse_funds(&fixture.admin, &round_id, &fixture.token, &0, &10);
+    assert_eq!(fixture.balance(&submitter), 1000);
+    assert_eq!(client.get_obligations(&fixture.token), 0);
+}
+
+#[test]
+fn test_emergency_withdraw_after_upgrade() {
+    let fixture = TestFixture::new();
+    let client = &fixture.client;
+    let env = &fixture.env;
+    let recovery = Address::generate(env);
+    let stake_token = env
+        .register_stellar_asset_contract_v2(fixture.admin.clone())
+        .address();
+    let stake_admin = token::StellarAssetClient::new(env, &stake_token);
+
+    // A round funded and staked in before the contract kept its obligations
+    let config = RoundConfig {
+        stake_unit: 10,
+        ..round_config(env)
+    };
+    let round_id = fixture.create_round(1000, 1000, 2000, &config);
+    fixture.fund(round_id, 1000);
+    let voter = fixture.add_voter();
+    stake_admin.mint(&voter, &100);
+    client.stake_for_credits(&voter, &round_id, &stake_token, &100);
+    fixture.mint(&client.address, 30);
+    stake_admin.mint(&client.address, &30);
+    env.as_contract(&client.address, || {
+        let persistent = env.storage().persistent();
+        persistent.remove(&GovernanceKey::Obligations(fixture.token.clone()));
+        persistent.remove(&GovernanceKey::Obligations(stake_token.clone()));
+        env.storage()
+            .instance()
+            .set(&DataKey::StorageVersion, &3u32);
+    });
+    assert_eq!(client.get_obligations(&fixture.token), 0);
+
+    // Migrating counts the escrow as owed, and the whole balance of a token stakes are
+    // locked in, since stakers can't be enumerated
+    client.migrate();
+    assert_eq!(client.get_obligations(&fixture.token), 1000);
+    assert_eq!(client.get_obligations(&stake_token), 130);
+
+    // Only the stray funding tokens can be recovered
+    let drain_id = client.schedule_emergency_withdraw(&fixture.token, &31, &recovery);
+    let stuck_id = client.schedule_emergency_withdraw(&fixture.token, &30, &recovery);
+    let stake_id = client.schedule_emergency_withdraw(&stake_token, &30, &recovery);
+    fixture.set_time(7 * 24 * 60 * 60);
+    for withdrawal_id in [drain_id, stake_id] {
+        assert_eq!(
+            client.try_execute_emergency_withdraw(&withdrawal_id),
+            Err(Ok(ContractError::InsufficientFunds))
+        );
+    }
+    client.execute_emergency_withdraw(&stuck_id);
+    assert_eq!(fixture.balance(&recovery), 30);
+    assert_eq!(fixture.balance(&client.address), 1000);
+}
@@ -0,0 +1,236 @@
+// contracts/rpgf/src/testutils.rs
+//
+// Reusable fixtures for tests of the contract, enabled by the `testutils` feature. A
+// `TestFixture` registers and initializes the contract, deploys a Stellar asset to fund rounds
+// with, and wraps the steps most tests repeat: creating and funding rounds, submitting projects,
+// registering voters, voting and moving the ledger past a round's deadlines.
+#![cfg(any(test, feature = "testutils"))]
+
+use crate::{
+    RetroPGFContract, RetroPGFContractClient, RoundConfig, SubmissionDeposit, TokenGate,
+    VotingMechanism,
+};
+use soroban_sdk::testutils::{Address as _, Ledger};
+use soroban_sdk::token::{StellarAssetClient, TokenClient};
+use soroban_sdk::{Address, Bytes, Env, Map, Vec};
+
+// Name given to fixture rounds and projects
+const FIXTURE_NAME: &[u8] = b"fixture";
+
+// Metadata hash given to fixture rounds and projects
+const FIXTURE_HASH: &[u8] = b"bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi";
+
+// A round configuration with every optional rule turned off: linear voting with 20 credits,
+// open to every registered voter
+pub fn round_config(env: &Env) -> RoundConfig {
+    RoundConfig {
+        vote_credits: 20,
+        mechanism: VotingMechanism::Linear,
+        max_votes_per_submission: 0,
+        allow_self_votes: false,
+        min_voters: 0,
+        max_submissions: 0,
+        inherit_global_voters: true,
+        token_gate: TokenGate::None,
+        reveal_period: 0,
+        grace_seconds: 0,
+        top_n: 0,
+        min_votes_to_fund: 0,
+        max_allocation: 0,
+        max_match_bps: 0,
+        cliff_seconds: 0,
+        duration_seconds: 0,
+        tranches: Vec::new(env),
+        challenge_period_seconds: 0,
+        time_weighted: false,
+        carryover_bps: 0,
+        stake_unit: 0,
+        max_stake_credits: 0,
+        attestation_budget: 0,
+        submission_deposit: SubmissionDeposit::None,
+        categories: Vec::new(env),
+        flag_threshold: 0,
+        appeal_window_seconds: 0,
+    }
+}
+
+// Define a test environment with the contract initialized and a token to fund rounds with
+pub struct TestFixture {
+    pub env: Env,                                // Environment with every auth mocked
+    pub client: RetroPGFContractClient<'static>, // Client of the registered contract
+    pub admin: Address,                          // Admin of the contract and of the token
+    pub token: Address,                          // Stellar asset rounds are funded with
+    pub funder: Address,                         // Depositor of the funds of fixture rounds
+}
+
+impl Default for TestFixture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestFixture {
+    // Function to register and initialize the contract and deploy the funding token
+    pub fn new() -> Self {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let contract_id = env.register_contract(None, RetroPGFContract);
+        let client = RetroPGFContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        client.initialize(&admin);
+
+        let token = env
+            .register_stellar_asset_contract_v2(admin.clone())
+            .address();
+        let funder = Address::generate(&env);
+
+        TestFixture {
+            env,
+            client,
+            admin,
+            token,
+            funder,
+        }
+    }
+
+    // Function to get a client of the funding token
+    pub fn token_client(&self) -> TokenClient<'static> {
+        TokenClient::new(&self.env, &self.token)
+    }
+
+    // Function to mint funding tokens to an address
+    pub fn mint(&self, to: &Address, amount: i128) {
+        StellarAssetClient::new(&self.env, &self.token).mint(to, &amount);
+    }
+
+    // Function to get an address's balance of the funding token
+    pub fn balance(&self, addr: &Address) -> i128 {
+        self.token_client().balance(addr)
+    }
+
+    // Function to create an unfunded round with the given deadlines and configuration
+    pub fn create_round(
+        &self,
+        funding: i128,
+        submission_deadline: u64,
+        voting_deadline: u64,
+        config: &RoundConfig,
+    ) -> u64 {
+        self.client.create_round(
+            &self.admin,
+            &funding,
+            &submission_deadline,
+            &voting_deadline,
+            config,
+            &Bytes::from_slice(&self.env, FIXTURE_NAME),
+            &Bytes::from_slice(&self.env, FIXTURE_HASH),
+            &None,
+        )
+    }
+
+    // Function to create a round accepting submissions for the next `ttl` seconds and votes
+    // for `ttl` seconds after that, with its funding minted and deposited in the round's escrow
+    pub fn create_active_round(&self, funding: i128, ttl: u64) -> u64 {
+        let now = self.env.ledger().timestamp();
+        let round_id =
+            self.create_round(funding, now + ttl, now + 2 * ttl, &round_config(&self.env));
+        self.fund(round_id, funding);
+        round_id
+    }
+
+    // Function to mint funding tokens to the funder and deposit them in a round's escrow
+    pub fn fund(&self, round_id: u64, amount: i128) {
+        self.mint(&self.funder, amount);
+        self.client
+            .deposit_funds(&self.funder, &round_id, &self.token, &amount);
+    }
+
+    // Function to submit a project to a round from a new submitter
+    pub fn submit_project(&self, round_id: u64) -> (Address, u64) {
+        let submitter = Address::generate(&self.env);
+        let submission_id = self.client.submit_project(
+            &submitter,
+            &round_id,
+            &Bytes::from_slice(&self.env, FIXTURE_NAME),
+            &Bytes::from_slice(&self.env, FIXTURE_HASH),
+            &None,
+            &None,
+        );
+        (submitter, submission_id)
+    }
+
+    // Function to submit `n` projects to a round, each from a new submitter
+    pub fn submit_n_projects(&self, round_id: u64, n: u32) -> Vec<u64> {
+        let mut submission_ids = Vec::new(&self.env);
+        for _ in 0..n {
+            submission_ids.push_back(self.submit_project(round_id).1);
+        }
+        submission_ids
+    }
+
+    // Function to approve every submission of a round and open voting
+    pub fn open_voting(&self, round_id: u64) {
+        for submission_id in self.client.get_round(&round_id).submissions.iter() {
+            self.client
+                .review_submission(&self.admin, &submission_id, &true);
+        }
+        self.client.start_voting(&round_id);
+    }
+
+    // Function to register a new voter
+    pub fn add_voter(&self) -> Address {
+        let voter = Address::generate(&self.env);
+        self.client.add_voter(&voter);
+        voter
+    }
+
+    // Helper function to build a ballot from (submission_id, votes) pairs
+    pub fn ballot(&self, votes: &[(u64, u64)]) -> Map<u64, u64> {
+        let mut allocations = Map::new(&self.env);
+        for (submission_id, amount) in votes {
+            allocations.set(*submission_id, *amount);
+        }
+        allocations
+    }
+
+    // Function to allocate a voter's votes in a round
+    pub fn vote(&self, voter: &Address, round_id: u64, allocations: &Map<u64, u64>) {
+        self.client.allocate_votes(voter, &round_id, allocations);
+    }
+
+    // Function to close voting early, bypassing the deadline but not the quorum
+    pub fn close(&self, round_id: u64) {
+        self.client
+            .close_voting(&self.admin, &round_id, &true, &false);
+    }
+
+    // Function to set the ledger timestamp
+    pub fn set_time(&self, timestamp: u64) {
+        self.env.ledger().with_mut(|li| li.timestamp = timestamp);
+    }
+
+    // Function to move the ledger timestamp forward
+    pub fn advance_time(&self, seconds: u64) {
+        let now = self.env.ledger().timestamp();
+        self.set_time(now + seconds);
+    }
+
+    // Function to move the ledger just past a round's submission deadline
+    pub fn pass_submission_deadline(&self, round_id: u64) {
+        self.set_time(self.client.get_round(&round_id).submission_deadline + 1);
+    }
+
+    // Function to move the ledger just past the last moment a round accepts ballots, including
+    // its reveal and grace periods
+    pub fn pass_voting_deadline(&self, round_id: u64) {
+        let round = self.client.get_round(&round_id);
+        self.set_time(round.reveal_deadline().max(round.grace_deadline()) + 1);
+    }
+
+    // Function to move the ledger to the end of a closed round's challenge period
+    pub fn pass_challenge_period(&self, round_id: u64) {
+        self.set_time(self.client.get_round(&round_id).challenge_ends);
+    }
+}